@@ -0,0 +1,22 @@
+// Core block textures compiled into the binary with the `embed-textures` feature (on by
+// default), so the program still looks right when it runs without its assets/ folder. About
+// 20 KB: only the small block textures, not the skybox, the HDR sky or the models
+#[cfg(feature = "embed-textures")]
+const EMBEDDED: &[(&str, &[u8])] = &[
+    ("UP_GRASSTEXTURE.jpg", include_bytes!("../assets/UP_GRASSTEXTURE.jpg")),
+    ("SIDE_GRASSTEXTURE.jpg", include_bytes!("../assets/SIDE_GRASSTEXTURE.jpg")),
+    ("bee_nest_top.png", include_bytes!("../assets/bee_nest_top.png")),
+    ("stone_normal.png", include_bytes!("../assets/stone_normal.png")),
+    ("cake_top.png", include_bytes!("../assets/cake_top.png")),
+    ("glowstone_texture.jpg", include_bytes!("../assets/glowstone_texture.jpg")),
+    ("end_portal.png", include_bytes!("../assets/end_portal.png")),
+    ("end_portal_strip.png", include_bytes!("../assets/end_portal_strip.png")),
+];
+
+#[cfg(not(feature = "embed-textures"))]
+const EMBEDDED: &[(&str, &[u8])] = &[];
+
+// Embedded copy of the file called `name` in assets/, if it is one of the core textures
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+    EMBEDDED.iter().find(|(file, _)| *file == name).map(|(_, bytes)| *bytes)
+}
@@ -0,0 +1,56 @@
+use crate::camera::Camera;
+use crate::camera_path::{Keyframe, KeyframeFile};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Slots 1 to 9, one per number key
+pub const BOOKMARK_SLOTS: usize = 9;
+
+// Saved camera views, each in a numbered slot that may be empty. Stored as keyframes at
+// time 0 so restoring one can reuse the camera path playback
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bookmarks {
+    slots: [Option<Keyframe>; BOOKMARK_SLOTS],
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keeps the camera's view in `slot` (1 to BOOKMARK_SLOTS), replacing what was there.
+    // Slots outside that range are ignored
+    pub fn store(&mut self, slot: usize, camera: &Camera) {
+        if let Some(entry) = slot.checked_sub(1).and_then(|index| self.slots.get_mut(index)) {
+            *entry = Some(Keyframe::from_camera(camera, 0.0));
+        }
+    }
+
+    // View saved in `slot`; None for an empty slot or one outside 1 to BOOKMARK_SLOTS
+    pub fn get(&self, slot: usize) -> Option<&Keyframe> {
+        slot.checked_sub(1).and_then(|index| self.slots.get(index)).and_then(Option::as_ref)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = BookmarksFile { slots: self.slots.iter().map(|slot| slot.as_ref().map(KeyframeFile::from)).collect() };
+        fs::write(path, serde_json::to_string_pretty(&file)?)
+    }
+
+    // Missing slots at the end of the file stay empty and extra ones are dropped
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file: BookmarksFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut bookmarks = Bookmarks::new();
+        for (entry, slot) in bookmarks.slots.iter_mut().zip(file.slots) {
+            *entry = slot.map(Keyframe::from);
+        }
+        Ok(bookmarks)
+    }
+}
+
+// On-disk form: one entry per slot, null when empty
+#[derive(Serialize, Deserialize)]
+struct BookmarksFile {
+    slots: Vec<Option<KeyframeFile>>,
+}
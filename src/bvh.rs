@@ -0,0 +1,233 @@
+use nalgebra_glm::Vec3;
+use crate::object::Object;
+use crate::ray_intersect::{RayIntersect, Intersect, nearest};
+use crate::voxel_grid::VoxelGrid;
+
+const MAX_LEAF_SIZE: usize = 2;
+
+struct BvhNode {
+    min: Vec3,
+    max: Vec3,
+    // Leaves cover `count` entries of Bvh::indices starting at `first`;
+    // inner nodes (count == 0) have their children at `first` and `first + 1`
+    first: usize,
+    count: usize,
+}
+
+// Bounding volume hierarchy over the scene objects. It owns the object list,
+// so it is built once and only rebuilt when a new list is handed over.
+// Regular grid-aligned blocks go into a VoxelGrid instead; the tree holds the rest.
+pub struct Bvh {
+    objects: Vec<Object>,
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>, // Objects without a finite box (infinite planes), tested every time
+    grid: Option<VoxelGrid>,
+}
+
+// Slab test; returns the entry distance when the ray hits the box before `max_distance`
+pub fn hit_aabb(min: &Vec3, max: &Vec3, ray_origin: &Vec3, inv_direction: &Vec3, max_distance: f32) -> Option<f32> {
+    let mut tmin = 0.0_f32;
+    let mut tmax = max_distance;
+
+    for axis in 0..3 {
+        let t0 = (min[axis] - ray_origin[axis]) * inv_direction[axis];
+        let t1 = (max[axis] - ray_origin[axis]) * inv_direction[axis];
+        tmin = tmin.max(t0.min(t1));
+        tmax = tmax.min(t0.max(t1));
+    }
+
+    if tmin <= tmax { Some(tmin) } else { None }
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Object>) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut blocks = Vec::new();
+        let mut boxes = Vec::with_capacity(objects.len());
+
+        for (index, object) in objects.iter().enumerate() {
+            let (min, max) = object.bounds();
+            boxes.push((min, max, (min + max) * 0.5));
+            if let Object::Cube(cube) = object
+                && let Some(cell) = VoxelGrid::cell_of(&cube.min, &cube.max)
+            {
+                blocks.push((index, cell));
+                continue;
+            }
+            if min.iter().chain(max.iter()).all(|c| c.is_finite()) {
+                bounded.push(index);
+            } else {
+                unbounded.push(index);
+            }
+        }
+
+        let grid = if blocks.is_empty() {
+            None
+        } else {
+            // Overlapping blocks that share a cell stay in the tree
+            let (grid, rejected) = VoxelGrid::new(&blocks);
+            bounded.extend(rejected);
+            Some(grid)
+        };
+
+        let mut bvh = Bvh {
+            objects,
+            nodes: Vec::new(),
+            indices: bounded,
+            unbounded,
+            grid,
+        };
+
+        if !bvh.indices.is_empty() {
+            bvh.nodes.push(BvhNode { min: Vec3::zeros(), max: Vec3::zeros(), first: 0, count: 0 });
+            let count = bvh.indices.len();
+            bvh.build_node(0, 0, count, &boxes);
+        }
+
+        bvh
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    // Hands the object list back, to change it and build a new hierarchy
+    pub fn into_objects(self) -> Vec<Object> {
+        self.objects
+    }
+
+    // Fills node `node` with the entries indices[start..end], splitting at the
+    // median centroid along the longest axis until leaves are small enough
+    fn build_node(&mut self, node: usize, start: usize, end: usize, boxes: &[(Vec3, Vec3, Vec3)]) {
+        let mut min = Vec3::repeat(f32::INFINITY);
+        let mut max = Vec3::repeat(f32::NEG_INFINITY);
+        let mut centroid_min = Vec3::repeat(f32::INFINITY);
+        let mut centroid_max = Vec3::repeat(f32::NEG_INFINITY);
+        for &index in &self.indices[start..end] {
+            let (box_min, box_max, centroid) = &boxes[index];
+            min = min.inf(box_min);
+            max = max.sup(box_max);
+            centroid_min = centroid_min.inf(centroid);
+            centroid_max = centroid_max.sup(centroid);
+        }
+
+        self.nodes[node].min = min;
+        self.nodes[node].max = max;
+
+        let count = end - start;
+        if count <= MAX_LEAF_SIZE {
+            self.nodes[node].first = start;
+            self.nodes[node].count = count;
+            return;
+        }
+
+        let extent = centroid_max - centroid_min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let mid = start + count / 2;
+        self.indices[start..end].select_nth_unstable_by(count / 2, |a, b| {
+            boxes[*a].2[axis].total_cmp(&boxes[*b].2[axis])
+        });
+
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode { min: Vec3::zeros(), max: Vec3::zeros(), first: 0, count: 0 });
+        self.nodes.push(BvhNode { min: Vec3::zeros(), max: Vec3::zeros(), first: 0, count: 0 });
+        self.nodes[node].first = left;
+
+        self.build_node(left, start, mid, boxes);
+        self.build_node(left + 1, mid, end, boxes);
+    }
+
+    // Closest hit along the ray, same result as testing every object in turn
+    pub fn traverse(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        let mut closest = self.grid.as_ref()
+            .and_then(|grid| grid.traverse(&self.objects, ray_origin, ray_direction, f32::MAX));
+
+        for &index in &self.unbounded {
+            closest = nearest(closest, self.objects[index].ray_intersect(ray_origin, ray_direction));
+        }
+
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let inv_direction = ray_direction.map(|c| 1.0 / c);
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let limit = closest.as_ref().map_or(f32::MAX, |i| i.distance);
+            if hit_aabb(&node.min, &node.max, ray_origin, &inv_direction, limit).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for &index in &self.indices[node.first..node.first + node.count] {
+                    closest = nearest(closest, self.objects[index].ray_intersect(ray_origin, ray_direction));
+                }
+            } else {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            }
+        }
+
+        closest
+    }
+
+    // Index of the object the ray hits first, with the hit. Tests every object, which is
+    // fine for one ray at a time (mouse picking) but not for rendering
+    pub fn pick(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<(usize, Intersect)> {
+        self.objects.iter()
+            .enumerate()
+            .filter_map(|(index, object)| object.ray_intersect(ray_origin, ray_direction).map(|hit| (index, hit)))
+            .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))
+    }
+
+    // Any hit closer than `max_distance`; stops at the first one found (shadow rays)
+    pub fn occluded(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> bool {
+        let blocks = |index: usize| {
+            self.objects[index].ray_intersect(ray_origin, ray_direction).is_some_and(|i| i.distance < max_distance)
+        };
+
+        if self.grid.as_ref().is_some_and(|grid| grid.any_hit(&self.objects, ray_origin, ray_direction, max_distance)) {
+            return true;
+        }
+
+        if self.unbounded.iter().any(|&index| blocks(index)) {
+            return true;
+        }
+
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_direction = ray_direction.map(|c| 1.0 / c);
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if hit_aabb(&node.min, &node.max, ray_origin, &inv_direction, max_distance).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                if self.indices[node.first..node.first + node.count].iter().any(|&index| blocks(index)) {
+                    return true;
+                }
+            } else {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            }
+        }
+
+        false
+    }
+}
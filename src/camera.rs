@@ -1,10 +1,73 @@
+use crate::ray_intersect::RayIntersect;
 use nalgebra_glm::Vec3;
 use std::f32::consts::PI;
 
+// Closest and farthest the eye gets from `center` when zooming
+pub const MIN_DISTANCE: f32 = 1.0;
+pub const MAX_DISTANCE: f32 = 10.0;
+
+// How far in front of a surface a blocked move stops the eye, so the near side of the
+// lens never ends up inside a cube
+const COLLISION_MARGIN: f32 = 0.05;
+
+// How the keys move the camera. Both keep `eye` and `center`, so switching between them
+// leaves the view where it was
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode {
+    Orbit, // Turns around `center` and zooms toward it
+    Fly,   // Moves freely and turns around its own `eye`; `center` travels along
+}
+
+impl CameraMode {
+    pub fn next(&self) -> CameraMode {
+        match self {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        }
+    }
+}
+
+// How fast the keys and the mouse move the camera
+#[derive(Clone, Copy, Debug)]
+pub struct CameraControls {
+    pub rotation_speed: f32,    // Radians per frame while an arrow key is held
+    pub zoom_speed: f32,        // Units per frame while W/S are held in orbit mode
+    pub fly_speed: f32,         // Units per frame in fly mode
+    pub fly_boost: f32,         // Speed multiplier while Ctrl is held in fly mode
+    pub mouse_sensitivity: f32, // Radians per pixel dragged with the left button
+    pub wheel_zoom: f32,        // Units per notch of the mouse wheel
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        CameraControls {
+            rotation_speed: PI / 50.0,
+            zoom_speed: 0.5,
+            fly_speed: 0.1,
+            fly_boost: 4.0,
+            mouse_sensitivity: 0.005,
+            wheel_zoom: 0.5,
+        }
+    }
+}
+
+// One move of the camera, as the controls ask for it; Camera::apply carries it out
+#[derive(Clone, Copy, Debug)]
+pub enum CameraMove {
+    Orbit(f32, f32), // Yaw and pitch around the center
+    Dolly(f32),      // Units toward the center
+    Translate(Vec3), // Eye and center together
+    Look(f32, f32),  // Yaw and pitch around the eye
+}
+
 pub struct Camera {
     pub eye: Vec3,    // Camera position in world space
     pub center: Vec3, // Point the camera is looking at
     pub up: Vec3,     // Up vector
+    pub aperture: f32,       // Lens radius; 0 is a pinhole with everything in focus
+    pub focus_distance: f32, // Distance along the view direction that stays sharp
+    pub mode: CameraMode,
+    pub noclip: bool, // Lets moves go through the scene instead of stopping at surfaces
 }
 
 impl Camera {
@@ -13,14 +76,106 @@ impl Camera {
             eye,
             center,
             up,
+            aperture: 0.0,
+            focus_distance: (center - eye).magnitude(),
+            mode: CameraMode::Orbit,
+            noclip: false,
         }
     }
 
-    pub fn basis_change(&self, vector: &Vec3) -> Vec3 {
+    // Carries out `movement`. Unless noclip is on, a ray from the old eye toward the new one
+    // looks for anything in the way, and the eye stops COLLISION_MARGIN short of it. An eye
+    // that is already inside an object may always leave it
+    pub fn apply(&mut self, movement: CameraMove, obstacles: &dyn RayIntersect) {
+        let from = self.eye;
+        match movement {
+            CameraMove::Orbit(delta_yaw, delta_pitch) => self.orbit(delta_yaw, delta_pitch),
+            CameraMove::Dolly(amount) => self.dolly(amount),
+            CameraMove::Translate(offset) => self.translate(&offset),
+            CameraMove::Look(delta_yaw, delta_pitch) => self.look(delta_yaw, delta_pitch),
+        }
+        if self.noclip {
+            return;
+        }
+
+        let motion = self.eye - from;
+        let length = motion.magnitude();
+        if length == 0.0 {
+            return;
+        }
+        let direction = motion / length;
+        let Some(hit) = obstacles.ray_intersect(&from, &direction) else {
+            return;
+        };
+        if hit.inside || hit.distance >= length + COLLISION_MARGIN {
+            return;
+        }
+
+        let eye = from + direction * (hit.distance - COLLISION_MARGIN).max(0.0);
+        // A translation keeps the view direction, so the center stops along with the eye
+        if let CameraMove::Translate(_) = movement {
+            self.center += eye - self.eye;
+        }
+        self.eye = eye;
+    }
+
+    // Unit vector the camera looks along
+    pub fn forward(&self) -> Vec3 {
+        (self.center - self.eye).normalize()
+    }
+
+    // Unit vector to the right of the view, level with the horizon
+    pub fn right(&self) -> Vec3 {
+        self.forward().cross(&self.up).normalize()
+    }
+
+    // Moves the eye `amount` units toward the center (away from it when negative), whatever
+    // the direction it looks from, keeping the distance within MIN_DISTANCE..=MAX_DISTANCE
+    pub fn dolly(&mut self, amount: f32) {
+        let offset = self.center - self.eye;
+        let distance = (offset.magnitude() - amount).clamp(MIN_DISTANCE, MAX_DISTANCE);
+        self.eye = self.center - offset.normalize() * distance;
+    }
+
+    // Moves the eye and the point it looks at together, so the view keeps its direction
+    pub fn translate(&mut self, offset: &Vec3) {
+        self.eye += offset;
+        self.center += offset;
+    }
+
+    // Turns the view around the eye: positive yaw to the right, positive pitch up. The
+    // center stays at the same distance, and pitch stops short of straight up or down
+    // like in orbit
+    pub fn look(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        let forward = self.forward();
+        let distance = (self.center - self.eye).magnitude();
+
+        // Angles of the view direction: yaw in the XZ plane, pitch above the horizon
+        let yaw = forward.z.atan2(forward.x) + delta_yaw;
+        let pitch = (forward.y.clamp(-1.0, 1.0).asin() + delta_pitch).clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
+
+        let direction = Vec3::new(yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos());
+        self.center = self.eye + direction * distance;
+    }
+
+    // Thin lens: the ray for world-space `direction` (from basis_change) leaves from `lens`,
+    // a point in the unit disk scaled by the aperture, and passes through the spot the
+    // pinhole ray reaches on the focus plane. Returns the new origin and direction
+    pub fn thin_lens(&self, direction: &Vec3, lens: (f32, f32)) -> (Vec3, Vec3) {
         let forward = (self.center - self.eye).normalize();
         let right = forward.cross(&self.up).normalize();
         let up = right.cross(&forward).normalize();
 
+        let focus_point = self.eye + direction * (self.focus_distance / direction.dot(&forward));
+        let origin = self.eye + (right * lens.0 + up * lens.1) * self.aperture;
+        (origin, (focus_point - origin).normalize())
+    }
+
+    pub fn basis_change(&self, vector: &Vec3) -> Vec3 {
+        let forward = self.forward();
+        let right = self.right();
+        let up = right.cross(&forward).normalize();
+
         let rotated = 
         vector.x * right +
         vector.y * up -
@@ -66,4 +221,34 @@ impl Camera {
 
         self.eye = new_eye;
     }
-}
\ No newline at end of file
+}
+impl Camera {
+    // Left and right eyes for stereo: `separation` apart along the right vector, both aimed
+    // at `center` so the views converge there and it sits at screen depth
+    pub fn stereo_eyes(&self, separation: f32) -> (Camera, Camera) {
+        let offset = self.right() * (separation * 0.5);
+        let eye = |position: Vec3| Camera {
+            eye: position,
+            center: self.center,
+            up: self.up,
+            aperture: self.aperture,
+            focus_distance: self.focus_distance,
+            mode: self.mode,
+            noclip: self.noclip,
+        };
+        (eye(self.eye - offset), eye(self.eye + offset))
+    }
+}
+
+// Cameras at `eye` looking through the six faces of a Skybox, in its order (+X, -X, +Y, -Y,
+// +Z, -Z) and with its file names. Rendered square with a 90° field of view, each image is
+// that face the way Skybox::sample reads it, so the six files load back as a skybox
+pub fn cubemap_cameras(eye: Vec3) -> [(&'static str, Camera); 6] {
+    let x = Vec3::new(1.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    let z = Vec3::new(0.0, 0.0, 1.0);
+    // The side faces stand upright; the top face meets the -Z face along its bottom edge and
+    // the bottom face along its top edge
+    [("px", x, y), ("nx", -x, y), ("py", y, z), ("ny", -y, -z), ("pz", z, y), ("nz", -z, y)]
+        .map(|(name, forward, up)| (name, Camera::new(eye, eye + forward, up)))
+}
@@ -0,0 +1,194 @@
+use crate::camera::Camera;
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Seconds between a captured keyframe and the one before it
+pub const KEYFRAME_SPACING: f32 = 2.0;
+
+// Where the camera is at one moment of a path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32, // Seconds from the start of the path
+    pub eye: Vec3,
+    pub center: Vec3,
+    pub up: Vec3,
+}
+
+impl Keyframe {
+    pub fn from_camera(camera: &Camera, time: f32) -> Self {
+        Keyframe { time, eye: camera.eye, center: camera.center, up: camera.up }
+    }
+}
+
+// How the camera gets from one keyframe to the next
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Linear,     // Straight lines, with a visible turn at every keyframe
+    CatmullRom, // A smooth curve through every keyframe
+}
+
+impl Interpolation {
+    pub fn next(&self) -> Interpolation {
+        match self {
+            Interpolation::Linear => Interpolation::CatmullRom,
+            Interpolation::CatmullRom => Interpolation::Linear,
+        }
+    }
+}
+
+// Keyframes in time order, played back by sampling the path at any time in between
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    pub interpolation: Interpolation,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        CameraPath { keyframes: Vec::new(), interpolation: Interpolation::CatmullRom }
+    }
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    // Adds a keyframe wherever its time falls; one at the same time as another replaces it
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        match self.keyframes.binary_search_by(|k| k.time.total_cmp(&keyframe.time)) {
+            Ok(index) => self.keyframes[index] = keyframe,
+            Err(index) => self.keyframes.insert(index, keyframe),
+        }
+    }
+
+    // Adds the camera as it is now, KEYFRAME_SPACING seconds after the last keyframe
+    pub fn capture(&mut self, camera: &Camera) {
+        let time = self.keyframes.last().map_or(0.0, |k| k.time + KEYFRAME_SPACING);
+        self.insert(Keyframe::from_camera(camera, time));
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    // Time of the last keyframe; 0 for an empty path
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    // Camera `time` seconds into the path, held at the first and last keyframes outside it.
+    // None for an empty path
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(Keyframe { time, ..*first });
+        }
+        if time >= last.time {
+            return Some(Keyframe { time, ..*last });
+        }
+
+        // Segment from keyframe i to i + 1, with its neighbours for the curve
+        let i = self.keyframes.partition_point(|k| k.time <= time) - 1;
+        let k1 = &self.keyframes[i];
+        let k2 = &self.keyframes[i + 1];
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k3 = &self.keyframes[(i + 2).min(self.keyframes.len() - 1)];
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        let blend = |p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3| match self.interpolation {
+            Interpolation::Linear => p1 + (p2 - p1) * t,
+            Interpolation::CatmullRom => catmull_rom(p0, p1, p2, p3, t),
+        };
+        Some(Keyframe {
+            time,
+            eye: blend(&k0.eye, &k1.eye, &k2.eye, &k3.eye),
+            center: blend(&k0.center, &k1.center, &k2.center, &k3.center),
+            up: blend(&k0.up, &k1.up, &k2.up, &k3.up).normalize(),
+        })
+    }
+
+    // Path from the camera as it is now to `target` in `duration` seconds, for a smooth
+    // jump instead of a cut
+    pub fn transition(camera: &Camera, target: &Keyframe, duration: f32) -> Self {
+        let mut path = CameraPath::new();
+        path.insert(Keyframe::from_camera(camera, 0.0));
+        path.insert(Keyframe { time: duration, ..*target });
+        path
+    }
+
+    // Puts the camera where the path is at `time`; an empty path leaves it alone
+    pub fn apply(&self, camera: &mut Camera, time: f32) {
+        if let Some(keyframe) = self.sample(time) {
+            camera.eye = keyframe.eye;
+            camera.center = keyframe.center;
+            camera.up = keyframe.up;
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = PathFile {
+            interpolation: self.interpolation,
+            keyframes: self.keyframes.iter().map(KeyframeFile::from).collect(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&file)?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file: PathFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut camera_path = CameraPath { keyframes: Vec::new(), interpolation: file.interpolation };
+        for keyframe in file.keyframes {
+            camera_path.insert(keyframe.into());
+        }
+        Ok(camera_path)
+    }
+}
+
+// Uniform Catmull-Rom spline: passes through p1 at t = 0 and p2 at t = 1, leaving each
+// with the direction from the point before to the point after
+fn catmull_rom(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+// On-disk form of a path: vectors as plain [x, y, z] arrays
+#[derive(Serialize, Deserialize)]
+struct PathFile {
+    interpolation: Interpolation,
+    keyframes: Vec<KeyframeFile>,
+}
+
+// Shared with the bookmarks file
+#[derive(Serialize, Deserialize)]
+pub(crate) struct KeyframeFile {
+    time: f32,
+    eye: [f32; 3],
+    center: [f32; 3],
+    up: [f32; 3],
+}
+
+impl From<&Keyframe> for KeyframeFile {
+    fn from(keyframe: &Keyframe) -> Self {
+        let array = |v: &Vec3| [v.x, v.y, v.z];
+        KeyframeFile { time: keyframe.time, eye: array(&keyframe.eye), center: array(&keyframe.center), up: array(&keyframe.up) }
+    }
+}
+
+impl From<KeyframeFile> for Keyframe {
+    fn from(keyframe: KeyframeFile) -> Self {
+        let vector = |[x, y, z]: [f32; 3]| Vec3::new(x, y, z);
+        Keyframe { time: keyframe.time, eye: vector(keyframe.eye), center: vector(keyframe.center), up: vector(keyframe.up) }
+    }
+}
@@ -0,0 +1,183 @@
+use nalgebra_glm::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use crate::bvh::{self, Bvh};
+use crate::material::MaterialId;
+use crate::ray_intersect::{Intersect, nearest};
+use crate::scene_builder::SceneBuilder;
+use crate::terrain::{self, TerrainSettings};
+use crate::voxel_grid::BLOCK_SIZE;
+
+// Columns per chunk side
+pub const CHUNK_SIZE: i32 = 16;
+
+// CHUNK_SIZE x CHUNK_SIZE columns of terrain with their own hierarchy.
+// Chunk (cx, cz) covers the block columns [cx * CHUNK_SIZE, (cx + 1) * CHUNK_SIZE).
+pub struct Chunk {
+    pub coord: (i32, i32),
+    pub min: Vec3,
+    pub max: Vec3,
+    bvh: Bvh,
+}
+
+impl Chunk {
+    pub fn generate(coord: (i32, i32), settings: &TerrainSettings, material: MaterialId) -> Self {
+        let from = (coord.0 * CHUNK_SIZE, coord.1 * CHUNK_SIZE);
+        let to = (from.0 + CHUNK_SIZE - 1, from.1 + CHUNK_SIZE - 1);
+
+        let mut builder = SceneBuilder::new();
+        terrain::generate_area(&mut builder, settings, from, to, material);
+        let objects = builder.build();
+
+        let mut min = Vec3::repeat(f32::INFINITY);
+        let mut max = Vec3::repeat(f32::NEG_INFINITY);
+        for object in &objects {
+            let (object_min, object_max) = object.bounds();
+            min = min.inf(&object_min);
+            max = max.sup(&object_max);
+        }
+
+        Chunk { coord, min, max, bvh: Bvh::new(objects) }
+    }
+
+    // Flat chunks have no blocks at all; the ground plane covers them
+    pub fn is_empty(&self) -> bool {
+        self.bvh.objects().is_empty()
+    }
+}
+
+// Chunk that contains a world position
+pub fn chunk_of(position: &Vec3) -> (i32, i32) {
+    let size = CHUNK_SIZE as f32 * BLOCK_SIZE;
+    ((position.x / size).floor() as i32, (position.z / size).floor() as i32)
+}
+
+fn chunk_distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+// Keeps the chunks within `radius` chunks of the camera loaded. Generation happens
+// on a worker thread; finished chunks are picked up by `update` between frames,
+// so the render never waits for them.
+pub struct ChunkManager {
+    radius: i32,
+    chunks: HashMap<(i32, i32), Chunk>,
+    pending: HashSet<(i32, i32)>,
+    requests: Sender<(i32, i32)>,
+    // Only touched from `update`; the mutex just lets the renderer share the manager across threads
+    finished: Mutex<Receiver<Chunk>>,
+}
+
+impl ChunkManager {
+    pub fn new(settings: TerrainSettings, material: MaterialId, radius: i32) -> Self {
+        let (requests, jobs) = mpsc::channel::<(i32, i32)>();
+        let (results, finished) = mpsc::channel();
+
+        // The worker stops once the manager (and with it the request sender) is dropped
+        thread::spawn(move || {
+            for coord in jobs {
+                if results.send(Chunk::generate(coord, &settings, material)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        ChunkManager {
+            radius,
+            chunks: HashMap::new(),
+            pending: HashSet::new(),
+            requests,
+            finished: Mutex::new(finished),
+        }
+    }
+
+    // Collects finished chunks, drops the ones left behind and requests the missing
+    // ones, nearest first. Returns true when the set of loaded chunks changed.
+    pub fn update(&mut self, eye: &Vec3) -> bool {
+        let center = chunk_of(eye);
+        // One extra ring before dropping, so moving back and forth over a border doesn't regenerate
+        let keep_radius = self.radius + 1;
+        let mut changed = false;
+
+        let finished = self.finished.get_mut().unwrap();
+        while let Ok(chunk) = finished.try_recv() {
+            self.pending.remove(&chunk.coord);
+            if chunk_distance(center, chunk.coord) <= keep_radius {
+                self.chunks.insert(chunk.coord, chunk);
+                changed = true;
+            }
+        }
+
+        let loaded = self.chunks.len();
+        self.chunks.retain(|coord, _| chunk_distance(center, *coord) <= keep_radius);
+        changed |= self.chunks.len() != loaded;
+
+        let mut missing = Vec::new();
+        for dx in -self.radius..=self.radius {
+            for dz in -self.radius..=self.radius {
+                let coord = (center.0 + dx, center.1 + dz);
+                if !self.chunks.contains_key(&coord) && !self.pending.contains(&coord) {
+                    missing.push(coord);
+                }
+            }
+        }
+        missing.sort_by_key(|coord| {
+            let (dx, dz) = (coord.0 - center.0, coord.1 - center.1);
+            dx * dx + dz * dz
+        });
+
+        for coord in missing {
+            if self.requests.send(coord).is_ok() {
+                self.pending.insert(coord);
+            }
+        }
+
+        changed
+    }
+
+    // Like `update`, but waits for every chunk around `eye` to be generated, for a single
+    // render that can't pick them up over the next frames
+    pub fn load_around(&mut self, eye: &Vec3) {
+        self.update(eye);
+        let finished = self.finished.get_mut().unwrap();
+        while !self.pending.is_empty() {
+            let Ok(chunk) = finished.recv() else {
+                break;
+            };
+            self.pending.remove(&chunk.coord);
+            self.chunks.insert(chunk.coord, chunk);
+        }
+    }
+
+    pub fn loaded(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values().filter(|chunk| !chunk.is_empty())
+    }
+
+    // Closest hit on the loaded terrain nearer than `max_distance`; chunks whose
+    // box the ray misses are skipped without looking at their blocks
+    pub fn traverse(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> Option<Intersect> {
+        let inv_direction = ray_direction.map(|c| 1.0 / c);
+        let mut closest = None;
+
+        for chunk in self.loaded() {
+            let limit = closest.as_ref().map_or(max_distance, |i: &Intersect| i.distance);
+            if bvh::hit_aabb(&chunk.min, &chunk.max, ray_origin, &inv_direction, limit).is_none() {
+                continue;
+            }
+            let hit = chunk.bvh.traverse(ray_origin, ray_direction).filter(|i| i.distance < limit);
+            closest = nearest(closest, hit);
+        }
+
+        closest
+    }
+
+    pub fn occluded(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> bool {
+        let inv_direction = ray_direction.map(|c| 1.0 / c);
+        self.loaded().any(|chunk| {
+            bvh::hit_aabb(&chunk.min, &chunk.max, ray_origin, &inv_direction, max_distance).is_some()
+                && chunk.bvh.occluded(ray_origin, ray_direction, max_distance)
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::sync::OnceLock;
 
 #[derive(Clone, Debug, PartialEq, Copy)]
 pub struct Color {
@@ -7,6 +8,32 @@ pub struct Color {
     pub b: u8,
 }
 
+// sRGB transfer curve on [0, 1]. Light adds up linearly, so colors are decoded
+// before shading and encoded again for the screen.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+// Decoding only ever sees 256 inputs, so it is tabulated once
+static DECODE: OnceLock<[f32; 256]> = OnceLock::new();
+
+fn decode_table() -> &'static [f32; 256] {
+    DECODE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f32 / 255.0)))
+}
+
+// u8 channel to [0, 1] and back, rounding and clamping on the way out
+fn unit(c: u8) -> f32 {
+    c as f32 / 255.0
+}
+
+fn channel(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
 impl Color {
     // Constructor to initialize the color using r, g, b values
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
@@ -30,6 +57,12 @@ impl Color {
         ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
 
+    // sRGB-encoded color (textures, hand-picked colors) to linear light
+    pub fn to_linear(&self) -> ColorF {
+        let table = decode_table();
+        ColorF::new(table[self.r as usize], table[self.g as usize], table[self.b as usize])
+    }
+
     pub fn intensity(&self) -> f32 {
         (self.r as f32 + self.g as f32 + self.b as f32) / (3.0 * 255.0)
     }
@@ -111,3 +144,117 @@ impl fmt::Display for Color {
         write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
     }
 }
+
+
+// Color with f32 channels, 1.0 being the old 255, for shading. Nothing is
+// clamped until the final write, so several lights add up without losing
+// energy and values above 1.0 survive for bright emitters.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct ColorF {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl ColorF {
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
+        ColorF { r, g, b }
+    }
+
+    pub const fn black() -> Self {
+        ColorF { r: 0.0, g: 0.0, b: 0.0 }
+    }
+
+    pub fn lerp(a: &ColorF, b: &ColorF, t: f32) -> ColorF {
+        *a * (1.0 - t) + *b * t
+    }
+
+    // Decodes an sRGB value already in float form (e.g. an interpolated sky color)
+    pub fn to_linear(&self) -> ColorF {
+        ColorF::new(srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b))
+    }
+
+    // Perceived brightness (Rec. 709 weights)
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    // Clamps to [0, 1] and stores the channels as they are
+    pub fn to_color(&self) -> Color {
+        Color::new(channel(self.r), channel(self.g), channel(self.b))
+    }
+
+    // Encodes linear light to sRGB without clamping, so values above 1.0 stay above it
+    pub fn to_srgb_f(&self) -> ColorF {
+        let encode = |c: f32| linear_to_srgb(c.max(0.0));
+        ColorF::new(encode(self.r), encode(self.g), encode(self.b))
+    }
+
+    // Clamps to [0, 1] and encodes linear light to sRGB for display
+    pub fn to_srgb(&self) -> Color {
+        let encode = |c: f32| channel(linear_to_srgb(c.clamp(0.0, 1.0)));
+        Color::new(encode(self.r), encode(self.g), encode(self.b))
+    }
+}
+
+impl From<Color> for ColorF {
+    fn from(color: Color) -> Self {
+        ColorF::new(unit(color.r), unit(color.g), unit(color.b))
+    }
+}
+
+impl Add for ColorF {
+    type Output = ColorF;
+
+    fn add(self, other: ColorF) -> ColorF {
+        ColorF::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl Mul<f32> for ColorF {
+    type Output = ColorF;
+
+    fn mul(self, scalar: f32) -> ColorF {
+        ColorF::new(self.r * scalar, self.g * scalar, self.b * scalar)
+    }
+}
+
+// Channel by channel, e.g. a surface color filtering a light color
+impl Mul<ColorF> for ColorF {
+    type Output = ColorF;
+
+    fn mul(self, other: ColorF) -> ColorF {
+        ColorF::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+// Curve that squeezes unbounded shading values into [0, 1] before display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Clamp,    // Cut everything above 1.0, the old behaviour
+    Reinhard, // x / (1 + x): never clips, but flattens bright areas
+    Aces,     // Narkowicz's fit of the ACES filmic curve: keeps contrast, rolls highlights off
+}
+
+impl ToneMapping {
+    // Applied in linear light, after the exposure multiplier
+    pub fn apply(&self, color: ColorF) -> ColorF {
+        let curve = |x: f32| {
+            let x = x.max(0.0);
+            match self {
+                ToneMapping::Clamp => x.min(1.0),
+                ToneMapping::Reinhard => x / (1.0 + x),
+                ToneMapping::Aces => ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).min(1.0),
+            }
+        };
+        ColorF::new(curve(color.r), curve(color.g), curve(color.b))
+    }
+
+    pub fn next(&self) -> ToneMapping {
+        match self {
+            ToneMapping::Clamp => ToneMapping::Reinhard,
+            ToneMapping::Reinhard => ToneMapping::Aces,
+            ToneMapping::Aces => ToneMapping::Clamp,
+        }
+    }
+}
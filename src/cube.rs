@@ -1,177 +1,129 @@
 use nalgebra_glm::Vec3;
-use crate::Material;
+use crate::material::MaterialId;
 use crate::ray_intersect::{RayIntersect, Intersect, CubeFace};
 
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Cube {
     pub min: Vec3,
     pub max: Vec3,
-    pub material: Material,
+    pub material: MaterialId,
 }
 
 impl Cube {
-    // Constructor for Cube, now accepts a reference to Material
-    pub fn new(min: Vec3, max: Vec3, material: &Material) -> Self {
+    pub fn new(min: Vec3, max: Vec3, material: MaterialId) -> Self {
         Cube {
             min,
             max,
-            material: material.clone(),  // Clone the material to own it
+            material,
         }
     }
 
-    fn calculate_normal(&self, hit_point: Vec3) -> Vec3 {
-        if (hit_point.x - self.min.x).abs() < 1e-4 {
-            return Vec3::new(-1.0, 0.0, 0.0);
-        }
-        if (hit_point.x - self.max.x).abs() < 1e-4 {
-            return Vec3::new(1.0, 0.0, 0.0);
-        }
-        if (hit_point.y - self.min.y).abs() < 1e-4 {
-            return Vec3::new(0.0, -1.0, 0.0);
-        }
-        if (hit_point.y - self.max.y).abs() < 1e-4 {
-            return Vec3::new(0.0, 1.0, 0.0);
-        }
-        if (hit_point.z - self.min.z).abs() < 1e-4 {
-            return Vec3::new(0.0, 0.0, -1.0);
-        }
-        Vec3::new(0.0, 0.0, 1.0)
+    // Block `size` units on a side that touches this cube's `face` from outside, lined up
+    // with its min corner: for a block of the grid, the grid cell next to that face
+    pub fn adjacent(&self, face: &CubeFace, size: f32, material: MaterialId) -> Cube {
+        let normal = face.normal();
+        let mut min = self.min;
+        for axis in 0..3 {
+            if normal[axis] > 0.0 {
+                min[axis] = self.max[axis];
+            } else if normal[axis] < 0.0 {
+                min[axis] = self.min[axis] - size;
+            }
+        }
+        Cube::new(min, min + Vec3::repeat(size), material)
     }
 
-    pub fn intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
-        let mut tmin = (self.min.x - ray_origin.x) / ray_direction.x;
-        let mut tmax = (self.max.x - ray_origin.x) / ray_direction.x;
-
-        if tmin > tmax {
-            std::mem::swap(&mut tmin, &mut tmax);
-        }
-
-        let mut tymin = (self.min.y - ray_origin.y) / ray_direction.y;
-        let mut tymax = (self.max.y - ray_origin.y) / ray_direction.y;
-
-        if tymin > tymax {
-            std::mem::swap(&mut tymin, &mut tymax);
-        }
-
-        if tmin > tymax || tymin > tmax {
-            return Intersect::empty();
-        }
-
-        tmin = tmin.max(tymin);
-        tmax = tmax.min(tymax);
-
-        let mut tzmin = (self.min.z - ray_origin.z) / ray_direction.z;
-        let mut tzmax = (self.max.z - ray_origin.z) / ray_direction.z;
-
-        if tzmin > tzmax {
-            std::mem::swap(&mut tzmin, &mut tzmax);
-        }
-
-        if tmin > tzmax || tzmin > tmax {
-            return Intersect::empty();
-        }
-
-        tmin = tmin.max(tzmin);
-        tmax = tmax.min(tzmax);
-
-        if tmin < 0.0 && tmax < 0.0 {
-            return Intersect::empty();
-        }
-
-        let intersection_point = ray_origin + ray_direction * tmin;
+    // The four corners of a face, in order around it (each one shares an edge with the next)
+    pub fn face_corners(&self, face: &CubeFace) -> [Vec3; 4] {
+        let normal = face.normal();
+        let axis = (0..3).find(|&axis| normal[axis] != 0.0).unwrap_or(0);
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let plane = if normal[axis] > 0.0 { self.max[axis] } else { self.min[axis] };
+        [(false, false), (true, false), (true, true), (false, true)].map(|(at_max_u, at_max_v)| {
+            let mut corner = Vec3::zeros();
+            corner[axis] = plane;
+            corner[u] = if at_max_u { self.max[u] } else { self.min[u] };
+            corner[v] = if at_max_v { self.max[v] } else { self.min[v] };
+            corner
+        })
+    }
 
-        // Determine which face was hit
-        let face = if (intersection_point.x - self.min.x).abs() < 1e-4 {
-            CubeFace::Left
-        } else if (intersection_point.x - self.max.x).abs() < 1e-4 {
-            CubeFace::Right
-        } else if (intersection_point.y - self.min.y).abs() < 1e-4 {
-            CubeFace::Bottom
-        } else if (intersection_point.y - self.max.y).abs() < 1e-4 {
-            CubeFace::Top
-        } else if (intersection_point.z - self.min.z).abs() < 1e-4 {
-            CubeFace::Back
-        } else {
-            CubeFace::Front
+    // Texture coordinates of a point on a face, in the cube's own space so every
+    // face shows the whole texture once. Seen from outside, u grows to the right
+    // and v downward, so side textures stand upright on all four walls.
+    fn face_uv(&self, point: &Vec3, face: &CubeFace) -> (f32, f32) {
+        let local = (point - self.min).component_div(&(self.max - self.min));
+        let (u, v) = match face {
+            CubeFace::Top => (local.x, local.z),
+            CubeFace::Bottom => (local.x, 1.0 - local.z),
+            CubeFace::Front => (local.x, 1.0 - local.y),
+            CubeFace::Back => (1.0 - local.x, 1.0 - local.y),
+            CubeFace::Right => (1.0 - local.z, 1.0 - local.y),
+            CubeFace::Left => (local.z, 1.0 - local.y),
         };
-
-        Intersect {
-            is_intersecting: true,
-            point: intersection_point,
-            normal: self.calculate_normal(intersection_point),
-            distance: tmin,
-            material: self.material.clone(),
-            face,
-        }
+        (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0))
     }
 }
 
 
 impl RayIntersect for Cube {
-    fn ray_intersect(&self, ray_origin: &Vec3, ray_dir: &Vec3) -> Intersect {
-        let mut tmin = (self.min.x - ray_origin.x) / ray_dir.x;
-        let mut tmax = (self.max.x - ray_origin.x) / ray_dir.x;
-
-        if tmin > tmax {
-            std::mem::swap(&mut tmin, &mut tmax);
-        }
-
-        let mut tymin = (self.min.y - ray_origin.y) / ray_dir.y;
-        let mut tymax = (self.max.y - ray_origin.y) / ray_dir.y;
-
-        if tymin > tymax {
-            std::mem::swap(&mut tymin, &mut tymax);
-        }
-
-        if tmin > tymax || tymin > tmax {
-            return Intersect::empty();
-        }
-
-        tmin = tmin.max(tymin);
-        tmax = tmax.min(tymax);
-
-        let mut tzmin = (self.min.z - ray_origin.z) / ray_dir.z;
-        let mut tzmax = (self.max.z - ray_origin.z) / ray_dir.z;
-
-        if tzmin > tzmax {
-            std::mem::swap(&mut tzmin, &mut tzmax);
-        }
-
-        if tmin > tzmax || tzmin > tmax {
-            return Intersect::empty();
-        }
-
-        tmin = tmin.max(tzmin);
-        tmax = tmax.min(tzmax);
-
-        if tmin < 0.0 && tmax < 0.0 {
-            return Intersect::empty();
-        }
-
-        let intersection_point = ray_origin + ray_dir * tmin;
-
-        // Determine which face was hit
-        let face = if (intersection_point.x - self.min.x).abs() < 1e-4 {
-            CubeFace::Left
-        } else if (intersection_point.x - self.max.x).abs() < 1e-4 {
-            CubeFace::Right
-        } else if (intersection_point.y - self.min.y).abs() < 1e-4 {
-            CubeFace::Bottom
-        } else if (intersection_point.y - self.max.y).abs() < 1e-4 {
-            CubeFace::Top
-        } else if (intersection_point.z - self.min.z).abs() < 1e-4 {
-            CubeFace::Back
+    // Slab test with the inverse direction. Axes the ray runs parallel to are
+    // checked against the origin instead of divided by zero, so rays straight
+    // down an axis don't turn into NaN; -0.0 counts as parallel too.
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_dir: &Vec3) -> Option<Intersect> {
+        let mut tmin = f32::NEG_INFINITY;
+        let mut tmax = f32::INFINITY;
+        let mut entry_face = None;
+        let mut exit_face = None;
+
+        for axis in 0..3 {
+            if ray_dir[axis] == 0.0 {
+                if ray_origin[axis] < self.min[axis] || ray_origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inv_dir = 1.0 / ray_dir[axis];
+            let t_min_side = (self.min[axis] - ray_origin[axis]) * inv_dir;
+            let t_max_side = (self.max[axis] - ray_origin[axis]) * inv_dir;
+            // The ray enters through the min side when it travels towards +axis
+            let (t_enter, t_exit) = if inv_dir > 0.0 { (t_min_side, t_max_side) } else { (t_max_side, t_min_side) };
+
+            if t_enter > tmin {
+                tmin = t_enter;
+                entry_face = Some(CubeFace::from_axis(axis, inv_dir < 0.0));
+            }
+            if t_exit < tmax {
+                tmax = t_exit;
+                exit_face = Some(CubeFace::from_axis(axis, inv_dir > 0.0));
+            }
+        }
+
+        let (entry_face, exit_face) = (entry_face?, exit_face?);
+        if tmin > tmax || tmax < 0.0 {
+            return None;
+        }
+
+        // A ray starting inside (camera in the house, refraction through glass)
+        // hits the face it leaves through, seen from inside
+        let inside = tmin < 0.0;
+        let (distance, face, normal) = if inside {
+            (tmax, exit_face.clone(), -exit_face.normal())
         } else {
-            CubeFace::Front
+            (tmin, entry_face.clone(), entry_face.normal())
         };
 
-        Intersect {
-            point: intersection_point,
-            distance: tmin,
-            normal: self.calculate_normal(intersection_point),
-            material: self.material.clone(),
-            is_intersecting: true,
-            face,  // Add this line
-        }
+        let point = ray_origin + ray_dir * distance;
+        Some(Intersect {
+            point,
+            distance,
+            normal,
+            material: self.material,
+            uv: Some(self.face_uv(&point, &face)),
+            face,
+            inside,
+        })
     }
-}
\ No newline at end of file
+}
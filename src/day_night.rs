@@ -0,0 +1,119 @@
+use nalgebra_glm::Vec3;
+use crate::color::{Color, ColorF};
+
+// Duración de un día completo, en segundos reales, al arrancar
+pub const DEFAULT_DAY_LENGTH: f32 = 60.0;
+// Límites de day_length para faster y slower
+pub const MIN_DAY_LENGTH: f32 = 7.5;
+pub const MAX_DAY_LENGTH: f32 = 960.0;
+
+// Hora del día en [0, 1): 0.25 es mediodía, 0.75 medianoche; 0 y 0.5 son el amanecer y el
+// atardecer. De ella salen la luz ambiente, la dirección del sol y el cielo (ver Sky)
+#[derive(Clone, Copy)]
+pub struct DayNightCycle {
+    pub time: f32,
+    // El sol avanza solo con advance salvo en pausa; update lo mueve igual
+    pub paused: bool,
+    // Segundos reales que dura un día completo con advance
+    pub day_length: f32,
+    day_color: ColorF,
+    night_color: ColorF,
+    pub sun_position: Vec3,
+}
+
+impl Default for DayNightCycle {
+    fn default() -> Self {
+        DayNightCycle::new()
+    }
+}
+
+impl DayNightCycle {
+    pub fn new() -> Self {
+        DayNightCycle {
+            time: 0.5, // Empezamos a mitad del día
+            paused: false,
+            day_length: DEFAULT_DAY_LENGTH,
+            day_color: ColorF::from(Color::new(255, 255, 255)),
+            night_color: ColorF::from(Color::new(10, 10, 50)),
+            sun_position: Vec3::new(-5.0, 0.0, 0.0), // Posición inicial del sol: en el horizonte
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        // Da la vuelta aunque delta pase de un día entero (un cuadro muy lento con días cortos)
+        self.time = (self.time + delta).rem_euclid(1.0);
+
+        // Actualizar la posición del sol: de noche queda debajo del horizonte (y < 0)
+        let angle = self.time * 2.0 * std::f32::consts::PI;
+        self.sun_position = Vec3::new(angle.cos(), angle.sin(), angle.sin()) * 5.0;
+    }
+
+    // Dirección en la que viaja la luz del sol, desde el mismo ángulo que su posición. De
+    // noche apunta hacia arriba, pero para entonces get_light_intensity ya es 0
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = self.time * 2.0 * std::f32::consts::PI;
+        -Vec3::new(angle.cos(), angle.sin(), angle.sin()).normalize()
+    }
+
+    // Dirección de la luz de la luna: está en el lado opuesto del cielo al sol, así que sale
+    // cuando él se pone
+    pub fn moon_direction(&self) -> Vec3 {
+        -self.sun_direction()
+    }
+
+    // Avanza el día lo que corresponde a `seconds` segundos reales, salvo en pausa
+    pub fn advance(&mut self, seconds: f32) {
+        if !self.paused {
+            self.update(seconds / self.day_length);
+        }
+    }
+
+    // El día pasa el doble de rápido (dura la mitad)
+    pub fn faster(&mut self) {
+        self.day_length = (self.day_length * 0.5).max(MIN_DAY_LENGTH);
+    }
+
+    // El día pasa a la mitad de velocidad (dura el doble)
+    pub fn slower(&mut self) {
+        self.day_length = (self.day_length * 2.0).min(MAX_DAY_LENGTH);
+    }
+
+    // Hora del reloj (horas y minutos, 24 h) que corresponde a `time`: el amanecer son las
+    // 6:00, el mediodía las 12:00 y la medianoche las 0:00
+    pub fn clock(&self) -> (u32, u32) {
+        let minutes = ((self.time * 24.0 + 6.0) * 60.0) as u32 % (24 * 60);
+        (minutes / 60, minutes % 60)
+    }
+
+    pub fn get_current_color(&self) -> ColorF {
+        let t = (self.time * std::f32::consts::PI * 2.0).sin() * 0.5 + 0.5;
+        ColorF::lerp(&self.night_color, &self.day_color, t)
+    }
+
+    // Altura del sol sobre el horizonte, de -1 (medianoche) a 1 (mediodía); cerca de 0 es el
+    // amanecer o el atardecer. Tiene el mismo signo que la y de sun_position
+    pub fn sun_height(&self) -> f32 {
+        (self.time * std::f32::consts::PI * 2.0).sin()
+    }
+
+    // Intensidad de la luz del sol, de 0 a 1 (mediodía). Se apaga al acercarse al horizonte y
+    // es 0 toda la noche: entonces solo iluminan la luna y la luz ambiente
+    pub fn get_light_intensity(&self) -> f32 {
+        let height = self.sun_height();
+        smoothstep(0.0, SUNSET_FADE, height) * (height * 0.4 + 0.6)
+    }
+
+    // Cuánto es de noche, de 0 a 1: 0 mientras el sol está arriba y 1 una vez que bajó
+    // SUNSET_FADE del horizonte. La luna y las estrellas aparecen con ella
+    pub fn night_amount(&self) -> f32 {
+        smoothstep(0.0, SUNSET_FADE, -self.sun_height())
+    }
+}
+
+// Altura del sol (en sun_height) a la que termina de aparecer o apagarse su luz
+const SUNSET_FADE: f32 = 0.2;
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
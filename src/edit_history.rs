@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use crate::cube::Cube;
+use crate::material::MaterialId;
+use crate::object::Object;
+use crate::world::World;
+
+// Edits kept for undo; the oldest ones are forgotten past this
+pub const HISTORY_LIMIT: usize = 256;
+
+// One change to the blocks of the hand-built scene, with what it takes to reverse it.
+// Indices are positions in the scene's object list at the time of the edit
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Place { index: usize, cube: Cube },
+    Remove { index: usize, cube: Cube },
+    Repaint { index: usize, from: MaterialId, to: MaterialId },
+}
+
+impl Edit {
+    // The edit that takes the scene back to how it was before this one
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Place { index, cube } => Edit::Remove { index: *index, cube: cube.clone() },
+            Edit::Remove { index, cube } => Edit::Place { index: *index, cube: cube.clone() },
+            Edit::Repaint { index, from, to } => Edit::Repaint { index: *index, from: *to, to: *from },
+        }
+    }
+
+    fn apply(&self, objects: &mut Vec<Object>) {
+        match self {
+            Edit::Place { index, cube } => objects.insert(*index, cube.clone().into()),
+            Edit::Remove { index, .. } => {
+                objects.remove(*index);
+            }
+            Edit::Repaint { index, to, .. } => {
+                if let Object::Cube(cube) = &mut objects[*index] {
+                    cube.material = *to;
+                }
+            }
+        }
+    }
+}
+
+// Edits done so far and the ones undone since, so Ctrl+Z and Ctrl+Y can walk back and
+// forth. Every change goes through World::edit_objects, which rebuilds the hierarchy; the
+// emissive lights follow on the next Scene::update_lights
+pub struct EditHistory {
+    done: VecDeque<Edit>,
+    undone: Vec<Edit>,
+    limit: usize,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        EditHistory::with_limit(HISTORY_LIMIT)
+    }
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        EditHistory { done: VecDeque::new(), undone: Vec::new(), limit }
+    }
+
+    // Makes the edit and records it; whatever was undone can no longer be redone
+    pub fn apply(&mut self, edit: Edit, world: &mut World) {
+        world.edit_objects(|objects| edit.apply(objects));
+        self.undone.clear();
+        self.done.push_back(edit);
+        if self.done.len() > self.limit {
+            self.done.pop_front();
+        }
+    }
+
+    // Reverses the last edit; false when there is nothing left to undo
+    pub fn undo(&mut self, world: &mut World) -> bool {
+        let Some(edit) = self.done.pop_back() else {
+            return false;
+        };
+        world.edit_objects(|objects| edit.inverse().apply(objects));
+        self.undone.push(edit);
+        true
+    }
+
+    // Makes the last undone edit again; false when there is nothing to redo
+    pub fn redo(&mut self, world: &mut World) -> bool {
+        let Some(edit) = self.undone.pop() else {
+            return false;
+        };
+        world.edit_objects(|objects| edit.apply(objects));
+        self.done.push_back(edit);
+        true
+    }
+
+    // Forgets everything, for when the object list is replaced as a whole (scene reload)
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
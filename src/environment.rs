@@ -0,0 +1,86 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use crate::color::{Color, ColorF};
+use crate::sky::Sky;
+use crate::texture::Skybox;
+
+// What a ray sees when it leaves the scene without hitting anything
+pub enum Environment {
+    // The same color in every direction
+    SolidColor(Color),
+    // Gradient sky that follows the day/night cycle
+    ProceduralSky(Sky),
+    // Six images around the scene, sampled by direction
+    Cubemap(Box<Skybox>),
+    // One latitude/longitude image around the scene (HDRI)
+    Equirect(Box<EquirectEnv>),
+}
+
+impl Environment {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Environment::SolidColor(_) => "solid color",
+            Environment::ProceduralSky(_) => "procedural sky",
+            Environment::Cubemap(_) => "cubemap",
+            Environment::Equirect(_) => "equirectangular",
+        }
+    }
+}
+
+// Equirectangular environment map: longitude across the image, latitude down it, with
+// the middle of the image toward -Z (where the camera starts looking). Radiance (.hdr)
+// and OpenEXR files keep their values above 1.0, so a sun in the map can outshine
+// everything else; other formats are treated as sRGB and decoded.
+pub struct EquirectEnv {
+    width: u32,
+    height: u32,
+    pixels: Vec<ColorF>, // Linear light, row by row from the top (straight up)
+}
+
+impl EquirectEnv {
+    pub fn load(path: &str) -> Result<Self, image::ImageError> {
+        let img = image::open(path)?.to_rgb32f();
+        let (width, height) = img.dimensions();
+        let hdr = matches!(
+            image::ImageFormat::from_path(path),
+            Ok(image::ImageFormat::Hdr | image::ImageFormat::OpenExr)
+        );
+        let pixels = img.pixels()
+            .map(|p| {
+                let color = ColorF::new(p[0], p[1], p[2]);
+                if hdr { color } else { color.to_linear() }
+            })
+            .collect();
+        Ok(EquirectEnv { width, height, pixels })
+    }
+
+    // Spherical coordinates of a direction as texture coordinates: u from the azimuth
+    // (0.5 toward -Z, growing toward +X), v from the angle to straight up
+    fn uv(direction: &Vec3) -> (f32, f32) {
+        let direction = direction.normalize();
+        let phi = direction.x.atan2(-direction.z);
+        let theta = direction.y.clamp(-1.0, 1.0).acos();
+        (0.5 + phi / (2.0 * PI), theta / PI)
+    }
+
+    fn texel(&self, x: i64, y: i64) -> ColorF {
+        // Around the horizon the image wraps; at the poles it stops at the first/last row
+        let x = x.rem_euclid(self.width as i64) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // Linear radiance seen along `direction`, blended between the four nearest texels
+    pub fn sample(&self, direction: &Vec3) -> ColorF {
+        let (u, v) = Self::uv(direction);
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = ColorF::lerp(&self.texel(x0, y0), &self.texel(x0 + 1, y0), tx);
+        let bottom = ColorF::lerp(&self.texel(x0, y0 + 1), &self.texel(x0 + 1, y0 + 1), tx);
+        ColorF::lerp(&top, &bottom, ty)
+    }
+}
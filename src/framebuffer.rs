@@ -1,3 +1,9 @@
+use image::RgbImage;
+use std::path::Path;
+use crate::color::Color;
+use crate::font::{self, GLYPH_SIZE};
+
+#[derive(Clone)]
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
@@ -29,6 +35,69 @@ impl Framebuffer {
         }
     }
 
+    // Line in the current color between two points given in pixels, which may lie outside
+    // the buffer: the part on screen is drawn and the rest is skipped
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32)) {
+        // Liang-Barsky clip to the buffer, so a far off-screen end costs nothing
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+        let edges = [
+            (-dx, from.0),
+            (dx, self.width as f32 - 1.0 - from.0),
+            (-dy, from.1),
+            (dy, self.height as f32 - 1.0 - from.1),
+        ];
+        for (p, q) in edges {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return;
+        }
+
+        let start = (from.0 + dx * t0, from.1 + dy * t0);
+        let steps = (dx.abs().max(dy.abs()) * (t1 - t0)).ceil() as usize;
+        for step in 0..=steps {
+            let t = if steps == 0 { 0.0 } else { step as f32 / steps as f32 * (t1 - t0) };
+            self.point((start.0 + dx * t).round() as usize, (start.1 + dy * t).round() as usize);
+        }
+    }
+
+    // Text with the top-left corner of its first character at (x, y), in `color` and without
+    // touching the current color. '\n' starts a new line below. Only the glyphs' set pixels
+    // are written, and whatever falls outside the buffer (even at negative positions) is
+    // skipped
+    pub fn draw_text(&mut self, x: isize, y: isize, text: &str, color: Color) {
+        let color = color.to_hex();
+        let (mut column, mut line) = (0, 0);
+        for c in text.chars() {
+            if c == '\n' {
+                column = 0;
+                line += 1;
+                continue;
+            }
+            let left = x + (column * GLYPH_SIZE) as isize;
+            let top = y + (line * GLYPH_SIZE) as isize;
+            for (row, bits) in font::glyph(c).into_iter().enumerate() {
+                for bit in 0..GLYPH_SIZE {
+                    let (px, py) = (left + bit as isize, top + row as isize);
+                    let inside = px >= 0 && py >= 0 && (px as usize) < self.width && (py as usize) < self.height;
+                    if bits >> bit & 1 == 1 && inside {
+                        self.buffer[py as usize * self.width + px as usize] = color;
+                    }
+                }
+            }
+            column += 1;
+        }
+    }
+
     pub fn set_background_color(&mut self, color: u32) {
         self.background_color = color;
     }
@@ -36,4 +105,13 @@ impl Framebuffer {
     pub fn set_current_color(&mut self, color: u32) {
         self.current_color = color;
     }
+
+    // Writes the pixels as an image; the format comes from the extension (.png, .jpg...)
+    pub fn save(&self, path: &Path) -> Result<(), image::ImageError> {
+        let image = RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let pixel = self.buffer[y as usize * self.width + x as usize];
+            image::Rgb([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
+        });
+        image.save(path)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,281 @@
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::light::Flicker;
+use crate::material::{Material, MaterialId, MaterialRegistry};
+use crate::object::Object;
+use crate::scene_builder::SceneBuilder;
+use crate::texture::TextureCache;
+
+// The house scene main.rs opens with: the house, its roof, the portal in front, the tree, the
+// pond, the glowstone and the lava. Its materials are registered in the given registry; the ids
+// main.rs needs again (for the palette, the test scenes and the portal animation) come back
+// alongside the blocks. The ground plane, the crystal sphere and the creeper are left to the
+// caller, since they depend on command line options and on files outside the texture cache.
+pub struct House {
+    pub objects: Vec<Object>,
+    pub grass: MaterialId,
+    pub wood: MaterialId,
+    pub portal: MaterialId,
+    pub stone: MaterialId,
+    pub treewood: MaterialId,
+    pub leaves: MaterialId,
+    pub glass: MaterialId,
+    pub stained_glass: MaterialId,
+    pub glowstone: MaterialId,
+    pub lava: MaterialId,
+    pub crystal: MaterialId,
+    pub mirror: MaterialId,
+    pub water: MaterialId,
+}
+
+// Emission of the portal at `time` seconds: it "breathes" between a dim and a bright purple
+// without ever going dark, so it never stops being a light
+pub fn portal_emission(time: f32) -> Color {
+    let pulse = 0.3 + 0.7 * ((time * 2.0).sin() * 0.5 + 0.5);
+    Color::new((100.0 * pulse) as u8, 0, (255.0 * pulse) as u8)
+}
+
+pub fn house(materials: &mut MaterialRegistry, textures: &mut TextureCache) -> House {
+    let grass_top_texture = textures.get("UP_GRASSTEXTURE.jpg");
+    let dirt_side_texture = textures.get("SIDE_GRASSTEXTURE.jpg");
+    // Grass on top, dirt on the bottom and the sides
+    let grass = materials.add("grass", Material::builder(Color::new(0, 255, 0))
+        .shininess(50.0)
+        .build()
+        .with_face_textures(grass_top_texture, dirt_side_texture.clone(), dirt_side_texture));
+
+    let wood_plank_texture = textures.get("cake_top.png");
+    let wood = materials.add("wood", Material::builder(Color::new(170, 137, 85))
+        .shininess(30.0)
+        .diffuse(0.7)
+        .refractive_index(2.0)
+        .build()
+        .with_textures(vec![wood_plank_texture]));
+
+    // The portal swirl is the same texture shifted frame by frame; main.rs updates its
+    // emission every frame with portal_emission
+    let portal_animation = textures.strip("end_portal_strip.png", 0.12);
+    let portal = materials.add("portal", Material::builder(Color::new(100, 0, 200))
+        .shininess(100.0)
+        .diffuse(0.1)
+        .specular(0.8)
+        .reflectivity(0.2)
+        .transparency(0.6)
+        .refractive_index(1.25)
+        .build()
+        .with_animation(portal_animation)
+        .with_emission(portal_emission(0.0))
+        .with_emission_strength(10.0));
+
+    // Glowing blocks around the portal
+    let portal_border = materials.add("portal_border", Material::builder(Color::new(200, 0, 255))
+        .shininess(90.0)
+        .diffuse(0.1)
+        .specular(0.8)
+        .reflectivity(0.5)
+        .transparency(0.3)
+        .refractive_index(1.2)
+        .build()
+        .with_emission(Color::new(180, 0, 255))
+        .with_emission_strength(10.0));
+
+    let stone_texture = textures.get("bee_nest_top.png");
+    // Normal map computed from the brightness of the same texture
+    let stone_normal_map = textures.get("stone_normal.png");
+    // Matte stone: the reflection is blurred instead of a mirror
+    let stone = materials.add("stone", Material::builder(Color::new(128, 128, 128))
+        .shininess(30.0)
+        .diffuse(0.7)
+        .specular(0.1)
+        .reflectivity(0.1)
+        .roughness(0.4)
+        .build()
+        .with_textures(vec![stone_texture])
+        .with_normal_map(stone_normal_map));
+
+    let tree_plank_texture = textures.get("tallo.png");
+    let treewood = materials.add("treewood", Material::builder(Color::new(139, 69, 19))
+        .diffuse(0.7)
+        .build()
+        .with_textures(vec![tree_plank_texture]));
+
+    // Cut-out leaves: transparent texels are holes the sky shows and the sun shines through
+    let leaves_texture = textures.get("leaves_cutout.png");
+    let leaves = materials.add("leaves", Material::builder(Color::new(34, 139, 34))
+        .diffuse(0.6)
+        .specular(0.3)
+        .build()
+        .with_textures(vec![leaves_texture])
+        .with_alpha_cutoff(0.5));
+
+    // Almost white, since its color also filters the light that goes through it
+    let glass = materials.add("glass", Material::builder(Color::new(235, 245, 250))
+        .shininess(60.0)
+        .diffuse(0.1)
+        .specular(0.1)
+        .reflectivity(0.1)
+        .transparency(0.5)
+        .build());
+
+    // Blue stained glass: tints the light coming in through the left wall
+    let stained_glass = materials.add("stained_glass", Material::builder(Color::new(60, 110, 255))
+        .shininess(60.0)
+        .diffuse(0.2)
+        .specular(0.3)
+        .reflectivity(0.05)
+        .transparency(0.7)
+        .build());
+
+    let azale_texture = textures.get("azalea.png");
+    let azale = materials.add("azale", Material::builder(Color::new(255, 182, 193))
+        .shininess(20.0)
+        .diffuse(0.7)
+        .build()
+        .with_textures(vec![azale_texture])
+        .with_alpha_cutoff(0.5));
+
+    // Shines with its texture and lights its surroundings strongly, flickering a little
+    // like a torch
+    let glowstone_texture = textures.get("glowstone_texture.jpg");
+    let glowstone = materials.add("glowstone", Material::builder(Color::new(255, 255, 200))
+        .diffuse(0.9)
+        .specular(0.1)
+        .build()
+        .with_textures(vec![glowstone_texture])
+        .with_emission(Color::new(255, 255, 150))
+        .with_emission_strength(10.0)
+        .with_flicker(Flicker::new(0.15, 3.0, 7)));
+
+    // Lava: an animated texture that flows and glows with its own colors, more in the bright
+    // veins than in the dark crust. Its light takes the average color of the visible frame;
+    // since the texture only slides, that average barely changes and the flicker does the rest
+    let lava_animation = textures.strip("lava_strip.png", 0.15);
+    let lava = materials.add("lava", Material::builder(Color::new(255, 100, 0))
+        .shininess(20.0)
+        .diffuse(0.9)
+        .specular(0.1)
+        .build()
+        .with_animation(lava_animation)
+        .with_texture_emission()
+        .with_emission_strength(4.0)
+        .with_flicker(Flicker::new(0.25, 1.5, 11)));
+
+    // Block coordinates in units of 0.5: block (x, y, z) starts at (x, y, z) * 0.5
+    let mut scene = SceneBuilder::new();
+
+    // Azalea flowers around the tree
+    scene.cube(Vec3::new(-2.8, 0.0, 2.5), Vec3::new(-2.6, 0.2, 2.7), azale)
+        .cube(Vec3::new(-3.2, 0.0, 3.2), Vec3::new(-3.0, 0.2, 3.4), azale)
+        .cube(Vec3::new(-2.9, 0.0, 3.6), Vec3::new(-2.7, 0.2, 3.8), azale)
+        .cube(Vec3::new(-3.3, 0.0, 2.8), Vec3::new(-3.1, 0.2, 3.0), azale);
+    // Flowers in front of the house
+    scene.cube(Vec3::new(-1.0, 0.0, 0.5), Vec3::new(-0.8, 0.2, 0.7), azale)
+        .cube(Vec3::new(1.0, 0.0, 0.5), Vec3::new(1.2, 0.2, 0.7), azale)
+        .cube(Vec3::new(0.0, 0.0, 0.8), Vec3::new(0.2, 0.2, 1.0), azale);
+
+    // Portal in front of the house: a single piece so it makes a single light
+    scene.cube(Vec3::new(-0.5, 0.0, -2.5), Vec3::new(0.5, 2.0, -2.0), portal);
+    // Portal frame, thinner than a block
+    scene.cube(Vec3::new(-0.7, -0.2, -2.6), Vec3::new(0.7, 0.0, -1.9), portal_border) // base
+        .cube(Vec3::new(-0.7, 2.0, -2.6), Vec3::new(0.7, 2.2, -1.9), portal_border) // top
+        .cube(Vec3::new(-0.7, 0.0, -2.6), Vec3::new(-0.5, 2.0, -1.9), portal_border) // left side
+        .cube(Vec3::new(0.5, 0.0, -2.6), Vec3::new(0.7, 2.0, -1.9), portal_border); // right side
+
+    // House walls, 4 blocks high
+    scene.wall((-3, -3), (2, -3), 0, 4, wood)   // back
+        .wall((-3, -3), (-3, 2), 0, 4, wood)    // left
+        .wall((2, -3), (2, 2), 0, 4, wood)      // right
+        .wall((-3, 2), (2, 2), 0, 4, wood);     // front
+    // Door
+    scene.clear([-1, 0, 2], [0, 1, 2]);
+    // Right window: a column of clear glass next to one of frosted ice, to compare how the
+    // inside looks through each
+    scene.fill([2, 1, -1], [2, 2, -1], glass);
+    scene.fill([2, 1, 0], [2, 2, 0], materials.add("ice", Material::ice()));
+    // Stained glass in the left wall; leaves a patch of blue light on the floor
+    scene.fill([-3, 1, -1], [-3, 2, 0], stained_glass);
+
+    // Stepped roof
+    scene.floor(4, (-4, -4), (3, 3), stone)
+        .floor(5, (-3, -3), (2, 2), stone)
+        .floor(6, (-2, -2), (1, 1), stone)
+        .floor(7, (-1, -1), (0, 0), stone);
+
+    // Tree: trunk and leaves
+    scene.fill([-6, 0, 6], [-6, 3, 6], treewood);
+    scene.fill([-7, 4, 5], [-5, 5, 7], leaves)
+        .place_block(-6, 6, 6, leaves);
+
+    // Pond by the tree: 2 x 2 blocks of water (a single piece, so the light doesn't break
+    // between blocks), somewhat lower than the stone rim around it. The surface moves with
+    // waves; the sides stay flat
+    scene.cube(Vec3::new(-5.0, 0.0, 2.5), Vec3::new(-4.0, 0.4, 3.5), materials.add("pond", Material::water()));
+    scene.fill([-11, 0, 4], [-8, 0, 7], stone)
+        .clear([-10, 0, 5], [-9, 0, 6]);
+
+    // Glowstone block next to the house
+    scene.place_block(4, 0, -2, glowstone);
+
+    // Lava pool flush with the ground next to the glowstone, with a stone step behind it that
+    // the glowstone lights up
+    scene.cube(Vec3::new(2.2, 0.0, -0.5), Vec3::new(3.2, 0.05, 0.5), lava);
+    scene.fill([5, 0, -2], [6, 0, -2], stone);
+
+    // Crystal for the sphere next to the house, to see refraction on a curved surface
+    let crystal = materials.add("crystal", Material::builder(Color::new(255, 255, 255))
+        .shininess(125.0)
+        .diffuse(0.0)
+        .specular(0.5)
+        .reflectivity(0.1)
+        .transparency(0.8)
+        .refractive_index(1.5)
+        .dispersion(0.03)
+        .build());
+
+    // Blue glass bar: seen lengthwise (1.2 of glass) it looks much darker than from the side
+    // (0.3), from the absorption inside the material
+    let blue_glass = materials.add("blue_glass", Material::builder(Color::new(255, 255, 255))
+        .shininess(125.0)
+        .diffuse(0.0)
+        .specular(0.5)
+        .reflectivity(0.05)
+        .transparency(0.9)
+        .refractive_index(1.5)
+        .build()
+        .with_absorption(Color::new(40, 90, 230), 2.5));
+    scene.cube(Vec3::new(3.4, 0.0, 1.0), Vec3::new(3.7, 0.3, 2.2), blue_glass);
+
+    // Perfect mirror for the facing mirrors test scene
+    let mirror = materials.add("mirror", Material::builder(Color::new(255, 255, 255))
+        .diffuse(0.0)
+        .specular(0.0)
+        .reflectivity(1.0)
+        .build());
+
+    // Water for the nested media test scene: almost all the light goes through it
+    let water = materials.add("water", Material::builder(Color::new(255, 255, 255))
+        .shininess(80.0)
+        .diffuse(0.0)
+        .specular(0.3)
+        .transparency(0.95)
+        .refractive_index(1.33)
+        .build()
+        .with_absorption(Color::new(170, 220, 230), 0.5));
+
+    House {
+        objects: scene.build(),
+        grass,
+        wood,
+        portal,
+        stone,
+        treewood,
+        leaves,
+        glass,
+        stained_glass,
+        glowstone,
+        lava,
+        crystal,
+        mirror,
+        water,
+    }
+}
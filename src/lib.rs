@@ -0,0 +1,42 @@
+// The raytracer as a library: main.rs is one program built on it, and other programs (or the
+// tests in tests/) can build their own scenes and render them into a Framebuffer
+pub mod framebuffer;
+pub mod font;
+pub mod ray_intersect;
+pub mod color;
+pub mod camera;
+pub mod camera_path;
+pub mod bookmarks;
+pub mod light;
+pub mod light_grid;
+pub mod material;
+pub mod cube;
+pub mod texture;
+pub mod assets;
+pub mod obj_loader;
+pub mod vox_loader;
+pub mod sphere;
+pub mod object;
+pub mod plane;
+pub mod mesh;
+pub mod bvh;
+pub mod voxel_grid;
+pub mod scene_builder;
+pub mod house;
+pub mod terrain;
+pub mod chunk;
+pub mod world;
+pub mod edit_history;
+pub mod sampling;
+pub mod environment;
+pub mod day_night;
+pub mod sky;
+pub mod medium;
+pub mod renderer;
+pub mod scene;
+pub mod scene_file;
+pub mod screenshot;
+
+// Render entry points
+pub use renderer::{cast_ray, Projection, RayContext, RayPath, RenderMode, RenderSettings, Renderer, UpscaleFilter};
+pub use scene::Scene;
@@ -1,16 +1,289 @@
 use nalgebra_glm::Vec3;
 use crate::color::Color;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    // Shines from `position` in every direction and fades with distance
+    Point,
+    // Infinitely far away (the sun): every ray comes along `direction`, the way
+    // the light travels, with no falloff and no range limit
+    Directional { direction: Vec3 },
+    // A point light limited to a cone around `direction`. Full strength inside
+    // `inner`, fading out toward `outer` (half-angles in radians)
+    Spot { direction: Vec3, inner: f32, outer: f32 },
+    // A glowing box (an emissive cube). Shading can sample points on its faces;
+    // everything else treats it as a point light at `position`, its center
+    Area { min: Vec3, max: Vec3 },
+}
+
+// How a light that isn't directional fades with the distance d to it. Past `radius` it never
+// lights anything, whatever the model
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Attenuation {
+    // 1 / (1 + d² / radius²): half strength at `radius`, where it stops abruptly. What every
+    // light used before the other models existed
+    #[default]
+    RadiusScaled,
+    // Full strength all the way to `radius`
+    None,
+    // Straight down from full strength at the light to zero at `radius`
+    Linear,
+    // 1 / (constant + linear d + quadratic d²), the classic fixed-function falloff
+    InverseSquare { constant: f32, linear: f32, quadratic: f32 },
+    // 1 / (1 + d²) times a window that brings it smoothly to exactly zero at `radius`, so the
+    // edge of the light's range doesn't show
+    SmoothWindowed,
+}
+
+// One face of an area light: the rectangle corner + s * edge_u + t * edge_v for s, t in [0, 1]
+pub struct AreaFace {
+    pub corner: Vec3,
+    pub edge_u: Vec3,
+    pub edge_v: Vec3,
+    pub normal: Vec3, // Outward
+}
+
+impl AreaFace {
+    pub fn area(&self) -> f32 {
+        self.edge_u.cross(&self.edge_v).magnitude()
+    }
+
+    pub fn point(&self, s: f32, t: f32) -> Vec3 {
+        self.corner + self.edge_u * s + self.edge_v * t
+    }
+}
+
+// Torch-like wobble: the intensity rises and dips by up to `amplitude` (a share of it), about
+// `frequency` times a second, and the position drifts a little along with it. It follows
+// smooth value noise, so it never strobes, and the same seed always flickers the same way
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flicker {
+    pub amplitude: f32,
+    pub frequency: f32,
+    pub seed: u32,
+}
+
+// Largest drift of a flickering light along each axis, per unit of amplitude
+pub const FLICKER_JITTER: f32 = 0.1;
+
+impl Flicker {
+    pub fn new(amplitude: f32, frequency: f32, seed: u32) -> Self {
+        Flicker { amplitude, frequency, seed }
+    }
+
+    // Factor for the intensity at `time` seconds, between 1 - amplitude and 1 + amplitude
+    pub fn intensity(&self, time: f32) -> f32 {
+        1.0 + self.amplitude * (2.0 * self.noise(time, 0) - 1.0)
+    }
+
+    // Offset of the position at `time` seconds, at most FLICKER_JITTER * amplitude per axis
+    pub fn jitter(&self, time: f32) -> Vec3 {
+        let axis = |channel| (2.0 * self.noise(time, channel) - 1.0) * FLICKER_JITTER * self.amplitude;
+        Vec3::new(axis(1), axis(2), axis(3))
+    }
+
+    // Two octaves of value noise in [0, 1]: random values at whole steps of time * frequency,
+    // blended with smoothstep in between. `channel` gives each use its own curve
+    fn noise(&self, time: f32, channel: u32) -> f32 {
+        let octave = |x: f32, octave: u32| {
+            let cell = x.floor();
+            let t = x - cell;
+            let value = |cell: f32| unit(hash(self.seed, channel * 2 + octave, cell as i32));
+            let (a, b) = (value(cell), value(cell + 1.0));
+            a + (b - a) * t * t * (3.0 - 2.0 * t)
+        };
+        let x = time * self.frequency;
+        octave(x, 0) * 0.7 + octave(x * 2.3, 1) * 0.3
+    }
+}
+
+// Integer hash (PCG-style output permutation) of a flicker's seed, curve and time step
+fn hash(seed: u32, channel: u32, cell: i32) -> u32 {
+    let mix = |h: u32| {
+        let h = h.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+        let word = ((h >> ((h >> 28) + 4)) ^ h).wrapping_mul(277_803_737);
+        (word >> 22) ^ word
+    };
+    mix(mix(mix(seed) ^ channel) ^ cell as u32)
+}
+
+fn unit(h: u32) -> f32 {
+    (h >> 8) as f32 / (1u32 << 24) as f32
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct Light {
     pub position: Vec3,
     pub color: Color,
     pub intensity: f32,
-    pub radius: f32,  
+    pub radius: f32,  // Range: points farther than this are not lit
+    pub size: f32,    // Radius of the emitting sphere for soft shadows; 0 is a point.
+                      // For directional lights it's the spread of the disc (tangent of its angle)
+    pub kind: LightKind,
+    pub flicker: Option<Flicker>,
+    pub attenuation: Attenuation,
+    pub enabled: bool, // Off, shading skips it as if it weren't there
 }
 
 impl Light {
     pub fn new(position: Vec3, color: Color, intensity: f32, radius: f32) -> Self {
-        Light { position, color, intensity, radius }
+        Light { position, color, intensity, radius, size: 0.0, kind: LightKind::Point, flicker: None, attenuation: Attenuation::default(), enabled: true }
+    }
+
+    pub fn directional(direction: Vec3, color: Color, intensity: f32) -> Self {
+        Light {
+            position: Vec3::zeros(),
+            color,
+            intensity,
+            radius: f32::INFINITY,
+            size: 0.0,
+            kind: LightKind::Directional { direction: direction.normalize() },
+            flicker: None,
+            attenuation: Attenuation::default(),
+            enabled: true,
+        }
+    }
+
+    pub fn spot(position: Vec3, direction: Vec3, inner: f32, outer: f32, color: Color, intensity: f32, radius: f32) -> Self {
+        Light {
+            kind: LightKind::Spot { direction: direction.normalize(), inner, outer },
+            ..Light::new(position, color, intensity, radius)
+        }
+    }
+
+    pub fn area(min: Vec3, max: Vec3, color: Color, intensity: f32, radius: f32) -> Self {
+        Light {
+            kind: LightKind::Area { min, max },
+            ..Light::new((min + max) * 0.5, color, intensity, radius)
+        }
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    pub fn with_flicker(mut self, flicker: Option<Flicker>) -> Self {
+        self.flicker = flicker;
+        self
+    }
+
+    // The light as it is `time` seconds into the run: with a flicker, its intensity scaled and
+    // its position (and box, for area lights) shifted by it; otherwise unchanged
+    pub fn flickered(&self, time: f32) -> Light {
+        let Some(flicker) = self.flicker else {
+            return *self;
+        };
+        let offset = flicker.jitter(time);
+        let kind = match self.kind {
+            LightKind::Area { min, max } => LightKind::Area { min: min + offset, max: max + offset },
+            kind => kind,
+        };
+        Light { position: self.position + offset, intensity: self.intensity * flicker.intensity(time), kind, ..*self }
+    }
+
+    // Unit vector from `point` toward the light and the distance to it
+    // (infinite for directional lights)
+    pub fn direction_from(&self, point: &Vec3) -> (Vec3, f32) {
+        match self.kind {
+            LightKind::Point | LightKind::Spot { .. } | LightKind::Area { .. } => {
+                let to_light = self.position - point;
+                let distance = to_light.magnitude();
+                (to_light / distance, distance)
+            }
+            LightKind::Directional { direction } => (-direction, f32::INFINITY),
+        }
+    }
+
+    // Distance from `point` along `direction` (unit) to where the ray enters an area light's
+    // box; None for other kinds of light. Shadow rays toward the box's center stop there, or
+    // the opaque emitter would block its own light
+    pub fn entry_distance(&self, point: &Vec3, direction: &Vec3) -> Option<f32> {
+        let LightKind::Area { min, max } = self.kind else {
+            return None;
+        };
+        let mut near = 0.0f32;
+        for axis in 0..3 {
+            if direction[axis] != 0.0 {
+                let t1 = (min[axis] - point[axis]) / direction[axis];
+                let t2 = (max[axis] - point[axis]) / direction[axis];
+                near = near.max(t1.min(t2));
+            }
+        }
+        Some(near)
+    }
+
+    // Whether the light has anything left at `distance`: inside its range and not faded to
+    // zero there. Shading skips the lights (and their shadow rays) that don't
+    pub fn reaches(&self, distance: f32) -> bool {
+        match self.kind {
+            LightKind::Directional { .. } => true,
+            _ => distance <= self.radius && self.attenuation(distance) > 0.0,
+        }
+    }
+
+    // Share of the intensity left at `distance`, following `attenuation`
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if let LightKind::Directional { .. } = self.kind {
+            return 1.0;
+        }
+        let d = distance;
+        match self.attenuation {
+            Attenuation::RadiusScaled => 1.0 / (1.0 + d * d / (self.radius * self.radius)),
+            Attenuation::None => 1.0,
+            Attenuation::Linear => (1.0 - d / self.radius).max(0.0),
+            Attenuation::InverseSquare { constant, linear, quadratic } => 1.0 / (constant + linear * d + quadratic * d * d).max(1e-6),
+            Attenuation::SmoothWindowed => {
+                let window = (1.0 - (d / self.radius).powi(4)).clamp(0.0, 1.0);
+                window * window / (1.0 + d * d)
+            }
+        }
     }
-}
\ No newline at end of file
+
+    // Share of the intensity left by the cone for a point seen along `to_light`
+    // (unit vector from the point toward the light); 1.0 for lights without a cone
+    pub fn cone(&self, to_light: &Vec3) -> f32 {
+        match self.kind {
+            LightKind::Spot { direction, inner, outer } => {
+                let cos_angle = (-to_light).dot(&direction);
+                let (cos_inner, cos_outer) = (inner.cos(), outer.cos());
+                let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-6)).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+            _ => 1.0,
+        }
+    }
+
+    // Faces of an area light's box that `point` is in front of; the others can't
+    // send it any light. At most one per axis, and none for other kinds of light
+    pub fn faces_toward(&self, point: &Vec3) -> [Option<AreaFace>; 3] {
+        let LightKind::Area { min, max } = self.kind else {
+            return [None, None, None];
+        };
+        let size = max - min;
+
+        std::array::from_fn(|axis| {
+            let mut edge_u = Vec3::zeros();
+            let mut edge_v = Vec3::zeros();
+            edge_u[(axis + 1) % 3] = size[(axis + 1) % 3];
+            edge_v[(axis + 2) % 3] = size[(axis + 2) % 3];
+            let mut normal = Vec3::zeros();
+            let mut corner = min;
+
+            if point[axis] < min[axis] {
+                normal[axis] = -1.0;
+            } else if point[axis] > max[axis] {
+                normal[axis] = 1.0;
+                corner[axis] = max[axis];
+            } else {
+                return None;
+            }
+            Some(AreaFace { corner, edge_u, edge_v, normal })
+        })
+    }
+}
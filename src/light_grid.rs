@@ -0,0 +1,89 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use crate::light::{Light, LightKind};
+
+// Side of the cells lights are sorted into
+pub const LIGHT_CELL_SIZE: f32 = 2.0;
+
+// A light whose range covers more cells than this goes in every cell's list instead
+const MAX_CELLS_PER_LIGHT: i64 = 4096;
+
+// Slack on each light's radius so a point on a cell's boundary is never left out by rounding
+const RADIUS_MARGIN: f32 = 1e-3;
+
+// Which lights can reach each part of space. A cell of LIGHT_CELL_SIZE lists the lights whose
+// range (the sphere of `radius` around them) touches it; lights with no range (directional)
+// or a huge one are in every list. A light missing from a cell's list can't reach any point
+// in it, so shading a hit there only has to look at the listed ones. Lists keep the order of
+// the light list, so the shading sums (and the random numbers used) don't change.
+// It is built for a given light list and must be rebuilt when the lights move
+pub struct LightGrid {
+    cells: HashMap<[i32; 3], Vec<usize>>,
+    everywhere: Vec<usize>, // Lights in every list, and the whole list of empty cells
+    light_count: usize,
+}
+
+fn cell_of(point: &Vec3) -> [i32; 3] {
+    [point.x, point.y, point.z].map(|v| (v / LIGHT_CELL_SIZE).floor() as i32)
+}
+
+// Squared distance from `point` to the box of `cell`
+fn distance_to_cell_squared(point: &Vec3, cell: [i32; 3]) -> f32 {
+    [point.x, point.y, point.z].iter().zip(cell)
+        .map(|(v, c)| {
+            let low = c as f32 * LIGHT_CELL_SIZE;
+            let nearest = v.clamp(low, low + LIGHT_CELL_SIZE);
+            (v - nearest) * (v - nearest)
+        })
+        .sum()
+}
+
+impl LightGrid {
+    pub fn new(lights: &[Light]) -> Self {
+        let mut everywhere = Vec::new();
+        let mut local: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+
+        for (index, light) in lights.iter().enumerate() {
+            let radius = light.radius + RADIUS_MARGIN;
+            let low = cell_of(&(light.position - Vec3::repeat(radius)));
+            let high = cell_of(&(light.position + Vec3::repeat(radius)));
+            let count = (0..3).try_fold(1_i64, |count, axis| count.checked_mul(high[axis] as i64 - low[axis] as i64 + 1));
+            let bounded = !matches!(light.kind, LightKind::Directional { .. }) && radius.is_finite();
+            if !bounded || count.is_none_or(|count| count > MAX_CELLS_PER_LIGHT) {
+                everywhere.push(index);
+                continue;
+            }
+
+            for x in low[0]..=high[0] {
+                for y in low[1]..=high[1] {
+                    for z in low[2]..=high[2] {
+                        let cell = [x, y, z];
+                        if distance_to_cell_squared(&light.position, cell) <= radius * radius {
+                            local.entry(cell).or_default().push(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Each cell's list gets the lights that are everywhere, merged in list order
+        let cells = local.into_iter()
+            .map(|(cell, mut list)| {
+                list.extend_from_slice(&everywhere);
+                list.sort_unstable();
+                (cell, list)
+            })
+            .collect();
+        LightGrid { cells, everywhere, light_count: lights.len() }
+    }
+
+    // Indices of the lights that may reach `point`, in increasing order
+    pub fn lights_at(&self, point: &Vec3) -> &[usize] {
+        self.cells.get(&cell_of(point)).unwrap_or(&self.everywhere)
+    }
+
+    // Length of the light list the grid was built for
+    pub fn light_count(&self) -> usize {
+        self.light_count
+    }
+}
@@ -1,405 +1,576 @@
-use nalgebra_glm::{Vec3, normalize};
-use minifb::{Key, Window, WindowOptions};
+use nalgebra_glm::Vec3;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use std::time::Duration;
 use std::f32::consts::PI;
-use rayon::prelude::*;
-
-
-mod framebuffer;
-mod ray_intersect;
-mod color;
-mod camera;
-mod light;
-mod material;
-mod cube;
-mod texture;
-mod obj_loader;
-
-use obj_loader::ObjModel;
-use framebuffer::Framebuffer;
-use color::Color;
-use ray_intersect::{Intersect, RayIntersect, CubeFace};
-use camera::Camera;
-use light::Light;
-use crate::cube::Cube;
-use crate::material::Material;
-use texture::Texture;
-
-
-const ORIGIN_BIAS: f32 = 1e-4;
-const SKYBOX_COLOR: Color = Color::new(68, 142, 228);
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use raytracer::obj_loader::ObjModel;
+use raytracer::vox_loader::{self, VoxModel};
+use raytracer::framebuffer::Framebuffer;
+use raytracer::font::GLYPH_SIZE;
+use raytracer::color::Color;
+use raytracer::camera::{cubemap_cameras, Camera, CameraControls, CameraMode, CameraMove};
+use raytracer::camera_path::CameraPath;
+use raytracer::bookmarks::Bookmarks;
+use raytracer::light::{Attenuation, Light, LightKind};
+use raytracer::material::{Material, MaterialId, MaterialRegistry};
+use raytracer::sphere::Sphere;
+use raytracer::cube::Cube;
+use raytracer::object::Object;
+use raytracer::plane::Plane;
+use raytracer::mesh::Mesh;
+use raytracer::bvh::Bvh;
+use raytracer::scene_builder::SceneBuilder;
+use raytracer::house::{house, portal_emission, House};
+use raytracer::voxel_grid::BLOCK_SIZE;
+use raytracer::terrain::{self, HeightmapSettings, TerrainSettings};
+use raytracer::chunk::ChunkManager;
+use raytracer::world::{Pick, World};
+use raytracer::edit_history::{Edit, EditHistory};
+use raytracer::environment::{Environment, EquirectEnv};
+use raytracer::day_night::DayNightCycle;
+use raytracer::sky::Sky;
+use raytracer::{Projection, RenderMode, RenderSettings, Renderer, Scene, UpscaleFilter};
+use raytracer::renderer::{primary_direction, project, take_ray_counts, FrameTimes, RayCounts};
+use raytracer::ray_intersect::Intersect;
+use raytracer::texture::{Skybox, TextureCache};
+use raytracer::scene_file;
+use raytracer::screenshot::{self, Recorder, RecordingSummary};
 
 
-fn offset_origin(intersect: &Intersect, direction: &Vec3) -> Vec3 {
-    let offset = intersect.normal * ORIGIN_BIAS;
-    if direction.dot(&intersect.normal) < 0.0 {
-        intersect.point - offset
-    } else {
-        intersect.point + offset
-    }
+const SKYBOX_COLOR: Color = Color::new(68, 142, 228);
+// Recorrido de cámara grabado con J, en la carpeta desde la que se ejecuta el programa
+const CAMERA_PATH_FILE: &str = "camera_path.json";
+// Escena guardada con Ctrl+S cuando --scene no es un .json, en la carpeta de trabajo
+const SCENE_SAVE_FILE: &str = "scene.json";
+// Lado de cada vóxel de un modelo .vox: un modelo de 32 vóxeles mide lo mismo que la casa
+const VOXEL_SIZE: f32 = 0.1;
+// Carpeta de las capturas de F12, dentro de la carpeta de trabajo
+const SCREENSHOT_DIR: &str = "screenshots";
+// Carpeta de las grabaciones de F9: cada una en una subcarpeta con la fecha y la hora
+const RECORDING_DIR: &str = "recordings";
+// Marcadores de cámara (Ctrl+1..9), junto al ejecutable
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+// Teclas de los marcadores, en el orden de sus números
+const BOOKMARK_KEYS: [Key; 9] = [
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+];
+
+const USAGE: &str = "\
+Uso: CC2028-Proyecto2 [opciones]
+
+Imagen y escena:
+  --width <n>                  Ancho de la ventana y de la imagen en píxeles (por defecto 800)
+  --height <n>                 Alto de la ventana y de la imagen en píxeles (por defecto 600)
+  --scene <archivo>            Reemplaza la casa por un modelo .obj (con su .mtl) o .vox
+                               (MagicaVoxel), o por un relieve de bloques desde una imagen
+                               en escala de grises (.png, .jpg...), sobre el césped. Con un
+                               .json guardado con Ctrl+S abre esa escena tal cual
+  --heightmap-height <n>       Bloques de alto del blanco en un relieve (por defecto 16)
+  --assets <carpeta>           Carpeta de las texturas de los bloques (por defecto ./assets)
+  --headless <salida.png>      Traza sin abrir ventana y guarda la imagen (mostrando el avance)
+  --frames <n>                 Cuadros que se promedian con --headless (por defecto 16)
+  --panorama                   Con --headless, una panorámica equirectangular de 360° desde
+                               la cámara; el alto pasa a ser la mitad de --width
+  --cubemap <carpeta>          Traza las seis caras de un cubemap desde la cámara (px.png,
+                               nx.png, py.png...) de --width de lado, para usarlas de skybox
+
+Calidad:
+  --samples <n>                Antialiasing: n x n rayos por píxel (por defecto 1)
+  --max-depth <n>              Rebotes de reflejo y refracción (por defecto 3)
+  --glossy-samples <n>         Rayos por reflejo rugoso o refracción esmerilada (por defecto 1)
+  --shadow-bias <valor>        Separación de los rayos de sombra (por defecto 0.001)
+
+Rendimiento:
+  --motion-scale <n>           Reducción de resolución con la cámara en movimiento: 1, 2 o 4
+  --upscale <filtro>           Cómo se agranda esa imagen: nearest o bilinear
+  --tile-size <n>              Lado de los bloques que se reparten entre los hilos (por defecto 32)
+  --tile-stats                 Tiempos de los bloques en la consola
+  --no-light-merge             Una luz por bloque emisivo, sin juntar los que se tocan
+  --no-light-culling           Cada punto mira todas las luces, no solo las que le llegan
+
+Cámara:
+  --bookmark-transition <s>    Segundos para llegar a un marcador; 0 salta directo (por defecto 0.5)
+
+Escenas de prueba:
+  --acne-test                  Dos bloques que se tocan (acné de sombras)
+  --dielectric-test            Vidrio dentro de agua (medios anidados)
+  --mirror-test                Bloque entre dos espejos
+  --attenuation-test           Tres faroles iguales con distinta atenuación (de noche)
+  --many-lights-test           Cien bloques de glowstone sueltos (de noche), para medir
+  --shadow-catcher-test        La casa flotando sobre el skybox, con el suelo invisible salvo
+                               por sus sombras
+
+  -h, --help                   Muestra esta ayuda
+";
+
+// Termina con un mensaje en vez de un panic cuando las opciones no sirven
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("Usa --help para ver las opciones");
+    std::process::exit(2);
 }
 
-
-fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
-    incident - 2.0 * incident.dot(normal) * normal
+// Lo que decide cómo se ve un cuadro, aparte de las opciones de render: si es igual al del
+// cuadro anterior y el promedio de cuadros ya no mejora, no hace falta volver a trazar
+#[derive(PartialEq)]
+struct FrameState {
+    view: (Vec3, Vec3, f32, f32), // Ojo, centro, apertura y enfoque de la cámara
+    day_time: f32,
+    lights: Vec<Light>,            // El portal que respira cambia su luz
+    animation_frames: Vec<usize>,  // Cuadro de cada objeto con textura animada
+    sky_time: f32,                 // Las nubes del cielo procedural se mueven con el tiempo
+    wave_time: f32,                // Y las olas del agua
+    environment: usize,
+    window_size: (usize, usize),
+    selection: Option<usize>,      // Objeto marcado con clic derecho
 }
 
+// Escena de prueba para el acné de sombras (--acne-test): dos bloques sueltos que comparten
+// una cara, sobre el suelo. Con un bias bien puesto sus caras se ven lisas, sin puntos oscuros
+// junto a la unión, con el sol a cualquier altura
+fn acne_test_scene(block: MaterialId, ground: MaterialId) -> Vec<Object> {
+    let mut scene = SceneBuilder::new();
+    scene.cube(Vec3::new(-0.5, 0.0, -0.25), Vec3::new(0.0, 0.5, 0.25), block)
+        .cube(Vec3::new(0.0, 0.0, -0.25), Vec3::new(0.5, 0.5, 0.25), block);
+
+    let mut objects = scene.build();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    objects
+}
 
-fn refract(incident: &Vec3, normal: &Vec3, eta_t: f32) -> Vec3 {
-    // Limitar el coseno entre -1 y 1 con paréntesis explícitos
-    let mut cosi = incident.dot(normal);
-    cosi = cosi.max(-1.0).min(1.0);
-
-    // Variables mutables con tipos explícitos
-    let mut n_cosi: f32 = 0.0;
-    let mut eta: f32 = 1.0;
-    let mut n_normal: Vec3 = *normal;
-
-    if cosi < 0.0 {
-        // Ray is entering the object
-        n_cosi = -cosi;
-        eta = 1.0 / eta_t;
-        n_normal = -(*normal);
-    } else {
-        // Ray is leaving the object
-        n_cosi = cosi;
-        eta = eta_t;
-        n_normal = *normal;
-    }
-
-    let k = 1.0 - eta * eta * (1.0 - n_cosi * n_cosi);
-
-    if k < 0.0 {
-        // Total internal reflection -> devolver el vector reflejado
-        reflect(incident, &n_normal)
-    } else {
-        // Fórmula de refracción (Snell)
-        eta * *incident + (eta * n_cosi - k.sqrt()) * n_normal
-    }
+// Escena de prueba de medios anidados (--dielectric-test): un bloque de vidrio sumergido en
+// un bloque de agua y otro pegado a su costado, sobre el suelo. Los rayos que cruzan de
+// agua a vidrio y de vidrio a agua tienen que verse sin manchas negras
+fn dielectric_test_scene(glass: MaterialId, water: MaterialId, ground: MaterialId) -> Vec<Object> {
+    let mut scene = SceneBuilder::new();
+    scene.cube(Vec3::new(-0.75, 0.0, -0.5), Vec3::new(0.75, 0.75, 0.5), water)
+        .cube(Vec3::new(-0.3, 0.15, -0.2), Vec3::new(0.3, 0.55, 0.2), glass)
+        .cube(Vec3::new(0.75, 0.0, -0.25), Vec3::new(1.25, 0.5, 0.25), glass);
+
+    let mut objects = scene.build();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    objects
 }
 
+// Escena de prueba de espejos enfrentados (--mirror-test): un bloque entre dos espejos
+// paralelos, sobre el suelo. Las copias del bloque se repiten hasta el límite de rebotes y el
+// pasillo termina en el color del cielo, sin brillos azules en las esquinas
+fn mirror_test_scene(mirror: MaterialId, block: MaterialId, ground: MaterialId) -> Vec<Object> {
+    let mut scene = SceneBuilder::new();
+    scene.cube(Vec3::new(-1.5, 0.0, -1.0), Vec3::new(-1.4, 1.2, 1.0), mirror)
+        .cube(Vec3::new(1.4, 0.0, -1.0), Vec3::new(1.5, 1.2, 1.0), mirror)
+        .cube(Vec3::new(-0.25, 0.0, -0.25), Vec3::new(0.25, 0.5, 0.25), block);
+
+    let mut objects = scene.build();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    objects
+}
 
-fn cast_shadow(intersect: &Intersect, light: &Light, objects: &[Cube]) -> f32 {
-    let light_dir = light.position - intersect.point;
-    let distance_to_light = light_dir.magnitude();
-    
-    if distance_to_light > light.radius {
-        return 1.0;
+// Escena de prueba de atenuación (--attenuation-test): tres postes iguales sobre el suelo, cada
+// uno con el mismo farol encima. Solo cambia cómo se apaga su luz con la distancia: de
+// izquierda a derecha RadiusScaled (la de siempre, que corta de golpe en el radio),
+// InverseSquare y SmoothWindowed. Devuelve los objetos y los faroles
+fn attenuation_test_scene(post: MaterialId, ground: MaterialId) -> (Vec<Object>, Vec<Light>) {
+    let models = [
+        Attenuation::RadiusScaled,
+        Attenuation::InverseSquare { constant: 1.0, linear: 0.5, quadratic: 2.0 },
+        Attenuation::SmoothWindowed,
+    ];
+    let mut scene = SceneBuilder::new();
+    let mut lamps = Vec::new();
+    for (i, attenuation) in models.into_iter().enumerate() {
+        let x = (i as f32 - 1.0) * 1.5;
+        scene.cube(Vec3::new(x - 0.05, 0.0, -0.05), Vec3::new(x + 0.05, 0.5, 0.05), post);
+        lamps.push(Light::new(Vec3::new(x, 0.65, 0.0), Color::new(255, 210, 150), 2.0, 1.5).with_attenuation(attenuation));
     }
 
-    let light_dir = light_dir.normalize();
-    let shadow_ray_origin = intersect.point + light_dir * 0.001;
+    let mut objects = scene.build();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    (objects, lamps)
+}
 
-    for object in objects {
-        let shadow_intersect = object.intersect(&shadow_ray_origin, &light_dir);
-        if shadow_intersect.is_intersecting && shadow_intersect.distance < distance_to_light {
-            return 0.95; // Permitimos que algo de luz pase a través de los objetos
+// Escena de prueba de muchas luces (--many-lights-test): cien bloques de glowstone en una
+// cuadrícula de 10 x 10 sobre el suelo, separados para que no se junten en una sola luz. Cada
+// uno alcanza solo a sus vecinos, así que se nota lo que ahorra mirar solo las luces cercanas
+fn many_lights_test_scene(glowstone: MaterialId, ground: MaterialId) -> Vec<Object> {
+    let mut scene = SceneBuilder::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            let (x, z) = ((i as f32 - 4.5) * 5.0, (j as f32 - 4.5) * 5.0);
+            scene.cube(Vec3::new(x - 0.15, 0.0, z - 0.15), Vec3::new(x + 0.15, 0.3, z + 0.15), glowstone);
         }
     }
 
-    0.0
+    let mut objects = scene.build();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    objects
 }
 
+// Fecha de la última modificación de un archivo; None si no se puede leer
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
 
-pub fn cast_ray(
-    ray_origin: &Vec3,
-    ray_direction: &Vec3,
-    objects: &[Cube],
-    lights: &[Light],
-    ambient_color: &Color,
-    depth: u32,
-) -> Color {
-    if depth > 3 {
-        return SKYBOX_COLOR;
-    }
-
-
-    let mut intersect = Intersect::empty();
-    let mut zbuffer = f32::INFINITY;
+// Escena cargada con --scene: el modelo .obj, con los materiales de su .mtl (las caras sin
+// material usan `material`), parado sobre el suelo
+fn obj_scene(model: &ObjModel, material: MaterialId, ground: MaterialId, materials: &mut MaterialRegistry) -> Vec<Object> {
+    vec![
+        Mesh::from_obj(model, Vec3::new(0.0, 0.0, 0.0), 1.0, material, materials).into(),
+        Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into(),
+    ]
+}
 
+// Escena cargada con --scene desde un modelo .vox de MagicaVoxel: un bloque por vóxel (los
+// iguales que quedan seguidos se juntan), centrado y parado sobre el suelo. Los índices de la
+// paleta que aparecen en `<modelo>.vox.emissive` brillan con su propio color
+fn vox_scene(path: &Path, ground: MaterialId, materials: &mut MaterialRegistry) -> Result<Vec<Object>, vox_loader::VoxError> {
+    let model = VoxModel::load(path)?;
+    let mut emissive_file = path.as_os_str().to_owned();
+    emissive_file.push(".emissive");
+    let emissive = vox_loader::load_emissive_indices(Path::new(&emissive_file))?;
+
+    let (cubes, offset) = model.to_cubes(VOXEL_SIZE, &emissive, true, materials);
+    let mut objects: Vec<Object> = cubes.into_iter()
+        .map(|cube| Cube::new(cube.min + offset, cube.max + offset, cube.material).into())
+        .collect();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    Ok(objects)
+}
 
-    for object in objects {
-        let i = object.ray_intersect(ray_origin, ray_direction);
-        if i.is_intersecting && i.distance < zbuffer {
-            zbuffer = i.distance;
-            intersect = i;
-        }
-    }
+// Escena cargada con --scene desde una imagen: un relieve de bloques de césped con piedra
+// debajo, centrado sobre el suelo
+fn heightmap_scene(path: &Path, heightmap: &HeightmapSettings, grass: MaterialId, stone: MaterialId) -> Result<Vec<Object>, image::ImageError> {
+    let mut objects: Vec<Object> = terrain::from_heightmap(path, heightmap, grass, stone)?
+        .into_iter()
+        .map(Object::from)
+        .collect();
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), grass).with_tile_size(0.5).into());
+    Ok(objects)
+}
 
+// Si el archivo es una escena guardada con Ctrl+S
+fn is_scene_file(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
 
-    if !intersect.is_intersecting {
-        // Simular el color del cielo basado en la dirección del rayo y la posición del sol
-        let sun_dir = lights[0].position.normalize();
-        let sun_intensity = ray_direction.dot(&sun_dir).max(0.0).powf(20.0);
-        let sky_color = ambient_color.mul_scalar(0.5); // Color base del cielo
-        let sun_color = Color::new(255, 255, 200).mul_scalar(sun_intensity); // Color del sol
-        return sky_color + sun_color;
+// Lee los objetos del archivo de --scene según su extensión: .json es una escena guardada
+// (sus materiales se registran por nombre), .vox es de MagicaVoxel, las imágenes son relieves
+// y todo lo demás se trata como .obj
+fn load_scene(path: &str, heightmap: &HeightmapSettings, material: MaterialId, ground: MaterialId, materials: &mut MaterialRegistry, textures: &mut TextureCache) -> Result<Vec<Object>, String> {
+    let file = Path::new(path);
+    if is_scene_file(path) {
+        scene_file::load(file, materials, textures).map(|loaded| loaded.objects).map_err(|e| e.to_string())
+    } else if file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("vox")) {
+        vox_scene(file, ground, materials).map_err(|e| e.to_string())
+    } else if image::ImageFormat::from_path(file).is_ok() {
+        heightmap_scene(file, heightmap, ground, material).map_err(|e| e.to_string())
+    } else {
+        ObjModel::load(path).map(|model| obj_scene(&model, material, ground, materials)).map_err(|e| e.to_string())
     }
+}
 
-
-    let material_color = if !intersect.material.textures.is_empty() {
-        let texture_index = match &intersect.material.color {
-            color if *color == Color::new(0, 255, 0) => {
-                // Esto es césped
-                match intersect.face {
-                    CubeFace::Top => 0, // Textura de césped para la cara superior
-                    _ => 1, // Textura de tierra para las otras caras
-                }
-            },
-            color if *color == Color::new(128, 128, 128) => {
-                // Esto es piedra, usa la textura de piedra para todas las caras
-                0 // Asumiendo que la textura de piedra es la primera (y única) en el vector de texturas
-            },
-            _ => 0, // Para otros materiales, usa la primera textura
-        };
-        let (u, v) = intersect.texture_coords();
-        // Escala u y v para que coincidan con el tamaño de unidad de 0.5
-        let scaled_u = (u * 2.0) % 1.0;
-        let scaled_v = (v * 2.0) % 1.0;
-        intersect.material.textures[texture_index].sample(scaled_u, scaled_v)
-    } else {
-        intersect.material.color
+// Escribe en la consola lo que se eligió con el clic derecho: el objeto, dónde lo tocó el
+// rayo y su material
+fn print_selection(index: usize, object: &Object, hit: &Intersect, materials: &MaterialRegistry) {
+    let kind = match object {
+        Object::Cube(_) => "bloque",
+        Object::Sphere(_) => "esfera",
+        Object::Plane(_) => "plano",
+        Object::Mesh(_) => "malla",
     };
+    let (min, max) = object.bounds();
+    println!(
+        "Objeto {} ({}): de ({:.2}, {:.2}, {:.2}) a ({:.2}, {:.2}, {:.2}), tocado en ({:.2}, {:.2}, {:.2}) cara {:?}",
+        index, kind, min.x, min.y, min.z, max.x, max.y, max.z, hit.point.x, hit.point.y, hit.point.z, hit.face,
+    );
+    let material = materials.get(object.material());
+    println!(
+        "  Material {}: color #{:06x}, difuso {:.2}, especular {:.2}, brillo {:.0}, reflejo {:.2}, rugosidad {:.2}, transparencia {:.2}, índice {:.2}, emisión #{:06x} x{}, {} texturas",
+        materials.name(object.material()).unwrap_or("sin nombre"), material.color.to_hex(), material.diffuse, material.specular,
+        material.shininess, material.reflectivity, material.roughness, material.transparency, material.refractive_index,
+        material.emission.to_hex(), material.emission_strength, material.textures.len(),
+    );
+}
 
+// La luz elegida con F5/F6 para los interruptores de depuración: su número, qué tipo de luz es
+// y si está apagada o aislada (el sol y la luna son siempre la 0 y la 1)
+fn light_label(scene: &Scene) -> String {
+    let index = scene.selected_light;
+    let kind = match scene.lights[index].kind {
+        LightKind::Directional { .. } if index == 0 => "sol",
+        LightKind::Directional { .. } if index == 1 => "luna",
+        LightKind::Directional { .. } => "direccional",
+        LightKind::Point => "puntual",
+        LightKind::Spot { .. } => "foco",
+        LightKind::Area { .. } => "de área",
+    };
+    format!(
+        "Luz {}/{} ({}){}{}",
+        index, scene.lights.len(), kind,
+        if scene.is_light_disabled(index) { ", apagada" } else { "" },
+        if scene.isolate_light { ", aislada" } else { "" },
+    )
+}
 
-    let is_glowstone = intersect.material.emission != Color::new(0, 0, 0);
-
-    let mut final_color = Color::new(0, 0, 0);
-
-    for light in lights {
-        let light_dir = light.position - intersect.point;
-        let distance_to_light = light_dir.magnitude();
-        
-        if distance_to_light <= light.radius {
-            let light_dir = light_dir.normalize();
-            let shadow_intensity = cast_shadow(&intersect, light, objects);
-            if shadow_intensity < 1.0 {
-                let attenuation = 1.0 / (1.0 + distance_to_light * distance_to_light / (light.radius * light.radius));
-                let light_intensity = (1.0 - shadow_intensity) * light.intensity * attenuation;
-
-                let diffuse_intensity = intersect.normal.dot(&light_dir).max(0.0);
-                let diffuse = material_color.mul(&light.color).mul_scalar(intersect.material.properties[0] * diffuse_intensity * light_intensity);
-
-                let view_dir = (ray_origin - intersect.point).normalize();
-                let halfway = (light_dir + view_dir).normalize();
-                let specular_intensity = halfway.dot(&intersect.normal).max(0.0).powf(intersect.material.shininess);
-                let specular = light.color.mul_scalar(intersect.material.properties[1] * specular_intensity * light_intensity);
+// Texto arriba a la izquierda, con una sombra negra un píxel más abajo para que se lea también
+// sobre el cielo claro
+fn draw_hud(framebuffer: &mut Framebuffer, text: &str) {
+    framebuffer.draw_text(5, 5, text, Color::new(0, 0, 0));
+    framebuffer.draw_text(4, 4, text, Color::new(255, 255, 255));
+}
 
-                if is_glowstone {
-                    // Para el glowstone, usamos más el color de la textura y menos la iluminación calculada
-                    final_color = final_color + material_color.mul_scalar(0.7) + (diffuse + specular).mul_scalar(0.3);
-                } else {
-                    final_color = final_color + diffuse + specular;
-                }
-            }
-        }
+// Al terminar una grabación: dónde quedaron los cuadros y cuántos
+fn print_recording_summary(summary: &RecordingSummary) {
+    println!("Grabación terminada: {} cuadros en {}", summary.written, summary.dir.display());
+    if summary.dropped > 0 {
+        println!("Se saltaron {} cuadros porque el disco no daba abasto", summary.dropped);
     }
-
-    // Añadimos la emisión de luz del material
-    if is_glowstone {
-        // Para el glowstone, mezclamos la emisión con el color de la textura
-        final_color = final_color.mul_scalar(0.6) + material_color.mul_scalar(0.4);
-    } else {
-        final_color = final_color + intersect.material.emission;
+    if summary.failed > 0 {
+        eprintln!("No se pudieron escribir {} cuadros", summary.failed);
     }
+}
 
-    // Añade iluminación ambiental
-    let ambient = material_color.mul(ambient_color).mul_scalar(0.1);
-    final_color = final_color + ambient;
-
-    // Color reflejado
-    let mut reflect_color = Color::black();
-    let reflectivity = intersect.material.properties[2];
-    if reflectivity > 0.0 {
-        let reflect_dir = normalize(&reflect(&ray_direction, &intersect.normal));
-        let reflect_origin = offset_origin(&intersect, &reflect_dir);
-        reflect_color = cast_ray(&reflect_origin, &reflect_dir, objects, lights, ambient_color, depth + 1);
-    }
+// Desglose de los rayos de un cuadro (tecla F4): cuántos de cada tipo y a qué ritmo se trazaron
+fn print_ray_counts(counts: RayCounts, render_time: Duration) {
+    let total = counts.total().max(1) as f64;
+    let share = |count: u64| 100.0 * count as f64 / total;
+    println!(
+        "Rayos: {} en {:.1} ms ({:.2} millones/s): cámara {} ({:.0}%), sombra {} ({:.0}%), rebote {} ({:.0}%)",
+        counts.total(),
+        render_time.as_secs_f64() * 1000.0,
+        counts.total() as f64 / render_time.as_secs_f64().max(1e-9) / 1e6,
+        counts.primary, share(counts.primary),
+        counts.shadow, share(counts.shadow),
+        counts.secondary, share(counts.secondary),
+    );
+}
 
+// Bloque de la rejilla junto al punto que tocó un rayo, del lado de afuera de la superficie
+// (el suelo, el terreno o algo que no es un bloque)
+fn grid_block(hit: &Intersect, material: MaterialId) -> Cube {
+    let cell = (hit.point + hit.normal * (BLOCK_SIZE * 0.5)) / BLOCK_SIZE;
+    let min = cell.map(f32::floor) * BLOCK_SIZE;
+    Cube::new(min, min + Vec3::repeat(BLOCK_SIZE), material)
+}
 
-    // Color refractado
-    let mut refract_color = Color::black();
-    let transparency = intersect.material.properties[3];
-    if transparency > 0.0 {
-        let refract_dir = normalize(&refract(&ray_direction, &intersect.normal, intersect.material.refractive_index));
-        let refract_origin = offset_origin(&intersect, &refract_dir);
-        refract_color = cast_ray(&refract_origin, &refract_dir, objects, lights, ambient_color, depth + 1);
+// Dibuja sobre la imagen el segmento entre dos puntos de la escena. La parte que pasa por
+// detrás de la cámara se corta antes de proyectarla
+fn draw_segment(framebuffer: &mut Framebuffer, camera: &Camera, fov: f32, a: &Vec3, b: &Vec3) {
+    const NEAR: f32 = 0.01;
+    let forward = camera.forward();
+    let depth = |p: &Vec3| (p - camera.eye).dot(&forward);
+    let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+
+    let (mut a, mut b) = (*a, *b);
+    let (da, db) = (depth(&a), depth(&b));
+    if da < NEAR && db < NEAR {
+        return;
+    }
+    if da < NEAR {
+        a = a + (b - a) * ((NEAR - da) / (db - da));
+    } else if db < NEAR {
+        b = b + (a - b) * ((NEAR - db) / (da - db));
+    }
+    if let (Some(from), Some(to)) = (project(camera, fov, width, height, &a), project(camera, fov, width, height, &b)) {
+        framebuffer.line(from, to);
     }
-
-
-    // Combinación de los colores difuso, especular, reflejado, refractado y emitido
-    let final_color = final_color * (1.0 - reflectivity - transparency) + 
-    (reflect_color * reflectivity) + 
-    (refract_color * transparency);
-
-
-    final_color
 }
 
+// Marca un objeto elegido dibujando las aristas de su caja sobre la imagen
+fn draw_box_edges(framebuffer: &mut Framebuffer, camera: &Camera, fov: f32, min: &Vec3, max: &Vec3) {
+    let corner = |i: usize| Vec3::new(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z },
+    );
 
-
-
-pub fn render(framebuffer: &mut Framebuffer, objects: &[Cube], camera: &Camera, lights: &[Light], ambient_color: &Color) {
-    let width = framebuffer.width as f32;
-    let height = framebuffer.height as f32;
-    let aspect_ratio = width / height;
-    let fov = PI / 3.0;
-    let perspective_scale = (fov * 0.5).tan();
-
-
-
-
-    // Crea un búfer temporal para almacenar los colores de los píxeles
-    let mut pixel_buffer = vec![0u32; (framebuffer.width * framebuffer.height) as usize];
-
-
-
-
-    // Utiliza paralelización para calcular los colores
-    pixel_buffer
-        .par_iter_mut()  // Iterador paralelo sobre el búfer
-        .enumerate()
-        .for_each(|(index, pixel)| {
-            let x = (index % framebuffer.width as usize) as u32;
-            let y = (index / framebuffer.width as usize) as u32;
-
-
-
-
-            let screen_x = (2.0 * x as f32) / width - 1.0;
-            let screen_y = -(2.0 * y as f32) / height + 1.0;
-
-
-
-
-            let screen_x = screen_x * aspect_ratio * perspective_scale;
-            let screen_y = screen_y * perspective_scale;
-
-
-
-
-            let ray_direction = normalize(&Vec3::new(screen_x, screen_y, -1.0));
-            let rotated_direction = camera.basis_change(&ray_direction);
-
-
-
-
-            let pixel_color = cast_ray(&camera.eye, &rotated_direction, objects, lights, ambient_color, 0);
-
-
-
-
-            // Asigna el color calculado en el buffer de píxeles
-            *pixel = pixel_color.to_hex();
-        });
-
-
-
-
-    // Finalmente, vuelca el pixel_buffer en el framebuffer
-    for (index, &pixel) in pixel_buffer.iter().enumerate() {
-        let x = (index % framebuffer.width as usize) as u32;
-        let y = (index / framebuffer.width as usize) as u32;
-        framebuffer.set_current_color(pixel);
-        framebuffer.point(x as usize, y as usize);
+    // Las esquinas i y j forman una arista cuando difieren en un solo eje
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            let j = i | axis;
+            if j != i {
+                draw_segment(framebuffer, camera, fov, &corner(i), &corner(j));
+            }
+        }
     }
 }
 
-fn generate_lights_from_emissive_materials(objects: &[Cube]) -> Vec<Light> {
-    let mut lights = Vec::new();
-    for cube in objects {
-        if cube.material.emission != Color::new(0, 0, 0) {
-            let position = (cube.min + cube.max) * 0.5;
-            let intensity = cube.material.emission.intensity() * 0.1;
-            let radius = (cube.max - cube.min).magnitude() * 2.0;
-            lights.push(Light {
-                position,
-                color: cube.material.emission,
-                intensity,
-                radius,
-            });
+// Esquinas de la cara del bloque que queda bajo la cruz del centro: la de un bloque de la
+// escena o la de la celda del terreno que tocó el rayo. None si el rayo del centro no da con
+// un bloque (el cielo, una esfera, un modelo) o sale desde dentro de uno
+fn aimed_face(scene: &Scene, camera: &Camera, fov: f32, width: f32, height: f32) -> Option<[Vec3; 4]> {
+    let direction = primary_direction(camera, fov, width, height, width / 2.0, height / 2.0);
+    match scene.world.pick(&camera.eye, &direction)? {
+        Pick::Object(index, hit) if !hit.inside => match &scene.world.scene.objects()[index] {
+            Object::Cube(cube) => Some(cube.face_corners(&hit.face)),
+            _ => None,
+        },
+        Pick::Terrain(hit) if !hit.inside => {
+            let cell = (hit.point - hit.normal * (BLOCK_SIZE * 0.5)) / BLOCK_SIZE;
+            let min = cell.map(f32::floor) * BLOCK_SIZE;
+            Some(Cube::new(min, min + Vec3::repeat(BLOCK_SIZE), hit.material).face_corners(&hit.face))
         }
+        _ => None,
     }
-    lights
-}
-
-fn generate_lights_from_emissive_objects(objects: &[Cube]) -> Vec<Light> {
-    objects.iter()
-        .filter(|cube| cube.material.emission != Color::new(0, 0, 0))
-        .map(|cube| {
-            let position = (cube.min + cube.max) * 0.5;
-            let intensity = cube.material.emission.intensity() * 10.0;  // Aumentamos significativamente la intensidad
-            let radius = (cube.max - cube.min).magnitude() * 10.0;  // Aumentamos aún más el radio
-            Light::new(position, cube.material.emission, intensity, radius)
-        })
-        .collect()
 }
 
-struct DayNightCycle {
-    time: f32,
-    day_color: Color,
-    night_color: Color,
-    sun_position: Vec3,
-}
-
-impl DayNightCycle {
-    fn new() -> Self {
-        DayNightCycle {
-            time: 0.5, // Empezamos a mitad del día
-            day_color: Color::new(255, 255, 255),
-            night_color: Color::new(10, 10, 50),
-            sun_position: Vec3::new(0.0, 5.0, 0.0), // Posición inicial del sol
-        }
+// Promedia `frames` cuadros de la cámara quieta, mostrando el avance, y guarda la imagen. Si
+// no se puede guardar termina el programa
+fn render_still(renderer: &mut Renderer, scene: &Scene, camera: &Camera, framebuffer: &mut Framebuffer, frames: u32, output: &Path) {
+    for frame in 1..=frames {
+        println!("Cuadro {}/{} de {} ({}x{})", frame, frames, output.display(), framebuffer.width, framebuffer.height);
+        renderer.render(scene, camera, framebuffer);
     }
-
-    fn update(&mut self, delta: f32) {
-        self.time += delta;
-        if self.time > 1.0 {
-            self.time -= 1.0;
-        }
-        if self.time < 0.0 {
-            self.time += 1.0;
-        }
-
-        // Actualizar la posición del sol
-        let angle = self.time * 2.0 * std::f32::consts::PI;
-        self.sun_position = Vec3::new(
-            5.0 * angle.cos(),
-            5.0 * angle.sin().abs() + 1.0, // Mantiene el sol por encima del horizonte
-            5.0 * angle.sin(),
-        );
+    if let Err(e) = framebuffer.save(output) {
+        eprintln!("No se pudo guardar {}: {}", output.display(), e);
+        std::process::exit(1);
     }
+    println!("Guardado {}", output.display());
+}
 
-    fn get_current_color(&self) -> Color {
-        let t = (self.time * std::f32::consts::PI * 2.0).sin() * 0.5 + 0.5;
-        Color::lerp(&self.night_color, &self.day_color, t)
+// El recorrido se guarda cada vez que cambia; si no se puede, solo se avisa
+fn save_camera_path(camera_path: &CameraPath) {
+    if let Err(e) = camera_path.save(Path::new(CAMERA_PATH_FILE)) {
+        eprintln!("No se pudo guardar {}: {}", CAMERA_PATH_FILE, e);
     }
+}
 
-    fn get_light_intensity(&self) -> f32 {
-        ((self.time * std::f32::consts::PI * 2.0).sin() * 0.4 + 0.6).max(0.2)
-    }
+// Si no se sabe dónde está el ejecutable, los marcadores quedan en la carpeta de trabajo
+fn bookmarks_file() -> PathBuf {
+    std::env::current_exe()
+        .map(|exe| exe.with_file_name(BOOKMARKS_FILE))
+        .unwrap_or_else(|_| PathBuf::from(BOOKMARKS_FILE))
 }
 
 fn main() {
-    let window_width = 800;
-    let window_height = 600;
-    let framebuffer_width = 800;
-    let framebuffer_height = 600;
+    // La ventana y la imagen tienen el mismo tamaño
+    let mut width = 800;
+    let mut height = 600;
     let frame_delay = Duration::from_millis(16);
 
-
-    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
-    let mut window = Window::new(
-        "Leonardo Mejía - Hansel y Gretel House",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    ).unwrap();
+    // Opciones de la línea de comandos, descritas en USAGE (--help). Un tamaño, una escena o
+    // un número que no sirven terminan el programa con un mensaje
+    let mut settings = RenderSettings::default();
+    let mut scene_file: Option<String> = None;
+    let mut heightmap = HeightmapSettings::default();
+    let mut headless: Option<PathBuf> = None;
+    let mut headless_frames = 16;
+    let mut panorama = false;
+    let mut cubemap: Option<PathBuf> = None;
+    let mut assets_dir: Option<PathBuf> = None;
+    let mut bookmark_transition = 0.5;
+    let mut acne_test = false;
+    let mut dielectric_test = false;
+    let mut mirror_test = false;
+    let mut attenuation_test = false;
+    let mut merge_lights = true;
+    let mut cull_lights = true;
+    let mut many_lights_test = false;
+    let mut shadow_catcher_test = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return;
+            }
+            "--width" => match args.next().and_then(|value| value.parse::<usize>().ok()) {
+                Some(value) if value >= 1 => width = value,
+                _ => exit_with_error("--width necesita un número entero mayor o igual a 1"),
+            },
+            "--height" => match args.next().and_then(|value| value.parse::<usize>().ok()) {
+                Some(value) if value >= 1 => height = value,
+                _ => exit_with_error("--height necesita un número entero mayor o igual a 1"),
+            },
+            "--scene" => match args.next() {
+                Some(path) if Path::new(&path).is_file() => scene_file = Some(path),
+                Some(path) => exit_with_error(&format!("--scene: no existe el archivo {}", path)),
+                None => exit_with_error("--scene necesita la ruta de un archivo .obj, .vox o de una imagen"),
+            },
+            "--heightmap-height" => match args.next().and_then(|value| value.parse::<i32>().ok()) {
+                Some(value) if value >= 1 => heightmap.max_height = value,
+                _ => exit_with_error("--heightmap-height necesita un número entero mayor o igual a 1"),
+            },
+            "--samples" => match args.next().and_then(|value| value.parse::<u32>().ok()) {
+                Some(samples) if samples >= 1 => settings.samples = samples,
+                _ => exit_with_error("--samples necesita un número entero mayor o igual a 1"),
+            },
+            "--headless" => match args.next() {
+                Some(path) => headless = Some(PathBuf::from(path)),
+                None => exit_with_error("--headless necesita la ruta de la imagen de salida"),
+            },
+            "--panorama" => panorama = true,
+            "--cubemap" => match args.next() {
+                Some(dir) => cubemap = Some(PathBuf::from(dir)),
+                None => exit_with_error("--cubemap necesita la carpeta de las caras"),
+            },
+            "--assets" => match args.next() {
+                Some(dir) if Path::new(&dir).is_dir() => assets_dir = Some(PathBuf::from(dir)),
+                Some(dir) => exit_with_error(&format!("--assets: no existe la carpeta {}", dir)),
+                None => exit_with_error("--assets necesita la ruta de una carpeta"),
+            },
+            "--frames" => match args.next().and_then(|value| value.parse::<u32>().ok()) {
+                Some(frames) if frames >= 1 => headless_frames = frames,
+                _ => exit_with_error("--frames necesita un número entero mayor o igual a 1"),
+            },
+            "--shadow-bias" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                // Sin separación los rayos vuelven a dar en la misma cara y nunca avanzan
+                Some(bias) if bias > 0.0 => settings.shadow_bias = bias,
+                _ => exit_with_error("--shadow-bias necesita un número mayor que 0"),
+            },
+            "--glossy-samples" => match args.next().and_then(|value| value.parse::<u32>().ok()) {
+                Some(samples) if samples >= 1 => settings.glossy_samples = samples,
+                _ => exit_with_error("--glossy-samples necesita un número entero mayor o igual a 1"),
+            },
+            "--max-depth" => match args.next().and_then(|value| value.parse::<u32>().ok()) {
+                Some(depth) => settings.max_depth = depth,
+                _ => exit_with_error("--max-depth necesita un número entero mayor o igual a 0"),
+            },
+            "--motion-scale" => match args.next().and_then(|value| value.parse::<u32>().ok()) {
+                Some(scale @ (1 | 2 | 4)) => settings.motion_scale = scale,
+                _ => exit_with_error("--motion-scale necesita 1, 2 o 4"),
+            },
+            "--upscale" => match args.next().as_deref() {
+                Some("nearest") => settings.upscale_filter = UpscaleFilter::Nearest,
+                Some("bilinear") => settings.upscale_filter = UpscaleFilter::Bilinear,
+                _ => exit_with_error("--upscale necesita nearest o bilinear"),
+            },
+            "--tile-size" => match args.next().and_then(|value| value.parse::<u32>().ok()) {
+                Some(size) if size >= 1 => settings.tile_size = size,
+                _ => exit_with_error("--tile-size necesita un número entero mayor o igual a 1"),
+            },
+            "--tile-stats" => settings.tile_stats = true,
+            "--no-light-merge" => merge_lights = false,
+            "--no-light-culling" => cull_lights = false,
+            "--bookmark-transition" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                Some(seconds) if seconds >= 0.0 => bookmark_transition = seconds,
+                _ => exit_with_error("--bookmark-transition necesita un número de segundos mayor o igual a 0"),
+            },
+            "--acne-test" => acne_test = true,
+            "--dielectric-test" => dielectric_test = true,
+            "--mirror-test" => mirror_test = true,
+            "--attenuation-test" => attenuation_test = true,
+            "--many-lights-test" => many_lights_test = true,
+            "--shadow-catcher-test" => shadow_catcher_test = true,
+            other => exit_with_error(&format!("Opción desconocida: {}", other)),
+        }
+    }
+    // La panorámica siempre es 2:1 y solo sale como imagen
+    if panorama {
+        if headless.is_none() {
+            exit_with_error("--panorama necesita --headless <salida.png>");
+        }
+        height = (width / 2).max(1);
+        settings.projection = Projection::Equirectangular;
+    }
+    // Sin --assets se usa ./assets si existe; si no, solo las texturas incluidas en el ejecutable
+    let assets_dir = assets_dir.or_else(|| Some(PathBuf::from("assets")).filter(|dir| dir.is_dir()));
 
 
-    // move the window around
-    window.set_position(500, 500);
-    window.update();
+    let mut framebuffer = Framebuffer::new(width, height);
 
 
     let light = Light::new(
@@ -410,253 +581,219 @@ fn main() {
     );
 
 
-    let rubber = Material::new(
-        Color::new(80, 0, 0),
-        1.0,
-        [0.9, 0.1, 0.0, 0.0],
-        0.0,
-    );
+    let rubber = Material::builder(Color::new(80, 0, 0))
+        .shininess(1.0)
+        .diffuse(0.9)
+        .specular(0.1)
+        .refractive_index(0.0)
+        .build();
 
-    let sun_model = ObjModel::load("assets/sphere.obj");
+    // Si el modelo no se puede leer, se avisa y el sol simplemente no se dibuja
+    let sun_vertices = match ObjModel::load("assets/sphere.obj") {
+        Ok(model) => model.vertices,
+        Err(e) => {
+            eprintln!("No se pudo cargar assets/sphere.obj: {}", e);
+            Vec::new()
+        }
+    };
     let mut day_night = DayNightCycle::new();
+    // Los faroles de --attenuation-test y los bloques de --many-lights-test se ven mejor sin el
+    // sol: arranca a medianoche con el día en pausa (Espacio lo reanuda)
+    if attenuation_test || many_lights_test {
+        day_night.time = 0.75;
+        day_night.update(0.0);
+        day_night.paused = true;
+    }
+    // Las sombras de --shadow-catcher-test necesitan sol: media mañana, también en pausa
+    if shadow_catcher_test {
+        day_night.time = 0.2;
+        day_night.update(0.0);
+        day_night.paused = true;
+    }
 
-    let mut sun_material = Material::new(
-        Color::new(255, 255, 200),
-        100.0,
-        [0.9, 0.3, 0.0, 0.0],
-        1.0,
-    ).with_emission(Color::new(255, 255, 180));
-
-
-    let ivory = Material::new(
-        Color::new(100, 100, 80),
-        50.0,
-        [0.6, 0.3, 0.6, 0.0],
-        0.0,
-    );
-
-
-    let glass = Material::new(
-        Color::new(255, 255, 255),
-        1425.0,
-        [0.0, 10.0, 0.5, 0.5],
-        0.3,
-    );
-
-
-    // Define the grass top and dirt side textures
-    let grass_top_texture = Texture::load("assets/UP_GRASSTEXTURE.jpg").expect("Failed to load grass top texture");
-    let dirt_side_texture = Texture::load("assets/SIDE_GRASSTEXTURE.jpg").expect("Failed to load dirt side texture");
-
-    let portal_texture = Texture::load("assets/end_portal.png").expect("Failed to load portal texture");
-
-    // Define el material de césped
-    let grass_texture = Texture::load("assets/UP_GRASSTEXTURE.jpg").expect("Failed to load grass texture");
-
-
-    let GRASS = Material::new(
-        Color::new(0, 255, 0),  // Color verde
-        50.0,                   // Ajuste el brillo si es necesario
-        [0.8, 0.2, 0.0, 0.0],   // Ajusta las propiedades: difuso, especular, reflectividad, transparencia
-        1.0
-    ).with_textures(vec![grass_top_texture, dirt_side_texture]);
-
-
-    let wood_plank_texture = Texture::load("assets/cake_top.png").expect("Failed to load wood plank texture");
-
-
-    let WOOD: Material = Material::new(
-        Color::new(170, 137, 85),   // Color marrón típico de la madera
-        30.0,                       // Ajuste el brillo
-        [0.7, 0.2, 0.0, 0.0],       // Propiedades: difuso, especular, reflectividad, transparencia
-        2.0                         // Índice de refracción (ajustado a 1.0 para superficies opacas)
-    ).with_textures(vec![wood_plank_texture.clone(), wood_plank_texture ]);
-
-    let stone_texture = Texture::load("assets/bee_nest_top.png").expect("Failed to load stone texture");
+    let mut sun_material = Material::builder(Color::new(255, 255, 200))
+        .shininess(100.0)
+        .diffuse(0.9)
+        .specular(0.3)
+        .build()
+        .with_emission(Color::new(255, 255, 180));
+
+
+    let ivory = Material::builder(Color::new(100, 100, 80))
+        .shininess(50.0)
+        .diffuse(0.6)
+        .specular(0.3)
+        .reflectivity(0.6)
+        .refractive_index(0.0)
+        .build();
+
+
+    let glass = Material::builder(Color::new(255, 255, 255))
+        .shininess(1425.0)
+        .diffuse(0.0)
+        .specular(1.0)
+        .reflectivity(0.5)
+        .transparency(0.5)
+        .refractive_index(0.3)
+        .build();
+
+
+    // Todos los materiales con nombre viven en el registro; los objetos solo guardan su id
+    let mut materials = MaterialRegistry::new();
+
+    // Cada archivo se decodifica una sola vez. Se busca en la carpeta de assets y, si no está,
+    // entre las texturas incluidas en el ejecutable; si tampoco, el material queda con un damero
+    // magenta y negro y un aviso en la consola en vez de cerrar el programa
+    let mut textures = TextureCache::new(assets_dir);
+
+    // La casa con su techo, el portal, el árbol, el estanque, la piedra luminosa y la lava
+    let House {
+        objects: house_objects,
+        grass: GRASS,
+        wood: WOOD,
+        portal: PORTAL,
+        stone: STONE,
+        treewood: TREEWOOD,
+        leaves: LEAVES,
+        glass: GLASS,
+        stained_glass: STAINED_GLASS,
+        glowstone: GLOWSTONE,
+        lava: LAVA,
+        crystal: CRYSTAL,
+        mirror: MIRROR,
+        water: WATER,
+    } = house(&mut materials, &mut textures);
 
     // Portal animado
-use std::time::Instant;
-let start_time = Instant::now();
-
-let portal_texture = Texture::load("assets/end_portal.png").expect("Failed to load portal texture");
-
-let elapsed = start_time.elapsed().as_secs_f32();
-let pulse = (elapsed * 2.0).sin() * 0.5 + 0.5; // efecto de "respiración" del portal
-let dynamic_emission = Color::new(
-    (100.0 * pulse) as u8,
-    0,
-    (255.0 * pulse) as u8,
-);
-
-// Material base del portal
-let PORTAL = Material::new(
-    Color::new(100, 0, 200),
-    100.0,
-    [0.1, 0.8, 0.2, 0.6], // un poco más transparente (antes era 0.4)
-    1.25,
-)
-.with_textures(vec![portal_texture.clone()])
-.with_emission(dynamic_emission);
-
-// Cubos brillantes alrededor del portal
-let PORTAL_BORDER = Material::new(
-    Color::new(200, 0, 255),
-    90.0,
-    [0.1, 0.8, 0.5, 0.3],
-    1.2,
-)
-.with_emission(Color::new(180, 0, 255));
-
-
-    let STONE: Material = Material::new(
-    Color::new(128, 128, 128),  // Color gris típico de la piedra
-    30.0,                       // Brillo moderado, la piedra no refleja mucha luz
-    [0.7, 0.1, 0.1, 0.0],       // Propiedades: difuso, especular, reflectividad, transparencia
-    1.0                         // Índice de refracción para superficies opacas
-).with_textures(vec![stone_texture.clone()]);  // Usa la misma textura para todas las caras
-    
-    let tree_plank_texture = Texture::load("assets/tallo.png").expect("Failed to load rawtree plank texture");
-
-    let TREEWOOD: Material = Material::new(
-        Color::new(139, 69, 19),    // Color marrón típico de la madera
-        10.0,                       // Ajuste el brillo (puede ser más bajo para que la madera no se vea muy brillante)
-        [0.7, 0.2, 0.0, 0.0],       // Propiedades: difuso, especular, reflectividad, transparencia
-        1.0                         // Índice de refracción (ajustado a 1.0 para superficies opacas)
-    ).with_textures(vec![tree_plank_texture.clone()]);
-
-    let leaves_texture = Texture::load("assets/hongo.png").expect("Failed to load leaves  texture");
-
-    let LEAVES: Material = Material::new(
-        Color::new(34, 139, 34),    // Color verde
-        10.0,                       // Brillo ligeramente más bajo para las hojas
-        [0.6, 0.3, 0.0, 0.0],       // Propiedades: difuso, especular, reflectividad, transparencia
-        1.0                         // Índice de refracción para superficies opacas
-    ).with_textures(vec![leaves_texture.clone()]);
-
-    // Material para Cristal
-    let GLASS: Material = Material::new(
-    Color::new(0, 0, 0),  
-    60.0,                      
-    [0.1, 0.1, 0.1, 0.5],       // Propiedades: bajo difuso, alto especular, sin reflectividad, alta transparencia
-    1.0                         // Índice de refracción típico para el vidrio
-);
-    
-let azale_texture = Texture::load("assets/azalea.png").expect("Failed to load azale texture");
-
-let AZALE: Material = Material::new(
-    Color::new(255, 182, 193), // color rosado base
-    20.0,                      // brillo moderado
-    [0.7, 0.2, 0.0, 0.0],      // difuso, especular, reflectividad, transparencia
-    1.0
-).with_textures(vec![azale_texture.clone()]);
-
-
-    let glowstone_texture = Texture::load("assets/glowstone_texture.jpg").expect("Failed to load glowstone texture");
-
-    let GLOWSTONE: Material = Material::new(
-        Color::new(255, 255, 200),  // Color base amarillento
-        10.0,                       // Reducimos el brillo para que la textura sea más visible
-        [0.9, 0.1, 0.0, 0.0],       // Aumentamos el difuso, reducimos el especular
-        1.0
-    ).with_textures(vec![glowstone_texture.clone()])
-     .with_emission(Color::new(255, 255, 150)); // Mantenemos la emisión fuerte
-
-    
-
-    // Define los objetos que componen el portal
-    let objects = [
-        // Portal mágico enfrente de la casa
-    // Portal mágico con marco
-    // Flores azale alrededor del árbol
-Cube { min: Vec3::new(-2.8, 0.0, 2.5), max: Vec3::new(-2.6, 0.2, 2.7), material: AZALE.clone() },
-Cube { min: Vec3::new(-3.2, 0.0, 3.2), max: Vec3::new(-3.0, 0.2, 3.4), material: AZALE.clone() },
-Cube { min: Vec3::new(-2.9, 0.0, 3.6), max: Vec3::new(-2.7, 0.2, 3.8), material: AZALE.clone() },
-Cube { min: Vec3::new(-3.3, 0.0, 2.8), max: Vec3::new(-3.1, 0.2, 3.0), material: AZALE.clone() },
-// Flores frente a la casa
-Cube { min: Vec3::new(-1.0, 0.0, 0.5), max: Vec3::new(-0.8, 0.2, 0.7), material: AZALE.clone() },
-Cube { min: Vec3::new(1.0, 0.0, 0.5), max: Vec3::new(1.2, 0.2, 0.7), material: AZALE.clone() },
-Cube { min: Vec3::new(0.0, 0.0, 0.8), max: Vec3::new(0.2, 0.2, 1.0), material: AZALE.clone() },
-
-Cube { 
-    min: Vec3::new(-0.5, 0.0, -2.5), 
-    max: Vec3::new(0.5, 2.0, -2.0), 
-    material: PORTAL.clone() 
-},
-
-// Cubos del marco del portal (bordes superiores, inferiores y laterales)
-Cube { min: Vec3::new(-0.7, -0.2, -2.6), max: Vec3::new(0.7, 0.0, -1.9), material: PORTAL_BORDER.clone() }, // base
-Cube { min: Vec3::new(-0.7, 2.0, -2.6), max: Vec3::new(0.7, 2.2, -1.9), material: PORTAL_BORDER.clone() }, // parte superior
-Cube { min: Vec3::new(-0.7, 0.0, -2.6), max: Vec3::new(-0.5, 2.0, -1.9), material: PORTAL_BORDER.clone() }, // lado izquierdo
-Cube { min: Vec3::new(0.5, 0.0, -2.6), max: Vec3::new(0.7, 2.0, -1.9), material: PORTAL_BORDER.clone() }, // lado derecho
-
-
-        Cube { min: Vec3::new(-4.0, -0.5, -4.0), max: Vec3::new(4.0, 0.0, 4.0), material: GRASS.clone() }, // Base de cesped
-       
-      // Pared trasera
-      Cube { min: Vec3::new(-1.5, 0.0, -1.5), max: Vec3::new(1.5, 2.0, -1.0), material: WOOD.clone() },
-
-      // Pared izquierda
-      Cube { min: Vec3::new(-1.5, 0.0, -1.5), max: Vec3::new(-1.0, 2.0, 1.5), material: WOOD.clone() },
-
-      // Parte inferior de la pared derecha
-      Cube { min: Vec3::new(1.0, 0.0, -1.5), max: Vec3::new(1.5, 0.5, 1.5), material: WOOD.clone() },
-
-      // Parte derecha de la pared derecha
-      Cube { min: Vec3::new(1.0, 0.0, -1.5), max: Vec3::new(1.5, 2.0, -0.5), material: WOOD.clone() },   
-
-      // Parte izquierda de la pared derecha
-      Cube { min: Vec3::new(1.0, 0.0, 0.5), max: Vec3::new(1.5, 2.0, 1.5), material: WOOD.clone() },
-
-      // Parte superior de la pared derecha (arriba de la ventana)
-      Cube { min: Vec3::new(1.0, 1.5, -1.5), max: Vec3::new(1.5, 2.0, 1.5), material: WOOD.clone() },
-
-      // Cristal para la ventana
-      Cube { min: Vec3::new(1.0, 0.5, -0.5), max: Vec3::new(1.5, 1.5, 0.5), material: GLASS.clone() },
-
-      // Pared frontal izquierda (antes de la puerta)
-      Cube { min: Vec3::new(-1.5, 0.0, 1.0), max: Vec3::new(-0.5, 2.0, 1.5), material: WOOD.clone() },
-
-      // Pared frontal derecha (después de la puerta)
-      Cube { min: Vec3::new(0.5, 0.0, 1.0), max: Vec3::new(1.5, 2.0, 1.5), material: WOOD.clone() },
-
-      // Pared frontal encima de la puerta
-      Cube { min: Vec3::new(-0.5, 1.0, 1.0), max: Vec3::new(0.5, 2.0, 1.5), material: WOOD.clone() },
-
-      // Techo de la casa
-      Cube { min: Vec3::new(-2.0, 2.0, -2.0), max: Vec3::new(2.0, 2.5, 2.0), material: STONE.clone() },
-      Cube { min: Vec3::new(-1.5, 2.5, -1.5), max: Vec3::new(1.5, 3.0, 1.5), material: STONE.clone() },
-      Cube { min: Vec3::new(-1.0, 3.0, -1.0), max: Vec3::new(1.0, 3.5, 1.0), material: STONE.clone() },
-      Cube { min: Vec3::new(-0.5, 3.5, -0.5), max: Vec3::new(0.5, 4.0, 0.5), material: STONE.clone() },
-
-      // Árbol (movido un bloque hacia adelante)
-      // Tronco del árbol
-      Cube { min: Vec3::new(-3.0, 0.0, 3.0), max: Vec3::new(-2.5, 0.5, 3.5), material: TREEWOOD.clone() },
-      Cube { min: Vec3::new(-3.0, 0.5, 3.0), max: Vec3::new(-2.5, 1.0, 3.5), material: TREEWOOD.clone() },
-      Cube { min: Vec3::new(-3.0, 1.0, 3.0), max: Vec3::new(-2.5, 1.5, 3.5), material: TREEWOOD.clone() },
-      Cube { min: Vec3::new(-3.0, 1.5, 3.0), max: Vec3::new(-2.5, 2.0, 3.5), material: TREEWOOD.clone() },
-
-      // Hojas del árbol
-      Cube { min: Vec3::new(-3.5, 2.0, 2.5), max: Vec3::new(-2.0, 2.5, 4.0), material: LEAVES.clone() },
-      Cube { min: Vec3::new(-3.5, 2.5, 2.5), max: Vec3::new(-2.0, 3.0, 4.0), material: LEAVES.clone() },
-      Cube { min: Vec3::new(-3.0, 3.0, 3.0), max: Vec3::new(-2.5, 3.5, 3.5), material: LEAVES.clone() },
-
-      // Bloque de piedra luminosa al lado de la casa
-      Cube { min: Vec3::new(2.0, 0.0, -1.0), max: Vec3::new(2.5, 0.5, -0.5), material: GLOWSTONE.clone() },
-  ];
+    use std::time::Instant;
+    let start_time = Instant::now();
+
+    let mut objects: Vec<Object> = house_objects;
+    // Suelo de césped infinito; cada textura cubre 0.5 unidades, igual que un bloque. Con
+    // --shadow-catcher-test no se ve, salvo por las sombras que caen en él
+    let ground = if shadow_catcher_test {
+        materials.add("shadow_catcher", Material::builder(Color::new(255, 255, 255)).build().as_shadow_catcher())
+    } else {
+        GRASS
+    };
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).with_tile_size(0.5).into());
+    objects.push(Sphere::new(Vec3::new(2.6, 0.5, 1.6), 0.5, CRYSTAL).into());
+
+    // Creeper low-poly cargado desde un .obj, parado en el jardín
+    let CREEPER = materials.add("creeper", Material::builder(Color::new(80, 180, 70))
+        .specular(0.1)
+        .build());
+    match ObjModel::load("assets/creeper.obj") {
+        Ok(creeper_model) => objects.push(Mesh::from_obj(&creeper_model, Vec3::new(2.8, 0.0, 3.0), 0.5, CREEPER, &mut materials).into()),
+        Err(e) => eprintln!("No se pudo cargar assets/creeper.obj: {}", e),
+    }
+
+    if acne_test {
+        objects = acne_test_scene(STONE, GRASS);
+    }
+    if dielectric_test {
+        objects = dielectric_test_scene(CRYSTAL, WATER, GRASS);
+    }
+    if mirror_test {
+        objects = mirror_test_scene(MIRROR, STONE, GRASS);
+    }
+    if many_lights_test {
+        objects = many_lights_test_scene(GLOWSTONE, GRASS);
+    }
+    let mut test_lamps = None;
+    if attenuation_test {
+        let (test_objects, lamps) = attenuation_test_scene(STONE, GRASS);
+        objects = test_objects;
+        test_lamps = Some(lamps);
+    }
+    // Una escena guardada trae también sus luces y su cámara, que reemplazan a las de abajo
+    let mut saved_view = None;
+    if let Some(path) = &scene_file {
+        let loaded = if is_scene_file(path) {
+            scene_file::load(Path::new(path), &mut materials, &mut textures)
+                .map(|loaded| {
+                    saved_view = Some((loaded.lights, loaded.camera));
+                    loaded.objects
+                })
+                .map_err(|e| e.to_string())
+        } else {
+            load_scene(path, &heightmap, STONE, GRASS, &mut materials, &mut textures)
+        };
+        match loaded {
+            Ok(scene_objects) => objects = scene_objects,
+            Err(e) => exit_with_error(&format!("No se pudo cargar la escena {}: {}", path, e)),
+        }
+    }
+    let (saved_lights, saved_camera) = saved_view.unzip();
 
 
     // Genera luces adicionales a partir de materiales emisivos
     let mut lights = vec![
-        Light::new(
-            Vec3::new(4.0, 1.0, 5.0),
+        // El sol es direccional: sombras paralelas que se mueven con el ángulo del día
+        Light::directional(
+            day_night.sun_direction(),
             Color::new(255, 255, 255),
             1.0,  // Reducimos la intensidad de la luz principal
-            10.0
-        )
+        ).with_size(0.05),  // Tamaño aparente del disco: sombras suaves con varios rayos de sombra
+        // La luna, del lado opuesto del cielo: luz fría y débil que solo aparece de noche
+        Light::directional(
+            day_night.moon_direction(),
+            Color::new(170, 190, 255),
+            0.0,
+        ).with_size(0.04),
     ];
 
-    // Añade las luces de los objetos emisivos
-    lights.extend(generate_lights_from_emissive_objects(&objects));
+    // Farol sobre la puerta: un foco cálido que apunta al suelo frente a la entrada
+    lights.push(Light::spot(
+        Vec3::new(0.0, 1.4, 1.8),
+        Vec3::new(0.0, -1.0, 0.35),
+        20.0_f32.to_radians(),  // Cono interior, a plena intensidad
+        35.0_f32.to_radians(),  // Cono exterior, donde se apaga
+        Color::new(255, 200, 120),
+        2.0,
+        4.0,
+    ));
+
+    // Los faroles de prueba reemplazan al de la puerta, que no tiene casa que iluminar
+    if let Some(lamps) = test_lamps {
+        lights.truncate(2);
+        lights.extend(lamps);
+    }
+    if let Some(saved_lights) = saved_lights {
+        lights = saved_lights;
+    }
+
+    // Fondos entre los que se cambia con B; el cielo procedural sigue el ciclo día/noche
+    let mut environments = vec![Environment::ProceduralSky(Sky::default()), Environment::SolidColor(SKYBOX_COLOR)];
+    match Skybox::load(
+        "assets/skybox/px.png", "assets/skybox/nx.png",
+        "assets/skybox/py.png", "assets/skybox/ny.png",
+        "assets/skybox/pz.png", "assets/skybox/nz.png",
+    ) {
+        Ok(skybox) => environments.push(Environment::Cubemap(Box::new(skybox))),
+        Err(e) => eprintln!("No se pudo cargar el skybox: {}", e),
+    }
+    // Cielo HDR en una sola imagen equirectangular; su sol pasa de 1.0 y brilla en los reflejos
+    match EquirectEnv::load("assets/sky.hdr") {
+        Ok(map) => environments.push(Environment::Equirect(Box::new(map))),
+        Err(e) => eprintln!("No se pudo cargar assets/sky.hdr: {}", e),
+    }
+
+    // La jerarquía se construye una sola vez; solo hay que rehacerla si cambia la lista de objetos.
+    // Las colinas alrededor de la casa se generan por chunks en otro hilo según se mueve la cámara
+    // (sobre el atrapasombras no hay colinas: la casa queda sola sobre el fondo)
+    let terrain_radius = if shadow_catcher_test { 0 } else { 2 };
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), GRASS, terrain_radius));
+    // La escena añade las luces de los objetos emisivos al final de `lights`, porque las rehace en
+    // cada cuadro
+    let mut scene = Scene::new(world, materials, lights, environments, day_night);
+    scene.merge_emissive_lights = merge_lights;
+    scene.cull_lights = cull_lights;
+    // La casa flotante se muestra sobre el skybox, si se pudo cargar
+    let skybox = scene.environments.iter().position(|environment| matches!(environment, Environment::Cubemap(_)));
+    if let Some(index) = skybox.filter(|_| shadow_catcher_test) {
+        scene.environment = index;
+    }
 
 
     // Inicializa la cámara
@@ -665,82 +802,788 @@ Cube { min: Vec3::new(0.5, 0.0, -2.6), max: Vec3::new(0.7, 2.0, -1.9), material:
         Vec3::new(0.0, 0.0, 0.0),  // punto al que la cámara está mirando (origen)
         Vec3::new(0.0, 1.0, 0.0)   // vector hacia arriba del mundo
     );
-    let rotation_speed = PI / 50.0;
-    let zoom_speed = 0.5;
-    const MAX_ZOOM: f32 = 1.0;
-    const MIN_ZOOM: f32 = 10.0;
-
+    // Los charcos de luz de los faroles se ven desde arriba, no al ras del suelo
+    if attenuation_test {
+        camera = Camera::new(Vec3::new(0.0, 1.8, 3.2), Vec3::new(0.0, 0.2, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    }
+    // Un poco desde arriba, para que se vean las sombras sobre el fondo
+    if shadow_catcher_test {
+        camera = Camera::new(Vec3::new(2.0, 3.5, 7.0), Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    }
+    // La cuadrícula de glowstone entera, vista en diagonal desde lo alto
+    if many_lights_test {
+        camera = Camera::new(Vec3::new(0.0, 18.0, 30.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    }
+    if let Some(saved_camera) = saved_camera {
+        camera = saved_camera;
+    }
+    // El renderer guarda el promedio de cuadros con la cámara quieta
+    let mut renderer = Renderer::new(settings);
+
+    // --headless y --cubemap: sin ventana, con el terreno ya generado, se promedian --frames
+    // cuadros quietos (cada uno con otros números al azar) y se guardan las imágenes. Antes de
+    // trazar se comprueba que la salida se puede escribir, para no perder un render largo al final
+    if headless.is_some() || cubemap.is_some() {
+        if let Some((output, Err(e))) = headless.as_ref().map(|output| (output, std::fs::File::create(output))) {
+            eprintln!("No se puede escribir {}: {}", output.display(), e);
+            std::process::exit(1);
+        }
+        scene.materials.get_mut(PORTAL).emission = portal_emission(0.0);
+        scene.update_lights();
+        scene.world.terrain.load_around(&camera.eye);
+        scene.update_submersion(&camera.eye);
+        renderer.settings.progress = true;
+
+        let start = Instant::now();
+        if let Some(output) = &headless {
+            render_still(&mut renderer, &scene, &camera, &mut framebuffer, headless_frames, output);
+        }
+        // Cada cara es una vista cuadrada de 90° en perspectiva, también con --panorama
+        if let Some(dir) = &cubemap {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                eprintln!("No se puede crear {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+            let face_settings = RenderSettings { fov: PI / 2.0, projection: Projection::Perspective, ..renderer.settings };
+            for (name, face_camera) in cubemap_cameras(camera.eye) {
+                let mut face_buffer = Framebuffer::new(width, width);
+                let output = dir.join(format!("{}.png", name));
+                render_still(&mut Renderer::new(face_settings), &scene, &face_camera, &mut face_buffer, headless_frames, &output);
+            }
+        }
+        println!("Terminado en {:.1} s", start.elapsed().as_secs_f32());
+        return;
+    }
 
-    let stone_texture = Texture::load("assets/bee_nest_top.png").expect("Failed to load stone texture");
+    // Estado del último cuadro trazado y cuántos cuadros se saltaron porque nada cambió
+    let mut last_frame_state: Option<FrameState> = None;
+    let mut skipped_frames: u64 = 0;
+    // Tiempo de render y subida de los últimos cuadros trazados, para los cuadros por segundo
+    let mut frame_times = FrameTimes::new();
+    // Texto sobre la imagen con los cuadros por segundo, la hora, la cámara y el material
+    let mut show_hud = true;
+    // Capturas que todavía se están escribiendo; al salir se esperan para no dejarlas a medias
+    let mut screenshots: Vec<std::thread::JoinHandle<()>> = Vec::new();
+    // Grabación de cuadros en curso (F9)
+    let mut recorder: Option<Recorder> = None;
+    // Momento del cuadro anterior, para que el día avance con el tiempo real
+    let mut last_frame = Instant::now();
+    // Velocidades de teclado y sensibilidad del mouse
+    let controls = CameraControls::default();
+    // Posición del mouse en el cuadro anterior mientras se arrastra con el botón izquierdo
+    let mut last_mouse: Option<(f32, f32)> = None;
+    // Objeto elegido con clic derecho y si el botón ya estaba apretado en el cuadro anterior
+    let mut selected: Option<usize> = None;
+    let mut right_was_down = false;
+    // Materiales que se ponen con Enter, elegidos con Alt+1..9, y el que está elegido
+    let palette = [GRASS, STONE, WOOD, TREEWOOD, LEAVES, GLASS, STAINED_GLASS, GLOWSTONE, LAVA];
+    let mut palette_index = 0;
+    // Bloques puestos, quitados y repintados, para deshacerlos con Ctrl+Z y rehacerlos con Ctrl+Y
+    let mut history = EditHistory::new();
+    // Recorrido de cámara de la sesión anterior, si lo hay, y desde cuándo se está reproduciendo
+    let mut camera_path = match CameraPath::load(Path::new(CAMERA_PATH_FILE)) {
+        Ok(camera_path) => camera_path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => CameraPath::new(),
+        Err(e) => {
+            eprintln!("No se pudo cargar {}: {}", CAMERA_PATH_FILE, e);
+            CameraPath::new()
+        }
+    };
+    // Se reproduce una copia: el recorrido grabado o el salto a un marcador
+    let mut playback: Option<(Instant, CameraPath)> = None;
+    let bookmarks_path = bookmarks_file();
+    let mut bookmarks = match Bookmarks::load(&bookmarks_path) {
+        Ok(bookmarks) => bookmarks,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Bookmarks::new(),
+        Err(e) => {
+            eprintln!("No se pudo cargar {}: {}", bookmarks_path.display(), e);
+            Bookmarks::new()
+        }
+    };
+    // Límites del campo de visión con Z y X: 20° (teleobjetivo) a 120° (gran angular)
+    const MIN_FOV: f32 = PI / 9.0;
+    const MAX_FOV: f32 = 2.0 * PI / 3.0;
+    let fov_step = 5.0_f32.to_radians();
 
 
     let mut day_night_cycle = DayNightCycle::new();
 
 
+
+    // La escena de --scene se recarga cuando cambia la fecha de su archivo
+    let mut scene_modified = scene_file.as_deref().and_then(modified_time);
+
+    let mut window = Window::new(
+        "Leonardo Mejía - Hansel y Gretel House",
+        width,
+        height,
+        WindowOptions::default(),
+    ).unwrap();
+
+    // move the window around
+    window.set_position(500, 500);
+    window.update();
+
     while window.is_open() {
     // Escucha salida
     if window.is_key_down(Key::Escape) {
         break;
     }
 
-    // 🔆 Actualiza ciclo día/noche
-    day_night.update(0.002);
+    // 🔆 Avanza el ciclo día/noche con el tiempo real salvo en pausa (espacio, solo en modo
+    // órbita: volando el espacio sube la cámara). Re Pág acelera el día al doble y Av Pág lo
+    // frena a la mitad. En el modo PathTrace se detiene para que la suma de cuadros no mezcle
+    // al sol en distintas alturas
+    let now = Instant::now();
+    let elapsed = now.duration_since(last_frame).as_secs_f32();
+    last_frame = now;
+    if camera.mode == CameraMode::Orbit && window.is_key_pressed(Key::Space, KeyRepeat::No) {
+        scene.day_night.paused = !scene.day_night.paused;
+    }
+    if window.is_key_pressed(Key::PageUp, KeyRepeat::No) {
+        scene.day_night.faster();
+        println!("Un día dura {} s", scene.day_night.day_length);
+    }
+    if window.is_key_pressed(Key::PageDown, KeyRepeat::No) {
+        scene.day_night.slower();
+        println!("Un día dura {} s", scene.day_night.day_length);
+    }
+    if renderer.settings.mode == RenderMode::RayTrace {
+        scene.day_night.advance(elapsed);
+    }
 
-    // 🔆 Calcula color y posición del sol
-    let ambient_color = day_night.get_current_color();
-    let sun_pos = day_night.sun_position * 2.0;
+    // 🔆 Calcula la posición del sol
+    let sun_pos = scene.day_night.sun_position * 2.0;
+
+    // 🔆 Recarga de --scene: si el modelo cambió se vuelve a leer y reemplaza los objetos (la
+    // cámara se queda donde está). Sus materiales (del .mtl o de la paleta) se registran de nuevo y las luces de
+    // los emisivos se rehacen abajo con update_lights. Si el archivo tiene un error se avisa y
+    // se sigue mostrando la última escena que cargó bien
+    if let Some(path) = &scene_file {
+        let modified = modified_time(path);
+        if modified.is_some() && modified != scene_modified {
+            scene_modified = modified;
+            match load_scene(path, &heightmap, STONE, GRASS, &mut scene.materials, &mut textures) {
+                Ok(objects) => {
+                    scene.world.scene = Bvh::new(objects);
+                    scene.reset_light_switches();
+                    selected = None;
+                    history.clear();
+                    renderer.reset();
+                    println!("Escena recargada: {}", path);
+                }
+                Err(e) => eprintln!("No se pudo recargar la escena {}: {}", path, e),
+            }
+        }
+    }
 
-    // 🔆 Luz principal (sol)
-    lights[0].position = sun_pos;
-    lights[0].color = Color::new(255, 255, 200);
-    lights[0].intensity = day_night.get_light_intensity() * 4.0;
+    // 🔆 Edición de bloques: Retroceso quita el bloque que está bajo la cruz del centro de la
+    // pantalla, Enter pone uno del material elegido con Alt+1..9 junto a la cara que mira y Tab
+    // lo pinta de ese material. No se pone un bloque donde está la cámara. Todo pasa por el
+    // historial: Ctrl+Z deshace y Ctrl+Y rehace. Las luces de los bloques que brillan se rehacen
+    // justo abajo con update_lights
+    let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+    let alt = window.is_key_down(Key::LeftAlt) || window.is_key_down(Key::RightAlt);
+    let mut edit = None;
+    let breaking = window.is_key_pressed(Key::Backspace, KeyRepeat::No);
+    let placing = window.is_key_pressed(Key::Enter, KeyRepeat::No);
+    let painting = window.is_key_pressed(Key::Tab, KeyRepeat::No);
+    if breaking || placing || painting {
+        let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+        let direction = primary_direction(&camera, renderer.settings.fov, width, height, width / 2.0, height / 2.0);
+        let pick = scene.world.pick(&camera.eye, &direction);
+        let material = palette[palette_index];
+        let hit_block = match &pick {
+            Some(Pick::Object(index, _)) => match &scene.world.scene.objects()[*index] {
+                Object::Cube(cube) => Some((*index, cube)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if breaking || painting {
+            match hit_block {
+                Some((index, cube)) if breaking => edit = Some(Edit::Remove { index, cube: cube.clone() }),
+                Some((index, cube)) if cube.material != material => {
+                    edit = Some(Edit::Repaint { index, from: cube.material, to: material });
+                }
+                Some(_) => {}
+                None if pick.is_some() => println!("Solo se pueden cambiar los bloques de la escena"),
+                None => {}
+            }
+        } else if let Some(pick) = &pick {
+            let block = match (hit_block, pick) {
+                (Some((_, cube)), Pick::Object(_, hit)) => cube.adjacent(&hit.face, BLOCK_SIZE, material),
+                (_, Pick::Object(_, hit) | Pick::Terrain(hit)) => grid_block(hit, material),
+            };
+            // Con un margen, para que el lente no quede pegado a la cara nueva
+            let margin = Vec3::repeat(0.05);
+            let eye_inside = (0..3).all(|axis| {
+                camera.eye[axis] > block.min[axis] - margin[axis] && camera.eye[axis] < block.max[axis] + margin[axis]
+            });
+            if eye_inside {
+                println!("No se puede poner un bloque donde está la cámara");
+            } else {
+                edit = Some(Edit::Place { index: scene.world.scene.objects().len(), cube: block });
+            }
+        }
+    }
+    let edited = match edit {
+        Some(edit) => {
+            history.apply(edit, &mut scene.world);
+            true
+        }
+        None if ctrl && window.is_key_pressed(Key::Z, KeyRepeat::Yes) => history.undo(&mut scene.world),
+        None if ctrl && window.is_key_pressed(Key::Y, KeyRepeat::Yes) => history.redo(&mut scene.world),
+        None => false,
+    };
+    // Los índices cambian al quitar o poner un bloque: la selección del clic derecho se pierde
+    if edited {
+        selected = None;
+        renderer.reset();
+    }
 
-    // 🔆 Controles de cámara
-    if window.is_key_down(Key::W) {
-        camera.eye.z = (camera.eye.z - zoom_speed).max(MAX_ZOOM);
+    // 🔆 Ctrl+S guarda la escena como está (bloques, materiales, luces y cámara): sobre el
+    // .json de --scene si se abrió uno, si no en scene.json. Se anota la fecha del archivo
+    // para que la recarga de --scene no lo vuelva a leer
+    if ctrl && window.is_key_pressed(Key::S, KeyRepeat::No) {
+        let save_path = scene_file.as_deref().filter(|path| is_scene_file(path)).unwrap_or(SCENE_SAVE_FILE);
+        match scene.save(Path::new(save_path), &camera) {
+            Ok(()) => {
+                println!("Escena guardada en {}", save_path);
+                if scene_file.as_deref() == Some(save_path) {
+                    scene_modified = modified_time(save_path);
+                }
+            }
+            Err(e) => eprintln!("No se pudo guardar la escena en {}: {}", save_path, e),
+        }
     }
-    if window.is_key_down(Key::S) {
-        camera.eye.z = (camera.eye.z + zoom_speed).min(MIN_ZOOM);
+
+    // 🔆 El portal respira: cambia la emisión de su material y la escena rehace las luces
+    // emisivas para que la luz que echa sobre la casa pulse con él. También mueve el sol y la
+    // luna con la hora del día
+    let time = start_time.elapsed().as_secs_f32();
+    scene.time = time;
+    scene.materials.get_mut(PORTAL).emission = portal_emission(time);
+    scene.update_lights();
+
+    // 🔆 Recorrido de cámara: J agrega la vista actual como keyframe (2 s después del
+    // anterior), U lo reproduce o lo detiene, Y lo borra e I cambia entre interpolación lineal
+    // y Catmull-Rom. Mientras se reproduce, el recorrido mueve la cámara y sus teclas no
+    // responden
+    if window.is_key_pressed(Key::J, KeyRepeat::No) {
+        camera_path.capture(&camera);
+        save_camera_path(&camera_path);
     }
-    if window.is_key_down(Key::Left) {
-        camera.orbit(rotation_speed, 0.0);
+    if !ctrl && window.is_key_pressed(Key::Y, KeyRepeat::No) {
+        camera_path.clear();
+        playback = None;
+        save_camera_path(&camera_path);
     }
-    if window.is_key_down(Key::Right) {
-        camera.orbit(-rotation_speed, 0.0);
+    if window.is_key_pressed(Key::I, KeyRepeat::No) {
+        camera_path.interpolation = camera_path.interpolation.next();
+        save_camera_path(&camera_path);
     }
-    if window.is_key_down(Key::Up) {
-        camera.orbit(0.0, -rotation_speed);
+    if window.is_key_pressed(Key::U, KeyRepeat::No) {
+        playback = match playback {
+            None if camera_path.keyframes().len() >= 2 => Some((Instant::now(), camera_path.clone())),
+            _ => None,
+        };
     }
-    if window.is_key_down(Key::Down) {
-        camera.orbit(0.0, rotation_speed);
+
+    // 🔆 Marcadores: Ctrl+1..9 guarda la vista en ese número y 1..9 vuelve a ella, pasando por
+    // un recorrido corto (--bookmark-transition, 0 para saltar). Un número vacío no hace nada.
+    // Alt+1..9 elige el material de los bloques que se ponen con Enter
+    for (index, key) in BOOKMARK_KEYS.iter().enumerate() {
+        if !window.is_key_pressed(*key, KeyRepeat::No) {
+            continue;
+        }
+        let slot = index + 1;
+        if alt {
+            palette_index = index;
+            println!("Material para poner: {}", scene.materials.name(palette[index]).unwrap_or("sin nombre"));
+        } else if ctrl {
+            bookmarks.store(slot, &camera);
+            if let Err(e) = bookmarks.save(&bookmarks_path) {
+                eprintln!("No se pudo guardar {}: {}", bookmarks_path.display(), e);
+            }
+        } else if let Some(bookmark) = bookmarks.get(slot) {
+            playback = Some((Instant::now(), CameraPath::transition(&camera, bookmark, bookmark_transition)));
+        }
     }
 
-    // 🔆 Control manual del ciclo día/noche
+    if let Some((start, path)) = &playback {
+        // Al llegar al último keyframe la cámara se queda ahí y vuelve a responder
+        let elapsed = start.elapsed().as_secs_f32();
+        path.apply(&mut camera, elapsed);
+        if elapsed >= path.duration() {
+            playback = None;
+        }
+        last_mouse = None;
+    } else {
+        // 🔆 Controles de cámara. C cambia entre órbita y vuelo libre sin mover la vista y K
+        // alterna el noclip. Todo movimiento pasa por camera.apply, que detiene la cámara antes
+        // de atravesar un bloque
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            camera.mode = camera.mode.next();
+        }
+        if window.is_key_pressed(Key::K, KeyRepeat::No) {
+            camera.noclip = !camera.noclip;
+        }
+        let obstacles = &scene.world;
+        match camera.mode {
+            CameraMode::Orbit => {
+                if window.is_key_down(Key::W) {
+                    camera.apply(CameraMove::Dolly(controls.zoom_speed), obstacles);
+                }
+                if window.is_key_down(Key::S) {
+                    camera.apply(CameraMove::Dolly(-controls.zoom_speed), obstacles);
+                }
+                if window.is_key_down(Key::Left) {
+                    camera.apply(CameraMove::Orbit(controls.rotation_speed, 0.0), obstacles);
+                }
+                if window.is_key_down(Key::Right) {
+                    camera.apply(CameraMove::Orbit(-controls.rotation_speed, 0.0), obstacles);
+                }
+                if window.is_key_down(Key::Up) {
+                    camera.apply(CameraMove::Orbit(0.0, -controls.rotation_speed), obstacles);
+                }
+                if window.is_key_down(Key::Down) {
+                    camera.apply(CameraMove::Orbit(0.0, controls.rotation_speed), obstacles);
+                }
+            }
+            // WASD avanza y se desliza según hacia dónde mira la cámara, espacio y Shift suben y
+            // bajan, las flechas giran la vista y Ctrl acelera
+            CameraMode::Fly => {
+                let forward = camera.forward();
+                let right = camera.right();
+                let mut movement = Vec3::new(0.0, 0.0, 0.0);
+                if window.is_key_down(Key::W) {
+                    movement += forward;
+                }
+                if window.is_key_down(Key::S) {
+                    movement -= forward;
+                }
+                if window.is_key_down(Key::D) {
+                    movement += right;
+                }
+                if window.is_key_down(Key::A) {
+                    movement -= right;
+                }
+                if window.is_key_down(Key::Space) {
+                    movement += camera.up;
+                }
+                if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) {
+                    movement -= camera.up;
+                }
+                if movement.magnitude() > 0.0 {
+                    let boost = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+                    let speed = if boost { controls.fly_speed * controls.fly_boost } else { controls.fly_speed };
+                    camera.apply(CameraMove::Translate(movement.normalize() * speed), obstacles);
+                }
+                if window.is_key_down(Key::Left) {
+                    camera.apply(CameraMove::Look(-controls.rotation_speed, 0.0), obstacles);
+                }
+                if window.is_key_down(Key::Right) {
+                    camera.apply(CameraMove::Look(controls.rotation_speed, 0.0), obstacles);
+                }
+                if window.is_key_down(Key::Up) {
+                    camera.apply(CameraMove::Look(0.0, controls.rotation_speed), obstacles);
+                }
+                if window.is_key_down(Key::Down) {
+                    camera.apply(CameraMove::Look(0.0, -controls.rotation_speed), obstacles);
+                }
+            }
+        }
+
+        // 🔆 Mouse: arrastrar con el botón izquierdo gira la cámara (en órbita alrededor del
+        // centro, volando alrededor del ojo) y la rueda la acerca o aleja del centro, con los
+        // mismos límites de distancia que W/S
+        let dragging = window.get_mouse_down(MouseButton::Left);
+        let mouse = window.get_mouse_pos(MouseMode::Pass).filter(|_| dragging);
+        if let (Some((x, y)), Some((last_x, last_y))) = (mouse, last_mouse) {
+            let (dx, dy) = ((x - last_x) * controls.mouse_sensitivity, (y - last_y) * controls.mouse_sensitivity);
+            let movement = match camera.mode {
+                CameraMode::Orbit => CameraMove::Orbit(dx, -dy),
+                CameraMode::Fly => CameraMove::Look(dx, -dy),
+            };
+            camera.apply(movement, obstacles);
+        }
+        last_mouse = mouse;
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            camera.apply(CameraMove::Dolly(scroll * controls.wheel_zoom), obstacles);
+        }
+    }
+
+    // 🔆 Selección: el clic derecho lanza el mismo rayo de cámara que el píxel bajo el mouse
+    // y marca el objeto que toca, escribiendo en la consola cuál es y su material. En el cielo
+    // quita la selección; los bloques del terreno solo se describen, no tienen índice
+    let right_down = window.get_mouse_down(MouseButton::Right);
+    let clicked = right_down && !right_was_down;
+    if let Some((mouse_x, mouse_y)) = window.get_mouse_pos(MouseMode::Discard).filter(|_| clicked) {
+        // La ventana puede no tener el tamaño de la imagen: el mouse se pasa a sus píxeles
+        let (window_width, window_height) = window.get_size();
+        let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+        let x = (mouse_x * width / window_width as f32).floor();
+        let y = (mouse_y * height / window_height as f32).floor();
+        let direction = primary_direction(&camera, renderer.settings.fov, width, height, x, y);
+        selected = match scene.world.pick(&camera.eye, &direction) {
+            Some(Pick::Object(index, hit)) => {
+                print_selection(index, &scene.world.scene.objects()[index], &hit, &scene.materials);
+                Some(index)
+            }
+            Some(Pick::Terrain(hit)) => {
+                println!("Terreno: bloque tocado en ({:.2}, {:.2}, {:.2})", hit.point.x, hit.point.y, hit.point.z);
+                None
+            }
+            None => {
+                println!("Selección quitada");
+                None
+            }
+        };
+    }
+    right_was_down = right_down;
+
+    // 🔆 F1 muestra u oculta el texto sobre la imagen
+    if window.is_key_pressed(Key::F1, KeyRepeat::No) {
+        show_hud = !show_hud;
+    }
+
+    // 🔆 F12 guarda la imagen que está en la ventana como PNG en screenshots/, con la fecha y
+    // la hora en el nombre. Se escribe en otro hilo para que el render no se trabe
+    if window.is_key_pressed(Key::F12, KeyRepeat::No) {
+        match screenshot::reserve_path(Path::new(SCREENSHOT_DIR), &screenshot::timestamp(SystemTime::now())) {
+            Ok(path) => screenshots.push(screenshot::save_in_background(framebuffer.clone(), path)),
+            Err(e) => eprintln!("No se pudo crear la captura en {}: {}", SCREENSHOT_DIR, e),
+        }
+    }
+    screenshots.retain(|handle| !handle.is_finished());
+
+    // 🔆 F9 empieza o termina una grabación: cada cuadro trazado se guarda como
+    // frame_00001.png, frame_00002.png... en recordings/<fecha>, para armar un video
+    if window.is_key_pressed(Key::F9, KeyRepeat::No) {
+        recorder = match recorder.take() {
+            Some(recorder) => {
+                print_recording_summary(&recorder.stop());
+                None
+            }
+            None => {
+                let dir = Path::new(RECORDING_DIR).join(screenshot::timestamp(SystemTime::now()));
+                match Recorder::start(&dir) {
+                    Ok(recorder) => {
+                        println!("Grabando en {}", recorder.dir().display());
+                        Some(recorder)
+                    }
+                    Err(e) => {
+                        eprintln!("No se pudo empezar a grabar en {}: {}", dir.display(), e);
+                        None
+                    }
+                }
+            }
+        };
+    }
+
+    // 🔆 Control manual del ciclo día/noche. Lo que cambia la imagen sin ser una opción del
+    // título marca scene_changed, para que el promedio de cuadros vuelva a empezar
+    let mut scene_changed = false;
     if window.is_key_down(Key::O) {
-        day_night.update(-0.005);
+        scene.day_night.update(-0.005);
+        scene_changed = true;
     }
     if window.is_key_down(Key::P) {
-        day_night.update(0.005);
+        scene.day_night.update(0.005);
+        scene_changed = true;
+    }
+    // En pausa Q retrocede y E adelanta el día, de a poco mientras se mantienen
+    if scene.day_night.paused {
+        if window.is_key_down(Key::Q) {
+            scene.day_night.update(-0.005);
+            scene_changed = true;
+        }
+        if window.is_key_down(Key::E) {
+            scene.day_night.update(0.005);
+            scene_changed = true;
+        }
+    }
+
+    // 🔆 Opciones de imagen: G alterna la corrección gamma, T cambia la curva de tone mapping,
+    // +/- ajustan la exposición y A cambia el antialiasing (en órbita; volando A mueve la cámara). En el adaptativo [ y ] mueven el
+    // umbral de borde y F2 muestra la máscara. H cambia los rayos de sombra y L alterna las
+    // luces de área de los bloques emisivos. M alterna los mipmaps y N tiñe cada nivel.
+    // B cambia el fondo, F alterna la niebla y V los rayos crepusculares. Con , y . se mueve
+    // la distancia de enfoque y con ; y ' la apertura (0 = todo enfocado). F3 alterna el
+    // anaglifo rojo/cian. R cambia entre el trazado de rayos y el de caminos, y espacio pausa
+    // el paso automático del día. Z y X cierran y abren el campo de visión
+    let mut settings_changed = false;
+    if window.is_key_pressed(Key::G, KeyRepeat::No) {
+        renderer.settings.gamma_correction = !renderer.settings.gamma_correction;
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::T, KeyRepeat::No) {
+        renderer.settings.tone_mapping = renderer.settings.tone_mapping.next();
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::Equal, KeyRepeat::Yes) || window.is_key_pressed(Key::NumPadPlus, KeyRepeat::Yes) {
+        renderer.settings.exposure = (renderer.settings.exposure * 1.1).min(16.0);
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::Minus, KeyRepeat::Yes) || window.is_key_pressed(Key::NumPadMinus, KeyRepeat::Yes) {
+        renderer.settings.exposure = (renderer.settings.exposure / 1.1).max(1.0 / 16.0);
+        settings_changed = true;
+    }
+    if camera.mode == CameraMode::Orbit && window.is_key_pressed(Key::A, KeyRepeat::No) {
+        renderer.settings.cycle_antialiasing();
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::LeftBracket, KeyRepeat::Yes) {
+        renderer.settings.edge_threshold = (renderer.settings.edge_threshold - 0.01).max(0.01);
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::RightBracket, KeyRepeat::Yes) {
+        renderer.settings.edge_threshold = (renderer.settings.edge_threshold + 0.01).min(1.0);
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::F2, KeyRepeat::No) {
+        renderer.settings.show_edges = !renderer.settings.show_edges;
+        scene_changed = true;
+    }
+    if window.is_key_pressed(Key::H, KeyRepeat::No) {
+        renderer.settings.shadow_samples = renderer.settings.next_shadow_samples();
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::L, KeyRepeat::No) {
+        renderer.settings.area_lights = !renderer.settings.area_lights;
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::M, KeyRepeat::No) {
+        renderer.settings.mipmaps = !renderer.settings.mipmaps;
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::N, KeyRepeat::No) {
+        renderer.settings.show_mip_levels = !renderer.settings.show_mip_levels;
+        scene_changed = true;
     }
+    if window.is_key_pressed(Key::F, KeyRepeat::No) {
+        renderer.settings.fog = !renderer.settings.fog;
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::V, KeyRepeat::No) {
+        renderer.settings.light_shafts = !renderer.settings.light_shafts;
+        settings_changed = true;
+    }
+    // En estéreo , y . mueven la separación de los ojos en vez del enfoque
+    if window.is_key_pressed(Key::F3, KeyRepeat::No) {
+        renderer.settings.stereo = !renderer.settings.stereo;
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::Comma, KeyRepeat::Yes) {
+        if renderer.settings.stereo {
+            renderer.settings.eye_separation = (renderer.settings.eye_separation - 0.025).max(0.0);
+        } else {
+            camera.focus_distance = (camera.focus_distance - 0.25).max(0.25);
+        }
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::Period, KeyRepeat::Yes) {
+        if renderer.settings.stereo {
+            renderer.settings.eye_separation = (renderer.settings.eye_separation + 0.025).min(1.0);
+        } else {
+            camera.focus_distance = (camera.focus_distance + 0.25).min(30.0);
+        }
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::Semicolon, KeyRepeat::Yes) {
+        // Al bajar de un paso vuelve exactamente a 0: la cámara estenopeica de siempre
+        camera.aperture = if camera.aperture <= 0.02 { 0.0 } else { camera.aperture - 0.02 };
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::Apostrophe, KeyRepeat::Yes) {
+        camera.aperture = (camera.aperture + 0.02).min(0.5);
+        settings_changed = true;
+    }
+    if !ctrl && window.is_key_pressed(Key::Z, KeyRepeat::Yes) {
+        renderer.settings.fov = (renderer.settings.fov - fov_step).max(MIN_FOV);
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::X, KeyRepeat::Yes) {
+        renderer.settings.fov = (renderer.settings.fov + fov_step).min(MAX_FOV);
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::R, KeyRepeat::No) {
+        renderer.settings.mode = renderer.settings.mode.next();
+        settings_changed = true;
+    }
+    if window.is_key_pressed(Key::B, KeyRepeat::No) {
+        scene.next_environment();
+        println!("Fondo: {}", scene.environment().name());
+        scene_changed = true;
+    }
+    // 🔆 Interruptores de luces, para ver qué aporta cada una: F5/F6 eligen la luz anterior o
+    // siguiente (el sol, la luna, las puestas a mano y las de los bloques que brillan), F7 la
+    // apaga o la vuelve a encender y F8 deja encendida solo esa. Recargar --scene los reinicia
+    if window.is_key_pressed(Key::F5, KeyRepeat::Yes) {
+        scene.select_light(-1);
+        println!("{}", light_label(&scene));
+        scene_changed = true;
+    }
+    if window.is_key_pressed(Key::F6, KeyRepeat::Yes) {
+        scene.select_light(1);
+        println!("{}", light_label(&scene));
+        scene_changed = true;
+    }
+    if window.is_key_pressed(Key::F7, KeyRepeat::No) {
+        scene.toggle_selected_light();
+        println!("{}", light_label(&scene));
+        scene_changed = true;
+    }
+    if window.is_key_pressed(Key::F8, KeyRepeat::No) {
+        scene.isolate_light = !scene.isolate_light;
+        println!("{}", light_label(&scene));
+        scene_changed = true;
+    }
+
+    // 🔆 El promedio de cuadros vuelve a empezar si cambia una opción o la escena; de la
+    // cámara se encarga el renderer
+    if settings_changed || scene_changed {
+        renderer.reset();
+    }
+
+    // 🔆 Carga los chunks cercanos a la cámara y suelta los lejanos
+    scene.world.terrain.update(&camera.eye);
+    // 🔆 Con el ojo dentro del estanque la vista pasa a ser la de debajo del agua
+    scene.update_submersion(&camera.eye);
+
+    // 🔆 Solo se traza si algo cambió desde el último cuadro, si hay una tecla apretada o si el
+    // promedio de cuadros todavía mejora; si no, se vuelve a mostrar el framebuffer como está
+    let sky_time = match scene.environment() {
+        Environment::ProceduralSky(sky) if sky.clouds.speed != 0.0 && sky.clouds.coverage > 0.0 => time,
+        _ => 0.0,
+    };
+    let has_waves = scene.world.scene.objects().iter()
+        .any(|object| scene.materials.get(object.material()).waves.is_some());
+    let wave_time = if has_waves { time } else { 0.0 };
+    let frame_state = FrameState {
+        view: (camera.eye, camera.center, camera.aperture, camera.focus_distance),
+        day_time: scene.day_night.time,
+        lights: scene.lights.clone(),
+        animation_frames: scene.world.scene.objects().iter()
+            .filter_map(|object| scene.materials.get(object.material()).animation.as_ref())
+            .map(|animation| animation.frame_index(time))
+            .collect(),
+        sky_time,
+        wave_time,
+        environment: scene.environment,
+        window_size: window.get_size(),
+        selection: selected,
+    };
+    let dirty = last_frame_state.as_ref() != Some(&frame_state)
+        || !window.get_keys().is_empty()
+        || !renderer.converged();
+
+    let mut render_time = None;
+    if dirty {
+        // 🔆 Render general. Con F4 apretada se cuentan los rayos de cada tipo y el desglose va a
+        // la consola en cada cuadro; sin ella no se cuenta nada
+        renderer.settings.count_rays = window.is_key_down(Key::F4);
+        let render_start = Instant::now();
+        renderer.render(&scene, &camera, &mut framebuffer);
+        let elapsed = render_start.elapsed();
+        if renderer.settings.count_rays {
+            print_ray_counts(take_ray_counts(), elapsed);
+        }
+        render_time = Some(elapsed);
+
+        // 🔆 Dibuja el sol, salvo cuando ya se puso
+        if scene.day_night.sun_position.y > 0.0 {
+            for vertex in &sun_vertices {
+                let pos = *vertex * 0.5 + sun_pos;
+                framebuffer.set_current_color(Color::new(255, 255, 120).to_hex());
+                framebuffer.point(
+                    (pos.x * 80.0 + framebuffer.width as f32 / 2.0) as usize,
+                    (pos.y * 80.0 + framebuffer.height as f32 / 2.0) as usize,
+                );
+            }
+        }
 
-    // 🔆 Render general
-    render(&mut framebuffer, &objects, &camera, &lights, &ambient_color);
+        // 🔆 Grabación: el cuadro va a la cola antes de dibujar la cruz, los contornos y el texto,
+        // que no salen en el video
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&framebuffer);
+        }
 
-    // 🔆 Dibuja el sol
-    for vertex in &sun_model.vertices {
-        let pos = *vertex * 0.5 + sun_pos;
-        framebuffer.set_current_color(Color::new(255, 255, 120).to_hex());
-        framebuffer.point(
-            (pos.x * 80.0 + framebuffer.width as f32 / 2.0) as usize,
-            (pos.y * 80.0 + framebuffer.height as f32 / 2.0) as usize,
-        );
+        // 🔆 Cruz del centro: marca el bloque que quitan o junto al que ponen Retroceso y Enter
+        framebuffer.set_current_color(Color::new(255, 255, 255).to_hex());
+        let (center_x, center_y) = ((framebuffer.width / 2) as f32, (framebuffer.height / 2) as f32);
+        framebuffer.line((center_x - 6.0, center_y), (center_x + 6.0, center_y));
+        framebuffer.line((center_x, center_y - 6.0), (center_x, center_y + 6.0));
+
+        // 🔆 Contorno de la cara del bloque bajo la cruz, que es la que quitan, pintan o junto a
+        // la que ponen las teclas de edición; se vuelve a buscar en cada cuadro trazado
+        let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+        if let Some(corners) = aimed_face(&scene, &camera, renderer.settings.fov, width, height) {
+            framebuffer.set_current_color(Color::new(255, 220, 0).to_hex());
+            for i in 0..4 {
+                draw_segment(&mut framebuffer, &camera, renderer.settings.fov, &corners[i], &corners[(i + 1) % 4]);
+            }
+        }
+
+        // 🔆 Aristas del objeto elegido (los planos no tienen caja que marcar)
+        if let Some(index) = selected {
+            let (min, max) = scene.world.scene.objects()[index].bounds();
+            if min.iter().chain(max.iter()).all(|c| c.is_finite()) {
+                framebuffer.set_current_color(Color::new(0, 255, 255).to_hex());
+                draw_box_edges(&mut framebuffer, &camera, renderer.settings.fov, &min, &max);
+            }
+        }
+
+        // 🔆 Texto sobre la imagen: se dibuja después del render, así que no se traza
+        if show_hud {
+            let (hours, minutes) = scene.day_night.clock();
+            let hud = format!(
+                "{}\nHora {:02}:{:02}{}\nCámara {:.1}, {:.1}, {:.1}\nBloque: {}\n{}",
+                frame_times.summary().unwrap_or_else(|| "-- fps".to_string()),
+                hours, minutes,
+                if scene.day_night.paused { " (pausa)".to_string() } else { format!(" (día de {} s)", scene.day_night.day_length) },
+                camera.eye.x, camera.eye.y, camera.eye.z,
+                scene.materials.name(palette[palette_index]).unwrap_or("sin nombre"),
+                light_label(&scene),
+            );
+            draw_hud(&mut framebuffer, &hud);
+        }
+        // Aviso de grabación arriba a la derecha, aunque el texto esté oculto
+        if recorder.is_some() {
+            let x = framebuffer.width as isize - 4 - 3 * GLYPH_SIZE as isize;
+            framebuffer.draw_text(x + 1, 5, "REC", Color::new(0, 0, 0));
+            framebuffer.draw_text(x, 4, "REC", Color::new(255, 40, 40));
+        }
+        last_frame_state = Some(frame_state);
+    } else {
+        skipped_frames += 1;
     }
 
-    // 🔆 Actualiza la ventana
+    // El título empieza con los cuadros por segundo (promedio de los últimos 30 trazados) y
+    // lleva la cuenta de cuadros promediados y de los que no hizo falta trazar
+    window.set_title(&format!(
+        "{} - {}",
+        frame_times.title(),
+        renderer.settings.title(&camera, renderer.frames(), skipped_frames)
+    ));
+
+    // 🔆 Actualiza la ventana. Los cuadros trazados cuentan el render más esta subida
+    let upload_start = Instant::now();
     window
         .update_with_buffer(&framebuffer.buffer, framebuffer.width, framebuffer.height)
         .unwrap();
+    if let Some(render_time) = render_time {
+        frame_times.push(render_time + upload_start.elapsed());
+    }
 
     std::thread::sleep(frame_delay);
 }
-}
\ No newline at end of file
+
+    if let Some(recorder) = recorder {
+        print_recording_summary(&recorder.stop());
+    }
+    for handle in screenshots {
+        let _ = handle.join();
+    }
+}
@@ -1,25 +1,102 @@
-use crate::color::Color;
-use crate::texture::Texture;
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use crate::color::{Color, ColorF};
+use crate::light::Flicker;
+use crate::ray_intersect::CubeFace;
+use crate::texture::{AnimatedTexture, Texture};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Ripples on the top of water: two sine waves crossing at an angle and scrolling with time,
+// summed as a height field whose slope tilts the shading normal. The surface itself stays flat
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Waves {
+    pub amplitude: f32,  // Height of the first wave; the second is half as tall
+    pub wavelength: f32, // Of the first wave; the second is 0.6 times as long
+    pub speed: f32,      // Units per second the crests travel
+}
+
+impl Waves {
+    pub fn new(amplitude: f32, wavelength: f32, speed: f32) -> Self {
+        Waves { amplitude, wavelength, speed }
+    }
+
+    // Normal of the rippled surface over (x, z), `time` seconds into the run; straight up
+    // with no amplitude
+    pub fn normal(&self, x: f32, z: f32, time: f32) -> Vec3 {
+        let waves = [
+            ((0.8, 0.6), self.amplitude, self.wavelength),
+            ((-0.6, 0.8), self.amplitude * 0.5, self.wavelength * 0.6),
+        ];
+        let (mut dx, mut dz) = (0.0, 0.0);
+        for ((along_x, along_z), amplitude, wavelength) in waves {
+            let k = 2.0 * PI / wavelength;
+            let slope = amplitude * k * (k * (along_x * x + along_z * z - self.speed * time)).cos();
+            dx += slope * along_x;
+            dz += slope * along_z;
+        }
+        Vec3::new(-dx, 1.0, -dz).normalize()
+    }
+}
+
+// Which sides of a surface rays see
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sidedness {
+    // As the geometry gives it: a cube hit from inside gets its normal turned toward the ray,
+    // while planes and meshes shade their back with the normal pointing away
+    #[default]
+    Geometry,
+    // Back faces are shaded like front faces, with the normal flipped toward the ray
+    DoubleSided,
+    // Rays go through back faces as if they weren't there, and light is only stopped by the
+    // front, so a one-sided plane casts no shadow when lit from behind
+    FrontOnly,
+}
 
 #[derive(Clone, Debug)]
 pub struct Material {
     pub color: Color,
     pub shininess: f32,
-    pub properties: [f32; 4],
+    pub diffuse: f32,      // Weight of the Lambert term
+    pub specular: f32,     // Weight of the Blinn-Phong highlight
+    pub reflectivity: f32, // Share of the final color taken from the reflected ray
+    pub roughness: f32,    // Blur of the reflection, 0 (mirror) to 1 (very rough)
+    pub transparency: f32, // Share taken from the refracted ray
+    pub refraction_roughness: f32, // Blur of what is seen through it, 0 (clear) to 1 (frosted); like roughness for the reflection
     pub refractive_index: f32,
-    pub textures: Vec<Texture>,
+    pub dispersion: f32, // How far apart the red and blue indices are from refractive_index; 0 refracts every color alike
+    pub textures: Vec<Arc<Texture>>, // Shared, so cloning a material only bumps reference counts
     pub emission: Color,
+    pub emission_strength: f32, // Times `emission` the surface glows with, added on top of its lit color; also the strength of its light
+    pub emission_from_texture: bool, // Glows with its texels (times emission_strength) instead of `emission`
+    pub uv_scale: f32, // Times the texture repeats across a face (or a sphere, a mesh...)
+    pub face_textures: [usize; 6], // Index into `textures` for each CubeFace, in CubeFace::index order
+    pub normal_map: Option<Arc<Texture>>, // Tangent-space normals (OpenGL, green up), same uv as the textures
+    pub alpha_cutoff: f32, // Texels with less alpha than this are holes rays pass through; 0 turns it off
+    pub animation: Option<Arc<AnimatedTexture>>, // Replaces the textures on every face while set
+    pub absorption: ColorF, // Beer-Lambert coefficient per channel and unit of distance inside; black absorbs nothing
+    pub flicker: Option<Flicker>, // Passed on to the light Scene makes for an emissive object
+    pub shadow_catcher: bool, // Invisible but for the shadows on it, for compositing over a photo
+    pub sidedness: Sidedness,
+    pub waves: Option<Waves>, // Ripples the top faces' normals with the frame time
+    pub fluid: bool, // A volume the camera can be under (water): seen from inside, it tints and fogs the view
 }
 
 impl Material {
-    pub fn new(color: Color, shininess: f32, properties: [f32; 4], refractive_index: f32) -> Self {
-        Material {
+    // Starts a material with sensible defaults: mostly diffuse, a faint highlight,
+    // opaque and not reflective
+    pub fn builder(color: Color) -> MaterialBuilder {
+        MaterialBuilder {
             color,
-            shininess,
-            properties,
-            refractive_index,
-            textures: Vec::new(),
-            emission: Color::new(0, 0, 0), // Por defecto, no emite luz
+            shininess: 10.0,
+            diffuse: 0.8,
+            specular: 0.2,
+            reflectivity: 0.0,
+            roughness: 0.0,
+            transparency: 0.0,
+            refraction_roughness: 0.0,
+            refractive_index: 1.0,
+            dispersion: 0.0,
         }
     }
 
@@ -28,35 +105,413 @@ impl Material {
         self
     }
 
+    pub fn with_emission_strength(mut self, strength: f32) -> Self {
+        self.emission_strength = strength;
+        self
+    }
+
+    // Makes the surface glow with its own texture instead of a flat `emission`: each texel
+    // gives off its color times emission_strength, so the bright cracks of lava glow more than
+    // the dark crust. The light the scene makes for it takes the current frame's average color
+    pub fn with_texture_emission(mut self) -> Self {
+        self.emission_from_texture = true;
+        self
+    }
+
+    // Whether the surface gives off light of its own (and gets a light in the scene for it)
+    pub fn is_emissive(&self) -> bool {
+        (self.emission != Color::new(0, 0, 0) || self.emission_from_texture) && self.emission_strength > 0.0
+    }
+
+    // Color of the light the surface gives off at `time`: `emission`, or with
+    // emission_from_texture the average of the texture on screen then (white if it has none)
+    pub fn emission_color(&self, time: f32) -> Color {
+        if !self.emission_from_texture {
+            return self.emission;
+        }
+        self.texture_for(&CubeFace::Top, time)
+            .map_or(Color::new(255, 255, 255), |texture| texture.average().to_srgb())
+    }
+
     // Method to create a black material with default values
     pub fn black() -> Self {
         Material {
             color: Color::new(0, 0, 0),    // Use integer values for Color
             shininess: 0.0,                 // Default shininess
-            properties: [0.0, 0.0, 0.0, 0.0], // Default properties (all set to 0)
+            diffuse: 0.0,
+            specular: 0.0,
+            reflectivity: 0.0,
+            roughness: 0.0,
+            transparency: 0.0,
+            refraction_roughness: 0.0,
             refractive_index: 1.0,          // Default refractive index (e.g., for air)
+            dispersion: 0.0,
             textures: Vec::new(),            // Empty textures vector
             emission: Color::new(0, 0, 0),   // No emission for black material
+            emission_strength: 1.0,
+            emission_from_texture: false,
+            uv_scale: 1.0,
+            face_textures: [0; 6],
+            normal_map: None,
+            alpha_cutoff: 0.0,
+            animation: None,
+            absorption: ColorF::black(),
+            flicker: None,
+            shadow_catcher: false,
+            sidedness: Sidedness::Geometry,
+            waves: None,
+            fluid: false,
         }
     }
 
-    pub fn with_textures(mut self, textures: Vec<Texture>) -> Self {
-        self.textures = textures;
+    pub fn with_uv_scale(mut self, uv_scale: f32) -> Self {
+        self.uv_scale = uv_scale;
+        self
+    }
+
+    // Accepts owned textures or already shared ones (`Arc<Texture>`)
+    pub fn with_textures<T: Into<Arc<Texture>>>(mut self, textures: impl IntoIterator<Item = T>) -> Self {
+        self.textures = textures.into_iter().map(Into::into).collect();
         self
     }
 
+    // Different textures for the top, the bottom and the four sides (grass blocks...)
+    pub fn with_face_textures(
+        mut self,
+        top: impl Into<Arc<Texture>>,
+        bottom: impl Into<Arc<Texture>>,
+        sides: impl Into<Arc<Texture>>,
+    ) -> Self {
+        self.textures = vec![top.into(), bottom.into(), sides.into()];
+        self.face_textures = [0, 1, 2, 2, 2, 2];
+        self
+    }
+
+    // Bumps the surface at shading time: cast_ray tilts the normal by the map
+    pub fn with_normal_map(mut self, normal_map: impl Into<Arc<Texture>>) -> Self {
+        self.normal_map = Some(normal_map.into());
+        self
+    }
+
+    // Alpha testing for cutout textures (leaves, flowers): texels below `cutoff` are
+    // skipped by camera and shadow rays
+    pub fn with_alpha_cutoff(mut self, cutoff: f32) -> Self {
+        self.alpha_cutoff = cutoff;
+        self
+    }
+
+    // Animated surface (portal, lava): every face shows the current frame
+    pub fn with_animation(mut self, animation: impl Into<Arc<AnimatedTexture>>) -> Self {
+        self.animation = Some(animation.into());
+        self
+    }
+
+    // Tints light traveling through the material: after `1 / density` units only about
+    // a third of the channels `color` lacks is left, and thicker parts look deeper
+    pub fn with_absorption(mut self, color: Color, density: f32) -> Self {
+        let color = color.to_linear();
+        self.absorption = ColorF::new(1.0 - color.r, 1.0 - color.g, 1.0 - color.b) * density;
+        self
+    }
+
+    // Makes the light of an emissive object with this material waver like a torch
+    pub fn with_flicker(mut self, flicker: Flicker) -> Self {
+        self.flicker = Some(flicker);
+        self
+    }
+
+    // Turns the surface into a shadow catcher: camera rays see the background through it,
+    // darkened where shadows fall, and every other ray (reflections, shadow rays) goes
+    // through as if it weren't there
+    pub fn as_shadow_catcher(mut self) -> Self {
+        self.shadow_catcher = true;
+        self
+    }
+
+    // Clear water: light mostly goes through (Fresnel reflects more of it at grazing angles),
+    // bends like real water and turns bluer the deeper it is. Its top ripples; the renderer
+    // moves the waves with the frame time, so the preset needs no updating
+    pub fn water() -> Material {
+        Material::builder(Color::new(255, 255, 255))
+            .shininess(120.0)
+            .diffuse(0.0)
+            .specular(0.5)
+            .transparency(0.95)
+            .refractive_index(1.33)
+            .build()
+            .with_absorption(Color::new(60, 150, 210), 1.5)
+            .with_waves(Waves::new(0.012, 0.6, 0.25))
+            .as_fluid()
+    }
+
+    // Frosted ice: see-through like glass but blurring what is behind it, with a faint blue
+    // tint that deepens with thickness
+    pub fn ice() -> Material {
+        Material::builder(Color::new(235, 245, 255))
+            .shininess(80.0)
+            .diffuse(0.05)
+            .specular(0.4)
+            .transparency(0.9)
+            .refraction_roughness(0.15)
+            .refractive_index(1.31)
+            .build()
+            .with_absorption(Color::new(200, 225, 255), 0.8)
+    }
+
+    // Marks the material as a volume the camera can sink into. With the eye inside one of its
+    // cubes (see Scene::update_submersion), the view takes its absorption as a tint and a
+    // short fog
+    pub fn as_fluid(mut self) -> Self {
+        self.fluid = true;
+        self
+    }
+
+    // Only the top faces ripple, so the sides of a water block don't shimmer
+    pub fn with_waves(mut self, waves: Waves) -> Self {
+        self.waves = Some(waves);
+        self
+    }
+
+    pub fn with_sidedness(mut self, sidedness: Sidedness) -> Self {
+        self.sidedness = sidedness;
+        self
+    }
+
+    // Share of each channel left after `distance` units inside the material
+    pub fn transmittance(&self, distance: f32) -> ColorF {
+        let a = &self.absorption;
+        ColorF::new((-a.r * distance).exp(), (-a.g * distance).exp(), (-a.b * distance).exp())
+    }
+
+    // Index seen by one color channel (0 red, 1 green, 2 blue); blue bends the most.
+    // None, for rays carrying all three, gives the base index
+    pub fn refractive_index_for(&self, channel: Option<usize>) -> f32 {
+        match channel {
+            Some(channel) => self.refractive_index + self.dispersion * (channel as f32 - 1.0),
+            None => self.refractive_index,
+        }
+    }
+
+    // Texture shown on a face `time` seconds into the run; every face uses the first
+    // texture unless with_face_textures says otherwise. None for untextured materials.
+    pub fn texture_for(&self, face: &CubeFace, time: f32) -> Option<&Texture> {
+        if let Some(animation) = &self.animation {
+            return Some(animation.frame(time));
+        }
+        let index = self.face_textures[face.index()];
+        self.textures.get(index).or(self.textures.first()).map(|texture| texture.as_ref())
+    }
+
+    // Reflectivity and transparency as shading uses them. The builder keeps their sum at most
+    // 1, but a material edited field by field or read from a scene file may go over; then both
+    // are scaled down together so the surface's own color never gets a negative weight
+    pub fn surface_weights(&self) -> (f32, f32) {
+        let (reflectivity, transparency) = (self.reflectivity.max(0.0), self.transparency.max(0.0));
+        let total = reflectivity + transparency;
+        if total > 1.0 {
+            (reflectivity / total, transparency / total)
+        } else {
+            (reflectivity, transparency)
+        }
+    }
+
     // Method to determine if the material is completely diffuse (no shininess)
     pub fn is_diffuse(&self) -> bool {
-        self.properties[1] == 0.0 && self.properties[2] == 0.0
+        self.specular == 0.0 && self.reflectivity == 0.0
     }
 
     // Method to determine if the material is reflective
     pub fn is_reflective(&self) -> bool {
-        self.properties[2] > 0.0
+        self.reflectivity > 0.0
     }
 
     // Method to determine if the material is transparent
     pub fn is_transparent(&self) -> bool {
-        self.properties[3] > 0.0
+        self.transparency > 0.0
+    }
+}
+
+// Named surface coefficients for Material::builder; textures and emission are
+// added afterwards with the `with_*` methods
+pub struct MaterialBuilder {
+    color: Color,
+    shininess: f32,
+    diffuse: f32,
+    specular: f32,
+    reflectivity: f32,
+    roughness: f32,
+    transparency: f32,
+    refraction_roughness: f32,
+    refractive_index: f32,
+    dispersion: f32,
+}
+
+impl MaterialBuilder {
+    pub fn shininess(mut self, shininess: f32) -> Self {
+        self.shininess = shininess;
+        self
+    }
+
+    pub fn diffuse(mut self, diffuse: f32) -> Self {
+        self.diffuse = diffuse;
+        self
+    }
+
+    pub fn specular(mut self, specular: f32) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn reflectivity(mut self, reflectivity: f32) -> Self {
+        self.reflectivity = reflectivity;
+        self
+    }
+
+    pub fn roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn refraction_roughness(mut self, roughness: f32) -> Self {
+        self.refraction_roughness = roughness;
+        self
+    }
+
+    pub fn transparency(mut self, transparency: f32) -> Self {
+        self.transparency = transparency;
+        self
+    }
+
+    pub fn refractive_index(mut self, refractive_index: f32) -> Self {
+        self.refractive_index = refractive_index;
+        self
+    }
+
+    pub fn dispersion(mut self, dispersion: f32) -> Self {
+        self.dispersion = dispersion;
+        self
+    }
+
+    // Panics on coefficients cast_ray can't blend: negative weights, or
+    // reflectivity + transparency above 1, which would give the local
+    // shading a negative share
+    pub fn build(self) -> Material {
+        self.try_build().unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    // Like `build`, but gives back what is wrong instead of panicking, for
+    // coefficients that come from a file
+    pub fn try_build(self) -> Result<Material, String> {
+        let weights = [self.diffuse, self.specular, self.reflectivity, self.transparency];
+        if !weights.iter().all(|w| *w >= 0.0) {
+            return Err(format!(
+                "material weights must not be negative (diffuse {}, specular {}, reflectivity {}, transparency {})",
+                self.diffuse, self.specular, self.reflectivity, self.transparency,
+            ));
+        }
+        if self.reflectivity + self.transparency > 1.0 {
+            return Err(format!("reflectivity ({}) + transparency ({}) must not exceed 1", self.reflectivity, self.transparency));
+        }
+        if !(0.0..=1.0).contains(&self.roughness) {
+            return Err(format!("roughness must be between 0 and 1 (got {})", self.roughness));
+        }
+        if !(0.0..=1.0).contains(&self.refraction_roughness) {
+            return Err(format!("refraction roughness must be between 0 and 1 (got {})", self.refraction_roughness));
+        }
+
+        Ok(Material {
+            color: self.color,
+            shininess: self.shininess,
+            diffuse: self.diffuse,
+            specular: self.specular,
+            reflectivity: self.reflectivity,
+            roughness: self.roughness,
+            transparency: self.transparency,
+            refraction_roughness: self.refraction_roughness,
+            refractive_index: self.refractive_index,
+            dispersion: self.dispersion,
+            textures: Vec::new(),
+            emission: Color::new(0, 0, 0), // Por defecto, no emite luz
+            emission_strength: 1.0,
+            emission_from_texture: false,
+            uv_scale: 1.0,
+            face_textures: [0; 6],
+            normal_map: None,
+            alpha_cutoff: 0.0,
+            animation: None,
+            absorption: ColorF::black(),
+            flicker: None,
+            shadow_catcher: false,
+            sidedness: Sidedness::Geometry,
+            waves: None,
+            fluid: false,
+        })
+    }
+}
+
+// Handle to a material in a MaterialRegistry; objects keep one of these
+// instead of their own copy of the material
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialId(usize);
+
+// Every material of the scene, stored once. Editing one through `get_mut`
+// changes every object that uses it without rebuilding the object list.
+pub struct MaterialRegistry {
+    materials: Vec<Material>,
+    names: HashMap<String, MaterialId>,
+}
+
+impl Default for MaterialRegistry {
+    fn default() -> Self {
+        MaterialRegistry::new()
+    }
+}
+
+impl MaterialRegistry {
+    pub fn new() -> Self {
+        MaterialRegistry {
+            materials: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    // Registers a named material; adding a name again replaces the material
+    // behind the id it already has
+    pub fn add(&mut self, name: &str, material: Material) -> MaterialId {
+        if let Some(&id) = self.names.get(name) {
+            self.materials[id.0] = material;
+            return id;
+        }
+        let id = self.insert(material);
+        self.names.insert(name.to_string(), id);
+        id
+    }
+
+    // Registers a material nobody needs to look up by name (.mtl materials...)
+    pub fn insert(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    pub fn id(&self, name: &str) -> Option<MaterialId> {
+        self.names.get(name).copied()
+    }
+
+    // Name a material was added with; None for unnamed ones
+    pub fn name(&self, id: MaterialId) -> Option<&str> {
+        self.names.iter().find(|(_, named)| **named == id).map(|(name, _)| name.as_str())
+    }
+
+    // Every material with its id, in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = (MaterialId, &Material)> {
+        self.materials.iter().enumerate().map(|(index, material)| (MaterialId(index), material))
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: MaterialId) -> &mut Material {
+        &mut self.materials[id.0]
     }
 }
@@ -0,0 +1,62 @@
+use crate::material::MaterialId;
+
+// Rays bounce at most a few times, so they can't be inside more media than this
+const MAX_MEDIA: usize = 4;
+
+// A transparent material a ray has entered, with the index it had at the time
+#[derive(Clone, Copy, Debug)]
+pub struct Medium {
+    pub material: MaterialId,
+    pub refractive_index: f32,
+}
+
+// Transparent materials a ray is inside, innermost last; empty means air. Each
+// refracted ray gets its own copy, so glass inside water knows it leaves into water
+// and not into air
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MediumStack {
+    media: [Option<Medium>; MAX_MEDIA],
+    len: usize,
+}
+
+impl MediumStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Medium the ray is traveling through, None in air
+    pub fn current(&self) -> Option<Medium> {
+        self.len.checked_sub(1).and_then(|top| self.media[top])
+    }
+
+    pub fn refractive_index(&self) -> f32 {
+        self.current().map_or(1.0, |medium| medium.refractive_index)
+    }
+
+    pub fn contains(&self, material: MaterialId) -> bool {
+        self.media[..self.len].iter().flatten().any(|medium| medium.material == material)
+    }
+
+    // Copy with `medium` as the innermost one. When the stack is full the ray stays
+    // where it was, which only matters past the bounce limit
+    pub fn entering(&self, medium: Medium) -> Self {
+        let mut stack = *self;
+        if stack.len < MAX_MEDIA {
+            stack.media[stack.len] = Some(medium);
+            stack.len += 1;
+        }
+        stack
+    }
+
+    // Copy without `material`, wherever it is: leaving an outer block that overlaps an
+    // inner one keeps the ray in the inner one
+    pub fn leaving(&self, material: MaterialId) -> Self {
+        let mut stack = MediumStack::new();
+        for medium in self.media[..self.len].iter().flatten() {
+            if medium.material != material {
+                stack = stack.entering(*medium);
+            }
+        }
+        stack
+    }
+}
@@ -0,0 +1,165 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::material::{MaterialId, MaterialRegistry};
+use crate::obj_loader::ObjModel;
+use crate::ray_intersect::{RayIntersect, Intersect, CubeFace};
+
+pub struct Triangle {
+    pub v0: Vec3,
+    pub v1: Vec3,
+    pub v2: Vec3,
+    pub normal: Vec3,
+    // Per-corner attributes from the OBJ, interpolated across the face when present
+    pub tex_coords: Option<[Vec2; 3]>,
+    pub normals: Option<[Vec3; 3]>,
+    pub material: Option<usize>, // Index into Mesh::materials; None uses Mesh::material
+}
+
+impl Triangle {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3) -> Self {
+        let normal = (v1 - v0).cross(&(v2 - v0)).normalize();
+        Triangle { v0, v1, v2, normal, tex_coords: None, normals: None, material: None }
+    }
+
+    // Möller–Trumbore: returns the distance along the ray and the barycentric
+    // weights (u, v) of v1 and v2 at the hit point, if any
+    pub fn intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<(f32, f32, f32)> {
+        let edge1 = self.v1 - self.v0;
+        let edge2 = self.v2 - self.v0;
+        let p = ray_direction.cross(&edge2);
+        let det = edge1.dot(&p);
+
+        // Ray parallel to the triangle
+        if det.abs() < 1e-8 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = ray_origin - self.v0;
+        let u = s.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&edge1);
+        let v = ray_direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&q) * inv_det;
+        if t > 1e-6 { Some((t, u, v)) } else { None }
+    }
+
+    // Smooth normal at the barycentric point, falling back to the face normal
+    pub fn normal_at(&self, u: f32, v: f32) -> Vec3 {
+        match self.normals {
+            Some([n0, n1, n2]) => (n0 * (1.0 - u - v) + n1 * u + n2 * v).normalize(),
+            None => self.normal,
+        }
+    }
+
+    // Interpolated texture coordinates, wrapped to [0, 1) and flipped vertically
+    // since OBJ puts v = 0 at the bottom of the image and Texture::sample at the top
+    pub fn uv_at(&self, u: f32, v: f32) -> Option<(f32, f32)> {
+        self.tex_coords.map(|[t0, t1, t2]| {
+            let uv = t0 * (1.0 - u - v) + t1 * u + t2 * v;
+            (uv.x.rem_euclid(1.0), (1.0 - uv.y).rem_euclid(1.0))
+        })
+    }
+}
+
+pub struct Mesh {
+    pub triangles: Vec<Triangle>,
+    pub material: MaterialId,        // Default for faces without a `usemtl`
+    pub materials: Vec<MaterialId>,  // Materials read from the model's .mtl files, in model order
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Mesh {
+    // Places the model in the world: every vertex is scaled and then moved by `position`.
+    // Faces keep the materials assigned in the .mtl, which are added to the
+    // registry; `material` covers the rest.
+    pub fn from_obj(model: &ObjModel, position: Vec3, scale: f32, material: MaterialId, registry: &mut MaterialRegistry) -> Self {
+        let transformed: Vec<Vec3> = model.vertices.iter().map(|v| v * scale + position).collect();
+
+        let triangles = model.faces
+            .iter()
+            .map(|face| {
+                let [a, b, c] = face.vertices;
+                let mut triangle = Triangle::new(transformed[a], transformed[b], transformed[c]);
+                triangle.tex_coords = face.tex_coords.map(|indices| indices.map(|i| model.tex_coords[i]));
+                triangle.normals = face.normals.map(|indices| indices.map(|i| model.normals[i]));
+                triangle.material = face.material;
+                triangle
+            })
+            .collect();
+
+        let mut min = Vec3::repeat(f32::INFINITY);
+        let mut max = Vec3::repeat(f32::NEG_INFINITY);
+        for vertex in &transformed {
+            min = min.inf(vertex);
+            max = max.sup(vertex);
+        }
+
+        Mesh {
+            triangles,
+            material,
+            materials: model.materials.iter().map(|(_, m)| registry.insert(m.clone())).collect(),
+            min,
+            max,
+        }
+    }
+
+    // Slab test against the bounding box so rays that miss the mesh skip every triangle
+    fn hits_bounds(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> bool {
+        let mut tmin = f32::NEG_INFINITY;
+        let mut tmax = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inv = 1.0 / ray_direction[axis];
+            let mut t0 = (self.min[axis] - ray_origin[axis]) * inv;
+            let mut t1 = (self.max[axis] - ray_origin[axis]) * inv;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            tmin = tmin.max(t0);
+            tmax = tmax.min(t1);
+        }
+
+        tmax >= tmin.max(0.0)
+    }
+}
+
+impl RayIntersect for Mesh {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        if !self.hits_bounds(ray_origin, ray_direction) {
+            return None;
+        }
+
+        let mut closest: Option<((f32, f32, f32), &Triangle)> = None;
+        for triangle in &self.triangles {
+            if let Some(hit) = triangle.intersect(ray_origin, ray_direction)
+                && closest.is_none_or(|((best, _, _), _)| hit.0 < best)
+            {
+                closest = Some((hit, triangle));
+            }
+        }
+
+        let ((distance, u, v), triangle) = closest?;
+        let normal = triangle.normal_at(u, v);
+        let material = match triangle.material {
+            Some(index) => self.materials[index],
+            None => self.material,
+        };
+        Some(Intersect {
+            point: ray_origin + ray_direction * distance,
+            normal,
+            distance,
+            material,
+            face: CubeFace::from_normal(&normal),
+            uv: triangle.uv_at(u, v),
+            inside: false,
+        })
+    }
+}
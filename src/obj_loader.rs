@@ -1,29 +1,234 @@
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{Vec2, Vec3};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use crate::color::Color;
+use crate::material::Material;
+use crate::texture::Texture;
+
+// One triangle of the model. Indices are 0-based; texture coordinates and
+// normals are only present when every corner of the face declared them.
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    pub vertices: [usize; 3],
+    pub tex_coords: Option<[usize; 3]>,
+    pub normals: Option<[usize; 3]>,
+    pub material: Option<usize>, // Index into ObjModel::materials set by the last `usemtl`
+}
 
 pub struct ObjModel {
     pub vertices: Vec<Vec3>,
+    pub tex_coords: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<(String, Material)>,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    // Line numbers are 1-based, as shown by text editors
+    Parse { line: usize, message: String },
+    IndexOutOfRange { line: usize, index: i64, count: usize },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(e) => write!(f, "error de lectura: {}", e),
+            ObjError::Parse { line, message } => write!(f, "línea {}: {}", line, message),
+            ObjError::IndexOutOfRange { line, index, count } => {
+                write!(f, "línea {}: índice {} fuera de rango (hay {} elementos)", line, index, count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(e: io::Error) -> Self {
+        ObjError::Io(e)
+    }
+}
+
+fn parse_values(parts: std::str::SplitWhitespace, line: usize, expected: usize) -> Result<Vec<f32>, ObjError> {
+    let values = parts
+        .map(|x| x.parse::<f32>().map_err(|_| ObjError::Parse { line, message: format!("número inválido '{}'", x) }))
+        .collect::<Result<Vec<f32>, ObjError>>()?;
+
+    if values.len() < expected {
+        return Err(ObjError::Parse { line, message: format!("se esperaban {} valores, hay {}", expected, values.len()) });
+    }
+    Ok(values)
+}
+
+// OBJ indices are 1-based, negative ones count back from the last element read
+fn resolve_index(token: &str, count: usize, line: usize) -> Result<usize, ObjError> {
+    let index = token.parse::<i64>()
+        .map_err(|_| ObjError::Parse { line, message: format!("índice inválido '{}'", token) })?;
+
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ObjError::IndexOutOfRange { line, index, count });
+    }
+    Ok(resolved as usize)
+}
+
+fn mtl_color(values: &[f32]) -> Color {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0) as u8;
+    Color::new(channel(values[0]), channel(values[1]), channel(values[2]))
+}
+
+// Reads a .mtl library. Texture paths are resolved next to the library file;
+// a texture that fails to load only loses the map, not the whole material.
+pub fn load_mtl(path: &Path) -> Result<Vec<(String, Material)>, ObjError> {
+    let file = File::open(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mut materials: Vec<(String, Material)> = Vec::new();
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line_number = number + 1;
+        let mut parts = line.split_whitespace();
+        let keyword = parts.next();
+
+        if keyword == Some("newmtl") {
+            let name = parts.collect::<Vec<_>>().join(" ");
+            materials.push((name, Material::builder(Color::new(255, 255, 255)).diffuse(0.9).specular(0.0).build()));
+            continue;
+        }
+
+        let Some((_, material)) = materials.last_mut() else {
+            continue;
+        };
+
+        match keyword {
+            Some("Kd") => material.color = mtl_color(&parse_values(parts, line_number, 3)?),
+            Some("Ks") => {
+                let ks = parse_values(parts, line_number, 3)?;
+                material.specular = (ks[0] + ks[1] + ks[2]) / 3.0;
+            }
+            Some("Ns") => material.shininess = parse_values(parts, line_number, 1)?[0],
+            Some("d") => material.transparency = 1.0 - parse_values(parts, line_number, 1)?[0].clamp(0.0, 1.0),
+            Some("Tr") => material.transparency = parse_values(parts, line_number, 1)?[0].clamp(0.0, 1.0),
+            Some("Ni") => material.refractive_index = parse_values(parts, line_number, 1)?[0],
+            Some("map_Kd") => {
+                // The file name is the last token; options like -s come before it
+                if let Some(file_name) = parts.last() {
+                    let texture_path = base_dir.join(file_name);
+                    match Texture::load(&texture_path.to_string_lossy()) {
+                        Ok(texture) => material.textures = vec![Arc::new(texture)],
+                        Err(e) => eprintln!("No se pudo cargar la textura {}: {}", texture_path.display(), e),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(materials)
 }
 
 impl ObjModel {
-    pub fn load(path: &str) -> Self {
-        let file = File::open(path).expect("No se pudo abrir el .obj");
-        let reader = BufReader::new(file);
+    pub fn load(path: &str) -> Result<Self, ObjError> {
+        let file = File::open(path)?;
+        let base_dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        Self::parse(BufReader::new(file), base_dir)
+    }
+
+    // `base_dir` is where `mtllib` references are looked up
+    pub fn parse<R: BufRead>(reader: R, base_dir: &Path) -> Result<Self, ObjError> {
         let mut vertices = Vec::new();
+        let mut tex_coords = Vec::new();
+        let mut normals = Vec::new();
+        let mut faces = Vec::new();
+        let mut materials: Vec<(String, Material)> = Vec::new();
+        let mut current_material = None;
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = number + 1;
+            let mut parts = line.split_whitespace();
+
+            match parts.next() {
+                Some("v") => {
+                    let v = parse_values(parts, line_number, 3)?;
+                    vertices.push(Vec3::new(v[0], v[1], v[2]));
+                }
+                Some("vt") => {
+                    let vt = parse_values(parts, line_number, 1)?;
+                    tex_coords.push(Vec2::new(vt[0], *vt.get(1).unwrap_or(&0.0)));
+                }
+                Some("vn") => {
+                    let vn = parse_values(parts, line_number, 3)?;
+                    normals.push(Vec3::new(vn[0], vn[1], vn[2]).normalize());
+                }
+                Some("f") => {
+                    // Each corner is "v", "v/vt", "v//vn" or "v/vt/vn"
+                    let mut corner_vertices = Vec::new();
+                    let mut corner_tex_coords = Vec::new();
+                    let mut corner_normals = Vec::new();
+                    for corner in parts {
+                        let mut fields = corner.split('/');
+                        let vertex = fields.next().unwrap_or("");
+                        corner_vertices.push(resolve_index(vertex, vertices.len(), line_number)?);
+                        match fields.next() {
+                            Some(vt) if !vt.is_empty() => corner_tex_coords.push(resolve_index(vt, tex_coords.len(), line_number)?),
+                            _ => {}
+                        }
+                        match fields.next() {
+                            Some(vn) if !vn.is_empty() => corner_normals.push(resolve_index(vn, normals.len(), line_number)?),
+                            _ => {}
+                        }
+                    }
+
+                    if corner_vertices.len() < 3 {
+                        return Err(ObjError::Parse { line: line_number, message: "una cara necesita al menos 3 vértices".to_string() });
+                    }
+
+                    let has_tex_coords = corner_tex_coords.len() == corner_vertices.len();
+                    let has_normals = corner_normals.len() == corner_vertices.len();
 
-        for line in reader.lines() {
-            let line = line.unwrap();
-            if line.starts_with("v ") {
-                let parts: Vec<f32> = line
-                    .split_whitespace()
-                    .skip(1)
-                    .map(|x| x.parse::<f32>().unwrap())
-                    .collect();
-                vertices.push(Vec3::new(parts[0], parts[1], parts[2]));
+                    // Polygons are split into a triangle fan around the first corner
+                    for i in 1..corner_vertices.len() - 1 {
+                        let corners = [0, i, i + 1];
+                        faces.push(Face {
+                            vertices: corners.map(|c| corner_vertices[c]),
+                            tex_coords: has_tex_coords.then(|| corners.map(|c| corner_tex_coords[c])),
+                            normals: has_normals.then(|| corners.map(|c| corner_normals[c])),
+                            material: current_material,
+                        });
+                    }
+                }
+                Some("mtllib") => {
+                    // Exporters often reference libraries that aren't shipped with the model;
+                    // that only costs the materials, so it's a warning rather than an error
+                    for library in parts {
+                        let library_path = base_dir.join(library);
+                        match load_mtl(&library_path) {
+                            Ok(library_materials) => materials.extend(library_materials),
+                            Err(ObjError::Io(e)) => eprintln!("No se pudo leer {}: {}", library_path.display(), e),
+                            Err(ObjError::Parse { line, message }) => {
+                                return Err(ObjError::Parse { line, message: format!("{}: {}", library_path.display(), message) });
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+                Some("usemtl") => {
+                    let name = parts.collect::<Vec<_>>().join(" ");
+                    current_material = materials.iter().position(|(n, _)| *n == name);
+                    if current_material.is_none() {
+                        eprintln!("Material '{}' no encontrado en las librerías .mtl", name);
+                    }
+                }
+                _ => {}
             }
         }
 
-        Self { vertices }
+        Ok(Self { vertices, tex_coords, normals, faces, materials })
     }
 }
@@ -0,0 +1,74 @@
+use nalgebra_glm::Vec3;
+use crate::cube::Cube;
+use crate::material::MaterialId;
+use crate::sphere::Sphere;
+use crate::plane::Plane;
+use crate::mesh::Mesh;
+use crate::ray_intersect::{RayIntersect, Intersect};
+
+// Every primitive the renderer knows about, so a scene can mix them in one list
+pub enum Object {
+    Cube(Cube),
+    Sphere(Sphere),
+    Plane(Plane),
+    Mesh(Mesh),
+}
+
+impl Object {
+    pub fn material(&self) -> MaterialId {
+        match self {
+            Object::Cube(cube) => cube.material,
+            Object::Sphere(sphere) => sphere.material,
+            Object::Plane(plane) => plane.material,
+            Object::Mesh(mesh) => mesh.material,
+        }
+    }
+
+    // Axis-aligned bounding box as (min, max); unbounded for infinite planes
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        match self {
+            Object::Cube(cube) => (cube.min, cube.max),
+            Object::Sphere(sphere) => {
+                let extent = Vec3::new(sphere.radius, sphere.radius, sphere.radius);
+                (sphere.center - extent, sphere.center + extent)
+            }
+            Object::Plane(_) => (Vec3::repeat(f32::NEG_INFINITY), Vec3::repeat(f32::INFINITY)),
+            Object::Mesh(mesh) => (mesh.min, mesh.max),
+        }
+    }
+}
+
+impl From<Cube> for Object {
+    fn from(cube: Cube) -> Self {
+        Object::Cube(cube)
+    }
+}
+
+impl From<Sphere> for Object {
+    fn from(sphere: Sphere) -> Self {
+        Object::Sphere(sphere)
+    }
+}
+
+impl From<Plane> for Object {
+    fn from(plane: Plane) -> Self {
+        Object::Plane(plane)
+    }
+}
+
+impl From<Mesh> for Object {
+    fn from(mesh: Mesh) -> Self {
+        Object::Mesh(mesh)
+    }
+}
+
+impl RayIntersect for Object {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        match self {
+            Object::Cube(cube) => cube.ray_intersect(ray_origin, ray_direction),
+            Object::Sphere(sphere) => sphere.ray_intersect(ray_origin, ray_direction),
+            Object::Plane(plane) => plane.ray_intersect(ray_origin, ray_direction),
+            Object::Mesh(mesh) => mesh.ray_intersect(ray_origin, ray_direction),
+        }
+    }
+}
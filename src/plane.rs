@@ -0,0 +1,74 @@
+use nalgebra_glm::Vec3;
+use crate::material::MaterialId;
+use crate::ray_intersect::{RayIntersect, Intersect, CubeFace};
+
+pub struct Plane {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub material: MaterialId,
+    pub tile_size: f32, // World units covered by one repetition of the texture
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material: MaterialId) -> Self {
+        Plane {
+            point,
+            normal: normal.normalize(),
+            material,
+            tile_size: 1.0,
+        }
+    }
+
+    pub fn with_tile_size(mut self, tile_size: f32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    // Two axes lying on the plane, used to derive planar UVs
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let helper = if self.normal.y.abs() < 0.999 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = helper.cross(&self.normal).normalize();
+        let bitangent = self.normal.cross(&tangent);
+        (tangent, bitangent)
+    }
+
+    fn uv(&self, point: &Vec3) -> (f32, f32) {
+        let (tangent, bitangent) = self.tangents();
+        let local = point - self.point;
+        let u = (local.dot(&tangent) / self.tile_size).rem_euclid(1.0);
+        let v = (local.dot(&bitangent) / self.tile_size).rem_euclid(1.0);
+        (u, v)
+    }
+}
+
+impl RayIntersect for Plane {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        let denom = self.normal.dot(ray_direction);
+
+        // Ray parallel to the plane
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+
+        let distance = (self.point - ray_origin).dot(&self.normal) / denom;
+        if distance <= 0.0 {
+            return None;
+        }
+
+        let point = ray_origin + ray_direction * distance;
+
+        Some(Intersect {
+            point,
+            normal: self.normal,
+            distance,
+            material: self.material,
+            face: CubeFace::from_normal(&self.normal),
+            uv: Some(self.uv(&point)),
+            inside: false,
+        })
+    }
+}
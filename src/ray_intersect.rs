@@ -1,14 +1,21 @@
 use nalgebra_glm::Vec3;
-use crate::material::Material;
+use crate::material::MaterialId;
 
+// A hit on some object; misses are `None`. The material is resolved through
+// the scene's MaterialRegistry when shading.
 #[derive(Debug, Clone)]
 pub struct Intersect {
-    pub is_intersecting: bool,
     pub point: Vec3,
     pub normal: Vec3,
     pub distance: f32,
-    pub material: Material,
+    pub material: MaterialId,
     pub face: CubeFace,
+    // Texture coordinates in [0, 1] with v = 0 at the top of the image;
+    // None when the primitive has no mapping (meshes without vt)
+    pub uv: Option<(f32, f32)>,
+    // The ray started inside the object and hit it on the way out; `normal`
+    // then points inward (toward the ray origin) and `face` is the exit face
+    pub inside: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -21,52 +28,84 @@ pub enum CubeFace {
     Back,
 }
 
-impl Intersect {
-    pub fn new() -> Self {
-        Self {
-            is_intersecting: false,
-            point: Vec3::new(0.0, 0.0, 0.0),
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            distance: f32::MAX,
-            material: Material::black(),
-            face: CubeFace::Top,  // Default to Top, or whichever face makes sense as a default
+impl CubeFace {
+    // Face on the min or max side of an axis (0 = x, 1 = y, 2 = z)
+    pub fn from_axis(axis: usize, max_side: bool) -> Self {
+        match (axis, max_side) {
+            (0, false) => CubeFace::Left,
+            (0, true) => CubeFace::Right,
+            (1, false) => CubeFace::Bottom,
+            (1, true) => CubeFace::Top,
+            (2, false) => CubeFace::Back,
+            _ => CubeFace::Front,
         }
     }
 
-    pub fn empty() -> Self {
-        Self {
-            is_intersecting: false,
-            point: Vec3::new(0.0, 0.0, 0.0),
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            distance: f32::MAX,
-            material: Material::black(),
-            face: CubeFace::Top,  // or any default face you prefer
+    // Position of the face in per-face tables (Material::face_textures)
+    pub fn index(&self) -> usize {
+        match self {
+            CubeFace::Top => 0,
+            CubeFace::Bottom => 1,
+            CubeFace::Left => 2,
+            CubeFace::Right => 3,
+            CubeFace::Front => 4,
+            CubeFace::Back => 5,
         }
     }
 
-    pub fn texture_coords(&self) -> (f32, f32) {
-        match self.face {
-            CubeFace::Top | CubeFace::Bottom => {
-                let u = self.point.x.fract().abs();
-                let v = self.point.z.fract().abs();
-                (u, v)
-            },
-            CubeFace::Left | CubeFace::Right => {
-                let u = self.point.z.fract().abs();
-                let v = self.point.y.fract().abs();
-                (u, v)
-            },
-            CubeFace::Front | CubeFace::Back => {
-                let u = self.point.x.fract().abs();
-                let v = self.point.y.fract().abs();
-                (u, v)
-            },
+    // Outward normal of the face on an axis-aligned box
+    pub fn normal(&self) -> Vec3 {
+        match self {
+            CubeFace::Left => Vec3::new(-1.0, 0.0, 0.0),
+            CubeFace::Right => Vec3::new(1.0, 0.0, 0.0),
+            CubeFace::Bottom => Vec3::new(0.0, -1.0, 0.0),
+            CubeFace::Top => Vec3::new(0.0, 1.0, 0.0),
+            CubeFace::Back => Vec3::new(0.0, 0.0, -1.0),
+            CubeFace::Front => Vec3::new(0.0, 0.0, 1.0),
         }
     }
+
+    // Directions in which u and v grow across the face, matching Cube's face uv
+    // (u to the right and v down, seen from outside). Together with the normal they
+    // form the tangent frame for normal maps.
+    pub fn tangents(&self) -> (Vec3, Vec3) {
+        match self {
+            CubeFace::Top => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+            CubeFace::Bottom => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+            CubeFace::Front => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            CubeFace::Back => (Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            CubeFace::Right => (Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, -1.0, 0.0)),
+            CubeFace::Left => (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0)),
+        }
+    }
+
+    // Face whose outward normal is closest to the given normal, so curved
+    // surfaces can still pick per-face textures (grass top, dirt sides...)
+    pub fn from_normal(normal: &Vec3) -> Self {
+        let (ax, ay, az) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+        if ay >= ax && ay >= az {
+            if normal.y > 0.0 { CubeFace::Top } else { CubeFace::Bottom }
+        } else if ax >= az {
+            if normal.x > 0.0 { CubeFace::Right } else { CubeFace::Left }
+        } else if normal.z > 0.0 {
+            CubeFace::Front
+        } else {
+            CubeFace::Back
+        }
+    }
+}
+
+// The nearer of two optional hits
+pub fn nearest(a: Option<Intersect>, b: Option<Intersect>) -> Option<Intersect> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.distance < a.distance { b } else { a }),
+        (a, b) => a.or(b),
+    }
 }
 
-pub trait RayIntersect {
-  fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect;
+// Send + Sync so every primitive can be shared by the rayon threads in render
+pub trait RayIntersect: Send + Sync {
+  fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect>;
 }
 
 
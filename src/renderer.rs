@@ -0,0 +1,1767 @@
+use nalgebra_glm::{Vec3, normalize};
+use std::time::{Duration, Instant};
+use std::io::{self, Write};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::f32::consts::PI;
+use rayon::prelude::*;
+use crate::framebuffer::Framebuffer;
+use crate::color::{Color, ColorF, ToneMapping};
+use crate::ray_intersect::{CubeFace, Intersect};
+use crate::camera::Camera;
+use crate::light::{Light, LightKind};
+use crate::material::{Material, MaterialId, Sidedness};
+use crate::sampling::{self, Sampler};
+use crate::environment::Environment;
+use crate::medium::{Medium, MediumStack};
+use crate::scene::Scene;
+use crate::texture::Texture;
+use crate::voxel_grid::BLOCK_SIZE;
+
+// Medio ángulo del cono de reflejo con rugosidad 1 (45°)
+const GLOSSY_MAX_ANGLE: f32 = PI / 4.0;
+
+// Cuadros que se promedian con la cámara quieta en el modo RayTrace; más allá el promedio es
+// móvil para que el sol, el portal y la lava no dejen estela
+const RAY_TRACE_ACCUMULATION_LIMIT: u32 = 16;
+
+// Cuadros tras los que el modo PathTrace deja de sumar con la cámara quieta: el ruido que
+// queda ya no se nota
+const PATH_TRACE_MAX_FRAMES: u32 = 1024;
+
+// Reflectividad desde la que un material cuenta como espejo perfecto y no se sombrea
+const MIRROR_REFLECTIVITY: f32 = 0.999;
+
+// Niebla dentro de un fluido: la visibilidad cae a 1/e cada 1 / UNDERWATER_FOG_DENSITY unidades
+const UNDERWATER_FOG_DENSITY: f32 = 0.6;
+
+// Con la cámara sumergida, toda la vista se tiñe con lo que absorbe esta distancia del fluido
+const UNDERWATER_TINT_DEPTH: f32 = 0.3;
+
+
+// Dirección (en el mundo) del rayo de cámara que pasa por el punto (x, y) de una imagen de
+// width x height con el campo de visión `fov`; (0, 0) es la esquina de arriba a la izquierda
+// y el píxel (x, y) se traza por su esquina. Es la cuenta de los modos RayTrace y PathTrace,
+// así el mouse elige exactamente lo que se ve en el píxel
+pub fn primary_direction(camera: &Camera, fov: f32, width: f32, height: f32, x: f32, y: f32) -> Vec3 {
+    let aspect_ratio = width / height;
+    let perspective_scale = (fov * 0.5).tan();
+    let screen_x = (2.0 * x / width - 1.0) * aspect_ratio * perspective_scale;
+    let screen_y = (1.0 - 2.0 * y / height) * perspective_scale;
+    camera.basis_change(&normalize(&Vec3::new(screen_x, screen_y, -1.0)))
+}
+
+// Dirección del rayo de cámara por el punto (x, y) de una panorámica equirectangular de
+// width x height: x recorre la longitud (-180° a 180°, con el centro de la imagen hacia donde
+// mira la cámara) e y la latitud (90° arriba, -90° abajo). El horizonte es el del mundo, no
+// se inclina con la cámara. En los polos sale el eje vertical, sin dividir por nada
+pub fn equirect_direction(camera: &Camera, width: f32, height: f32, x: f32, y: f32) -> Vec3 {
+    let longitude = (x / width - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - y / height) * PI;
+    // Rumbo de la cámara en el plano horizontal; mirando justo arriba o abajo queda -Z
+    let forward = camera.forward();
+    let level = Vec3::new(forward.x, 0.0, forward.z);
+    let heading = if level.norm() > 1e-6 { level.normalize() } else { Vec3::new(0.0, 0.0, -1.0) };
+    let right = Vec3::new(-heading.z, 0.0, heading.x);
+    heading * (latitude.cos() * longitude.cos())
+        + right * (latitude.cos() * longitude.sin())
+        + Vec3::new(0.0, latitude.sin(), 0.0)
+}
+
+// Rayo de cámara de un píxel según settings.projection. La apertura (profundidad de campo)
+// solo se usa en perspectiva
+fn camera_ray(camera: &Camera, settings: &RenderSettings, width: f32, height: f32, x: f32, y: f32, sampler: &mut Sampler) -> (Vec3, Vec3) {
+    match settings.projection {
+        Projection::Perspective => {
+            let direction = primary_direction(camera, settings.fov, width, height, x, y);
+            if camera.aperture > 0.0 {
+                camera.thin_lens(&direction, sampler.in_disk())
+            } else {
+                (camera.eye, direction)
+            }
+        }
+        Projection::Equirectangular => (camera.eye, equirect_direction(camera, width, height, x, y)),
+    }
+}
+
+// Lo contrario de primary_direction: dónde cae en la imagen un punto del mundo. None si
+// está detrás de la cámara (o casi en el plano del ojo)
+pub fn project(camera: &Camera, fov: f32, width: f32, height: f32, point: &Vec3) -> Option<(f32, f32)> {
+    let forward = camera.forward();
+    let right = camera.right();
+    let up = right.cross(&forward).normalize();
+    let relative = point - camera.eye;
+    let depth = relative.dot(&forward);
+    if depth < 1e-4 {
+        return None;
+    }
+    let perspective_scale = (fov * 0.5).tan();
+    let screen_x = relative.dot(&right) / depth / (perspective_scale * width / height);
+    let screen_y = relative.dot(&up) / depth / perspective_scale;
+    Some(((screen_x + 1.0) * 0.5 * width, (1.0 - screen_y) * 0.5 * height))
+}
+
+// Mueve el origen de un rayo secundario fuera de la superficie, hacia el lado de la
+// normal por el que sale el rayo, para que no vuelva a chocar con ella (acné)
+fn offset_origin(intersect: &Intersect, direction: &Vec3, bias: f32) -> Vec3 {
+    let offset = intersect.normal * bias;
+    if direction.dot(&intersect.normal) < 0.0 {
+        intersect.point - offset
+    } else {
+        intersect.point + offset
+    }
+}
+
+
+fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
+    incident - 2.0 * incident.dot(normal) * normal
+}
+
+
+// `normal` apunta hacia el lado del que llega el rayo y `eta` es n1 / n2: el índice del
+// medio que deja entre el del medio en el que entra
+fn refract(incident: &Vec3, normal: &Vec3, eta: f32) -> Vec3 {
+    let cosi = (-incident.dot(normal)).clamp(0.0, 1.0);
+    let k = 1.0 - eta * eta * (1.0 - cosi * cosi);
+
+    if k < 0.0 {
+        // Total internal reflection -> devolver el vector reflejado
+        reflect(incident, normal)
+    } else {
+        // Fórmula de refracción (Snell)
+        eta * *incident + (eta * cosi - k.sqrt()) * normal
+    }
+}
+
+
+// Fracción de la luz que se refleja al pasar de un medio de índice n1 a uno de índice n2
+// (aproximación de Schlick). `normal` mira hacia el rayo que llega; 1.0 es reflexión
+// interna total
+fn fresnel(incident: &Vec3, normal: &Vec3, n1: f32, n2: f32) -> f32 {
+    let mut cos = (-incident.dot(normal)).clamp(0.0, 1.0);
+
+    // Dos bloques del mismo material pegados: no hay superficie en la que reflejarse
+    if n1 == n2 {
+        return 0.0;
+    }
+
+    if n1 > n2 {
+        // Al salir del medio más denso Schlick tiene que usar el ángulo transmitido
+        let sin_t2 = (n1 / n2) * (n1 / n2) * (1.0 - cos * cos);
+        if sin_t2 > 1.0 {
+            return 1.0;
+        }
+        cos = (1.0 - sin_t2).sqrt();
+    }
+
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+
+// Coordenadas de textura del punto. Cada primitiva entrega u y v en [0, 1]; uv_scale
+// repite la textura dentro de la cara (el WrapMode de la textura decide cómo)
+fn texture_uv(material: &Material, intersect: &Intersect) -> (f32, f32) {
+    let (u, v) = intersect.uv.unwrap_or((0.0, 0.0));
+    (u * material.uv_scale, v * material.uv_scale)
+}
+
+
+// Nivel de mipmap para una textura vista a la distancia del golpe: log2 de cuántos texels
+// caben en un píxel. Como primera aproximación supone que la textura cubre un bloque (con
+// uv_scale copias) e ignora la inclinación de la superficie
+fn texture_lod(material: &Material, texture: &Texture, intersect: &Intersect, settings: &RenderSettings) -> f32 {
+    let texels_per_unit = texture.width.max(texture.height) as f32 * material.uv_scale / BLOCK_SIZE;
+    let footprint = intersect.distance * settings.pixel_spread;
+    (footprint * texels_per_unit).max(1.0).log2()
+}
+
+
+// Color con el que el modo de depuración pinta cada nivel de mipmap
+fn mip_level_tint(lod: f32) -> ColorF {
+    const TINTS: [ColorF; 6] = [
+        ColorF::new(1.0, 1.0, 1.0),
+        ColorF::new(1.0, 0.2, 0.2),
+        ColorF::new(1.0, 1.0, 0.2),
+        ColorF::new(0.2, 1.0, 0.2),
+        ColorF::new(0.2, 1.0, 1.0),
+        ColorF::new(0.2, 0.2, 1.0),
+    ];
+    TINTS[(lod.round() as usize).min(TINTS.len() - 1)]
+}
+
+
+// Color de la superficie en un punto: la textura de la cara si el material tiene una, si no
+// su color plano. En lineal si la corrección gamma está activa
+fn surface_color(material: &Material, intersect: &Intersect, settings: &RenderSettings) -> ColorF {
+    // El material decide qué textura va en cada cara (césped arriba, tierra a los lados...)
+    if let Some(texture) = material.texture_for(&intersect.face, settings.time) {
+        let (u, v) = texture_uv(material, intersect);
+        // De lejos se lee un nivel reducido de la textura en vez de un texel suelto que parpadea
+        let lod = if settings.mipmaps { texture_lod(material, texture, intersect, settings) } else { 0.0 };
+        let color = if settings.gamma_correction {
+            texture.sample_linear_lod(u, v, lod)
+        } else {
+            ColorF::from(texture.sample_lod(u, v, lod))
+        };
+        if settings.show_mip_levels {
+            let level = lod.min((texture.mip_levels() - 1) as f32);
+            color * mip_level_tint(level)
+        } else {
+            color
+        }
+    } else {
+        settings.decode(material.color)
+    }
+}
+
+
+// Luz que echa la superficie por sí misma: el color de emisión por su fuerza. En los materiales
+// con textura la emisión sale teñida por ella (el dibujo del glowstone brilla, sus grietas no);
+// `surface` es el color de la superficie en ese punto, el de surface_color
+fn emitted_color(material: &Material, surface: &ColorF, settings: &RenderSettings) -> ColorF {
+    if !material.is_emissive() {
+        return ColorF::black();
+    }
+    // Con emisión de la textura cada texel brilla con su propio color
+    if material.emission_from_texture {
+        return *surface * material.emission_strength;
+    }
+    let emission = settings.decode(material.emission) * material.emission_strength;
+    if material.textures.is_empty() && material.animation.is_none() {
+        emission
+    } else {
+        emission * *surface
+    }
+}
+
+// Los texels con menos alfa que el alpha_cutoff del material son huecos: los rayos los
+// atraviesan como si el objeto no estuviera ahí (hojas, flores)
+fn is_alpha_hole(material: &Material, intersect: &Intersect, settings: &RenderSettings) -> bool {
+    if material.alpha_cutoff <= 0.0 {
+        return false;
+    }
+    match material.texture_for(&intersect.face, settings.time) {
+        Some(texture) if texture.has_alpha() => {
+            let (u, v) = texture_uv(material, intersect);
+            texture.sample_alpha(u, v) < material.alpha_cutoff
+        }
+        _ => false,
+    }
+}
+
+// Si el rayo da en la cara de atrás: contra la normal hacia afuera del objeto (la de un golpe
+// desde dentro de un cubo ya viene dada vuelta hacia el rayo)
+fn is_back_face(intersect: &Intersect, direction: &Vec3) -> bool {
+    let outward = if intersect.inside { -intersect.normal } else { intersect.normal };
+    direction.dot(&outward) > 0.0
+}
+
+// Golpes que un rayo que sale de la cámara (o rebota) atraviesa como si no hubiera nada: los
+// huecos de las texturas recortadas, las caras de atrás de los materiales FrontOnly y, salvo en
+// el primer tramo desde la cámara, los atrapasombras
+fn passes_through(material: &Material, intersect: &Intersect, direction: &Vec3, settings: &RenderSettings, camera_ray: bool) -> bool {
+    is_alpha_hole(material, intersect, settings)
+        || (material.sidedness == Sidedness::FrontOnly && is_back_face(intersect, direction))
+        || (!camera_ray && material.shadow_catcher)
+}
+
+// Con DoubleSided la cara de atrás se sombrea como la de adelante: la normal se da vuelta hacia
+// el rayo antes de usarla
+fn apply_sidedness(material: &Material, intersect: &mut Intersect, direction: &Vec3) {
+    if material.sidedness == Sidedness::DoubleSided && direction.dot(&intersect.normal) > 0.0 {
+        intersect.normal = -intersect.normal;
+    }
+}
+
+
+// Normal con la que se ilumina: la geométrica, inclinada por el normal map del material si
+// tiene uno. El marco tangente sale de la cara (exacto en cubos, aproximado en esferas y planos).
+// En el agua, la cara de arriba sigue las olas en el segundo `time`; los costados quedan lisos
+fn shading_normal(material: &Material, intersect: &Intersect, time: f32) -> Vec3 {
+    if let (Some(waves), CubeFace::Top) = (&material.waves, &intersect.face) {
+        let up = waves.normal(intersect.point.x, intersect.point.z, time);
+        // Desde abajo del agua la normal apunta hacia abajo
+        return if intersect.inside { -up } else { up };
+    }
+    let (Some(normal_map), Some(_)) = (&material.normal_map, intersect.uv) else {
+        return intersect.normal;
+    };
+
+    // El mapa guarda cada componente de -1..1 en 0..255 (sin corrección gamma)
+    let (u, v) = texture_uv(material, intersect);
+    let texel = normal_map.sample_f(u, v);
+    let local = Vec3::new(texel.r * 2.0 - 1.0, texel.g * 2.0 - 1.0, texel.b * 2.0 - 1.0);
+
+    // En los golpes desde dentro la normal apunta hacia adentro; el mapa va sobre la exterior
+    let outward = if intersect.inside { -intersect.normal } else { intersect.normal };
+    let (u_dir, _) = intersect.face.tangents();
+    let tangent = (u_dir - outward * outward.dot(&u_dir)).normalize();
+    // El verde apunta hacia arriba en la imagen, al revés de v
+    let bitangent = outward.cross(&tangent);
+
+    let perturbed = (tangent * local.x + bitangent * local.y + outward * local.z).normalize();
+    if intersect.inside { -perturbed } else { perturbed }
+}
+
+
+// Rayos trazados desde la última llamada a take_ray_counts, con settings.count_rays encendido
+static PRIMARY_RAYS: AtomicU64 = AtomicU64::new(0);
+static SHADOW_RAYS: AtomicU64 = AtomicU64::new(0);
+static SECONDARY_RAYS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RayCounts {
+    pub primary: u64,   // Desde la cámara (o el primer tramo de cada camino)
+    pub shadow: u64,    // Hacia las luces, también los de los rayos crepusculares
+    pub secondary: u64, // Reflejos, refracciones y rebotes
+}
+
+impl RayCounts {
+    pub fn total(&self) -> u64 {
+        self.primary + self.shadow + self.secondary
+    }
+}
+
+// Devuelve los rayos contados hasta ahora y vuelve las cuentas a cero
+pub fn take_ray_counts() -> RayCounts {
+    RayCounts {
+        primary: PRIMARY_RAYS.swap(0, Ordering::Relaxed),
+        shadow: SHADOW_RAYS.swap(0, Ordering::Relaxed),
+        secondary: SECONDARY_RAYS.swap(0, Ordering::Relaxed),
+    }
+}
+
+#[inline]
+fn count_ray(counter: &AtomicU64, settings: &RenderSettings) {
+    if settings.count_rays {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Luz que sobrevive a lo largo de un rayo de sombra, por canal en [0, 1]: cada objeto que
+// cruza la filtra con su color y su transparencia, así los opacos la cortan, un cristal
+// de color la tiñe y varios cristales seguidos se suman
+fn shadow_transmission(origin: &Vec3, direction: &Vec3, max_distance: f32, scene: &Scene, settings: &RenderSettings) -> ColorF {
+    let white = ColorF::new(1.0, 1.0, 1.0);
+    count_ray(&SHADOW_RAYS, settings);
+
+    // Lo normal es que no haya nada en medio, y esa consulta es la más barata
+    if !scene.world.occluded(origin, direction, max_distance) {
+        return white;
+    }
+
+    let mut transmission = white;
+    let mut origin = *origin;
+    let mut remaining = max_distance;
+
+    while let Some(hit) = scene.world.traverse(&origin, direction) {
+        if hit.distance >= remaining {
+            break;
+        }
+        // Cada objeto cuenta una vez: al entrar, no al salir por la otra cara. Los huecos
+        // de una textura recortada y los atrapasombras dejan pasar la luz entera. La luz viaja
+        // al revés que el rayo de sombra: un material FrontOnly la detiene solo si le llega
+        // por delante, que para el rayo es su cara de atrás (al salir, en un cubo)
+        let material = scene.materials.get(hit.material);
+        let counted = match material.sidedness {
+            Sidedness::FrontOnly => is_back_face(&hit, direction),
+            _ => !hit.inside,
+        };
+        if counted && !is_alpha_hole(material, &hit, settings) && !material.shadow_catcher {
+            let (_, transparency) = material.surface_weights();
+            if transparency <= 0.0 {
+                return ColorF::black();
+            }
+            transmission = transmission * surface_color(material, &hit, settings) * transparency;
+        }
+        origin = hit.point + direction * settings.shadow_bias;
+        remaining -= hit.distance + settings.shadow_bias;
+    }
+
+    transmission
+}
+
+
+// Luz que llega desde `light` sin que la tapen otros objetos, por canal en [0, 1]. Con varias
+// muestras la luz es una esfera de radio light.size: cada rayo va a un punto al azar del
+// disco que la esfera presenta al punto sombreado y se promedia (penumbra en los bordes)
+fn cast_shadow(intersect: &Intersect, light: &Light, scene: &Scene, settings: &RenderSettings, sampler: &mut Sampler) -> ColorF {
+    let (to_light, distance) = light.direction_from(&intersect.point);
+    if !light.reaches(distance) || light.cone(&to_light) <= 0.0 {
+        return ColorF::black();
+    }
+
+    let samples = if light.size > 0.0 { settings.shadow_samples.max(1) } else { 1 };
+    let (tangent, bitangent) = sampling::basis(&to_light);
+    let mut transmission = ColorF::black();
+
+    for _ in 0..samples {
+        let (light_dir, distance_to_light) = if samples == 1 {
+            (to_light, distance)
+        } else {
+            let (dx, dy) = sampler.in_disk();
+            let offset = (tangent * dx + bitangent * dy) * light.size;
+            match light.kind {
+                // El disco del sol está infinitamente lejos: solo se inclina la dirección
+                LightKind::Directional { .. } => ((to_light + offset).normalize(), f32::INFINITY),
+                _ => {
+                    let to_target = light.position + offset - intersect.point;
+                    (to_target.normalize(), to_target.magnitude())
+                }
+            }
+        };
+
+        // Separado de la superficie por la normal y hacia la luz
+        let shadow_ray_origin = offset_origin(intersect, &light_dir, settings.shadow_bias) + light_dir * settings.shadow_bias;
+        // Hacia un bloque emisivo el rayo termina en su cara: el bloque es opaco y si no
+        // taparía su propia luz
+        let distance_to_light = match light.entry_distance(&shadow_ray_origin, &light_dir) {
+            Some(entry) => entry - settings.shadow_bias,
+            None => distance_to_light,
+        };
+
+        // Hacia una luz direccional el rayo revisa toda la geometría, sin límite de distancia
+        transmission = transmission + shadow_transmission(&shadow_ray_origin, &light_dir, distance_to_light.min(f32::MAX), scene, settings);
+    }
+
+    transmission * (1.0 / samples as f32)
+}
+
+// Bloque emisivo como luz de área: muestras repartidas por las caras que ven el punto, cada
+// una con su rayo de sombra. `shade` da lo que deja en la superficie la luz que llega desde una
+// dirección, con su color y su intensidad
+fn sample_area_light(
+    intersect: &Intersect,
+    light: &Light,
+    light_color: ColorF,
+    scene: &Scene,
+    settings: &RenderSettings,
+    sampler: &mut Sampler,
+    shade: &impl Fn(&Vec3, ColorF, f32) -> ColorF,
+) -> ColorF {
+    let samples = settings.area_light_samples.max(1);
+    let mut lit = ColorF::black();
+
+    for face in light.faces_toward(&intersect.point).into_iter().flatten() {
+        // Cada muestra representa una parte igual de la cara
+        let share = light.intensity * face.area() / samples as f32;
+        for _ in 0..samples {
+            let sample = face.point(sampler.next_f32(), sampler.next_f32());
+            let to_sample = sample - intersect.point;
+            let distance = to_sample.magnitude();
+            let sample_dir = to_sample / distance;
+
+            // El rayo de sombra se detiene justo antes de la cara, así el propio
+            // bloque emisor no se tapa a sí mismo
+            let bias = settings.shadow_bias;
+            let shadow_ray_origin = offset_origin(intersect, &sample_dir, bias) + sample_dir * bias;
+            let transmission = shadow_transmission(&shadow_ray_origin, &sample_dir, distance - 3.0 * bias, scene, settings);
+
+            // Atenuación geométrica: coseno en la cara emisora entre la distancia al cuadrado
+            // (con un mínimo para evitar picos pegados al bloque)
+            let cos_emitter = (-sample_dir).dot(&face.normal);
+            let light_intensity = share * cos_emitter / (distance * distance).max(0.05);
+
+            lit = lit + shade(&sample_dir, light_color * transmission, light_intensity);
+        }
+    }
+    lit
+}
+
+
+// Lo que ve un rayo de cámara que da en un atrapasombras: el fondo en su dirección, oscurecido
+// por canal en la proporción de luz que le quitan las sombras. Esa proporción compara la luz
+// directa que llega al punto con la que llegaría sin nada en medio, con el ambiente (que no
+// tiene sombra) en las dos; sin luces que lleguen, el fondo queda tal cual
+fn shadow_catcher_color(intersect: &Intersect, ray_direction: &Vec3, scene: &Scene, ambient_color: &ColorF, settings: &RenderSettings, sampler: &mut Sampler) -> ColorF {
+    let normal = if ray_direction.dot(&intersect.normal) < 0.0 { intersect.normal } else { -intersect.normal };
+    let ambient = *ambient_color * 0.1;
+    let (mut lit, mut unshadowed) = (ambient, ambient);
+    for light in scene.lights_at(&intersect.point) {
+        let (light_dir, distance_to_light) = light.direction_from(&intersect.point);
+        let cone = light.cone(&light_dir);
+        let cosine = normal.dot(&light_dir);
+        if !light.enabled || light.intensity <= 0.0 || !light.reaches(distance_to_light) || cone <= 0.0 || cosine <= 0.0 {
+            continue;
+        }
+        let incoming = settings.decode(light.color) * (light.intensity * light.attenuation(distance_to_light) * cone * cosine);
+        lit = lit + incoming * cast_shadow(intersect, light, scene, settings, sampler);
+        unshadowed = unshadowed + incoming;
+    }
+
+    let share = |lit: f32, unshadowed: f32| if unshadowed > 0.0 { lit / unshadowed } else { 1.0 };
+    let shadow = ColorF::new(share(lit.r, unshadowed.r), share(lit.g, unshadowed.g), share(lit.b, unshadowed.b));
+    environment_color(scene, ray_direction, settings) * shadow
+}
+
+
+// Luz del sol (y de la luna) dispersada por el aire entre la cámara y el primer objeto del
+// rayo: rayos crepusculares. Se avanza por el rayo en pasos y desde cada uno se lanza un rayo
+// de sombra hacia cada luz direccional, así las ventanas y sus marcos recortan los haces
+fn light_shafts(origin: &Vec3, direction: &Vec3, scene: &Scene, settings: &RenderSettings, sampler: &mut Sampler) -> ColorF {
+    let hit_distance = scene.world.traverse(origin, direction).map_or(f32::INFINITY, |hit| hit.distance);
+    let march_distance = hit_distance.min(settings.shaft_distance);
+    let steps = settings.shaft_steps.max(1);
+    let step = march_distance / steps as f32;
+    // Un desfase al azar por píxel cambia las bandas de los pasos por un ruido fino
+    let jitter = sampler.next_f32();
+
+    let mut scattered = ColorF::black();
+    for light in &scene.lights {
+        let LightKind::Directional { direction: light_direction } = light.kind else {
+            continue;
+        };
+        if !light.enabled || light.intensity <= 0.0 {
+            continue;
+        }
+        let to_light = -light_direction;
+
+        // Henyey-Greenstein: el aire dispersa sobre todo hacia adelante, así los haces se
+        // ven más al mirar hacia la luz
+        let g = 0.6;
+        let cos_theta = direction.dot(&to_light);
+        let phase = (1.0 - g * g) / (4.0 * PI * (1.0 + g * g - 2.0 * g * cos_theta).powf(1.5));
+
+        let mut in_scattered = ColorF::black();
+        for i in 0..steps {
+            let t = (i as f32 + jitter) * step;
+            let point = origin + direction * t;
+            let transmission = shadow_transmission(&point, &to_light, f32::MAX, scene, settings);
+            // La luz dispersada en t también se apaga en el camino de vuelta a la cámara
+            in_scattered = in_scattered + transmission * (-settings.shaft_scattering * t).exp();
+        }
+
+        let strength = settings.shaft_scattering * step * phase * light.intensity;
+        scattered = scattered + in_scattered * settings.decode(light.color) * strength;
+    }
+    scattered
+}
+
+
+// Cómo se calcula la imagen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    RayTrace,  // cast_ray: sombras, reflejos y refracciones, con ambiente plano
+    PathTrace, // trace_path: luz rebotada entre superficies, sumada cuadro a cuadro
+}
+
+impl RenderMode {
+    pub fn next(&self) -> RenderMode {
+        match self {
+            RenderMode::RayTrace => RenderMode::PathTrace,
+            RenderMode::PathTrace => RenderMode::RayTrace,
+        }
+    }
+}
+
+// Cómo salen de la cámara los rayos de los píxeles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,     // La imagen de siempre, con el campo de visión de la cámara
+    Equirectangular, // Panorámica de 360° x 180° (imagen 2:1) alrededor del ojo
+}
+
+impl Projection {
+    // Ancho de un píxel a una unidad de distancia de la cámara, para elegir el mipmap
+    fn pixel_spread(&self, fov: f32, width: f32, height: f32) -> f32 {
+        match self {
+            Projection::Perspective => 2.0 * (fov * 0.5).tan() / height,
+            Projection::Equirectangular => 2.0 * PI / width,
+        }
+    }
+}
+
+// Cómo se agranda la imagen trazada a menor resolución
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpscaleFilter {
+    Nearest,  // Cada píxel trazado se vuelve un bloque: nítido pero pixelado
+    Bilinear, // Mezcla los cuatro píxeles trazados más cercanos: más suave
+}
+
+// Opciones de render que se pueden cambiar mientras corre la ventana
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    // Trazado de rayos clásico o de caminos
+    pub mode: RenderMode,
+    // Sombrea en luz lineal y codifica a sRGB al final; apagado usa los colores tal cual
+    pub gamma_correction: bool,
+    // Curva para llevar los valores mayores a 1.0 (sol, glowstone) a la pantalla
+    pub tone_mapping: ToneMapping,
+    // Multiplica la luz antes de la curva
+    pub exposure: f32,
+    // Antialiasing: rayos por lado de cada píxel (1, 2 o 4 -> 1, 4 o 16 rayos)
+    pub samples: u32,
+    // Antialiasing adaptativo: 1 rayo por píxel y solo se repiten los bordes
+    pub adaptive: bool,
+    // Diferencia de luminancia (ya con tone mapping, 0..1) con un vecino para contar como borde
+    pub edge_threshold: f32,
+    // Rayos por lado al repetir un píxel de borde
+    pub edge_samples: u32,
+    // Rayos de sombra hacia cada luz con tamaño; 1 da sombras duras como antes
+    pub shadow_samples: u32,
+    // Separación de los rayos de sombra, reflejo y refracción respecto a la superficie de la
+    // que salen; muy poca da acné entre bloques que se tocan, mucha despega las sombras
+    pub shadow_bias: f32,
+    // Los bloques emisivos iluminan desde sus caras; apagado son luces puntuales en su centro
+    pub area_lights: bool,
+    // Muestras por cara de cada bloque emisivo
+    pub area_light_samples: u32,
+    // Pinta la máscara de bordes en rojo en vez de repetirlos, para ajustar el umbral
+    pub show_edges: bool,
+    // Texturas lejanas desde niveles reducidos (mipmaps) en vez del tamaño completo
+    pub mipmaps: bool,
+    // Tiñe cada nivel de mipmap de un color (blanco, rojo, amarillo, verde, cian, azul)
+    pub show_mip_levels: bool,
+    pub projection: Projection,
+    // Anaglifo rojo/cian: una imagen por ojo, el rojo del izquierdo y el verde y el azul del
+    // derecho. Cuesta el doble de un cuadro normal
+    pub stereo: bool,
+    // Distancia entre los ojos en modo estéreo, en unidades de la escena
+    pub eye_separation: f32,
+    // Campo de visión vertical, en radianes; el horizontal sale de él y de la proporción de
+    // la ventana
+    pub fov: f32,
+    // Ancho de un píxel a una unidad de distancia de la cámara; render lo calcula en cada
+    // cuadro a partir del campo de visión y el alto de la ventana
+    pub pixel_spread: f32,
+    // Rayos de reflejo en materiales rugosos, y de refracción en los esmerilados (solo desde
+    // los rayos de cámara); 1 es rápido pero con grano, para imágenes finales conviene más
+    pub glossy_samples: u32,
+    // Rayos por muestra de píxel cuando la cámara tiene apertura (profundidad de campo)
+    pub lens_samples: u32,
+    // Rayos crepusculares: marcha por el rayo de cámara con rayos de sombra hacia el sol. Caro,
+    // así que empieza apagado
+    pub light_shafts: bool,
+    // Pasos de la marcha por cada rayo de cámara
+    pub shaft_steps: u32,
+    // Cuánta luz dispersa el aire por unidad de distancia
+    pub shaft_scattering: f32,
+    // Hasta dónde se marcha cuando el rayo no choca antes con algo
+    pub shaft_distance: f32,
+    // Mientras se mueve la cámara se traza una imagen tantas veces más chica (1, 2 o 4) y se
+    // agranda con upscale_filter; al soltar las teclas vuelve el tamaño completo
+    pub motion_scale: u32,
+    pub upscale_filter: UpscaleFilter,
+    // Lado en píxeles de los bloques en que render reparte la imagen entre los hilos
+    pub tile_size: u32,
+    // Escribe en la consola cuánto tardan los bloques en cada cuadro, para ver si algunos
+    // dejan a los demás hilos esperando
+    pub tile_stats: bool,
+    // Escribe en la consola cuántos bloques del cuadro van trazados, para renders largos sin
+    // ventana
+    pub progress: bool,
+    // Cuenta los rayos de cámara, de sombra y de rebote para take_ray_counts. Apagado solo
+    // cuesta comparar esta opción en cada rayo
+    pub count_rays: bool,
+    // Rebotes de reflejo y refracción antes de quedarse con el fondo en esa dirección; en el
+    // modo PathTrace, rebotes de cada camino
+    pub max_depth: u32,
+    // Niebla por distancia hacia el color del horizonte
+    pub fog: bool,
+    // Cuánto espesa la niebla por unidad de distancia: a 1 / fog_density queda un 37% del color
+    pub fog_density: f32,
+    // Segundos desde que arrancó el programa, para elegir el cuadro de las texturas animadas;
+    // render lo toma de la escena en cada cuadro
+    pub time: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            mode: RenderMode::RayTrace,
+            gamma_correction: true,
+            tone_mapping: ToneMapping::Aces,
+            exposure: 1.0,
+            samples: 1,
+            adaptive: false,
+            edge_threshold: 0.1,
+            edge_samples: 4,
+            shadow_samples: 1,
+            shadow_bias: 1e-3,
+            area_lights: true,
+            area_light_samples: 2,
+            show_edges: false,
+            mipmaps: true,
+            show_mip_levels: false,
+            projection: Projection::Perspective,
+            stereo: false,
+            eye_separation: 0.15,
+            fov: PI / 3.0,
+            pixel_spread: 0.0,
+            glossy_samples: 1,
+            lens_samples: 8,
+            light_shafts: false,
+            shaft_steps: 24,
+            shaft_scattering: 0.05,
+            shaft_distance: 12.0,
+            motion_scale: 2,
+            upscale_filter: UpscaleFilter::Bilinear,
+            tile_size: 32,
+            tile_stats: false,
+            progress: false,
+            count_rays: false,
+            max_depth: 3,
+            fog: true,
+            fog_density: 0.04,
+            time: 0.0,
+        }
+    }
+}
+
+impl RenderSettings {
+    // Los colores escritos a mano y las texturas están en sRGB; con corrección se pasan a lineal
+    fn decode(&self, color: Color) -> ColorF {
+        if self.gamma_correction { color.to_linear() } else { ColorF::from(color) }
+    }
+
+    // Título de la ventana con las opciones actuales, el campo de visión, el enfoque de la
+    // cámara, los cuadros promediados y los que se saltaron porque nada cambió
+    pub fn title(&self, camera: &Camera, frames: u32, skipped: u64) -> String {
+        let gamma = if self.gamma_correction { "on" } else { "off" };
+        let antialiasing = if self.adaptive {
+            format!("adaptive {}x{}, threshold {:.2}", self.edge_samples, self.edge_samples, self.edge_threshold)
+        } else {
+            format!("{}x{}", self.samples, self.samples)
+        };
+        format!(
+            "Leonardo Mejía - Hansel y Gretel House ({:?}, gamma {}, {:?}, exposure {:.2}, AA {}, shadows {}, {} lights, mipmaps {}, fog {}, shafts {}, stereo {}, {:?} camera{}, fov {:.0}°, aperture {:.2}, focus {:.1}, {} frames, {} skipped)",
+            self.mode, gamma, self.tone_mapping, self.exposure, antialiasing, self.shadow_samples,
+            if self.area_lights { "area" } else { "point" },
+            if self.mipmaps { "on" } else { "off" },
+            if self.fog { "on" } else { "off" },
+            if self.light_shafts { "on" } else { "off" },
+            if self.stereo { format!("{:.3}", self.eye_separation) } else { "off".to_string() },
+            camera.mode, if camera.noclip { " (noclip)" } else { "" }, self.fov.to_degrees(),
+            camera.aperture, camera.focus_distance, frames, skipped
+        )
+    }
+
+    // 1 -> 4 -> 16 -> 1
+    pub fn next_shadow_samples(&self) -> u32 {
+        match self.shadow_samples {
+            1 => 4,
+            4 => 16,
+            _ => 1,
+        }
+    }
+
+    // 1 -> 2x2 -> 4x4 -> adaptativo -> 1
+    pub fn cycle_antialiasing(&mut self) {
+        if self.adaptive {
+            self.adaptive = false;
+            self.samples = 1;
+        } else {
+            match self.samples {
+                1 => self.samples = 2,
+                2 => self.samples = 4,
+                _ => {
+                    self.samples = 1;
+                    self.adaptive = true;
+                }
+            }
+        }
+    }
+}
+
+
+// Materiales cuyo reflejo y transparencia suman más de 1 y ya se avisaron en la consola
+static OVERWEIGHT_WARNED: Mutex<Vec<MaterialId>> = Mutex::new(Vec::new());
+
+// Avisa una sola vez por material cuando hay que recortar su reflejo y su transparencia (ver
+// Material::surface_weights). Los materiales correctos no llegan a tocar el candado
+fn warn_if_overweight(id: MaterialId, material: &Material) {
+    if material.reflectivity + material.transparency <= 1.0 {
+        return;
+    }
+    let mut warned = OVERWEIGHT_WARNED.lock().unwrap_or_else(PoisonError::into_inner);
+    if !warned.contains(&id) {
+        warned.push(id);
+        eprintln!(
+            "Aviso: el material {:?} tiene reflejo {} y transparencia {}, que suman más de 1; se reducen en proporción",
+            id, material.reflectivity, material.transparency,
+        );
+    }
+}
+
+// Color del fondo en la dirección de un rayo que no chocó con nada
+fn environment_color(scene: &Scene, ray_direction: &Vec3, settings: &RenderSettings) -> ColorF {
+    // El cielo procedural y el mapa HDR ya dan lineal; sin corrección gamma se sombrea con valores sRGB
+    let linear = |radiance: ColorF| if settings.gamma_correction { radiance } else { radiance.to_srgb_f() };
+    match scene.environment() {
+        Environment::SolidColor(color) => settings.decode(*color),
+        Environment::ProceduralSky(sky) => linear(sky.sample(ray_direction, &scene.day_night, settings.time)),
+        Environment::Cubemap(skybox) => settings.decode(skybox.sample(ray_direction)),
+        Environment::Equirect(map) => linear(map.sample(ray_direction)),
+    }
+}
+
+
+// Dirección al azar en el cono de un reflejo borroso alrededor de `reflect_dir`, más ancho
+// cuanto más rugoso el material. Las que quedarían por debajo de la superficie (del lado
+// contrario a `reflect_dir` según `normal`) se quedan con el espejo. Con la dirección refractada
+// da la refracción esmerilada
+fn glossy_direction(reflect_dir: &Vec3, normal: &Vec3, roughness: f32, sampler: &mut Sampler) -> Vec3 {
+    let spread = (roughness * GLOSSY_MAX_ANGLE).tan();
+    let (tangent, bitangent) = sampling::basis(reflect_dir);
+    let (dx, dy) = sampler.in_disk();
+    let dir = (reflect_dir + (tangent * dx + bitangent * dy) * spread).normalize();
+    if dir.dot(normal) * reflect_dir.dot(normal) <= 0.0 { *reflect_dir } else { dir }
+}
+
+
+// Color de la niebla de un tramo de `distance` dentro de un fluido y cuánto se ve a través de
+// ella. La niebla es la luz del cielo de arriba filtrada por una unidad del fluido: azul verdosa
+// en el agua, y oscura de noche
+fn underwater_fog(fluid: &Material, scene: &Scene, settings: &RenderSettings, distance: f32) -> (ColorF, f32) {
+    let sky = environment_color(scene, &Vec3::new(0.0, 1.0, 0.0), settings);
+    (sky * fluid.transmittance(1.0), (-UNDERWATER_FOG_DENSITY * distance).exp())
+}
+
+// Tinte de toda la vista: el del fluido en el que está la cámara, o ninguno
+fn underwater_tint(scene: &Scene) -> ColorF {
+    match scene.submerged {
+        Some(fluid) => scene.materials.get(fluid).transmittance(UNDERWATER_TINT_DEPTH),
+        None => ColorF::new(1.0, 1.0, 1.0),
+    }
+}
+
+
+// Índices n1 y n2 a cada lado de una cara transparente, con `index` como el del material,
+// según la pila de medios del rayo, y la pila con la que sigue el rayo refractado. El rayo sale
+// de un material que ya está en la pila, o que golpea desde dentro sin haberlo registrado (entró
+// por una cara compartida con otro bloque); en cualquier otro caso entra en él
+fn media_interface(media: &MediumStack, intersect: &Intersect, index: f32) -> (f32, f32, MediumStack) {
+    let in_stack = media.contains(intersect.material);
+    if in_stack || intersect.inside {
+        let outside = media.leaving(intersect.material);
+        let n1 = if in_stack { media.refractive_index() } else { index };
+        (n1, outside.refractive_index(), outside)
+    } else {
+        let medium = Medium { material: intersect.material, refractive_index: index };
+        (media.refractive_index(), index, media.entering(medium))
+    }
+}
+
+
+// Lo que comparten todos los rayos de un cuadro: la escena, los ajustes y el color ambiente,
+// ya pasado a lineal
+#[derive(Clone, Copy)]
+pub struct RayContext<'a> {
+    pub scene: &'a Scene,
+    pub settings: &'a RenderSettings,
+    pub ambient_color: ColorF,
+}
+
+// Lo que lleva cada rayo de su camino: los medios en los que está, el canal que sigue tras
+// abrirse por dispersión (None con los tres) y cuántos rebotes lleva
+#[derive(Clone, Copy)]
+pub struct RayPath<'a> {
+    pub media: &'a MediumStack,
+    pub channel: Option<usize>,
+    pub depth: u32,
+}
+
+impl<'a> RayPath<'a> {
+    // Un rayo de cámara, que empieza en `media` (vacía salvo bajo el agua)
+    pub fn primary(media: &'a MediumStack) -> Self {
+        RayPath { media, channel: None, depth: 0 }
+    }
+}
+
+pub fn cast_ray(
+    ray_origin: &Vec3,
+    ray_direction: &Vec3,
+    context: &RayContext,
+    sampler: &mut Sampler,
+    path: RayPath,
+) -> ColorF {
+    let (scene, settings, ambient_color) = (context.scene, context.settings, &context.ambient_color);
+    let RayPath { media, channel, depth } = path;
+    // Los colores se pasan a lineal con settings.decode; ambient_color ya llega convertido desde render
+    let decode = |color: Color| settings.decode(color);
+
+    // Los espejos perfectos no abren un rayo nuevo: el bucle sigue por el reflejo guardando en
+    // `throughput` cuánto deja pasar la cadena, así muchos rebotes no hacen crecer la pila.
+    // `carried` junta lo que ya aportaron los tramos anteriores (su niebla)
+    let (mut segment_origin, mut segment_direction, mut depth) = (*ray_origin, *ray_direction, depth);
+    let mut throughput = ColorF::new(1.0, 1.0, 1.0);
+    let mut carried = ColorF::black();
+
+    loop {
+        let (ray_origin, ray_direction) = (&segment_origin, &segment_direction);
+
+        // Los rayos que rebotan demasiado se quedan con el fondo en su dirección
+        if depth > settings.max_depth {
+            return carried + throughput * environment_color(scene, ray_direction, settings);
+        }
+        count_ray(if depth == 0 { &PRIMARY_RAYS } else { &SECONDARY_RAYS }, settings);
+
+
+        // Un golpe en un hueco de una textura recortada (o en lo que passes_through deja pasar)
+        // no cuenta: el rayo sigue desde ahí y puede dar con la cara de atrás del mismo bloque o
+        // con lo que haya detrás
+        let mut origin = *ray_origin;
+        let hit = loop {
+            match scene.world.traverse(&origin, ray_direction) {
+                Some(hit) if passes_through(scene.materials.get(hit.material), &hit, ray_direction, settings, depth == 0) => {
+                    origin = hit.point + ray_direction * settings.shadow_bias;
+                }
+                hit => break hit,
+            }
+        };
+
+        let mut intersect = match hit {
+            Some(intersect) => intersect,
+            None => return carried + throughput * environment_color(scene, ray_direction, settings),
+        };
+
+
+        let material = scene.materials.get(intersect.material);
+        apply_sidedness(material, &mut intersect, ray_direction);
+        // Atrapasombras visto desde la cámara: el fondo, con las sombras que caen en él
+        if material.shadow_catcher {
+            return carried + throughput * shadow_catcher_color(&intersect, ray_direction, scene, ambient_color, settings, sampler);
+        }
+        warn_if_overweight(intersect.material, material);
+        // Reflejo y transparencia ya recortados para que sumen a lo sumo 1
+        let (base_reflectivity, base_transparency) = material.surface_weights();
+
+        let material_color = surface_color(material, &intersect, settings);
+        // Normal para la luz, el reflejo y la refracción; los orígenes de rayos secundarios
+        // siguen usando la geométrica para no quedar dentro de la superficie
+        let normal = shading_normal(material, &intersect, settings.time);
+
+
+        // Absorción (Beer-Lambert) del medio por el que viajó el rayo hasta aquí: el de arriba de
+        // la pila o, si está vacía, el bloque que golpea desde dentro. Lo que trae se apaga según
+        // esa distancia; así se atenúan el rayo refractado y también los reflejos internos
+        let traversed = match media.current() {
+            Some(medium) => Some(scene.materials.get(medium.material)),
+            None if intersect.inside => Some(material),
+            None => None,
+        };
+        // Distancia desde el origen real del rayo, sin cortar en los huecos de alfa
+        let distance = (intersect.point - ray_origin).magnitude();
+        let transmittance = match traversed {
+            Some(medium) if medium.absorption != ColorF::black() => medium.transmittance(distance),
+            _ => ColorF::new(1.0, 1.0, 1.0),
+        };
+
+        // Niebla: cuanto más lejos el golpe, más se acerca al color del horizonte en esa dirección.
+        // Dentro de un fluido (la cámara bajo el agua, o un rayo refractado en ella) la niebla es
+        // la del fluido, mucho más corta, y va aunque la de afuera esté apagada. Los rayos
+        // reflejados y refractados ya traen la niebla de su propio tramo
+        let (fog_color, visibility) = match traversed {
+            Some(medium) if medium.fluid => underwater_fog(medium, scene, settings, distance),
+            _ if settings.fog => {
+                let horizon = Vec3::new(ray_direction.x, 0.0, ray_direction.z).try_normalize(1e-6).unwrap_or_else(|| Vec3::new(0.0, 0.0, -1.0));
+                (environment_color(scene, &horizon, settings), (-settings.fog_density * distance).exp())
+            }
+            _ => (ColorF::black(), 1.0),
+        };
+
+        // Espejo perfecto: su color propio no llega a verse, así que no se sombrea y el bucle
+        // sigue por el reflejo en vez de trazar un rayo nuevo
+        if base_reflectivity >= MIRROR_REFLECTIVITY && base_transparency == 0.0 && material.roughness == 0.0 {
+            carried = carried + throughput * fog_color * (1.0 - visibility);
+            throughput = throughput * transmittance * (visibility * base_reflectivity);
+            let reflect_dir = normalize(&reflect(ray_direction, &normal));
+            segment_origin = offset_origin(&intersect, &reflect_dir, settings.shadow_bias);
+            segment_direction = reflect_dir;
+            depth += 1;
+            continue;
+        }
+
+
+        let mut final_color = ColorF::black();
+
+        // Difusa y especular que deja una luz (o una muestra de ella) que llega desde light_dir.
+        // Una cara que le da la espalda a la luz no recibe nada: sin esto el brillo especular
+        // aparecía igual del lado oscuro (el vidrio de la ventana de noche)
+        let view_dir = (ray_origin - intersect.point).normalize();
+        let shade = |light_dir: &Vec3, light_color: ColorF, light_intensity: f32| {
+            let diffuse_intensity = normal.dot(light_dir);
+            if diffuse_intensity <= 0.0 {
+                return ColorF::black();
+            }
+            let diffuse = material_color * light_color * (material.diffuse * diffuse_intensity * light_intensity);
+
+            let halfway = (light_dir + view_dir).normalize();
+            let specular_intensity = halfway.dot(&normal).max(0.0).powf(material.shininess);
+            let specular = light_color * (material.specular * specular_intensity * light_intensity);
+
+            diffuse + specular
+        };
+
+        for light in scene.lights_at(&intersect.point) {
+            let light_color = decode(light.color);
+            let (light_dir, distance_to_light) = light.direction_from(&intersect.point);
+
+            // Los focos solo iluminan dentro de su cono; las luces apagadas (la luna de día, o con
+            // los interruptores de depuración) no gastan rayos de sombra, y tampoco las que quedan
+            // detrás de la cara. Las de área se miran muestra por muestra: parte del bloque puede
+            // quedar delante aunque su centro no
+            let cone = light.cone(&light_dir);
+            if !light.enabled || light.intensity <= 0.0 || !light.reaches(distance_to_light) || cone <= 0.0 {
+                continue;
+            }
+            let sampled_area = settings.area_lights && matches!(light.kind, LightKind::Area { .. });
+            if !sampled_area && normal.dot(&light_dir) <= 0.0 {
+                continue;
+            }
+
+            let lit = if sampled_area {
+                sample_area_light(&intersect, light, light_color, scene, settings, sampler, &shade)
+            } else {
+                let transmission = cast_shadow(&intersect, light, scene, settings, sampler);
+                let attenuation = light.attenuation(distance_to_light) * cone;
+                let light_intensity = light.intensity * attenuation;
+                // La sombra filtra el color de la luz: un cristal azul deja pasar luz azul
+                shade(&light_dir, light_color * transmission, light_intensity)
+            };
+
+            final_color = final_color + lit;
+        }
+
+        // Añade iluminación ambiental
+        let ambient = material_color * *ambient_color * 0.1;
+        final_color = final_color + ambient;
+
+        // Normal del lado por el que llega el rayo, para Snell y Fresnel
+        let facing_normal = if ray_direction.dot(&normal) < 0.0 { normal } else { -normal };
+
+        // Índices a cada lado de la cara, con `index` como el del material
+        let interface = |index: f32| media_interface(media, &intersect, index);
+
+        // Con dispersión el rayo se abre en tres, uno por canal y cada uno con su propio índice.
+        // Solo se abre la primera vez: los rayos de un canal siguen siendo uno solo en cada golpe
+        let dispersed = base_transparency > 0.0 && material.dispersion > 0.0 && channel.is_none();
+        let channel_interface = |c: usize| interface(material.refractive_index_for(Some(c)));
+
+        // En materiales transparentes Fresnel reparte la transparencia entre reflejo y refracción:
+        // de frente casi todo atraviesa, en ángulos rasantes se comporta como espejo.
+        // Los opacos (el techo de piedra) mantienen su reflectividad constante.
+        // El reparto va por canal: con dispersión uno puede reflejarse por completo y otro no
+        let reflected_share: [f32; 3] = if dispersed {
+            std::array::from_fn(|c| {
+                let (n1, n2, _) = channel_interface(c);
+                fresnel(ray_direction, &facing_normal, n1, n2)
+            })
+        } else if base_transparency > 0.0 {
+            let (n1, n2, _) = interface(material.refractive_index_for(channel));
+            [fresnel(ray_direction, &facing_normal, n1, n2); 3]
+        } else {
+            [0.0; 3]
+        };
+        let reflectivity = reflected_share.map(|share| base_reflectivity + base_transparency * share);
+        let transparency = reflected_share.map(|share| base_transparency * (1.0 - share));
+
+        // Color reflejado
+        let mut reflect_color = ColorF::black();
+        if reflectivity.iter().any(|weight| *weight > 0.0) {
+            let reflect_dir = normalize(&reflect(&ray_direction, &normal));
+            if material.roughness > 0.0 {
+                // Reflejo borroso: rayos repartidos en un cono alrededor del espejo, más ancho cuanto
+                // más rugoso. Solo los rayos de cámara usan varias muestras para no multiplicarlas
+                // en cada rebote
+                let samples = if depth == 0 { settings.glossy_samples.max(1) } else { 1 };
+                for _ in 0..samples {
+                    let dir = glossy_direction(&reflect_dir, &facing_normal, material.roughness, sampler);
+                    let origin = offset_origin(&intersect, &dir, settings.shadow_bias);
+                    reflect_color = reflect_color + cast_ray(&origin, &dir, context, sampler, RayPath { media, channel, depth: depth + 1 });
+                }
+                reflect_color = reflect_color * (1.0 / samples as f32);
+            } else {
+                let reflect_origin = offset_origin(&intersect, &reflect_dir, settings.shadow_bias);
+                reflect_color = cast_ray(&reflect_origin, &reflect_dir, context, sampler, RayPath { media, channel, depth: depth + 1 });
+            }
+        }
+
+
+        // Color refractado
+        let mut refract_color = ColorF::black();
+        // El rayo refractado sigue con la pila de medios del otro lado de la cara. En los
+        // materiales esmerilados (hielo) se abre en un cono alrededor de la refracción, como el
+        // reflejo borroso, y lo de detrás se ve desenfocado
+        let mut trace_refraction = |(n1, n2, refracted_media): (f32, f32, MediumStack), channel: Option<usize>| {
+            let refract_dir = normalize(&refract(&ray_direction, &facing_normal, n1 / n2));
+            if material.refraction_roughness > 0.0 {
+                let samples = if depth == 0 { settings.glossy_samples.max(1) } else { 1 };
+                let mut color = ColorF::black();
+                for _ in 0..samples {
+                    let dir = glossy_direction(&refract_dir, &facing_normal, material.refraction_roughness, sampler);
+                    let origin = offset_origin(&intersect, &dir, settings.shadow_bias);
+                    color = color + cast_ray(&origin, &dir, context, sampler, RayPath { media: &refracted_media, channel, depth: depth + 1 });
+                }
+                color * (1.0 / samples as f32)
+            } else {
+                let refract_origin = offset_origin(&intersect, &refract_dir, settings.shadow_bias);
+                cast_ray(&refract_origin, &refract_dir, context, sampler, RayPath { media: &refracted_media, channel, depth: depth + 1 })
+            }
+        };
+        if dispersed {
+            // Cada rayo aporta solo su canal; los que se reflejan por completo no se trazan
+            let mut channels = [0.0; 3];
+            for c in 0..3 {
+                if transparency[c] > 0.0 {
+                    let color = trace_refraction(channel_interface(c), Some(c));
+                    channels[c] = [color.r, color.g, color.b][c];
+                }
+            }
+            refract_color = ColorF::new(channels[0], channels[1], channels[2]);
+        } else if transparency[0] > 0.0 {
+            refract_color = trace_refraction(interface(material.refractive_index_for(channel)), channel);
+        }
+
+
+        // Combinación de los colores difuso, especular, reflejado y refractado.
+        // Lo que queda para el color local no depende de Fresnel, que solo reparte la transparencia
+        let weights = |w: [f32; 3]| ColorF::new(w[0], w[1], w[2]);
+        let final_color = final_color * (1.0 - base_reflectivity - base_transparency) +
+        (reflect_color * weights(reflectivity)) +
+        (refract_color * weights(transparency));
+
+        // La emisión va aparte, con la fuerza que le da el material: un emisor que refleja o
+        // deja pasar la luz (el portal) brilla igual que uno opaco
+        let final_color = final_color + emitted_color(material, &material_color, settings);
+
+
+        // Absorción del medio y niebla del tramo, calculadas antes de sombrear
+        let final_color = ColorF::lerp(&fog_color, &(final_color * transmittance), visibility);
+        return carried + throughput * final_color;
+    }
+}
+
+
+
+
+// Integrador de caminos (modo PathTrace). En cada golpe se elige al azar qué hace el rayo, con
+// la probabilidad de su peso en el material: reflejarse, refractarse o rebotar difuso, y se sigue
+// un solo camino. En los rebotes difusos se suma la luz directa de cada luz con rayos de sombra y
+// el camino sigue en una dirección con peso coseno: los interiores reciben la luz que rebota en
+// las paredes en vez del ambiente plano, y cada superficie tiñe a las de al lado
+fn trace_path(
+    ray_origin: &Vec3,
+    ray_direction: &Vec3,
+    scene: &Scene,
+    settings: &RenderSettings,
+    sampler: &mut Sampler,
+) -> ColorF {
+    let decode = |color: Color| settings.decode(color);
+
+    let (mut origin, mut direction) = (*ray_origin, *ray_direction);
+    // El camino empieza en el fluido en el que está la cámara, si lo hay
+    let mut media = scene.camera_media();
+    let mut throughput = ColorF::new(1.0, 1.0, 1.0);
+    let mut radiance = ColorF::black();
+    // La emisión cuenta vista desde la cámara o por un espejo; tras un rebote difuso ya la sumó
+    // la luz directa (los bloques emisivos también son luces)
+    let mut count_emission = true;
+
+    for bounce in 0..=settings.max_depth {
+        count_ray(if bounce == 0 { &PRIMARY_RAYS } else { &SECONDARY_RAYS }, settings);
+        // Los huecos de las texturas recortadas y lo demás que deja pasar passes_through se
+        // atraviesan igual que en cast_ray
+        let mut segment_origin = origin;
+        let hit = loop {
+            match scene.world.traverse(&segment_origin, &direction) {
+                Some(hit) if passes_through(scene.materials.get(hit.material), &hit, &direction, settings, bounce == 0) => {
+                    segment_origin = hit.point + direction * settings.shadow_bias;
+                }
+                hit => break hit,
+            }
+        };
+        let Some(mut intersect) = hit else {
+            return radiance + throughput * environment_color(scene, &direction, settings);
+        };
+        let material = scene.materials.get(intersect.material);
+        apply_sidedness(material, &mut intersect, &direction);
+        // Sin ambiente plano: la sombra de un atrapasombras es tan oscura como la luz directa que falta
+        if material.shadow_catcher {
+            return radiance + throughput * shadow_catcher_color(&intersect, &direction, scene, &ColorF::black(), settings, sampler);
+        }
+
+        // Absorción del medio que se atravesó y niebla del tramo
+        let distance = (intersect.point - origin).magnitude();
+        let traversed = media.current().map(|medium| scene.materials.get(medium.material));
+        if let Some(medium) = traversed {
+            throughput = throughput * medium.transmittance(distance);
+        }
+        if let Some(fluid) = traversed.filter(|medium| medium.fluid) {
+            let (fog_color, visibility) = underwater_fog(fluid, scene, settings, distance);
+            radiance = radiance + throughput * fog_color * (1.0 - visibility);
+            throughput = throughput * visibility;
+        } else if settings.fog {
+            let horizon = Vec3::new(direction.x, 0.0, direction.z).try_normalize(1e-6).unwrap_or_else(|| Vec3::new(0.0, 0.0, -1.0));
+            let visibility = (-settings.fog_density * distance).exp();
+            radiance = radiance + throughput * environment_color(scene, &horizon, settings) * (1.0 - visibility);
+            throughput = throughput * visibility;
+        }
+
+        let material_color = surface_color(material, &intersect, settings);
+        let normal = shading_normal(material, &intersect, settings.time);
+        let facing_normal = if direction.dot(&normal) < 0.0 { normal } else { -normal };
+
+        // Fresnel reparte la transparencia igual que en cast_ray (sin dispersión)
+        let (n1, n2, refracted_media) = media_interface(&media, &intersect, material.refractive_index);
+        let (base_reflectivity, base_transparency) = material.surface_weights();
+        let reflected_share = if base_transparency > 0.0 { fresnel(&direction, &facing_normal, n1, n2) } else { 0.0 };
+        let reflectivity = base_reflectivity + base_transparency * reflected_share;
+        let transparency = base_transparency * (1.0 - reflected_share);
+
+        // La emisión se suma antes de elegir el evento, así no depende de cuál salga
+        if count_emission {
+            radiance = radiance + throughput * emitted_color(material, &material_color, settings);
+        }
+
+        // Como cada evento sale con la probabilidad de su peso, el peso no multiplica el camino
+        let choice = sampler.next_f32();
+        if choice < reflectivity {
+            let reflect_dir = normalize(&reflect(&direction, &normal));
+            direction = if material.roughness > 0.0 {
+                glossy_direction(&reflect_dir, &facing_normal, material.roughness, sampler)
+            } else {
+                reflect_dir
+            };
+            count_emission = true;
+        } else if choice < reflectivity + transparency {
+            let refract_dir = normalize(&refract(&direction, &facing_normal, n1 / n2));
+            direction = if material.refraction_roughness > 0.0 {
+                glossy_direction(&refract_dir, &facing_normal, material.refraction_roughness, sampler)
+            } else {
+                refract_dir
+            };
+            media = refracted_media;
+            count_emission = true;
+        } else {
+            // Luz directa: el mismo difuso y especular que cast_ray, sin el ambiente. Los bloques
+            // emisivos se muestrean por sus caras igual que allá
+            let view_dir = -direction;
+            let shade = |light_dir: &Vec3, light_color: ColorF, light_intensity: f32| {
+                let diffuse_intensity = normal.dot(light_dir);
+                if diffuse_intensity <= 0.0 {
+                    return ColorF::black();
+                }
+                let halfway = (light_dir + view_dir).normalize();
+                let specular_intensity = halfway.dot(&normal).max(0.0).powf(material.shininess);
+                material_color * light_color * (material.diffuse * diffuse_intensity * light_intensity)
+                    + light_color * (material.specular * specular_intensity * light_intensity)
+            };
+            let mut direct = ColorF::black();
+            for light in scene.lights_at(&intersect.point) {
+                let (light_dir, distance_to_light) = light.direction_from(&intersect.point);
+                let cone = light.cone(&light_dir);
+                if !light.enabled || light.intensity <= 0.0 || !light.reaches(distance_to_light) || cone <= 0.0 {
+                    continue;
+                }
+                let light_color = decode(light.color);
+                if settings.area_lights && matches!(light.kind, LightKind::Area { .. }) {
+                    direct = direct + sample_area_light(&intersect, light, light_color, scene, settings, sampler, &shade);
+                } else if normal.dot(&light_dir) > 0.0 {
+                    let transmission = cast_shadow(&intersect, light, scene, settings, sampler);
+                    let light_intensity = light.intensity * light.attenuation(distance_to_light) * cone;
+                    direct = direct + shade(&light_dir, light_color * transmission, light_intensity);
+                }
+            }
+            radiance = radiance + throughput * direct;
+
+            // Con peso coseno, el coseno y la probabilidad se cancelan: queda solo el albedo
+            throughput = throughput * material_color * material.diffuse;
+            direction = sampler.cosine_direction(&facing_normal);
+            count_emission = false;
+        }
+        origin = offset_origin(&intersect, &direction, settings.shadow_bias);
+    }
+
+    // El camino se corta en el límite de rebotes sin sumar nada más
+    radiance
+}
+
+
+// Modo RayTrace. Con `scale` mayor que 1 se traza una imagen `scale` veces más chica y se
+// agranda al tamaño del framebuffer con settings.upscale_filter (mientras se mueve la cámara)
+fn render_ray_traced(framebuffer: &mut Framebuffer, accumulation: &mut Accumulation, scene: &Scene, camera: &Camera, settings: &RenderSettings, scale: u32) {
+    // Tamaño de la imagen que se traza; redondea hacia arriba para cubrir todo el framebuffer
+    let scale = scale.max(1) as usize;
+    let (image_width, image_height) = (framebuffer.width.div_ceil(scale), framebuffer.height.div_ceil(scale));
+    let width = image_width as f32;
+    let height = image_height as f32;
+    let settings = &RenderSettings { pixel_spread: settings.projection.pixel_spread(settings.fov, width, height), time: scene.time, ..*settings };
+
+    // El ambiente se pasa a lineal una vez por cuadro en lugar de en cada rayo
+    let ambient_color = scene.ambient_color();
+    let ambient_color = if settings.gamma_correction { ambient_color.to_linear() } else { ambient_color };
+    let context = RayContext { scene, settings, ambient_color };
+
+
+
+
+    // Crea un búfer temporal en flotante; el tone mapping trabaja sobre él antes de pasar a u32
+    let mut pixel_buffer = vec![ColorF::black(); image_width * image_height];
+
+
+
+
+    // Color que trae un rayo de cámara, con los rayos crepusculares si están activos. Bajo el
+    // agua el rayo sale desde dentro de ella y la vista entera toma su tinte
+    let camera_media = scene.camera_media();
+    let tint = underwater_tint(scene);
+    let trace_primary = |origin: &Vec3, direction: &Vec3, sampler: &mut Sampler| -> ColorF {
+        let color = cast_ray(origin, direction, &context, sampler, RayPath::primary(&camera_media));
+        let color = if settings.light_shafts {
+            color + light_shafts(origin, direction, scene, settings, sampler)
+        } else {
+            color
+        };
+        color * tint
+    };
+
+    // Cuadros ya promediados: el primero tras un cambio es el de siempre y los siguientes
+    // usan otros números al azar para que el promedio tenga muestras nuevas
+    let frame = accumulation.frames() as usize;
+    let pixel_count = image_width * image_height;
+
+    // Color de un píxel con n x n rayos: uno en cada celda de una rejilla sobre el píxel,
+    // movido al azar dentro de ella. Con n = 1 es el rayo de siempre por la esquina del píxel,
+    // salvo al promediar cuadros, que también se mueve
+    let trace_pixel = |index: usize, n: u32| -> ColorF {
+        let x = (index % image_width) as u32;
+        let y = (index / image_width) as u32;
+
+        let n = n.max(1);
+        let mut sampler = Sampler::new((frame * pixel_count + index) as u32);
+        let mut sum = ColorF::black();
+
+        for sy in 0..n {
+            for sx in 0..n {
+                let (jitter_x, jitter_y) = if n == 1 && frame == 0 {
+                    (0.0, 0.0)
+                } else {
+                    (
+                        (sx as f32 + sampler.next_f32()) / n as f32 - 0.5,
+                        (sy as f32 + sampler.next_f32()) / n as f32 - 0.5,
+                    )
+                };
+
+                // Profundidad de campo: varios rayos desde puntos de la lente hacia el mismo punto
+                // del plano de enfoque; lo que está fuera de ese plano se desenfoca
+                let lens_samples = if camera.aperture > 0.0 && settings.projection == Projection::Perspective {
+                    settings.lens_samples.max(1)
+                } else {
+                    1
+                };
+                let mut lens_sum = ColorF::black();
+                for _ in 0..lens_samples {
+                    let (origin, direction) = camera_ray(camera, settings, width, height, x as f32 + jitter_x, y as f32 + jitter_y, &mut sampler);
+                    lens_sum = lens_sum + trace_primary(&origin, &direction, &mut sampler);
+                }
+                sum = sum + lens_sum * (1.0 / lens_samples as f32);
+            }
+        }
+
+        // El promedio se hace en flotante para no acumular el redondeo de u8
+        sum * (1.0 / (n * n) as f32)
+    };
+
+    let (width, height, tile_size) = (image_width, image_height, settings.tile_size as usize);
+
+    // Primera pasada: todos los píxeles (en el adaptativo con un solo rayo)
+    let first_samples = if settings.adaptive { 1 } else { settings.samples };
+    let mut tile_times = render_tiles(&mut pixel_buffer, width, height, tile_size, settings.progress, |index, _| trace_pixel(index, first_samples));
+
+    // Segunda pasada: solo los píxeles que contrastan con sus vecinos se vuelven a lanzar
+    if settings.adaptive {
+        let edges = edge_mask(&pixel_buffer, width, height, settings);
+        let edge_times = render_tiles(&mut pixel_buffer, width, height, tile_size, settings.progress, |index, pixel| {
+            if !edges[index] {
+                pixel
+            } else if settings.show_edges {
+                ColorF::new(1.0, 0.0, 0.0)
+            } else {
+                trace_pixel(index, settings.edge_samples)
+            }
+        });
+        // Cada bloque cuenta lo que tardó en las dos pasadas
+        for (time, edge_time) in tile_times.iter_mut().zip(edge_times) {
+            *time += edge_time;
+        }
+    }
+
+    if settings.tile_stats {
+        print_tile_stats(&tile_times);
+    }
+
+
+    // El promedio y el framebuffer siempre tienen el tamaño completo
+    let pixel_buffer = if scale > 1 {
+        upscale(&pixel_buffer, width, height, framebuffer.width, framebuffer.height, settings.upscale_filter)
+    } else {
+        pixel_buffer
+    };
+    write_pixels(framebuffer, accumulation.add(&pixel_buffer, RAY_TRACE_ACCUMULATION_LIMIT), settings);
+}
+
+// Agranda una imagen de `width` x `height` a `out_width` x `out_height`. Los rayos pasan por la
+// esquina de cada píxel, así que la esquina del píxel de salida cae en x * width / out_width
+fn upscale(pixels: &[ColorF], width: usize, height: usize, out_width: usize, out_height: usize, filter: UpscaleFilter) -> Vec<ColorF> {
+    let (ratio_x, ratio_y) = (width as f32 / out_width as f32, height as f32 / out_height as f32);
+    let texel = |x: usize, y: usize| pixels[y.min(height - 1) * width + x.min(width - 1)];
+
+    (0..out_width * out_height)
+        .into_par_iter()
+        .map(|index| {
+            let x = (index % out_width) as f32 * ratio_x;
+            let y = (index / out_width) as f32 * ratio_y;
+            match filter {
+                UpscaleFilter::Nearest => texel(x.round() as usize, y.round() as usize),
+                UpscaleFilter::Bilinear => {
+                    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+                    let (tx, ty) = (x.fract(), y.fract());
+                    let top = ColorF::lerp(&texel(x0, y0), &texel(x0 + 1, y0), tx);
+                    let bottom = ColorF::lerp(&texel(x0, y0 + 1), &texel(x0 + 1, y0 + 1), tx);
+                    ColorF::lerp(&top, &bottom, ty)
+                }
+            }
+        })
+        .collect()
+}
+
+// Recorre la imagen en bloques de tile_size x tile_size. Cada hilo toma un bloque entero y lo
+// llena en orden en su propio búfer, que después se copia a su lugar: mejor uso de la caché
+// que píxeles sueltos repartidos entre hilos. `shade` recibe el índice del píxel y su valor
+// actual. Con `progress` escribe el porcentaje de bloques terminados cada vez que sube.
+// Devuelve lo que tardó cada bloque
+fn render_tiles(
+    pixels: &mut [ColorF],
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    progress: bool,
+    shade: impl Fn(usize, ColorF) -> ColorF + Sync,
+) -> Vec<Duration> {
+    let tile_size = tile_size.max(1);
+    let tiles_x = width.div_ceil(tile_size);
+    let tiles_y = height.div_ceil(tile_size);
+    // Esquinas (incluida y excluida) del bloque número `tile`
+    let bounds = |tile: usize| {
+        let (x0, y0) = ((tile % tiles_x) * tile_size, (tile / tiles_x) * tile_size);
+        (x0, y0, (x0 + tile_size).min(width), (y0 + tile_size).min(height))
+    };
+
+    let current = &*pixels;
+    let tile_count = tiles_x * tiles_y;
+    let done = AtomicUsize::new(0);
+    let tiles: Vec<(Vec<ColorF>, Duration)> = (0..tile_count)
+        .into_par_iter()
+        .map(|tile| {
+            let start = Instant::now();
+            let (x0, y0, x1, y1) = bounds(tile);
+            let mut buffer = Vec::with_capacity((x1 - x0) * (y1 - y0));
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let index = y * width + x;
+                    buffer.push(shade(index, current[index]));
+                }
+            }
+            if progress {
+                let finished = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if finished * 100 / tile_count != (finished - 1) * 100 / tile_count {
+                    print!("\rBloques {}/{} ({}%)", finished, tile_count, finished * 100 / tile_count);
+                    if finished == tile_count {
+                        println!();
+                    }
+                    let _ = io::stdout().flush();
+                }
+            }
+            (buffer, start.elapsed())
+        })
+        .collect();
+
+    let mut times = Vec::with_capacity(tiles.len());
+    for (tile, (buffer, elapsed)) in tiles.into_iter().enumerate() {
+        let (x0, y0, x1, y1) = bounds(tile);
+        for (row, y) in (y0..y1).enumerate() {
+            let row_width = x1 - x0;
+            pixels[y * width + x0..y * width + x1].copy_from_slice(&buffer[row * row_width..(row + 1) * row_width]);
+        }
+        times.push(elapsed);
+    }
+    times
+}
+
+// Menor, promedio y mayor tiempo de los bloques de un cuadro. Un máximo muy por encima del
+// promedio es un bloque caro (el cristal, la ventana) que deja a los demás hilos esperando
+fn print_tile_stats(times: &[Duration]) {
+    let (Some(min), Some(max)) = (times.iter().min(), times.iter().max()) else {
+        return;
+    };
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    println!(
+        "Bloques: {}, min {:.2} ms, promedio {:.2} ms, max {:.2} ms (max / promedio {:.1})",
+        times.len(),
+        min.as_secs_f64() * 1000.0,
+        mean.as_secs_f64() * 1000.0,
+        max.as_secs_f64() * 1000.0,
+        max.as_secs_f64() / mean.as_secs_f64().max(1e-9),
+    );
+}
+
+// Cuadros que entran en el promedio de FrameTimes
+pub const FRAME_TIME_WINDOW: usize = 30;
+
+// Lo que tardaron los últimos cuadros trazados (render y subida a la ventana), para los
+// cuadros por segundo del título
+#[derive(Default)]
+pub struct FrameTimes {
+    times: VecDeque<Duration>,
+}
+
+impl FrameTimes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Agrega un cuadro y olvida el más viejo pasados FRAME_TIME_WINDOW
+    pub fn push(&mut self, time: Duration) {
+        self.times.push_back(time);
+        if self.times.len() > FRAME_TIME_WINDOW {
+            self.times.pop_front();
+        }
+    }
+
+    // Promedio de los cuadros guardados; None antes del primero
+    pub fn average(&self) -> Option<Duration> {
+        if self.times.is_empty() {
+            return None;
+        }
+        Some(self.times.iter().sum::<Duration>() / self.times.len() as u32)
+    }
+
+    // "12.4 fps (80.6 ms)"; None antes del primer cuadro
+    pub fn summary(&self) -> Option<String> {
+        let seconds = self.average()?.as_secs_f64().max(1e-9);
+        Some(format!("{:.1} fps ({:.1} ms)", 1.0 / seconds, seconds * 1000.0))
+    }
+
+    // "Raytracer — 12.4 fps (80.6 ms)"; sin cuadros todavía, solo el nombre
+    pub fn title(&self) -> String {
+        match self.summary() {
+            Some(summary) => format!("Raytracer — {}", summary),
+            None => "Raytracer".to_string(),
+        }
+    }
+}
+
+// Pasa el búfer en flotante al framebuffer
+fn write_pixels(framebuffer: &mut Framebuffer, pixels: &[ColorF], settings: &RenderSettings) {
+    // Tone mapping: exposición y curva en lineal, luego a sRGB si se sombreó en lineal
+    let pixel_buffer: Vec<u32> = pixels
+        .par_iter()
+        .map(|&color| {
+            let color = settings.tone_mapping.apply(color * settings.exposure);
+            let color = if settings.gamma_correction { color.to_srgb() } else { color.to_color() };
+            color.to_hex()
+        })
+        .collect();
+
+
+
+
+    // Finalmente, vuelca el pixel_buffer en el framebuffer
+    for (index, &pixel) in pixel_buffer.iter().enumerate() {
+        let x = (index % framebuffer.width as usize) as u32;
+        let y = (index / framebuffer.width as usize) as u32;
+        framebuffer.set_current_color(pixel);
+        framebuffer.point(x as usize, y as usize);
+    }
+}
+
+
+// Promedio de los cuadros desde la última vez que cambió la imagen: con la cámara quieta cada
+// cuadro trae otras muestras (otro punto del píxel, otro camino) y el promedio se limpia de
+// ruido. Es una vista rápida con grano mientras se mueve que se afina al soltar las teclas
+pub struct Accumulation {
+    average: Vec<ColorF>,
+    frames: u32,
+}
+
+impl Accumulation {
+    pub fn new(len: usize) -> Self {
+        Accumulation { average: vec![ColorF::black(); len], frames: 0 }
+    }
+
+    // Empieza de nuevo: al mover la cámara o cambiar una opción lo promediado ya no vale
+    pub fn reset(&mut self) {
+        self.frames = 0;
+    }
+
+    // Cuadros mezclados desde el último reset
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // Mezcla un cuadro nuevo. Los primeros `limit` pesan todos igual; después cada uno nuevo
+    // pesa 1 / limit y lo viejo se va desvaneciendo, así lo que cambia despacio sin que nadie
+    // toque nada (el sol) no deja una estela
+    pub fn add(&mut self, frame: &[ColorF], limit: u32) -> &[ColorF] {
+        let weight = 1.0 / (self.frames + 1).min(limit.max(1)) as f32;
+        self.average
+            .par_iter_mut()
+            .zip(frame)
+            .for_each(|(average, color)| *average = ColorF::lerp(average, color, weight));
+        self.frames += 1;
+        &self.average
+    }
+}
+
+// Modo PathTrace: un camino por píxel en cada cuadro, promediado con los anteriores
+fn render_path_traced(framebuffer: &mut Framebuffer, accumulation: &mut Accumulation, scene: &Scene, camera: &Camera, settings: &RenderSettings) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    let settings = &RenderSettings { pixel_spread: settings.projection.pixel_spread(settings.fov, width, height), time: scene.time, ..*settings };
+
+    // Cada cuadro usa otros números al azar; si no, sumaría siempre el mismo camino
+    let frame = accumulation.frames() as usize;
+    let pixel_count = framebuffer.width * framebuffer.height;
+
+    let tint = underwater_tint(scene);
+    let mut pixel_buffer = vec![ColorF::black(); pixel_count];
+    pixel_buffer
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(index, pixel)| {
+            let x = (index % framebuffer.width) as f32;
+            let y = (index / framebuffer.width) as f32;
+            let mut sampler = Sampler::new((frame * pixel_count + index) as u32);
+
+            // Un punto al azar dentro del píxel: el promedio hace de antialiasing
+            let (jitter_x, jitter_y) = (sampler.next_f32() - 0.5, sampler.next_f32() - 0.5);
+            let (origin, direction) = camera_ray(camera, settings, width, height, x + jitter_x, y + jitter_y, &mut sampler);
+            *pixel = trace_path(&origin, &direction, scene, settings, &mut sampler) * tint;
+        });
+
+    // Sin límite: con el ciclo del día detenido la imagen converge del todo
+    write_pixels(framebuffer, accumulation.add(&pixel_buffer, u32::MAX), settings);
+}
+
+// Marca los píxeles cuya luminancia, ya con exposición y tone mapping, difiere de la de
+// algún vecino (arriba, abajo, izquierda, derecha) más que edge_threshold
+fn edge_mask(pixels: &[ColorF], width: usize, height: usize, settings: &RenderSettings) -> Vec<bool> {
+    let luminance: Vec<f32> = pixels
+        .par_iter()
+        .map(|&color| settings.tone_mapping.apply(color * settings.exposure).luminance())
+        .collect();
+
+    (0..pixels.len())
+        .into_par_iter()
+        .map(|index| {
+            let (x, y) = (index % width, index / width);
+            let center = luminance[index];
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            neighbours.into_iter().flatten().any(|n| (luminance[n] - center).abs() > settings.edge_threshold)
+        })
+        .collect()
+}
+
+
+// Dibuja una escena en el framebuffer con sus opciones. Guarda el promedio de cuadros que se
+// arma con la cámara quieta y la última vista, para trazar a menor resolución mientras se mueve
+pub struct Renderer {
+    pub settings: RenderSettings,
+    accumulation: Accumulation,
+    // En modo estéreo `accumulation` es la del ojo izquierdo y esta la del derecho, que se
+    // traza en `right_eye` antes de mezclarla
+    right_accumulation: Accumulation,
+    right_eye: Framebuffer,
+    last_view: Option<(Vec3, Vec3, f32, f32)>, // Ojo, centro, apertura y enfoque del último cuadro
+    last_scale: u32,
+}
+
+impl Renderer {
+    pub fn new(settings: RenderSettings) -> Self {
+        Renderer {
+            settings,
+            accumulation: Accumulation::new(0),
+            right_accumulation: Accumulation::new(0),
+            right_eye: Framebuffer::new(0, 0),
+            last_view: None,
+            last_scale: 1,
+        }
+    }
+
+    // El promedio de cuadros vuelve a empezar; hay que llamarlo al cambiar una opción o algo de
+    // la escena que no sea la cámara
+    pub fn reset(&mut self) {
+        self.accumulation.reset();
+        self.right_accumulation.reset();
+    }
+
+    // Cuadros mezclados en la imagen actual
+    pub fn frames(&self) -> u32 {
+        self.accumulation.frames()
+    }
+
+    // Si con la cámara quieta ya no vale la pena seguir trazando: en RayTrace el promedio
+    // llegó a su límite y en PathTrace el ruido que queda ya no se nota
+    pub fn converged(&self) -> bool {
+        let limit = match self.settings.mode {
+            RenderMode::RayTrace => RAY_TRACE_ACCUMULATION_LIMIT,
+            RenderMode::PathTrace => PATH_TRACE_MAX_FRAMES,
+        };
+        self.accumulation.frames() >= limit
+    }
+
+    pub fn render(&mut self, scene: &Scene, camera: &Camera, framebuffer: &mut Framebuffer) {
+        // El promedio vuelve a empezar si se mueve la cámara. Mientras se mueve se traza a menor
+        // resolución; el primer cuadro quieto vuelve al tamaño completo y también empieza de
+        // cero, sin mezclarse con los borrosos
+        let view = (camera.eye, camera.center, camera.aperture, camera.focus_distance);
+        let moved = self.last_view.is_some_and(|last| last != view);
+        let scale = if moved { self.settings.motion_scale } else { 1 };
+        if moved || scale != self.last_scale {
+            self.reset();
+        }
+        self.last_view = Some(view);
+        self.last_scale = scale;
+
+        // Los promedios siempre tienen el tamaño del framebuffer
+        let pixel_count = framebuffer.width * framebuffer.height;
+        if self.accumulation.average.len() != pixel_count {
+            self.accumulation = Accumulation::new(pixel_count);
+        }
+
+        if !self.settings.stereo {
+            render_view(framebuffer, &mut self.accumulation, scene, camera, &self.settings, scale);
+            return;
+        }
+
+        // Estéreo: cada ojo con su promedio y la misma resolución reducida en movimiento
+        if self.right_accumulation.average.len() != pixel_count {
+            self.right_accumulation = Accumulation::new(pixel_count);
+        }
+        if (self.right_eye.width, self.right_eye.height) != (framebuffer.width, framebuffer.height) {
+            self.right_eye = Framebuffer::new(framebuffer.width, framebuffer.height);
+        }
+        let (left, right) = camera.stereo_eyes(self.settings.eye_separation);
+        render_view(framebuffer, &mut self.accumulation, scene, &left, &self.settings, scale);
+        render_view(&mut self.right_eye, &mut self.right_accumulation, scene, &right, &self.settings, scale);
+        framebuffer.buffer
+            .par_iter_mut()
+            .zip(&self.right_eye.buffer)
+            .for_each(|(left, right)| *left = (*left & 0xFF0000) | (right & 0x00FFFF));
+    }
+}
+
+fn render_view(framebuffer: &mut Framebuffer, accumulation: &mut Accumulation, scene: &Scene, camera: &Camera, settings: &RenderSettings, scale: u32) {
+    match settings.mode {
+        RenderMode::RayTrace => render_ray_traced(framebuffer, accumulation, scene, camera, settings, scale),
+        RenderMode::PathTrace => render_path_traced(framebuffer, accumulation, scene, camera, settings),
+    }
+}
@@ -0,0 +1,54 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+
+// Cheap deterministic random numbers for jittering samples. Each pixel seeds its
+// own generator, so the rayon threads never share state and a still camera gives
+// the same image every frame (no flickering noise).
+pub struct Sampler {
+    state: u32,
+}
+
+impl Sampler {
+    pub fn new(seed: u32) -> Self {
+        // Hash the seed so neighbouring pixels don't start with neighbouring states
+        let mut sampler = Sampler { state: seed.wrapping_mul(0x9e37_79b9) ^ 0x85eb_ca6b };
+        sampler.next_u32();
+        sampler
+    }
+
+    // PCG-style step with an output permutation
+    fn next_u32(&mut self) -> u32 {
+        self.state = self.state.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+        let word = ((self.state >> ((self.state >> 28) + 4)) ^ self.state).wrapping_mul(277_803_737);
+        (word >> 22) ^ word
+    }
+
+    // Uniform value in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / 16_777_216.0
+    }
+
+    // Uniform point in the unit disk
+    pub fn in_disk(&mut self) -> (f32, f32) {
+        let r = self.next_f32().sqrt();
+        let theta = 2.0 * PI * self.next_f32();
+        (r * theta.cos(), r * theta.sin())
+    }
+
+    // Direction in the hemisphere around `normal` (unit length), more likely the closer
+    // it is to the normal (cosine-weighted), the spread of a diffuse bounce
+    pub fn cosine_direction(&mut self, normal: &Vec3) -> Vec3 {
+        let (x, y) = self.in_disk();
+        let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+        let (tangent, bitangent) = basis(normal);
+        (tangent * x + bitangent * y + normal * z).normalize()
+    }
+}
+
+// Two unit vectors perpendicular to `normal` (unit length) and to each other
+pub fn basis(normal: &Vec3) -> (Vec3, Vec3) {
+    let helper = if normal.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let tangent = normal.cross(&helper).normalize();
+    let bitangent = normal.cross(&tangent);
+    (tangent, bitangent)
+}
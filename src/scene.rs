@@ -0,0 +1,306 @@
+use nalgebra_glm::Vec3;
+use std::io;
+use std::path::Path;
+use crate::camera::Camera;
+use crate::color::{Color, ColorF};
+use crate::day_night::DayNightCycle;
+use crate::environment::Environment;
+use crate::light::{Light, LightKind};
+use crate::light_grid::LightGrid;
+use crate::material::{MaterialId, MaterialRegistry};
+use crate::medium::{Medium, MediumStack};
+use crate::object::Object;
+use crate::scene_file;
+use crate::world::World;
+
+// How far past a fluid's surface the eye has to go to count as in or out of it, so bobbing
+// right at the surface doesn't flip the view every frame
+pub const SUBMERSION_BAND: f32 = 0.02;
+
+// Everything the renderer draws besides the camera: what rays can hit, the materials they
+// find there, the lights, the backgrounds and the time of day. The main loop changes it
+// between frames; Renderer only reads it
+pub struct Scene {
+    pub world: World,
+    pub materials: MaterialRegistry,
+    pub lights: Vec<Light>,
+    pub environments: Vec<Environment>, // Backgrounds to switch between
+    pub environment: usize,             // Index of the one rays see
+    pub day_night: DayNightCycle,
+    pub time: f32, // Seconds since the start, for animated textures and drifting clouds
+    // Emissive cubes of the same material that touch share one light (the portal frame gives
+    // one instead of four); off, every emissive object gets its own, for debugging
+    pub merge_emissive_lights: bool,
+    // Shading only looks at the lights whose range touches the hit's cell of the light grid;
+    // off, it checks every light, to compare (the image is the same)
+    pub cull_lights: bool,
+    // Debugging switches, by index in `lights` (the emissive lights come in the order of their
+    // first object, so an index keeps naming the same light while the objects don't change).
+    // With `isolate_light`, every light but the selected one is off
+    pub selected_light: usize,
+    pub isolate_light: bool,
+    disabled_lights: Vec<usize>,
+    // Fluid material the camera is in, from update_submersion: camera rays start inside it
+    pub submerged: Option<MaterialId>,
+    // The lights placed by hand, as given: `lights` starts with them (moved and flickered by
+    // update_lights) and goes on with the ones of the emissive objects
+    fixed_lights: Vec<Light>,
+    light_grid: LightGrid, // For `lights`, rebuilt by update_lights
+}
+
+impl Scene {
+    // `lights` must start with the sun and the moon, which update_lights moves with the day.
+    // Lights for the emissive objects are added here
+    pub fn new(world: World, materials: MaterialRegistry, lights: Vec<Light>, environments: Vec<Environment>, day_night: DayNightCycle) -> Self {
+        assert!(lights.len() >= 2, "the scene needs the sun and the moon as its first two lights");
+        assert!(!environments.is_empty(), "the scene needs at least one environment");
+        let fixed_lights = lights.clone();
+        let mut scene = Scene {
+            world,
+            materials,
+            lights,
+            environments,
+            environment: 0,
+            day_night,
+            time: 0.0,
+            merge_emissive_lights: true,
+            cull_lights: true,
+            selected_light: 0,
+            isolate_light: false,
+            disabled_lights: Vec::new(),
+            submerged: None,
+            fixed_lights,
+            light_grid: LightGrid::new(&[]),
+        };
+        scene.update_lights();
+        scene
+    }
+
+    // Writes the objects, materials, hand-placed lights and `camera` as a scene file that
+    // scene_file::load reads back. The lights of the emissive objects aren't saved: the
+    // loaded scene makes them again
+    pub fn save(&self, path: &Path, camera: &Camera) -> io::Result<()> {
+        scene_file::save(path, self.world.scene.objects(), &self.materials, &self.fixed_lights, camera)
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environments[self.environment]
+    }
+
+    pub fn next_environment(&mut self) {
+        self.environment = (self.environment + 1) % self.environments.len();
+    }
+
+    // Flat light for the parts shadow rays don't reach, from the time of day
+    pub fn ambient_color(&self) -> ColorF {
+        self.day_night.get_current_color()
+    }
+
+    // Moves the sun and the moon to the time of day and remakes the lights of the emissive
+    // objects, so a material whose emission changes (the portal) lights the house with it.
+    // Lights with a flicker are set to how they look at the scene's `time`, so call it every frame
+    pub fn update_lights(&mut self) {
+        self.lights.clear();
+        self.lights.extend_from_slice(&self.fixed_lights);
+        self.lights.extend(generate_lights_from_emissive_objects(self.world.scene.objects(), &self.materials, self.merge_emissive_lights, self.time));
+
+        // 🔆 Luz principal (sol)
+        let sun = &mut self.lights[0];
+        sun.kind = LightKind::Directional { direction: self.day_night.sun_direction() };
+        sun.color = Color::new(255, 255, 200);
+        // Sin atenuación por distancia; antes el sol llegaba a la casa a la mitad de su intensidad.
+        // Con el sol debajo del horizonte es 0
+        sun.intensity = self.day_night.get_light_intensity() * 2.0;
+        // Con el cielo procedural, las nubes que tapan el sol oscurecen la escena
+        if let Environment::ProceduralSky(sky) = &self.environments[self.environment] {
+            sun.intensity *= 1.0 - 0.8 * sky.cloud_cover(&-self.day_night.sun_direction(), self.time);
+        }
+
+        // 🔆 Luna: un 10% de la luz del sol a mediodía, creciendo a medida que anochece
+        let moon = &mut self.lights[1];
+        moon.kind = LightKind::Directional { direction: self.day_night.moon_direction() };
+        moon.intensity = 0.2 * self.day_night.night_amount();
+
+        // Los interruptores de depuración apagan luces sin sacarlas de la lista
+        self.selected_light = self.selected_light.min(self.lights.len() - 1);
+        for (index, light) in self.lights.iter_mut().enumerate() {
+            *light = light.flickered(self.time);
+            light.enabled &= !self.disabled_lights.contains(&index) && (!self.isolate_light || index == self.selected_light);
+        }
+        self.light_grid = LightGrid::new(&self.lights);
+    }
+
+    // The lights that can reach `point`. With culling on, only the ones listed in its cell of
+    // the light grid; all of them if culling is off or the list changed length since the last
+    // update_lights (lights moved by hand need update_lights, or rebuild_light_grid, first)
+    pub fn lights_at(&self, point: &Vec3) -> impl Iterator<Item = &Light> {
+        let culled = (self.cull_lights && self.light_grid.light_count() == self.lights.len())
+            .then(|| self.light_grid.lights_at(point).iter().map(|&index| &self.lights[index]));
+        let all = culled.is_none().then(|| self.lights.iter());
+        culled.into_iter().flatten().chain(all.into_iter().flatten())
+    }
+
+    // Moves the selection `step` lights forward (backward if negative), wrapping around
+    pub fn select_light(&mut self, step: isize) {
+        let count = self.lights.len() as isize;
+        self.selected_light = (self.selected_light as isize + step).rem_euclid(count) as usize;
+    }
+
+    // Switches the selected light off, or back on; true when it ends up on
+    pub fn toggle_selected_light(&mut self) -> bool {
+        let index = self.selected_light;
+        match self.disabled_lights.iter().position(|&disabled| disabled == index) {
+            Some(position) => {
+                self.disabled_lights.remove(position);
+                true
+            }
+            None => {
+                self.disabled_lights.push(index);
+                false
+            }
+        }
+    }
+
+    // Whether the light at `index` was switched off with toggle_selected_light
+    pub fn is_light_disabled(&self, index: usize) -> bool {
+        self.disabled_lights.contains(&index)
+    }
+
+    // Every light back on and the selection on the sun, for when the objects (and so the
+    // emissive lights) are replaced. Takes effect on the next update_lights
+    pub fn reset_light_switches(&mut self) {
+        self.selected_light = 0;
+        self.isolate_light = false;
+        self.disabled_lights.clear();
+    }
+
+    // Finds out whether `eye` is inside a cube of a fluid material (Material::as_fluid), so call
+    // it every frame with the camera's eye. Once under, the eye has to rise SUBMERSION_BAND
+    // above the surface to come out, and it has to sink as far below it to go under again
+    pub fn update_submersion(&mut self, eye: &Vec3) {
+        // How deep `eye` is in the cubes of `material`: negative outside, by how far
+        let depth_in = |material: MaterialId| {
+            self.world.scene.objects().iter()
+                .filter_map(|object| match object {
+                    Object::Cube(cube) if cube.material == material => Some(cube),
+                    _ => None,
+                })
+                .map(|cube| (0..3).map(|axis| (eye[axis] - cube.min[axis]).min(cube.max[axis] - eye[axis])).fold(f32::INFINITY, f32::min))
+                .fold(f32::NEG_INFINITY, f32::max)
+        };
+        if self.submerged.is_some_and(|material| depth_in(material) > -SUBMERSION_BAND) {
+            return;
+        }
+        self.submerged = self.world.scene.objects().iter()
+            .map(|object| object.material())
+            .find(|&material| self.materials.get(material).fluid && depth_in(material) > SUBMERSION_BAND);
+    }
+
+    // Media a camera ray starts in: the fluid the camera is under, if any
+    pub fn camera_media(&self) -> MediumStack {
+        match self.submerged {
+            Some(material) => {
+                let refractive_index = self.materials.get(material).refractive_index;
+                MediumStack::new().entering(Medium { material, refractive_index })
+            }
+            None => MediumStack::new(),
+        }
+    }
+
+    // Sorts `lights` into the light grid again, for when they are changed outside update_lights
+    pub fn rebuild_light_grid(&mut self) {
+        self.light_grid = LightGrid::new(&self.lights);
+    }
+}
+
+// Range of an emissive object's light, per unit of the diagonal of its box
+const EMISSIVE_LIGHT_RANGE: f32 = 10.0;
+
+// Lights for the emissive objects at `time`, which picks the frame of the ones that glow with an
+// animated texture (their light follows its brightness)
+fn generate_lights_from_emissive_objects(objects: &[Object], materials: &MaterialRegistry, merge: bool, time: f32) -> Vec<Light> {
+    let emissive: Vec<&Object> = objects.iter()
+        .filter(|object| materials.get(object.material()).is_emissive())
+        .collect();
+    let groups = if merge { touching_groups(&emissive) } else { (0..emissive.len()).map(|i| vec![i]).collect() };
+
+    groups.into_iter()
+        .map(|group| {
+            let material = materials.get(emissive[group[0]].material());
+            let emission = material.emission_color(time);
+            // La luz brilla tanto como la superficie: la fuerza de la emisión del material
+            let intensity = emission.intensity() * material.emission_strength;
+            let light = match group[..] {
+                [single] => {
+                    let object = emissive[single];
+                    let (min, max) = object.bounds();
+                    let radius = (max - min).magnitude() * EMISSIVE_LIGHT_RANGE;
+                    match object {
+                        // Los cubos pueden iluminar desde sus caras (ver RenderSettings::area_lights)
+                        Object::Cube(_) => Light::area(min, max, emission, intensity, radius),
+                        _ => Light::new((min + max) * 0.5, emission, intensity, radius),
+                    }
+                }
+                // Varios cubos pegados: una luz puntual en su centroide (pesado por volumen) con la
+                // intensidad de todos juntos y el alcance de la caja que los contiene
+                _ => {
+                    let (mut min, mut max) = emissive[group[0]].bounds();
+                    let (mut weighted, mut volume) = (Vec3::zeros(), 0.0);
+                    for &index in &group {
+                        let (cube_min, cube_max) = emissive[index].bounds();
+                        min = min.inf(&cube_min);
+                        max = max.sup(&cube_max);
+                        let size = cube_max - cube_min;
+                        let cube_volume = (size.x * size.y * size.z).max(1e-9);
+                        weighted += (cube_min + cube_max) * (0.5 * cube_volume);
+                        volume += cube_volume;
+                    }
+                    let radius = (max - min).magnitude() * EMISSIVE_LIGHT_RANGE;
+                    Light::new(weighted / volume, emission, intensity * group.len() as f32, radius)
+                }
+            };
+            light.with_flicker(material.flicker)
+        })
+        .collect()
+}
+
+// Groups of indices into `emissive` whose cubes share a material and touch or overlap, directly
+// or through other cubes of the group; every other object is a group of its own. Groups come in
+// the order of their first object
+fn touching_groups(emissive: &[&Object]) -> Vec<Vec<usize>> {
+    const TOUCH: f32 = 1e-4;
+    let mut parent: Vec<usize> = (0..emissive.len()).collect();
+
+    for (a, first) in emissive.iter().enumerate() {
+        let Object::Cube(first) = first else { continue };
+        for (b, second) in emissive.iter().enumerate().skip(a + 1) {
+            let Object::Cube(second) = second else { continue };
+            let touching = (0..3).all(|axis| first.min[axis] <= second.max[axis] + TOUCH && second.min[axis] <= first.max[axis] + TOUCH);
+            if touching && first.material == second.material {
+                let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                parent[ra.max(rb)] = ra.min(rb);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root = vec![usize::MAX; emissive.len()];
+    for i in 0..emissive.len() {
+        let r = root(&mut parent, i);
+        if group_of_root[r] == usize::MAX {
+            group_of_root[r] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of_root[r]].push(i);
+    }
+    groups
+}
+
+// Union-find: the first index of the group `i` is in so far, flattening the path on the way
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
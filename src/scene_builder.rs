@@ -0,0 +1,122 @@
+use nalgebra_glm::Vec3;
+use std::collections::BTreeMap;
+use crate::cube::Cube;
+use crate::material::MaterialId;
+use crate::object::Object;
+use crate::voxel_grid::BLOCK_SIZE;
+
+// Places blocks by integer grid position instead of hand-written coordinates.
+// Block (x, y, z) covers [x, x + 1) * BLOCK_SIZE on every axis. Ranges are
+// inclusive on both ends, and placing a block where one already exists replaces it.
+pub struct SceneBuilder {
+    blocks: BTreeMap<[i32; 3], MaterialId>,
+    objects: Vec<Object>,
+}
+
+fn ordered(a: i32, b: i32) -> std::ops::RangeInclusive<i32> {
+    a.min(b)..=a.max(b)
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        SceneBuilder::new()
+    }
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        SceneBuilder {
+            blocks: BTreeMap::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    pub fn place_block(&mut self, x: i32, y: i32, z: i32, material: MaterialId) -> &mut Self {
+        self.blocks.insert([x, y, z], material);
+        self
+    }
+
+    pub fn remove_block(&mut self, x: i32, y: i32, z: i32) -> &mut Self {
+        self.blocks.remove(&[x, y, z]);
+        self
+    }
+
+    // Solid box of blocks between two corners
+    pub fn fill(&mut self, from: [i32; 3], to: [i32; 3], material: MaterialId) -> &mut Self {
+        for x in ordered(from[0], to[0]) {
+            for y in ordered(from[1], to[1]) {
+                for z in ordered(from[2], to[2]) {
+                    self.place_block(x, y, z, material);
+                }
+            }
+        }
+        self
+    }
+
+    // Removes every block between two corners (doors, windows...)
+    pub fn clear(&mut self, from: [i32; 3], to: [i32; 3]) -> &mut Self {
+        for x in ordered(from[0], to[0]) {
+            for y in ordered(from[1], to[1]) {
+                for z in ordered(from[2], to[2]) {
+                    self.remove_block(x, y, z);
+                }
+            }
+        }
+        self
+    }
+
+    // Horizontal layer at height `y` between two (x, z) corners
+    pub fn floor(&mut self, y: i32, from: (i32, i32), to: (i32, i32), material: MaterialId) -> &mut Self {
+        self.fill([from.0, y, from.1], [to.0, y, to.1], material)
+    }
+
+    // Vertical wall `height` blocks tall starting at `y`, running from one (x, z)
+    // column to another; the two columns should share either x or z
+    pub fn wall(&mut self, from: (i32, i32), to: (i32, i32), y: i32, height: i32, material: MaterialId) -> &mut Self {
+        self.fill([from.0, y, from.1], [to.0, y + height - 1, to.1], material)
+    }
+
+    // Only the shell of the box between two corners
+    pub fn hollow_box(&mut self, from: [i32; 3], to: [i32; 3], material: MaterialId) -> &mut Self {
+        let low = [0, 1, 2].map(|axis| from[axis].min(to[axis]));
+        let high = [0, 1, 2].map(|axis| from[axis].max(to[axis]));
+        for x in low[0]..=high[0] {
+            for y in low[1]..=high[1] {
+                for z in low[2]..=high[2] {
+                    let on_shell = x == low[0] || x == high[0]
+                        || y == low[1] || y == high[1]
+                        || z == low[2] || z == high[2];
+                    if on_shell {
+                        self.place_block(x, y, z, material);
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    // Free-form cube for decorations that don't follow the block grid
+    pub fn cube(&mut self, min: Vec3, max: Vec3, material: MaterialId) -> &mut Self {
+        self.objects.push(Cube::new(min, max, material).into());
+        self
+    }
+
+    pub fn add(&mut self, object: impl Into<Object>) -> &mut Self {
+        self.objects.push(object.into());
+        self
+    }
+
+    // Blocks (in grid order) followed by the free-form objects, in insertion order
+    pub fn build(self) -> Vec<Object> {
+        let mut objects: Vec<Object> = self.blocks
+            .into_iter()
+            .map(|([x, y, z], material)| {
+                let min = Vec3::new(x as f32, y as f32, z as f32) * BLOCK_SIZE;
+                let max = min + Vec3::repeat(BLOCK_SIZE);
+                Cube::new(min, max, material).into()
+            })
+            .collect();
+        objects.extend(self.objects);
+        objects
+    }
+}
@@ -0,0 +1,436 @@
+use nalgebra_glm::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use crate::camera::Camera;
+use crate::color::{Color, ColorF};
+use crate::cube::Cube;
+use crate::light::{Attenuation, Flicker, Light, LightKind};
+use crate::material::{Material, MaterialId, MaterialRegistry, Sidedness, Waves};
+use crate::mesh::{Mesh, Triangle};
+use crate::object::Object;
+use crate::plane::Plane;
+use crate::sphere::Sphere;
+use crate::texture::TextureCache;
+
+// A scene as JSON: the objects, the materials they use (by name, with textures as file
+// names for a TextureCache instead of pixels), the hand-placed lights and the camera.
+// Scene::save writes it and `load` reads it back
+#[derive(Serialize, Deserialize)]
+struct SceneFile {
+    camera: CameraFile,
+    materials: Vec<MaterialFile>,
+    objects: Vec<ObjectFile>,
+    lights: Vec<LightFile>,
+}
+
+// What `load` gives back; the materials go straight into the caller's registry
+pub struct LoadedScene {
+    pub objects: Vec<Object>,
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+}
+
+type V3 = [f32; 3];
+
+fn v3(v: &Vec3) -> V3 {
+    [v.x, v.y, v.z]
+}
+
+fn vec3([x, y, z]: V3) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+// A field left out of a material takes the value the builder would give it
+fn default_emission_strength() -> f32 {
+    1.0
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CameraFile {
+    eye: V3,
+    center: V3,
+    up: V3,
+    aperture: f32,
+    focus_distance: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnimationFile {
+    strip: String,
+    frame_time: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FlickerFile {
+    amplitude: f32,
+    frequency: f32,
+    seed: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MaterialFile {
+    name: String,
+    color: [u8; 3],
+    shininess: f32,
+    diffuse: f32,
+    specular: f32,
+    reflectivity: f32,
+    roughness: f32,
+    transparency: f32,
+    refractive_index: f32,
+    dispersion: f32,
+    textures: Vec<String>,
+    emission: [u8; 3],
+    #[serde(default = "default_emission_strength")]
+    emission_strength: f32,
+    #[serde(default)]
+    emission_from_texture: bool,
+    uv_scale: f32,
+    face_textures: [usize; 6],
+    normal_map: Option<String>,
+    alpha_cutoff: f32,
+    animation: Option<AnimationFile>,
+    absorption: V3,
+    flicker: Option<FlickerFile>,
+    #[serde(default)]
+    shadow_catcher: bool,
+    #[serde(default)]
+    sidedness: SidednessFile,
+    waves: Option<WavesFile>,
+    #[serde(default)]
+    fluid: bool,
+    #[serde(default)]
+    refraction_roughness: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriangleFile {
+    vertices: [V3; 3],
+    normal: V3,
+    tex_coords: Option<[[f32; 2]; 3]>,
+    normals: Option<[V3; 3]>,
+    material: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum ObjectFile {
+    Cube { min: V3, max: V3, material: String },
+    Sphere { center: V3, radius: f32, material: String },
+    Plane { point: V3, normal: V3, material: String, tile_size: f32 },
+    Mesh { material: String, materials: Vec<String>, min: V3, max: V3, triangles: Vec<TriangleFile> },
+}
+
+#[derive(Serialize, Deserialize)]
+enum LightKindFile {
+    Point,
+    Directional { direction: V3 },
+    Spot { direction: V3, inner: f32, outer: f32 },
+    Area { min: V3, max: V3 },
+}
+
+#[derive(Serialize, Deserialize)]
+struct WavesFile {
+    amplitude: f32,
+    wavelength: f32,
+    speed: f32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+enum SidednessFile {
+    #[default]
+    Geometry,
+    DoubleSided,
+    FrontOnly,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+enum AttenuationFile {
+    #[default]
+    RadiusScaled,
+    None,
+    Linear,
+    InverseSquare { constant: f32, linear: f32, quadratic: f32 },
+    SmoothWindowed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LightFile {
+    position: V3,
+    color: [u8; 3],
+    intensity: f32,
+    radius: Option<f32>, // None for no range limit, which JSON can't write as a number
+    size: f32,
+    kind: LightKindFile,
+    flicker: Option<FlickerFile>,
+    #[serde(default)]
+    attenuation: AttenuationFile,
+}
+
+fn flicker_file(flicker: &Flicker) -> FlickerFile {
+    FlickerFile { amplitude: flicker.amplitude, frequency: flicker.frequency, seed: flicker.seed }
+}
+
+fn flicker(saved: FlickerFile) -> Flicker {
+    Flicker::new(saved.amplitude, saved.frequency, saved.seed)
+}
+
+fn color_array(color: &Color) -> [u8; 3] {
+    [color.r, color.g, color.b]
+}
+
+fn color([r, g, b]: [u8; 3]) -> Color {
+    Color::new(r, g, b)
+}
+
+// Writes the scene. Materials without a name in the registry are saved as "material_<n>",
+// after their position in it. Textures must have come from a TextureCache, since only
+// their file names are written
+pub fn save(path: &Path, objects: &[Object], materials: &MaterialRegistry, lights: &[Light], camera: &Camera) -> io::Result<()> {
+    let mut names: HashMap<MaterialId, String> = HashMap::new();
+    let mut material_files = Vec::new();
+    for (index, (id, material)) in materials.iter().enumerate() {
+        let name = materials.name(id).map_or_else(|| format!("material_{}", index), str::to_string);
+        material_files.push(material_file(&name, material)?);
+        names.insert(id, name);
+    }
+    let name = |id: &MaterialId| names[id].clone();
+
+    let objects = objects.iter()
+        .map(|object| match object {
+            Object::Cube(cube) => ObjectFile::Cube { min: v3(&cube.min), max: v3(&cube.max), material: name(&cube.material) },
+            Object::Sphere(sphere) => ObjectFile::Sphere { center: v3(&sphere.center), radius: sphere.radius, material: name(&sphere.material) },
+            Object::Plane(plane) => ObjectFile::Plane {
+                point: v3(&plane.point),
+                normal: v3(&plane.normal),
+                material: name(&plane.material),
+                tile_size: plane.tile_size,
+            },
+            Object::Mesh(mesh) => ObjectFile::Mesh {
+                material: name(&mesh.material),
+                materials: mesh.materials.iter().map(name).collect(),
+                min: v3(&mesh.min),
+                max: v3(&mesh.max),
+                triangles: mesh.triangles.iter()
+                    .map(|triangle| TriangleFile {
+                        vertices: [v3(&triangle.v0), v3(&triangle.v1), v3(&triangle.v2)],
+                        normal: v3(&triangle.normal),
+                        tex_coords: triangle.tex_coords.map(|uvs| uvs.map(|uv| [uv.x, uv.y])),
+                        normals: triangle.normals.map(|normals| normals.map(|n| v3(&n))),
+                        material: triangle.material,
+                    })
+                    .collect(),
+            },
+        })
+        .collect();
+
+    let lights = lights.iter()
+        .map(|light| LightFile {
+            position: v3(&light.position),
+            color: color_array(&light.color),
+            intensity: light.intensity,
+            radius: light.radius.is_finite().then_some(light.radius),
+            size: light.size,
+            kind: match light.kind {
+                LightKind::Point => LightKindFile::Point,
+                LightKind::Directional { direction } => LightKindFile::Directional { direction: v3(&direction) },
+                LightKind::Spot { direction, inner, outer } => LightKindFile::Spot { direction: v3(&direction), inner, outer },
+                LightKind::Area { min, max } => LightKindFile::Area { min: v3(&min), max: v3(&max) },
+            },
+            flicker: light.flicker.as_ref().map(flicker_file),
+            attenuation: match light.attenuation {
+                Attenuation::RadiusScaled => AttenuationFile::RadiusScaled,
+                Attenuation::None => AttenuationFile::None,
+                Attenuation::Linear => AttenuationFile::Linear,
+                Attenuation::InverseSquare { constant, linear, quadratic } => AttenuationFile::InverseSquare { constant, linear, quadratic },
+                Attenuation::SmoothWindowed => AttenuationFile::SmoothWindowed,
+            },
+        })
+        .collect();
+
+    let file = SceneFile {
+        camera: CameraFile {
+            eye: v3(&camera.eye),
+            center: v3(&camera.center),
+            up: v3(&camera.up),
+            aperture: camera.aperture,
+            focus_distance: camera.focus_distance,
+        },
+        materials: material_files,
+        objects,
+        lights,
+    };
+    fs::write(path, serde_json::to_string_pretty(&file)?)
+}
+
+fn material_file(name: &str, material: &Material) -> io::Result<MaterialFile> {
+    let unnamed = || invalid(format!("el material {} tiene una textura que no viene de un archivo", name));
+    let textures = material.textures.iter()
+        .map(|texture| texture.source().map(str::to_string).ok_or_else(unnamed))
+        .collect::<io::Result<_>>()?;
+    let normal_map = match &material.normal_map {
+        Some(texture) => Some(texture.source().ok_or_else(unnamed)?.to_string()),
+        None => None,
+    };
+    let animation = match &material.animation {
+        Some(animation) => Some(AnimationFile {
+            strip: animation.source().ok_or_else(unnamed)?.to_string(),
+            frame_time: animation.frame_time(),
+        }),
+        None => None,
+    };
+    Ok(MaterialFile {
+        name: name.to_string(),
+        color: color_array(&material.color),
+        shininess: material.shininess,
+        diffuse: material.diffuse,
+        specular: material.specular,
+        reflectivity: material.reflectivity,
+        roughness: material.roughness,
+        transparency: material.transparency,
+        refractive_index: material.refractive_index,
+        dispersion: material.dispersion,
+        textures,
+        emission: color_array(&material.emission),
+        emission_strength: material.emission_strength,
+        emission_from_texture: material.emission_from_texture,
+        uv_scale: material.uv_scale,
+        face_textures: material.face_textures,
+        normal_map,
+        alpha_cutoff: material.alpha_cutoff,
+        animation,
+        absorption: [material.absorption.r, material.absorption.g, material.absorption.b],
+        flicker: material.flicker.as_ref().map(flicker_file),
+        shadow_catcher: material.shadow_catcher,
+        sidedness: match material.sidedness {
+            Sidedness::Geometry => SidednessFile::Geometry,
+            Sidedness::DoubleSided => SidednessFile::DoubleSided,
+            Sidedness::FrontOnly => SidednessFile::FrontOnly,
+        },
+        waves: material.waves.map(|waves| WavesFile { amplitude: waves.amplitude, wavelength: waves.wavelength, speed: waves.speed }),
+        fluid: material.fluid,
+        refraction_roughness: material.refraction_roughness,
+    })
+}
+
+// Reads a scene written by `save`. Its materials are added to `materials` by name, so one
+// that is already registered keeps its id and takes the saved settings; textures come from
+// `textures` by file name
+pub fn load(path: &Path, materials: &mut MaterialRegistry, textures: &mut TextureCache) -> io::Result<LoadedScene> {
+    let file: SceneFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    let mut ids = HashMap::new();
+    for saved in file.materials {
+        // The coefficients go through the builder, which rejects the ones cast_ray can't blend
+        let built = Material::builder(color(saved.color))
+            .shininess(saved.shininess)
+            .diffuse(saved.diffuse)
+            .specular(saved.specular)
+            .reflectivity(saved.reflectivity)
+            .roughness(saved.roughness)
+            .transparency(saved.transparency)
+            .refraction_roughness(saved.refraction_roughness)
+            .refractive_index(saved.refractive_index)
+            .dispersion(saved.dispersion)
+            .try_build()
+            .map_err(|message| invalid(format!("material {}: {}", saved.name, message)))?;
+        let material = Material {
+            textures: saved.textures.iter().map(|name| textures.get(name)).collect(),
+            emission: color(saved.emission),
+            emission_strength: saved.emission_strength,
+            emission_from_texture: saved.emission_from_texture,
+            uv_scale: saved.uv_scale,
+            face_textures: saved.face_textures,
+            normal_map: saved.normal_map.as_deref().map(|name| textures.get(name)),
+            alpha_cutoff: saved.alpha_cutoff,
+            animation: saved.animation.map(|animation| Arc::new(textures.strip(&animation.strip, animation.frame_time))),
+            absorption: ColorF::new(saved.absorption[0], saved.absorption[1], saved.absorption[2]),
+            flicker: saved.flicker.map(flicker),
+            shadow_catcher: saved.shadow_catcher,
+            sidedness: match saved.sidedness {
+                SidednessFile::Geometry => Sidedness::Geometry,
+                SidednessFile::DoubleSided => Sidedness::DoubleSided,
+                SidednessFile::FrontOnly => Sidedness::FrontOnly,
+            },
+            waves: saved.waves.map(|waves| Waves::new(waves.amplitude, waves.wavelength, waves.speed)),
+            fluid: saved.fluid,
+            ..built
+        };
+        ids.insert(saved.name.clone(), materials.add(&saved.name, material));
+    }
+    let id = |name: &String| ids.get(name).copied().ok_or_else(|| invalid(format!("material desconocido: {}", name)));
+
+    let mut objects = Vec::with_capacity(file.objects.len());
+    for object in file.objects {
+        objects.push(match object {
+            ObjectFile::Cube { min, max, material } => Cube::new(vec3(min), vec3(max), id(&material)?).into(),
+            ObjectFile::Sphere { center, radius, material } => Sphere::new(vec3(center), radius, id(&material)?).into(),
+            // Built field by field: Plane::new would normalize the normal again
+            ObjectFile::Plane { point, normal, material, tile_size } => {
+                Plane { point: vec3(point), normal: vec3(normal), material: id(&material)?, tile_size }.into()
+            }
+            ObjectFile::Mesh { material, materials, min, max, triangles } => {
+                let materials = materials.iter().map(id).collect::<io::Result<Vec<_>>>()?;
+                let triangles = triangles.into_iter()
+                    .map(|triangle| match triangle.material {
+                        // Indexes the mesh's own list, which ray_intersect trusts to be in range
+                        Some(index) if index >= materials.len() => Err(invalid(format!(
+                            "material de triángulo fuera de rango: {} (la malla tiene {})", index, materials.len(),
+                        ))),
+                        material => Ok(Triangle {
+                            v0: vec3(triangle.vertices[0]),
+                            v1: vec3(triangle.vertices[1]),
+                            v2: vec3(triangle.vertices[2]),
+                            normal: vec3(triangle.normal),
+                            tex_coords: triangle.tex_coords.map(|uvs| uvs.map(|[u, v]| Vec2::new(u, v))),
+                            normals: triangle.normals.map(|normals| normals.map(vec3)),
+                            material,
+                        }),
+                    })
+                    .collect::<io::Result<_>>()?;
+                Mesh { material: id(&material)?, materials, min: vec3(min), max: vec3(max), triangles }.into()
+            }
+        });
+    }
+
+    if file.lights.len() < 2 {
+        return Err(invalid("faltan el sol y la luna, que tienen que ser las dos primeras luces".to_string()));
+    }
+    let lights = file.lights.into_iter()
+        .map(|light| Light {
+            position: vec3(light.position),
+            color: color(light.color),
+            intensity: light.intensity,
+            radius: light.radius.unwrap_or(f32::INFINITY),
+            size: light.size,
+            kind: match light.kind {
+                LightKindFile::Point => LightKind::Point,
+                LightKindFile::Directional { direction } => LightKind::Directional { direction: vec3(direction) },
+                LightKindFile::Spot { direction, inner, outer } => LightKind::Spot { direction: vec3(direction), inner, outer },
+                LightKindFile::Area { min, max } => LightKind::Area { min: vec3(min), max: vec3(max) },
+            },
+            flicker: light.flicker.map(flicker),
+            attenuation: match light.attenuation {
+                AttenuationFile::RadiusScaled => Attenuation::RadiusScaled,
+                AttenuationFile::None => Attenuation::None,
+                AttenuationFile::Linear => Attenuation::Linear,
+                AttenuationFile::InverseSquare { constant, linear, quadratic } => Attenuation::InverseSquare { constant, linear, quadratic },
+                AttenuationFile::SmoothWindowed => Attenuation::SmoothWindowed,
+            },
+            enabled: true,
+        })
+        .collect();
+
+    let mut camera = Camera::new(vec3(file.camera.eye), vec3(file.camera.center), vec3(file.camera.up));
+    camera.aperture = file.camera.aperture;
+    camera.focus_distance = file.camera.focus_distance;
+
+    Ok(LoadedScene { objects, lights, camera })
+}
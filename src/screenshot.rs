@@ -0,0 +1,130 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::framebuffer::Framebuffer;
+
+// UTC date and time as "2024-05-17_21-04-09", for file names that sort in the order they
+// were taken
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm), in 400-year eras
+    // that start on March 1st so the leap day falls at the end of each year
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year, month, day, of_day / 3600, of_day / 60 % 60, of_day % 60
+    )
+}
+
+// Creates `dir` if needed and an empty "<stamp>.png" in it, or "<stamp>_2.png" and so on when
+// that name is taken. The file is created here, not just checked, so two screenshots in the
+// same second never get the same name even before either has been written
+pub fn reserve_path(dir: &Path, stamp: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    for attempt in 1.. {
+        let name = if attempt == 1 { format!("{}.png", stamp) } else { format!("{}_{}.png", stamp, attempt) };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("attempt counts up without end")
+}
+
+// Writes `framebuffer` as a PNG on its own thread, so encoding a big image doesn't hold up
+// the window. The outcome goes to the console; a failed write is reported, never a panic
+pub fn save_in_background(framebuffer: Framebuffer, path: PathBuf) -> JoinHandle<()> {
+    thread::spawn(move || match framebuffer.save(&path) {
+        Ok(()) => println!("Captura guardada en {}", path.display()),
+        Err(e) => {
+            eprintln!("No se pudo guardar la captura {}: {}", path.display(), e);
+            // Don't leave the reserved file behind empty
+            let _ = fs::remove_file(&path);
+        }
+    })
+}
+
+// Frames a Recorder holds for its writer thread at most; while the queue is full new frames
+// are dropped, which lowers the recorded frame rate instead of the memory growing
+pub const RECORD_QUEUE: usize = 8;
+
+// Writes a sequence of frames as frame_00001.png, frame_00002.png... in one directory, on a
+// thread of its own. The numbers have no gaps: a dropped frame doesn't use one up
+pub struct Recorder {
+    dir: PathBuf,
+    sender: SyncSender<(u64, Framebuffer)>,
+    writer: JoinHandle<u64>,
+    queued: u64,
+    dropped: u64,
+}
+
+// How a recording went, from Recorder::stop
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingSummary {
+    pub dir: PathBuf,
+    pub written: u64, // Frames on disk
+    pub dropped: u64, // Frames skipped because the writer was behind
+    pub failed: u64,  // Frames queued but not written (disk full, no permission...)
+}
+
+impl Recorder {
+    // Creates `dir` (and its parents) and starts the writer thread
+    pub fn start(dir: &Path) -> io::Result<Recorder> {
+        fs::create_dir_all(dir)?;
+        let (sender, receiver) = mpsc::sync_channel::<(u64, Framebuffer)>(RECORD_QUEUE);
+        let writer_dir = dir.to_path_buf();
+        let writer = thread::spawn(move || {
+            let mut written = 0;
+            for (number, frame) in receiver {
+                let path = writer_dir.join(format!("frame_{:05}.png", number));
+                match frame.save(&path) {
+                    Ok(()) => written += 1,
+                    Err(e) => eprintln!("No se pudo guardar el cuadro {}: {}", path.display(), e),
+                }
+            }
+            written
+        });
+        Ok(Recorder { dir: dir.to_path_buf(), sender, writer, queued: 0, dropped: 0 })
+    }
+
+    // Queues a copy of the frame; false if it was dropped because the queue is full
+    pub fn record(&mut self, framebuffer: &Framebuffer) -> bool {
+        match self.sender.try_send((self.queued + 1, framebuffer.clone())) {
+            Ok(()) => {
+                self.queued += 1;
+                true
+            }
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                self.dropped += 1;
+                false
+            }
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Waits for the queued frames to be written and says how it went
+    pub fn stop(self) -> RecordingSummary {
+        drop(self.sender);
+        let written = self.writer.join().unwrap_or(0);
+        RecordingSummary { dir: self.dir, written, dropped: self.dropped, failed: self.queued - written }
+    }
+}
@@ -0,0 +1,208 @@
+use nalgebra_glm::Vec3;
+use crate::color::ColorF;
+use crate::day_night::DayNightCycle;
+
+// Procedural sky for rays that leave the scene. Colors are linear and depend on how high
+// the ray looks and how high the sun is: blue at noon, orange around the sun on the
+// horizon at dawn and dusk, deep navy at night.
+#[derive(Clone, Copy, Debug)]
+pub struct Sky {
+    pub sun_size: f32,      // Angular radius of the sun disc, in radians
+    pub halo_size: f32,     // Angle over which the glow around the sun fades, in radians
+    pub halo_strength: f32, // Brightness of the glow right next to the disc
+    pub moon_size: f32,     // Angular radius of the moon disc, in radians
+    pub star_density: f32,  // Share of the sky cells that hold a star
+    pub clouds: Clouds,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Sky {
+            sun_size: 0.03,
+            halo_size: 0.25,
+            halo_strength: 1.5,
+            moon_size: 0.04,
+            star_density: 0.004,
+            clouds: Clouds::default(),
+        }
+    }
+}
+
+// A flat layer of fractal-noise clouds drifting overhead
+#[derive(Clone, Copy, Debug)]
+pub struct Clouds {
+    pub coverage: f32, // Share of the sky covered, 0 (clear) to 1 (overcast)
+    pub speed: f32,    // Drift in noise cells per second
+    pub softness: f32, // Width of the fuzzy cloud edges; small values give hard edges
+    pub height: f32,   // Height of the layer; rays reach it at a distance of height / elevation
+    pub scale: f32,    // Noise cells per unit along the layer
+}
+
+impl Default for Clouds {
+    fn default() -> Self {
+        Clouds { coverage: 0.45, speed: 0.05, softness: 0.2, height: 1.0, scale: 1.5 }
+    }
+}
+
+const ZENITH_DAY: ColorF = ColorF::new(0.10, 0.28, 0.75);
+const ZENITH_NIGHT: ColorF = ColorF::new(0.004, 0.006, 0.03);
+const ZENITH_TWILIGHT: ColorF = ColorF::new(0.12, 0.10, 0.30);
+const HORIZON_DAY: ColorF = ColorF::new(0.55, 0.70, 0.90);
+const HORIZON_NIGHT: ColorF = ColorF::new(0.01, 0.015, 0.05);
+const HORIZON_SUNSET: ColorF = ColorF::new(1.0, 0.40, 0.10);
+const GROUND: ColorF = ColorF::new(0.08, 0.07, 0.06);
+const MOON: ColorF = ColorF::new(0.55, 0.62, 0.80);
+// Cells per unit of direction for the star pattern; one cell is about 0.2 degrees
+const STAR_GRID: f32 = 300.0;
+
+// Integer hash (PCG-style output permutation) so every star cell always gets the same value
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h = h.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let word = ((h >> ((h >> 28) + 4)) ^ h).wrapping_mul(277_803_737);
+    (word >> 22) ^ word
+}
+
+fn unit(h: u32) -> f32 {
+    (h >> 8) as f32 / 16_777_216.0
+}
+
+// Smoothly interpolated random values on an integer grid, in [0, 1]
+fn value_noise(x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let (tx, ty) = (tx * tx * (3.0 - 2.0 * tx), ty * ty * (3.0 - 2.0 * ty));
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let corner = |dx: i32, dy: i32| unit(hash(ix + dx, iy + dy, 17));
+
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    top + (bottom - top) * ty
+}
+
+// Four octaves of value noise, each twice as fine and half as strong, in [0, 1]
+fn fractal_noise(x: f32, y: f32) -> f32 {
+    let (mut sum, mut amplitude, mut frequency, mut total) = (0.0, 0.5, 1.0, 0.0);
+    for _ in 0..4 {
+        sum += value_noise(x * frequency, y * frequency) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl Sky {
+    // Share of the sky hidden by clouds along `direction` (any length), `time` seconds
+    // into the run: 0 for clear sky (or rays below the horizon), 1 for thick cloud
+    pub fn cloud_cover(&self, direction: &Vec3, time: f32) -> f32 {
+        let direction = direction.normalize();
+        if direction.y <= 0.0 || self.clouds.coverage <= 0.0 {
+            return 0.0;
+        }
+
+        // Where the ray crosses the cloud layer, scrolled by the wind
+        let clouds = &self.clouds;
+        let distance = clouds.height / direction.y;
+        let drift = time * clouds.speed;
+        let x = direction.x * distance * clouds.scale + drift;
+        let z = direction.z * distance * clouds.scale + drift * 0.6;
+
+        // Stretched so `coverage` roughly matches the share of sky covered (the octaves
+        // average out and bunch the raw values around 0.5)
+        let noise = ((fractal_noise(x, z) - 0.5) * 2.5 + 0.5).clamp(0.0, 1.0);
+        // The soft edge fits between 0 and 1: coverage 1 leaves no gaps, 0 no clouds
+        let softness = clouds.softness.max(1e-3);
+        let threshold = 1.0 - clouds.coverage * (1.0 + softness);
+        let density = smoothstep(threshold, threshold + softness, noise);
+        // Far away the layer is a blur of tiny cells; it thins out toward the horizon instead
+        density * smoothstep(0.0, 0.15, direction.y)
+    }
+
+    // Radiance seen along `direction` (any length) at the cycle's time of day, with the
+    // clouds where they are `time` seconds into the run
+    pub fn sample(&self, direction: &Vec3, cycle: &DayNightCycle, time: f32) -> ColorF {
+        let direction = direction.normalize();
+        let to_sun = -cycle.sun_direction();
+        let height = cycle.sun_height();
+
+        // 0 at night, 1 in full day; twilight peaks while the sun crosses the horizon
+        let day = smoothstep(-0.15, 0.35, height);
+        let twilight = (1.0 - height.abs() / 0.35).max(0.0);
+
+        // The sunset glow is strongest on the side of the sky where the sun is
+        let flat = |v: Vec3| Vec3::new(v.x, 0.0, v.z).try_normalize(1e-6).unwrap_or_else(Vec3::zeros);
+        let toward_sun = flat(direction).dot(&flat(to_sun)) * 0.5 + 0.5;
+
+        let zenith = ColorF::lerp(&ZENITH_NIGHT, &ZENITH_DAY, day);
+        let zenith = ColorF::lerp(&zenith, &ZENITH_TWILIGHT, twilight * 0.6);
+        let horizon = ColorF::lerp(&HORIZON_NIGHT, &HORIZON_DAY, day);
+        let horizon = ColorF::lerp(&horizon, &HORIZON_SUNSET, twilight * (0.3 + 0.7 * toward_sun));
+
+        let elevation = direction.y;
+        let mut color = if elevation >= 0.0 {
+            ColorF::lerp(&horizon, &zenith, elevation.sqrt())
+        } else {
+            // Below the horizon: the haze fades into a dark ground lit like the sky
+            ColorF::lerp(&horizon, &(GROUND * (0.05 + day)), (-elevation * 4.0).min(1.0))
+        };
+
+        // Sun disc and halo, white at noon and orange when low; gone once the sun sets, and
+        // never drawn below the horizon line, where the ground would hide it
+        let sun_visible = smoothstep(0.0, 0.05, height) * smoothstep(0.0, 0.02, elevation);
+        if sun_visible > 0.0 {
+            let sun_color = ColorF::lerp(&ColorF::new(1.0, 0.45, 0.15), &ColorF::new(1.0, 0.95, 0.85), smoothstep(0.0, 0.5, height));
+            let angle = direction.dot(&to_sun).clamp(-1.0, 1.0).acos();
+            let disc = if angle < self.sun_size { 20.0 } else { 0.0 };
+            let halo = self.halo_strength * (-(angle - self.sun_size).max(0.0) / self.halo_size.max(1e-4)).exp();
+            color = color + sun_color * ((disc + halo) * sun_visible);
+        }
+
+        // Moon and stars come out as the daylight fades
+        let night = cycle.night_amount();
+        if night > 0.0 {
+            let to_moon = -cycle.moon_direction();
+            let angle = direction.dot(&to_moon).clamp(-1.0, 1.0).acos();
+            let disc = if angle < self.moon_size { 3.0 } else { 0.0 };
+            let glow = 0.15 * (-(angle - self.moon_size).max(0.0) / 0.1).exp();
+            color = color + MOON * ((disc + glow) * night);
+
+            if elevation > 0.0 && angle >= self.moon_size {
+                color = color + self.star(&direction) * (night * smoothstep(0.0, 0.15, elevation));
+            }
+        }
+
+        // Clouds go over everything: white at noon, warm at dusk, dark grey at night
+        let cover = self.cloud_cover(&direction, time);
+        if cover > 0.0 {
+            let cloud_color = ColorF::lerp(&ColorF::new(0.02, 0.02, 0.03), &ColorF::new(0.85, 0.86, 0.9), day);
+            let cloud_color = ColorF::lerp(&cloud_color, &(HORIZON_SUNSET * 0.8), twilight * 0.5 * toward_sun);
+            color = ColorF::lerp(&color, &cloud_color, cover);
+        }
+
+        color
+    }
+
+    // Star in the grid cell that `direction` falls in, if that cell has one. The pattern
+    // only depends on the direction, so the stars stay put from frame to frame
+    fn star(&self, direction: &Vec3) -> ColorF {
+        let cell = direction * STAR_GRID;
+        let (x, y, z) = (cell.x.floor() as i32, cell.y.floor() as i32, cell.z.floor() as i32);
+        let h = hash(x, y, z);
+        if unit(h) >= self.star_density {
+            return ColorF::black();
+        }
+        // A second hash picks brightness and a slight warm or cool tint
+        let h2 = hash(z, x, y);
+        let brightness = 0.5 + 2.5 * unit(h2).powi(3);
+        let tint = unit(h2 >> 3);
+        ColorF::new(0.85 + 0.15 * tint, 0.9, 1.0 - 0.15 * tint) * brightness
+    }
+}
@@ -0,0 +1,70 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use crate::material::MaterialId;
+use crate::ray_intersect::{RayIntersect, Intersect, CubeFace};
+
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+    pub material: MaterialId,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f32, material: MaterialId) -> Self {
+        Sphere {
+            center,
+            radius,
+            material,
+        }
+    }
+
+    // Spherical (longitude/latitude) mapping of a point on the surface
+    fn uv(&self, normal: &Vec3) -> (f32, f32) {
+        let u = 0.5 + normal.z.atan2(normal.x) / (2.0 * PI);
+        let v = 0.5 - normal.y.clamp(-1.0, 1.0).asin() / PI;
+        (u, v)
+    }
+}
+
+impl RayIntersect for Sphere {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        let oc = ray_origin - self.center;
+
+        let a = ray_direction.dot(ray_direction);
+        let b = 2.0 * oc.dot(ray_direction);
+        let c = oc.dot(&oc) - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrt_discriminant = discriminant.sqrt();
+        let t0 = (-b - sqrt_discriminant) / (2.0 * a);
+        let t1 = (-b + sqrt_discriminant) / (2.0 * a);
+
+        // Nearest hit in front of the origin; t1 is used when the ray starts inside
+        let distance = if t0 > 0.0 {
+            t0
+        } else if t1 > 0.0 {
+            t1
+        } else {
+            return None;
+        };
+
+        let inside = t0 <= 0.0;
+        let point = ray_origin + ray_direction * distance;
+        let outward = (point - self.center).normalize();
+        let normal = if inside { -outward } else { outward };
+
+        Some(Intersect {
+            point,
+            normal,
+            distance,
+            material: self.material,
+            face: CubeFace::from_normal(&outward),
+            uv: Some(self.uv(&outward)),
+            inside,
+        })
+    }
+}
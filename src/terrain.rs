@@ -0,0 +1,159 @@
+use nalgebra_glm::Vec3;
+use std::path::Path;
+use crate::cube::Cube;
+use crate::material::MaterialId;
+use crate::scene_builder::SceneBuilder;
+use crate::voxel_grid::BLOCK_SIZE;
+
+// Parameters of the generated hillside, all in blocks
+pub struct TerrainSettings {
+    pub seed: u32,
+    pub amplitude: f32,   // Maximum hill height
+    pub frequency: f32,   // Noise features per block; lower gives wider hills
+    pub flat_radius: f32, // Area around the origin kept flat for the house
+    pub falloff: f32,     // Distance over which hills grow out of the flat area
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        TerrainSettings {
+            seed: 1337,
+            amplitude: 4.0,
+            frequency: 0.08,
+            flat_radius: 14.0,
+            falloff: 6.0,
+        }
+    }
+}
+
+// Deterministic pseudo-random value in [0, 1) for a lattice point
+fn hash(seed: u32, x: i32, z: i32) -> f32 {
+    let mut h = seed
+        .wrapping_add((x as u32).wrapping_mul(0x27d4_eb2d))
+        .wrapping_add((z as u32).wrapping_mul(0x1656_67b1));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    (h & 0x00ff_ffff) as f32 / 16_777_216.0
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+// 2D value noise: random values on the integer lattice, smoothly interpolated
+pub fn value_noise(seed: u32, x: f32, z: f32) -> f32 {
+    let x0 = x.floor() as i32;
+    let z0 = z.floor() as i32;
+    let tx = smoothstep(x - x0 as f32);
+    let tz = smoothstep(z - z0 as f32);
+
+    let a = hash(seed, x0, z0);
+    let b = hash(seed, x0 + 1, z0);
+    let c = hash(seed, x0, z0 + 1);
+    let d = hash(seed, x0 + 1, z0 + 1);
+
+    let top = a + (b - a) * tx;
+    let bottom = c + (d - c) * tx;
+    top + (bottom - top) * tz
+}
+
+// Three octaves of value noise, normalized back to [0, 1)
+fn fractal_noise(seed: u32, x: f32, z: f32) -> f32 {
+    let mut total = 0.0;
+    let mut weight = 1.0;
+    let mut scale = 1.0;
+    let mut weights = 0.0;
+    for octave in 0..3 {
+        total += value_noise(seed.wrapping_add(octave), x * scale, z * scale) * weight;
+        weights += weight;
+        weight *= 0.5;
+        scale *= 2.0;
+    }
+    total / weights
+}
+
+// Number of blocks stacked on the column (x, z); 0 means bare ground
+pub fn height_at(settings: &TerrainSettings, x: i32, z: i32) -> i32 {
+    let distance = ((x * x + z * z) as f32).sqrt();
+    let blend = ((distance - settings.flat_radius) / settings.falloff).clamp(0.0, 1.0);
+    let noise = fractal_noise(settings.seed, x as f32 * settings.frequency, z as f32 * settings.frequency);
+    (noise * settings.amplitude * smoothstep(blend)).round() as i32
+}
+
+// Adds the visible blocks of the columns between two (x, z) corners: the top of
+// every column plus the side blocks that stick out above a neighbour. The hills
+// go on forever, so neighbouring areas line up without gaps or duplicated blocks.
+pub fn generate_area(builder: &mut SceneBuilder, settings: &TerrainSettings, from: (i32, i32), to: (i32, i32), material: MaterialId) {
+    let height = |x, z| height_at(settings, x, z);
+    for x in from.0..=to.0 {
+        for z in from.1..=to.1 {
+            let h = height(x, z);
+            if h <= 0 {
+                continue;
+            }
+
+            let lowest_neighbour = height(x - 1, z)
+                .min(height(x + 1, z))
+                .min(height(x, z - 1))
+                .min(height(x, z + 1));
+
+            for y in lowest_neighbour.clamp(0, h - 1)..h {
+                builder.place_block(x, y, z, material);
+            }
+        }
+    }
+}
+
+// How a heightmap image becomes blocks
+pub struct HeightmapSettings {
+    pub max_height: i32, // Blocks in a column where the image is white
+    pub soil_depth: i32, // Blocks of grass at the top of a column; the rest are stone
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        HeightmapSettings { max_height: 16, soil_depth: 1 }
+    }
+}
+
+// Blocky terrain from a grayscale image, one column of blocks per pixel, centered on the
+// origin with pixel rows running along z. The brightness sets the column height (black is
+// no column at all); 8 and 16-bit images give the same heights for the same shades, and
+// color images are read by their luminance. As in generate_area, only the blocks that can
+// be seen are made: the top of every column and the side blocks that stick out above a
+// neighbour or the edge of the image
+pub fn from_heightmap(path: &Path, settings: &HeightmapSettings, grass: MaterialId, stone: MaterialId) -> Result<Vec<Cube>, image::ImageError> {
+    let image = image::open(path)?.to_luma16();
+    let (width, depth) = (image.width() as i32, image.height() as i32);
+    let heights: Vec<i32> = image.pixels()
+        .map(|pixel| (pixel.0[0] as f32 / u16::MAX as f32 * settings.max_height as f32).round() as i32)
+        .collect();
+    let height = |x: i32, z: i32| {
+        if x < 0 || z < 0 || x >= width || z >= depth { 0 } else { heights[(z * width + x) as usize] }
+    };
+
+    let mut cubes = Vec::new();
+    for z in 0..depth {
+        for x in 0..width {
+            let h = height(x, z);
+            if h <= 0 {
+                continue;
+            }
+
+            let lowest_neighbour = height(x - 1, z)
+                .min(height(x + 1, z))
+                .min(height(x, z - 1))
+                .min(height(x, z + 1));
+
+            for y in lowest_neighbour.clamp(0, h - 1)..h {
+                let material = if y >= h - settings.soil_depth { grass } else { stone };
+                let min = Vec3::new((x - width / 2) as f32, y as f32, (z - depth / 2) as f32) * BLOCK_SIZE;
+                cubes.push(Cube::new(min, min + Vec3::repeat(BLOCK_SIZE), material));
+            }
+        }
+    }
+    Ok(cubes)
+}
@@ -1,33 +1,327 @@
-use crate::color::Color;
+use crate::assets;
+use crate::color::{Color, ColorF};
+use image::{GenericImageView, Rgba, RgbaImage};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// How texture coordinates outside [0, 1] map back onto the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat,       // Tile: 1.25 samples like 0.25
+    Clamp,        // Stretch the border texels: 1.25 samples like 1.0
+    MirrorRepeat, // Tile, flipping every other copy: 1.25 samples like 0.75
+}
+
+impl WrapMode {
+    // Maps any coordinate (negative ones too) into [0, 1]
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            WrapMode::Repeat => t.rem_euclid(1.0),
+            WrapMode::Clamp => t.clamp(0.0, 1.0),
+            WrapMode::MirrorRepeat => {
+                let t = t.rem_euclid(2.0);
+                if t > 1.0 { 2.0 - t } else { t }
+            }
+        }
+    }
+}
+
+// One image of the mip chain
+#[derive(Debug, Clone)]
+struct MipLevel {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,         // sRGB
+    linear_pixels: Vec<ColorF>, // Same texels decoded to linear light
+}
+
+impl MipLevel {
+    fn new(width: u32, height: u32, linear_pixels: Vec<ColorF>) -> Self {
+        let pixels = linear_pixels.iter().map(ColorF::to_srgb).collect();
+        MipLevel { width, height, pixels, linear_pixels }
+    }
+
+    // Half the size (rounding down, at least 1x1); each texel is the average of the
+    // 2x2 block under it, taken in linear light so the mips don't darken
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let texel = |x: u32, y: u32| {
+            let (x, y) = (x.min(self.width - 1), y.min(self.height - 1));
+            self.linear_pixels[(y * self.width + x) as usize]
+        };
+
+        let mut linear_pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x * 2, y * 2);
+                let sum = texel(sx, sy) + texel(sx + 1, sy) + texel(sx, sy + 1) + texel(sx + 1, sy + 1);
+                linear_pixels.push(sum * 0.25);
+            }
+        }
+        MipLevel::new(width, height, linear_pixels)
+    }
+
+    // u and v already wrapped into [0, 1]
+    fn index(&self, u: f32, v: f32) -> usize {
+        let x = ((u * self.width as f32) as u32).min(self.width - 1);
+        let y = ((v * self.height as f32) as u32).min(self.height - 1);
+        (y * self.width + x) as usize
+    }
+}
 
 #[derive(Debug, Clone)] // Añade Debug y Clone aquí
 pub struct Texture {
     pub width: u32,
     pub height: u32,
-    pub pixels: Vec<Color>,
+    levels: Vec<MipLevel>, // Mip chain: the image as loaded first, then halving down to 1x1
+    alpha: Option<Vec<f32>>, // Full-size alpha in [0, 1]; None when every texel is opaque
+    pub wrap: WrapMode,    // Applied by every sample method; Repeat by default
+    source: Option<String>, // File name it came from through a TextureCache, so a saved scene can name it
 }
 
 impl Texture {
     pub fn load(path: &str) -> Result<Self, image::ImageError> {
-        let img = image::open(path)?.to_rgba8();
+        Ok(Self::from_image(&image::open(path)?.to_rgba8()))
+    }
+
+    // Decodes an image file already in memory (embedded in the binary...); the format is
+    // guessed from its contents
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, image::ImageError> {
+        Ok(Self::from_image(&image::load_from_memory(bytes)?.to_rgba8()))
+    }
+
+    fn from_image(img: &RgbaImage) -> Self {
         let (width, height) = img.dimensions();
-        let pixels = img.pixels()
+        let pixels: Vec<Color> = img.pixels()
             .map(|p| Color::new(p[0], p[1], p[2]))
             .collect();
+        // Opaque images (every JPG, most PNGs) keep no alpha at all
+        let alpha = img.pixels()
+            .any(|p| p[3] < 255)
+            .then(|| img.pixels().map(|p| p[3] as f32 / 255.0).collect());
+        let linear_pixels = pixels.iter().map(Color::to_linear).collect();
 
-        Ok(Self { width, height, pixels })
+        // Level 0 keeps the file's exact sRGB values instead of a round trip through linear
+        let mut levels = vec![MipLevel { width, height, pixels, linear_pixels }];
+        while let Some(last) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+            let next = last.downsample();
+            levels.push(next);
+        }
+
+        Self { width, height, levels, alpha, wrap: WrapMode::Repeat, source: None }
+    }
+
+    // 2x2 magenta and black checker that stands in for a missing image: the surface still
+    // renders and is easy to spot
+    pub fn placeholder() -> Self {
+        let (magenta, black) = (Rgba([255, 0, 255, 255]), Rgba([0, 0, 0, 255]));
+        Self::from_image(&RgbaImage::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 { magenta } else { black }))
+    }
+
+    // Like `load`, but a missing or unreadable file gives the placeholder and a warning
+    pub fn load_or_placeholder(path: &str) -> Self {
+        Self::load(path).unwrap_or_else(|e| {
+            eprintln!("No se pudo cargar la textura {}: {}; se usa la de reemplazo", path, e);
+            Self::placeholder()
+        })
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    // Name the TextureCache found it under; None for textures built or loaded directly
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    // Mean of every texel in linear light: the 1x1 end of the mip chain
+    pub fn average(&self) -> ColorF {
+        self.levels[self.levels.len() - 1].linear_pixels[0]
+    }
+
+    // Number of images in the mip chain, the full-size one included
+    pub fn mip_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn level(&self, lod: f32) -> &MipLevel {
+        &self.levels[(lod.max(0.0) as usize).min(self.levels.len() - 1)]
     }
 
     pub fn sample(&self, u: f32, v: f32) -> Color {
-        let x = ((u * self.width as f32) as u32).min(self.width - 1);
-        let y = ((v * self.height as f32) as u32).min(self.height - 1);
-        self.pixels[(y * self.width + x) as usize]
+        self.sample_lod(u, v, 0.0)
+    }
+
+    // Same texel as `sample`, as a float color for shading
+    pub fn sample_f(&self, u: f32, v: f32) -> ColorF {
+        ColorF::from(self.sample(u, v))
+    }
+
+    pub fn sample_linear(&self, u: f32, v: f32) -> ColorF {
+        self.sample_linear_lod(u, v, 0.0)
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
+    // Opacity of the full-size texel at (u, v): 0 transparent, 1 opaque
+    pub fn sample_alpha(&self, u: f32, v: f32) -> f32 {
+        match &self.alpha {
+            Some(alpha) => alpha[self.levels[0].index(self.wrap.apply(u), self.wrap.apply(v))],
+            None => 1.0,
+        }
+    }
+
+    // Texel from the mip level nearest to `lod` (0 = full size, each step halves it)
+    pub fn sample_lod(&self, u: f32, v: f32, lod: f32) -> Color {
+        let level = self.level(lod.round());
+        level.pixels[level.index(self.wrap.apply(u), self.wrap.apply(v))]
+    }
+
+    // Linear texel blended between the two mip levels around `lod`, so the switch
+    // from one level to the next doesn't show as a line on the ground
+    pub fn sample_linear_lod(&self, u: f32, v: f32, lod: f32) -> ColorF {
+        let (u, v) = (self.wrap.apply(u), self.wrap.apply(v));
+        let lod = lod.max(0.0);
+        let (near, far) = (self.level(lod.floor()), self.level(lod.ceil()));
+        let a = near.linear_pixels[near.index(u, v)];
+        let b = far.linear_pixels[far.index(u, v)];
+        ColorF::lerp(&a, &b, lod.fract())
+    }
+}
+
+// A looping sequence of same-sized frames (end portal, lava). Picking the frame for a
+// time is just an index, so sampling costs the same as a still texture
+#[derive(Debug, Clone)]
+pub struct AnimatedTexture {
+    frames: Vec<Texture>,
+    frame_time: f32, // Seconds each frame stays on screen
+    source: Option<String>, // Strip file name when it came from a TextureCache
+}
+
+impl AnimatedTexture {
+    pub fn new(frames: Vec<Texture>, frame_time: f32) -> Self {
+        assert!(!frames.is_empty(), "an animated texture needs at least one frame");
+        assert!(frame_time > 0.0, "frame time must be positive (got {})", frame_time);
+        AnimatedTexture { frames, frame_time, source: None }
+    }
+
+    // Square frames stacked top to bottom in one image, the way Minecraft stores them
+    pub fn load_strip(path: &str, frame_time: f32) -> Result<Self, image::ImageError> {
+        Ok(Self::from_strip(&image::open(path)?.to_rgba8(), frame_time))
+    }
+
+    // Same strip from an image file already in memory
+    pub fn strip_from_bytes(bytes: &[u8], frame_time: f32) -> Result<Self, image::ImageError> {
+        Ok(Self::from_strip(&image::load_from_memory(bytes)?.to_rgba8(), frame_time))
+    }
+
+    fn from_strip(img: &RgbaImage, frame_time: f32) -> Self {
+        let size = img.width();
+        let frames = (0..(img.height() / size).max(1))
+            .map(|frame| Texture::from_image(&img.view(0, frame * size, size, size.min(img.height())).to_image()))
+            .collect();
+        Self::new(frames, frame_time)
+    }
+
+    // Like `load_strip`, but a missing or unreadable file gives a single placeholder frame
+    // and a warning
+    pub fn load_strip_or_placeholder(path: &str, frame_time: f32) -> Self {
+        Self::load_strip(path, frame_time).unwrap_or_else(|e| {
+            eprintln!("No se pudo cargar la animación {}: {}; se usa la textura de reemplazo", path, e);
+            Self::new(vec![Texture::placeholder()], frame_time)
+        })
+    }
+
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    // Name of the strip the TextureCache read it from; None otherwise
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    // Frame on screen `time` seconds after the start; the sequence loops
+    pub fn frame(&self, time: f32) -> &Texture {
+        &self.frames[self.frame_index(time)]
+    }
+
+    // Position of that frame in the sequence, to tell when the picture changes
+    pub fn frame_index(&self, time: f32) -> usize {
+        ((time / self.frame_time).floor() as i64).rem_euclid(self.frames.len() as i64) as usize
+    }
+}
+
+// Textures loaded so far, by file name: asking for the same file again shares the first
+// decode instead of reading it twice. A file is looked up in the asset directory first (so a
+// texture pack can replace any of them), then among the copies embedded in the binary; when
+// neither has it, the placeholder is cached in its place
+pub struct TextureCache {
+    dir: Option<PathBuf>, // None uses only the embedded copies
+    textures: HashMap<String, Arc<Texture>>,
+}
+
+impl TextureCache {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        TextureCache { dir, textures: HashMap::new() }
+    }
+
+    pub fn get(&mut self, name: &str) -> Arc<Texture> {
+        if let Some(texture) = self.textures.get(name) {
+            return texture.clone();
+        }
+        let mut texture = match self.read(name).map(|bytes| Texture::from_bytes(&bytes)) {
+            Some(Ok(texture)) => texture,
+            Some(Err(e)) => {
+                eprintln!("No se pudo cargar la textura {}: {}; se usa la de reemplazo", name, e);
+                Texture::placeholder()
+            }
+            None => {
+                eprintln!("No se encontró la textura {}; se usa la de reemplazo", name);
+                Texture::placeholder()
+            }
+        };
+        texture.source = Some(name.to_string());
+        self.textures.entry(name.to_string()).or_insert(Arc::new(texture)).clone()
+    }
+
+    // Animated strip looked up the same way; strips aren't shared, so they aren't cached
+    pub fn strip(&self, name: &str, frame_time: f32) -> AnimatedTexture {
+        let animation = match self.read(name).map(|bytes| AnimatedTexture::strip_from_bytes(&bytes, frame_time)) {
+            Some(Ok(animation)) => animation,
+            Some(Err(e)) => {
+                eprintln!("No se pudo cargar la animación {}: {}; se usa la textura de reemplazo", name, e);
+                AnimatedTexture::new(vec![Texture::placeholder()], frame_time)
+            }
+            None => {
+                eprintln!("No se encontró la animación {}; se usa la textura de reemplazo", name);
+                AnimatedTexture::new(vec![Texture::placeholder()], frame_time)
+            }
+        };
+        AnimatedTexture { source: Some(name.to_string()), ..animation }
+    }
+
+    // Contents of the file on disk, or of its embedded copy
+    fn read(&self, name: &str) -> Option<Cow<'static, [u8]>> {
+        if let Some(bytes) = self.dir.as_ref().and_then(|dir| fs::read(dir.join(name)).ok()) {
+            return Some(Cow::Owned(bytes));
+        }
+        assets::embedded(name).map(Cow::Borrowed)
     }
 }
 
 // --- Skybox simple face-based ---
 pub struct Skybox {
-    // Orden: +X, -X, +Y, -Y, +Z, -Z
+    // Orden: +X, -X, +Y, -Y, +Z, -Z. Las caras usan WrapMode::Clamp para que los bordes
+    // no tomen texels del lado opuesto de la imagen (costuras de un píxel)
     px: Texture,
     nx: Texture,
     py: Texture,
@@ -38,20 +332,25 @@ pub struct Skybox {
 
 impl Skybox {
     pub fn load(px: &str, nx: &str, py: &str, ny: &str, pz: &str, nz: &str) -> Result<Self, String> {
+        let face = |path: &str, name: &str| {
+            Texture::load(path)
+                .map(|texture| texture.with_wrap(WrapMode::Clamp))
+                .map_err(|e| format!("skybox {}: {}", name, e))
+        };
         Ok(Skybox {
-            px: Texture::load(px).map_err(|e| format!("skybox px: {}", e))?,
-            nx: Texture::load(nx).map_err(|e| format!("skybox nx: {}", e))?,
-            py: Texture::load(py).map_err(|e| format!("skybox py: {}", e))?,
-            ny: Texture::load(ny).map_err(|e| format!("skybox ny: {}", e))?,
-            pz: Texture::load(pz).map_err(|e| format!("skybox pz: {}", e))?,
-            nz: Texture::load(nz).map_err(|e| format!("skybox nz: {}", e))?,
+            px: face(px, "px")?,
+            nx: face(nx, "nx")?,
+            py: face(py, "py")?,
+            ny: face(ny, "ny")?,
+            pz: face(pz, "pz")?,
+            nz: face(nz, "nz")?,
         })
     }
 
-    // direction: Vec3 en espacio de cámara (normalizado).
-    // devuelve Color
+    // direction: Vec3 en espacio del mundo (no hace falta normalizarlo).
+    // Cada cara se ve como desde el centro del cubo, con +Y arriba: u crece hacia la derecha
+    // del que mira y v hacia abajo. Arriba y abajo continúan la cara -Z (la que mira la cámara)
     pub fn sample(&self, dir: &nalgebra_glm::Vec3) -> crate::color::Color {
-        use nalgebra_glm as glm;
         let x = dir.x;
         let y = dir.y;
         let z = dir.z;
@@ -61,49 +360,37 @@ impl Skybox {
         let ay = y.abs();
         let az = z.abs();
 
-        // u,v deben estar en [0,1] para la textura seleccionada
+        // u,v en [0,1] para la textura seleccionada
         let (tex, u, v) = if ax >= ay && ax >= az {
             // +/- X faces
             if x > 0.0 {
-                // +X face (px)
-                let u = ( -z / ax + 1.0) * 0.5;
-                let v = ( -y / ax + 1.0) * 0.5;
-                (&self.px, u, v)
+                // +X face (px): a la derecha queda +Z
+                (&self.px, (z / ax + 1.0) * 0.5, (-y / ax + 1.0) * 0.5)
             } else {
-                // -X face (nx)
-                let u = ( z / ax + 1.0) * 0.5;
-                let v = ( -y / ax + 1.0) * 0.5;
-                (&self.nx, u, v)
+                // -X face (nx): a la derecha queda -Z
+                (&self.nx, (-z / ax + 1.0) * 0.5, (-y / ax + 1.0) * 0.5)
             }
         } else if ay >= ax && ay >= az {
             // +/- Y faces
             if y > 0.0 {
-                // +Y (py)
-                let u = ( x / ay + 1.0) * 0.5;
-                let v = ( z / ay + 1.0) * 0.5;
-                (&self.py, u, v)
+                // +Y (py): su borde de abajo toca la parte de arriba de -Z
+                (&self.py, (x / ay + 1.0) * 0.5, (-z / ay + 1.0) * 0.5)
             } else {
-                // -Y (ny)
-                let u = ( x / ay + 1.0) * 0.5;
-                let v = ( -z / ay + 1.0) * 0.5;
-                (&self.ny, u, v)
+                // -Y (ny): su borde de arriba toca la parte de abajo de -Z
+                (&self.ny, (x / ay + 1.0) * 0.5, (z / ay + 1.0) * 0.5)
             }
         } else {
             // +/- Z faces
             if z > 0.0 {
-                // +Z (pz)
-                let u = ( x / az + 1.0) * 0.5;
-                let v = ( -y / az + 1.0) * 0.5;
-                (&self.pz, u, v)
+                // +Z (pz): a la derecha queda -X
+                (&self.pz, (-x / az + 1.0) * 0.5, (-y / az + 1.0) * 0.5)
             } else {
-                // -Z (nz)
-                let u = ( -x / az + 1.0) * 0.5;
-                let v = ( -y / az + 1.0) * 0.5;
-                (&self.nz, u, v)
+                // -Z (nz): a la derecha queda +X
+                (&self.nz, (x / az + 1.0) * 0.5, (-y / az + 1.0) * 0.5)
             }
         };
 
-        // sample(u,v) devuelve Color (tu Texture ya tiene sample)
-        tex.sample(u % 1.0, v % 1.0)
+        // sample(u,v) devuelve Color; el modo Clamp de la cara se encarga de los bordes
+        tex.sample(u, v)
     }
 }
@@ -0,0 +1,209 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::material::{Material, MaterialId, MaterialRegistry};
+
+// Emission strength of the voxels listed as emissive, the same as the glowstone block's
+pub const VOX_EMISSION_STRENGTH: f32 = 10.0;
+
+// One filled voxel: position in the model's grid and palette index (1 to 255; 0 is empty)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Voxel {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub color_index: u8,
+}
+
+// The first model of a MagicaVoxel .vox file. MagicaVoxel is Z-up; to_cubes turns it Y-up
+pub struct VoxModel {
+    pub size: (u32, u32, u32),
+    pub voxels: Vec<Voxel>,
+    pub palette: [Color; 256], // By color index; entry 0 is never drawn
+}
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxError::Io(e) => write!(f, "error de lectura: {}", e),
+            VoxError::Format(message) => write!(f, "archivo .vox inválido: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<io::Error> for VoxError {
+    fn from(e: io::Error) -> Self {
+        VoxError::Io(e)
+    }
+}
+
+// Little-endian reader over the file contents that fails instead of panicking past the end
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], VoxError> {
+        let end = self.position.checked_add(count).filter(|end| *end <= self.bytes.len());
+        let Some(end) = end else {
+            return Err(VoxError::Format(format!("termina antes de tiempo (byte {})", self.position)));
+        };
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+impl VoxModel {
+    pub fn load(path: &Path) -> Result<Self, VoxError> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    // Reads the SIZE and XYZI chunks of the first model and the RGBA palette; every other
+    // chunk (scene graph, layers, materials) is skipped. Files without a palette are drawn
+    // in gray
+    pub fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(4)? != b"VOX " {
+            return Err(VoxError::Format("falta la firma 'VOX '".to_string()));
+        }
+        reader.u32()?; // Version
+
+        let mut size = None;
+        let mut voxels = None;
+        let mut palette = [Color::new(128, 128, 128); 256];
+
+        while reader.position < bytes.len() {
+            let id = reader.take(4)?;
+            let content_size = reader.u32()? as usize;
+            let children_size = reader.u32()? as usize;
+            // MAIN only wraps the other chunks, which follow as its children
+            if id == b"MAIN" {
+                reader.take(content_size)?;
+                continue;
+            }
+            let mut content = Reader { bytes: reader.take(content_size)?, position: 0 };
+            reader.take(children_size)?;
+
+            match id {
+                b"SIZE" if size.is_none() => size = Some((content.u32()?, content.u32()?, content.u32()?)),
+                b"XYZI" if voxels.is_none() => {
+                    let count = content.u32()? as usize;
+                    let mut list = Vec::with_capacity(count.min(content_size / 4));
+                    for _ in 0..count {
+                        let v = content.take(4)?;
+                        if v[3] != 0 {
+                            list.push(Voxel { x: v[0], y: v[1], z: v[2], color_index: v[3] });
+                        }
+                    }
+                    voxels = Some(list);
+                }
+                // Color index i (1 to 255) is entry i - 1 of the chunk
+                b"RGBA" => {
+                    for entry in palette.iter_mut().skip(1) {
+                        let c = content.take(4)?;
+                        *entry = Color::new(c[0], c[1], c[2]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match (size, voxels) {
+            (Some(size), Some(voxels)) => Ok(VoxModel { size, voxels, palette }),
+            _ => Err(VoxError::Format("no tiene un modelo (faltan SIZE o XYZI)".to_string())),
+        }
+    }
+
+    // One cube per voxel, `voxel_size` units on a side, with the model standing on y = 0:
+    // MagicaVoxel's z becomes y (up) and its y becomes -z. Each color used gets a solid
+    // material, emissive for the indices in `emissive`. With `merge`, runs of voxels of the
+    // same color along x become a single longer cube, which keeps big models manageable.
+    // Also returns the offset that centers the model on the origin in x and z
+    pub fn to_cubes(&self, voxel_size: f32, emissive: &[u8], merge: bool, materials: &mut MaterialRegistry) -> (Vec<Cube>, Vec3) {
+        let (sx, sy, sz) = (self.size.0 as usize, self.size.1 as usize, self.size.2 as usize);
+        let mut grid = vec![0u8; sx * sy * sz];
+        let cell = |x: usize, y: usize, z: usize| (z * sy + y) * sx + x;
+        for v in &self.voxels {
+            let (x, y, z) = (v.x as usize, v.y as usize, v.z as usize);
+            if x < sx && y < sy && z < sz {
+                grid[cell(x, y, z)] = v.color_index;
+            }
+        }
+
+        let mut material_ids: HashMap<u8, MaterialId> = HashMap::new();
+        let mut cubes = Vec::new();
+        for z in 0..sz {
+            for y in 0..sy {
+                let mut x = 0;
+                while x < sx {
+                    let index = grid[cell(x, y, z)];
+                    if index == 0 {
+                        x += 1;
+                        continue;
+                    }
+                    let mut end = x + 1;
+                    while merge && end < sx && grid[cell(end, y, z)] == index {
+                        end += 1;
+                    }
+
+                    let material = *material_ids.entry(index).or_insert_with(|| {
+                        let color = self.palette[index as usize];
+                        let mut material = Material::builder(color).build();
+                        if emissive.contains(&index) {
+                            material = material.with_emission(color).with_emission_strength(VOX_EMISSION_STRENGTH);
+                        }
+                        materials.insert(material)
+                    });
+                    let min = Vec3::new(x as f32, z as f32, -(y as f32 + 1.0)) * voxel_size;
+                    let max = Vec3::new(end as f32, z as f32 + 1.0, -(y as f32)) * voxel_size;
+                    cubes.push(Cube::new(min, max, material));
+                    x = end;
+                }
+            }
+        }
+
+        let offset = Vec3::new(-(sx as f32), 0.0, sy as f32) * (0.5 * voxel_size);
+        (cubes, offset)
+    }
+}
+
+// Palette indices listed in a text file, one or more per line; `#` starts a comment.
+// A missing file means no emissive colors
+pub fn load_emissive_indices(path: &Path) -> Result<Vec<u8>, VoxError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut indices = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        for token in line.split_whitespace() {
+            match token.parse::<u8>() {
+                Ok(index) if index >= 1 => indices.push(index),
+                _ => return Err(VoxError::Format(format!("{} línea {}: índice de paleta inválido '{}'", path.display(), number + 1, token))),
+            }
+        }
+    }
+    Ok(indices)
+}
@@ -0,0 +1,167 @@
+use nalgebra_glm::Vec3;
+use crate::object::Object;
+use crate::ray_intersect::{RayIntersect, Intersect, nearest};
+
+// Size of a regular block in this world
+pub const BLOCK_SIZE: f32 = 0.5;
+
+const SNAP_EPSILON: f32 = 1e-4;
+
+// Regular 3D grid of BLOCK_SIZE cells. Every cell holds at most one cube that
+// fills it exactly, and rays walk it cell by cell (Amanatides & Woo) instead of
+// testing every block.
+pub struct VoxelGrid {
+    origin: Vec3,          // Min corner of cell (0, 0, 0)
+    dims: [usize; 3],
+    cells: Vec<Option<usize>>, // Index into the object list
+}
+
+fn snap(value: f32) -> Option<i64> {
+    let cells = value / BLOCK_SIZE;
+    let rounded = cells.round();
+    if (cells - rounded).abs() < SNAP_EPSILON { Some(rounded as i64) } else { None }
+}
+
+impl VoxelGrid {
+    // Grid cell of an axis-aligned cube, if it is exactly one grid-aligned block
+    pub fn cell_of(min: &Vec3, max: &Vec3) -> Option<[i64; 3]> {
+        let size = max - min;
+        if size.iter().any(|s| (s - BLOCK_SIZE).abs() > SNAP_EPSILON) {
+            return None;
+        }
+        Some([snap(min.x)?, snap(min.y)?, snap(min.z)?])
+    }
+
+    // Builds a grid from (object index, cell) pairs. Returns the grid and the
+    // indices that could not be placed because their cell was already taken.
+    pub fn new(blocks: &[(usize, [i64; 3])]) -> (Self, Vec<usize>) {
+        let mut low = [i64::MAX; 3];
+        let mut high = [i64::MIN; 3];
+        for (_, cell) in blocks {
+            for axis in 0..3 {
+                low[axis] = low[axis].min(cell[axis]);
+                high[axis] = high[axis].max(cell[axis]);
+            }
+        }
+
+        let dims = [0, 1, 2].map(|axis| (high[axis] - low[axis] + 1).max(0) as usize);
+        let mut grid = VoxelGrid {
+            origin: Vec3::new(low[0] as f32, low[1] as f32, low[2] as f32) * BLOCK_SIZE,
+            dims,
+            cells: vec![None; dims[0] * dims[1] * dims[2]],
+        };
+
+        let mut rejected = Vec::new();
+        for (index, cell) in blocks {
+            let slot = grid.slot([0, 1, 2].map(|axis| (cell[axis] - low[axis]) as usize));
+            if grid.cells[slot].is_none() {
+                grid.cells[slot] = Some(*index);
+            } else {
+                rejected.push(*index);
+            }
+        }
+
+        (grid, rejected)
+    }
+
+    fn slot(&self, cell: [usize; 3]) -> usize {
+        (cell[2] * self.dims[1] + cell[1]) * self.dims[0] + cell[0]
+    }
+
+    // Walks the cells crossed by the ray and calls `visit` for every occupied one,
+    // in order, until it returns true or the ray leaves the grid / passes `max_distance`
+    fn walk(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32, mut visit: impl FnMut(usize, f32) -> bool) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let grid_max = self.origin + Vec3::new(self.dims[0] as f32, self.dims[1] as f32, self.dims[2] as f32) * BLOCK_SIZE;
+
+        // Distances where the ray enters and leaves the grid bounds
+        let mut t_enter = 0.0_f32;
+        let mut t_exit = max_distance;
+        for axis in 0..3 {
+            let inv = 1.0 / ray_direction[axis];
+            let t0 = (self.origin[axis] - ray_origin[axis]) * inv;
+            let t1 = (grid_max[axis] - ray_origin[axis]) * inv;
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+        if t_enter > t_exit {
+            return;
+        }
+
+        let start = ray_origin + ray_direction * t_enter;
+        let mut cell = [0i64; 3];
+        let mut step = [0i64; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+
+        for axis in 0..3 {
+            let local = (start[axis] - self.origin[axis]) / BLOCK_SIZE;
+            cell[axis] = (local.floor() as i64).clamp(0, self.dims[axis] as i64 - 1);
+
+            if ray_direction[axis] > 0.0 {
+                step[axis] = 1;
+                let boundary = self.origin[axis] + (cell[axis] + 1) as f32 * BLOCK_SIZE;
+                t_max[axis] = (boundary - ray_origin[axis]) / ray_direction[axis];
+                t_delta[axis] = BLOCK_SIZE / ray_direction[axis];
+            } else if ray_direction[axis] < 0.0 {
+                step[axis] = -1;
+                let boundary = self.origin[axis] + cell[axis] as f32 * BLOCK_SIZE;
+                t_max[axis] = (boundary - ray_origin[axis]) / ray_direction[axis];
+                t_delta[axis] = -BLOCK_SIZE / ray_direction[axis];
+            }
+        }
+
+        loop {
+            let slot = self.slot(cell.map(|c| c as usize));
+            let t_cell_exit = t_max[0].min(t_max[1]).min(t_max[2]);
+            if let Some(index) = self.cells[slot]
+                && visit(index, t_cell_exit)
+            {
+                return;
+            }
+
+            let axis = if t_max[0] <= t_max[1] && t_max[0] <= t_max[2] {
+                0
+            } else if t_max[1] <= t_max[2] {
+                1
+            } else {
+                2
+            };
+
+            if t_max[axis] > t_exit {
+                return;
+            }
+
+            cell[axis] += step[axis];
+            if cell[axis] < 0 || cell[axis] >= self.dims[axis] as i64 {
+                return;
+            }
+            t_max[axis] += t_delta[axis];
+        }
+    }
+
+    // Closest block hit closer than `max_distance`
+    pub fn traverse(&self, objects: &[Object], ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> Option<Intersect> {
+        let mut closest = None;
+        self.walk(ray_origin, ray_direction, max_distance, |index, t_cell_exit| {
+            let hit = objects[index].ray_intersect(ray_origin, ray_direction).filter(|i| i.distance < max_distance);
+            closest = nearest(closest.take(), hit);
+            // Cells are visited front to back, so a hit inside the current cell can't be beaten
+            closest.as_ref().is_some_and(|i| i.distance <= t_cell_exit + SNAP_EPSILON)
+        });
+        closest
+    }
+
+    // Any block closer than `max_distance`; stops at the first occluder
+    pub fn any_hit(&self, objects: &[Object], ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> bool {
+        let mut hit = false;
+        self.walk(ray_origin, ray_direction, max_distance, |index, _| {
+            hit = objects[index].ray_intersect(ray_origin, ray_direction).is_some_and(|i| i.distance < max_distance);
+            hit
+        });
+        hit
+    }
+}
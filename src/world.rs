@@ -0,0 +1,59 @@
+use nalgebra_glm::Vec3;
+use crate::bvh::Bvh;
+use crate::chunk::ChunkManager;
+use crate::object::Object;
+use crate::ray_intersect::{Intersect, RayIntersect, nearest};
+
+// What a ray from the camera hit first
+pub enum Pick {
+    Object(usize, Intersect), // Index into the scene's objects
+    Terrain(Intersect),       // A block of a terrain chunk, which has no index of its own
+}
+
+// Everything a ray can hit: the hand-built scene, built once, plus the terrain
+// chunks currently loaded around the camera
+pub struct World {
+    pub scene: Bvh,
+    pub terrain: ChunkManager,
+}
+
+impl World {
+    pub fn new(scene: Bvh, terrain: ChunkManager) -> Self {
+        World { scene, terrain }
+    }
+
+    pub fn traverse(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        let scene = self.scene.traverse(ray_origin, ray_direction);
+        let limit = scene.as_ref().map_or(f32::MAX, |i| i.distance);
+        nearest(scene, self.terrain.traverse(ray_origin, ray_direction, limit))
+    }
+
+    // Changes the objects of the hand-built scene (placing and breaking blocks) and
+    // rebuilds its hierarchy around the new list
+    pub fn edit_objects(&mut self, edit: impl FnOnce(&mut Vec<Object>)) {
+        let mut objects = std::mem::replace(&mut self.scene, Bvh::new(Vec::new())).into_objects();
+        edit(&mut objects);
+        self.scene = Bvh::new(objects);
+    }
+
+    pub fn pick(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Pick> {
+        let object = self.scene.pick(ray_origin, ray_direction);
+        let limit = object.as_ref().map_or(f32::MAX, |(_, hit)| hit.distance);
+        match self.terrain.traverse(ray_origin, ray_direction, limit) {
+            Some(hit) => Some(Pick::Terrain(hit)),
+            None => object.map(|(index, hit)| Pick::Object(index, hit)),
+        }
+    }
+
+    pub fn occluded(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> bool {
+        self.scene.occluded(ray_origin, ray_direction, max_distance)
+            || self.terrain.occluded(ray_origin, ray_direction, max_distance)
+    }
+}
+
+// Lets code that only needs the nearest hit (camera collision) take the whole world
+impl RayIntersect for World {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<Intersect> {
+        self.traverse(ray_origin, ray_direction)
+    }
+}
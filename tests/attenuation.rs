@@ -0,0 +1,54 @@
+use nalgebra_glm::Vec3;
+use raytracer::color::Color;
+use raytracer::light::{Attenuation, Light};
+
+fn lamp(attenuation: Attenuation) -> Light {
+    Light::new(Vec3::zeros(), Color::new(255, 255, 255), 1.0, 2.0).with_attenuation(attenuation)
+}
+
+#[test]
+fn the_default_model_keeps_the_old_falloff() {
+    let light = Light::new(Vec3::zeros(), Color::new(255, 255, 255), 1.0, 2.0);
+    assert_eq!(light.attenuation, Attenuation::RadiusScaled);
+    for d in [0.0, 0.5, 1.0, 2.0] {
+        assert!((light.attenuation(d) - 1.0 / (1.0 + d * d / 4.0)).abs() < 1e-6);
+    }
+    assert!(light.reaches(2.0) && !light.reaches(2.01));
+}
+
+#[test]
+fn each_model_follows_its_formula() {
+    let none = lamp(Attenuation::None);
+    assert_eq!(none.attenuation(1.9), 1.0);
+
+    let linear = lamp(Attenuation::Linear);
+    assert!((linear.attenuation(0.5) - 0.75).abs() < 1e-6);
+    assert_eq!(linear.attenuation(2.0), 0.0);
+
+    let inverse_square = lamp(Attenuation::InverseSquare { constant: 1.0, linear: 0.5, quadratic: 2.0 });
+    assert_eq!(inverse_square.attenuation(0.0), 1.0);
+    assert!((inverse_square.attenuation(1.0) - 1.0 / 3.5).abs() < 1e-6);
+}
+
+#[test]
+fn smooth_window_reaches_exactly_zero_at_the_radius() {
+    let light = lamp(Attenuation::SmoothWindowed);
+    assert_eq!(light.attenuation(0.0), 1.0);
+    let mut previous = 1.0;
+    for step in 1..=100 {
+        let value = light.attenuation(step as f32 * 0.02);
+        assert!(value <= previous, "it only fades out");
+        previous = value;
+    }
+    assert_eq!(light.attenuation(2.0), 0.0);
+    // So the shadow early-out skips it from the edge of its range on, not just past it
+    assert!(light.reaches(1.99) && !light.reaches(2.0));
+    assert!(lamp(Attenuation::Linear).reaches(1.99) && !lamp(Attenuation::Linear).reaches(2.0));
+}
+
+#[test]
+fn directional_lights_never_fade() {
+    let sun = Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::new(255, 255, 255), 1.0).with_attenuation(Attenuation::Linear);
+    assert_eq!(sun.attenuation(1000.0), 1.0);
+    assert!(sun.reaches(f32::INFINITY));
+}
@@ -0,0 +1,44 @@
+use nalgebra_glm::Vec3;
+use raytracer::bookmarks::Bookmarks;
+use raytracer::camera::Camera;
+use raytracer::camera_path::CameraPath;
+
+fn camera(x: f32) -> Camera {
+    Camera::new(Vec3::new(x, 1.0, 5.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+}
+
+#[test]
+fn empty_and_out_of_range_slots_have_nothing() {
+    let mut bookmarks = Bookmarks::new();
+    bookmarks.store(0, &camera(1.0));
+    bookmarks.store(10, &camera(1.0));
+    assert_eq!(bookmarks, Bookmarks::new());
+    for slot in 0..=10 {
+        assert!(bookmarks.get(slot).is_none());
+    }
+}
+
+#[test]
+fn restoring_goes_from_the_current_view_to_the_bookmark() {
+    let mut bookmarks = Bookmarks::new();
+    bookmarks.store(3, &camera(2.0));
+
+    let mut current = camera(-2.0);
+    let transition = CameraPath::transition(&current, bookmarks.get(3).unwrap(), 0.5);
+    transition.apply(&mut current, 0.0);
+    assert_eq!(current.eye.x, -2.0);
+    transition.apply(&mut current, 0.5);
+    assert_eq!(current.eye.x, 2.0);
+}
+
+#[test]
+fn saved_bookmarks_load_back_the_same() {
+    let file = std::env::temp_dir().join(format!("bookmarks_test_{}.json", std::process::id()));
+    let mut bookmarks = Bookmarks::new();
+    bookmarks.store(1, &camera(1.0));
+    bookmarks.store(9, &camera(9.0));
+    bookmarks.save(&file).unwrap();
+    let loaded = Bookmarks::load(&file);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(loaded.unwrap(), bookmarks);
+}
@@ -0,0 +1,76 @@
+use nalgebra_glm::Vec3;
+use raytracer::bvh::Bvh;
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::material::{Material, MaterialRegistry};
+use raytracer::object::Object;
+use raytracer::plane::Plane;
+use raytracer::ray_intersect::RayIntersect;
+use raytracer::sampling::Sampler;
+use raytracer::sphere::Sphere;
+use raytracer::voxel_grid::BLOCK_SIZE;
+
+// Random point in the box from -extent to extent on each axis
+fn point_in(sampler: &mut Sampler, extent: f32) -> Vec3 {
+    Vec3::new(sampler.next_f32(), sampler.next_f32(), sampler.next_f32()).map(|c| (c * 2.0 - 1.0) * extent)
+}
+
+// A seeded jumble of every kind of object the hierarchy sorts: grid-aligned blocks (some in
+// the same cell), loose boxes, spheres and an infinite floor
+fn random_objects(sampler: &mut Sampler) -> Vec<Object> {
+    let mut materials = MaterialRegistry::new();
+    let material = materials.add("gray", Material::builder(Color::new(128, 128, 128)).build());
+    let mut objects: Vec<Object> = Vec::new();
+    for _ in 0..60 {
+        let cell = point_in(sampler, 6.0).map(|c| c.round() * BLOCK_SIZE);
+        objects.push(Cube::new(cell, cell + Vec3::repeat(BLOCK_SIZE), material).into());
+    }
+    for _ in 0..40 {
+        let min = point_in(sampler, 4.0);
+        let size = Vec3::new(sampler.next_f32(), sampler.next_f32(), sampler.next_f32()) * 1.5 + Vec3::repeat(0.05);
+        objects.push(Cube::new(min, min + size, material).into());
+    }
+    for _ in 0..15 {
+        objects.push(Sphere::new(point_in(sampler, 4.0), 0.1 + sampler.next_f32() * 0.6, material).into());
+    }
+    objects.push(Plane::new(Vec3::new(0.0, -4.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material).into());
+    objects
+}
+
+fn random_direction(sampler: &mut Sampler) -> Vec3 {
+    loop {
+        let direction = point_in(sampler, 1.0);
+        if direction.magnitude() > 0.1 {
+            return direction.normalize();
+        }
+    }
+}
+
+#[test]
+fn traverse_and_occluded_match_testing_every_object() {
+    let mut sampler = Sampler::new(2024);
+    let bvh = Bvh::new(random_objects(&mut sampler));
+
+    for _ in 0..2000 {
+        let origin = point_in(&mut sampler, 5.0);
+        let direction = random_direction(&mut sampler);
+        let hits: Vec<f32> = bvh.objects().iter()
+            .filter_map(|object| object.ray_intersect(&origin, &direction))
+            .map(|hit| hit.distance)
+            .collect();
+
+        let closest = hits.iter().copied().reduce(f32::min);
+        let found = bvh.traverse(&origin, &direction).map(|hit| hit.distance);
+        match (found, closest) {
+            (Some(found), Some(closest)) => assert!((found - closest).abs() < 1e-4, "{} vs {} from {:?} toward {:?}", found, closest, origin, direction),
+            _ => assert_eq!(found, closest, "from {:?} toward {:?}", origin, direction),
+        }
+
+        let max_distance = sampler.next_f32() * 8.0;
+        assert_eq!(
+            bvh.occluded(&origin, &direction, max_distance),
+            hits.iter().any(|&distance| distance < max_distance),
+            "within {} from {:?} toward {:?}", max_distance, origin, direction,
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use nalgebra_glm::Vec3;
+use raytracer::camera::{Camera, CameraMove, MIN_DISTANCE};
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::material::{Material, MaterialRegistry};
+use std::f32::consts::PI;
+
+fn distance(camera: &Camera) -> f32 {
+    (camera.center - camera.eye).magnitude()
+}
+
+#[test]
+fn dolly_after_orbiting_moves_toward_the_center() {
+    let mut camera = Camera::new(Vec3::new(0.0, 0.0, 6.5), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    camera.orbit(PI / 2.0, 0.0);
+    let direction = camera.forward();
+
+    // Every step gets closer until the clamp stops it, looking the same way throughout
+    let mut last = distance(&camera);
+    while last > MIN_DISTANCE {
+        camera.dolly(0.5);
+        let current = distance(&camera);
+        assert!(current < last, "dolly went from {} to {} away from the center", last, current);
+        assert!((camera.forward() - direction).magnitude() < 1e-4, "dolly turned the camera");
+        last = current;
+    }
+    assert!((last - MIN_DISTANCE).abs() < 1e-4, "stopped at {} instead of {}", last, MIN_DISTANCE);
+
+    camera.dolly(0.5);
+    assert!((distance(&camera) - MIN_DISTANCE).abs() < 1e-4, "dolly went past the closest distance");
+}
+
+// Camera at z = 5 looking at a unit cube around the origin, whose near face is at z = 0.5
+fn facing_a_cube() -> (Camera, Cube) {
+    let mut materials = MaterialRegistry::new();
+    let stone = materials.add("stone", Material::builder(Color::new(128, 128, 128)).build());
+    let cube = Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), stone);
+    let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    (camera, cube)
+}
+
+#[test]
+fn moves_stop_in_front_of_a_cube() {
+    let (mut camera, cube) = facing_a_cube();
+    camera.apply(CameraMove::Translate(Vec3::new(0.0, 0.0, -6.0)), &cube);
+    assert!(camera.eye.z > 0.5 && camera.eye.z < 0.6, "eye stopped at z = {}", camera.eye.z);
+    // The view keeps its direction
+    assert!((camera.forward() - Vec3::new(0.0, 0.0, -1.0)).magnitude() < 1e-4);
+}
+
+#[test]
+fn noclip_moves_through_a_cube() {
+    let (mut camera, cube) = facing_a_cube();
+    camera.noclip = true;
+    camera.apply(CameraMove::Translate(Vec3::new(0.0, 0.0, -6.0)), &cube);
+    assert!((camera.eye.z + 1.0).abs() < 1e-4, "eye stopped at z = {}", camera.eye.z);
+}
@@ -0,0 +1,61 @@
+use nalgebra_glm::Vec3;
+use raytracer::camera::Camera;
+use raytracer::camera_path::{CameraPath, Interpolation, Keyframe, KEYFRAME_SPACING};
+
+fn keyframe(time: f32, x: f32) -> Keyframe {
+    Keyframe { time, eye: Vec3::new(x, 1.0, 5.0), center: Vec3::new(x, 0.0, 0.0), up: Vec3::new(0.0, 1.0, 0.0) }
+}
+
+fn path(interpolation: Interpolation) -> CameraPath {
+    let mut path = CameraPath::new();
+    path.interpolation = interpolation;
+    // Out of order on purpose: the path sorts them
+    for (time, x) in [(2.0, 4.0), (0.0, 0.0), (5.0, -1.0), (3.0, 2.0)] {
+        path.insert(keyframe(time, x));
+    }
+    path
+}
+
+#[test]
+fn both_interpolations_pass_through_every_keyframe() {
+    for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+        let path = path(interpolation);
+        for k in path.keyframes() {
+            let sample = path.sample(k.time).unwrap();
+            assert!((sample.eye - k.eye).magnitude() < 1e-5, "{:?} misses the keyframe at {}", interpolation, k.time);
+            assert!((sample.center - k.center).magnitude() < 1e-5);
+        }
+        // Outside the path the camera holds at the ends
+        assert_eq!(path.sample(-1.0).unwrap().eye, path.keyframes()[0].eye);
+        assert_eq!(path.sample(99.0).unwrap().eye, path.keyframes()[3].eye);
+    }
+}
+
+#[test]
+fn linear_interpolation_is_halfway_at_half_time() {
+    let path = path(Interpolation::Linear);
+    assert!((path.sample(1.0).unwrap().eye.x - 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn capture_spaces_keyframes_in_time() {
+    let mut path = CameraPath::new();
+    let mut camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    path.capture(&camera);
+    camera.eye.x = 3.0;
+    path.capture(&camera);
+    assert_eq!(path.duration(), KEYFRAME_SPACING);
+
+    path.apply(&mut camera, 0.0);
+    assert_eq!(camera.eye, Vec3::new(0.0, 0.0, 5.0));
+}
+
+#[test]
+fn saved_path_loads_back_the_same() {
+    let file = std::env::temp_dir().join(format!("camera_path_test_{}.json", std::process::id()));
+    let path = path(Interpolation::Linear);
+    path.save(&file).unwrap();
+    let loaded = CameraPath::load(&file);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(loaded.unwrap(), path);
+}
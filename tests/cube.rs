@@ -0,0 +1,99 @@
+use nalgebra_glm::Vec3;
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::material::{Material, MaterialRegistry};
+use raytracer::ray_intersect::{CubeFace, RayIntersect};
+
+fn unit_cube() -> Cube {
+    let mut materials = MaterialRegistry::new();
+    let gray = materials.add("gray", Material::builder(Color::new(128, 128, 128)).build());
+    Cube::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), gray)
+}
+
+fn axis(index: usize) -> Vec3 {
+    let mut v = Vec3::zeros();
+    v[index] = 1.0;
+    v
+}
+
+#[test]
+fn rays_parallel_to_a_face_hit_only_from_within_its_slab() {
+    let cube = unit_cube();
+    for parallel in 0..3 {
+        // Travels along the next axis, so its component along `parallel` is exactly 0
+        let along = (parallel + 1) % 3;
+        let direction = axis(along);
+        let mut origin = Vec3::repeat(0.5);
+        origin[along] = -1.0;
+
+        let hit = cube.ray_intersect(&origin, &direction).expect("inside the slab");
+        assert!((hit.distance - 1.0).abs() < 1e-6);
+        assert_eq!(hit.face.index(), CubeFace::from_axis(along, false).index());
+        assert!(hit.point.iter().all(|c| c.is_finite()));
+
+        for outside in [-0.5, 1.5] {
+            origin[parallel] = outside;
+            assert!(cube.ray_intersect(&origin, &direction).is_none(), "{:?} toward {:?}", origin, direction);
+        }
+    }
+}
+
+#[test]
+fn negative_zero_components_count_as_parallel() {
+    let cube = unit_cube();
+    let direction = Vec3::new(-0.0, -0.0, -1.0);
+    let hit = cube.ray_intersect(&Vec3::new(0.5, 0.5, 3.0), &direction).expect("straight down the z axis");
+    let positive = cube.ray_intersect(&Vec3::new(0.5, 0.5, 3.0), &Vec3::new(0.0, 0.0, -1.0)).unwrap();
+    assert_eq!(hit.distance, 2.0);
+    assert_eq!(hit.distance, positive.distance);
+    assert_eq!(hit.face.index(), CubeFace::Front.index());
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+
+    assert!(cube.ray_intersect(&Vec3::new(1.5, 0.5, 3.0), &direction).is_none());
+    assert!(cube.ray_intersect(&Vec3::new(0.5, -0.5, 3.0), &direction).is_none());
+}
+
+#[test]
+fn rays_starting_inside_hit_the_face_they_leave_through() {
+    let cube = unit_cube();
+    let origin = Vec3::new(0.25, 0.5, 0.5);
+    let hit = cube.ray_intersect(&origin, &Vec3::new(1.0, 0.0, 0.0)).expect("every ray from inside leaves");
+    assert!(hit.inside);
+    assert!((hit.distance - 0.75).abs() < 1e-6);
+    assert_eq!(hit.face.index(), CubeFace::Right.index());
+
+    // Also at a slant, and not through the face it would enter by from outside
+    let direction = Vec3::new(-1.0, 1.0, 0.0).normalize();
+    let hit = cube.ray_intersect(&origin, &direction).unwrap();
+    assert!(hit.inside);
+    assert_eq!(hit.face.index(), CubeFace::Left.index());
+    assert!((hit.distance - 0.25 * 2.0_f32.sqrt()).abs() < 1e-6);
+
+    // A ray leaving a cube behind it misses
+    assert!(cube.ray_intersect(&Vec3::new(0.5, 0.5, 2.0), &Vec3::new(0.0, 0.0, 1.0)).is_none());
+}
+
+#[test]
+fn a_ray_from_inside_leaves_through_each_face() {
+    let mut materials = MaterialRegistry::new();
+    let gray = materials.add("gray", Material::builder(Color::new(128, 128, 128)).build());
+    // Off-center inside a box of a different size on every axis, so each face is at its own distance
+    let cube = Cube::new(Vec3::new(-1.0, -2.0, -3.0), Vec3::new(1.0, 2.0, 3.0), gray);
+    let origin = Vec3::new(0.2, 0.3, 0.4);
+    let faces = [
+        (CubeFace::Right, 0.8),
+        (CubeFace::Left, 1.2),
+        (CubeFace::Top, 1.7),
+        (CubeFace::Bottom, 2.3),
+        (CubeFace::Front, 2.6),
+        (CubeFace::Back, 3.4),
+    ];
+    for (face, tmax) in faces {
+        let hit = cube.ray_intersect(&origin, &face.normal()).expect("every ray from inside leaves");
+        assert!((hit.distance - tmax).abs() < 1e-5, "{:?}: {} instead of {}", face, hit.distance, tmax);
+        assert_eq!(hit.face.index(), face.index(), "{:?}", face);
+        assert!(hit.inside, "{:?}", face);
+        assert_eq!(hit.normal, -face.normal(), "{:?}", face);
+        assert!(hit.normal.dot(&(origin - hit.point)) > 0.0, "{:?}: the normal should point back at the origin", face);
+    }
+}
@@ -0,0 +1,116 @@
+use nalgebra_glm::Vec3;
+use raytracer::day_night::{DayNightCycle, DEFAULT_DAY_LENGTH, MAX_DAY_LENGTH, MIN_DAY_LENGTH};
+use raytracer::sky::Sky;
+
+#[test]
+fn the_day_advances_with_real_time_unless_paused() {
+    let mut cycle = DayNightCycle::new();
+    cycle.time = 0.0;
+    cycle.advance(DEFAULT_DAY_LENGTH / 4.0);
+    assert!((cycle.time - 0.25).abs() < 1e-5);
+
+    cycle.paused = true;
+    cycle.advance(10.0);
+    assert!((cycle.time - 0.25).abs() < 1e-5);
+    // Scrubbing by hand still works while paused
+    cycle.update(0.1);
+    assert!((cycle.time - 0.35).abs() < 1e-5);
+}
+
+#[test]
+fn long_steps_wrap_around_midnight() {
+    let mut cycle = DayNightCycle::new();
+    cycle.time = 0.9;
+    cycle.advance(DEFAULT_DAY_LENGTH * 2.3);
+    assert!((cycle.time - 0.2).abs() < 1e-4, "{}", cycle.time);
+    cycle.update(-0.5);
+    assert!((cycle.time - 0.7).abs() < 1e-4, "{}", cycle.time);
+}
+
+#[test]
+fn speed_doubles_and_halves_within_its_limits() {
+    let mut cycle = DayNightCycle::new();
+    cycle.faster();
+    assert_eq!(cycle.day_length, DEFAULT_DAY_LENGTH / 2.0);
+    cycle.slower();
+    cycle.slower();
+    assert_eq!(cycle.day_length, DEFAULT_DAY_LENGTH * 2.0);
+
+    for _ in 0..20 {
+        cycle.faster();
+    }
+    assert_eq!(cycle.day_length, MIN_DAY_LENGTH);
+    for _ in 0..20 {
+        cycle.slower();
+    }
+    assert_eq!(cycle.day_length, MAX_DAY_LENGTH);
+}
+
+// Cycle at `time`, with update run so sun_position follows
+fn at(time: f32) -> DayNightCycle {
+    let mut cycle = DayNightCycle::new();
+    cycle.time = time;
+    cycle.update(0.0);
+    cycle
+}
+
+#[test]
+fn the_sun_rises_at_dawn_peaks_at_noon_and_sets_at_dusk() {
+    let dawn = at(0.0);
+    assert!(dawn.sun_position.y.abs() < 1e-4 && dawn.sun_position.x > 4.9, "{:?}", dawn.sun_position);
+    assert!(dawn.sun_direction().y.abs() < 1e-4);
+    assert!(dawn.get_light_intensity() < 1e-4);
+
+    let noon = at(0.25);
+    assert!((noon.sun_position.y - 5.0).abs() < 1e-4, "{:?}", noon.sun_position);
+    assert!(noon.sun_direction().y < -0.7, "the light comes from above");
+    assert!((noon.get_light_intensity() - 1.0).abs() < 1e-4);
+    assert_eq!(noon.night_amount(), 0.0);
+
+    let dusk = at(0.5);
+    assert!(dusk.sun_position.y.abs() < 1e-4 && dusk.sun_position.x < -4.9, "{:?}", dusk.sun_position);
+    assert!(dusk.get_light_intensity() < 1e-4);
+
+    // A little after dawn the light is coming back, but not yet at full strength
+    let morning = at(0.02);
+    assert!(morning.get_light_intensity() > 0.0 && morning.get_light_intensity() < 0.6);
+}
+
+#[test]
+fn at_midnight_the_sun_is_below_the_horizon_and_only_the_moon_lights() {
+    let midnight = at(0.75);
+    assert!((midnight.sun_position.y + 5.0).abs() < 1e-4, "{:?}", midnight.sun_position);
+    assert!(midnight.sun_direction().y > 0.7, "the sun shines up from below the ground");
+    assert_eq!(midnight.get_light_intensity(), 0.0);
+    assert_eq!(midnight.night_amount(), 1.0);
+    assert!(midnight.moon_direction().y < -0.7, "the moon is overhead");
+
+    // The whole night, not just midnight, gets no sunlight
+    for step in 1..50 {
+        let cycle = at(0.5 + 0.5 * step as f32 / 50.0);
+        assert!(cycle.sun_position.y < 0.0);
+        assert_eq!(cycle.get_light_intensity(), 0.0, "time {}", cycle.time);
+    }
+}
+
+#[test]
+fn the_sky_has_no_sun_glow_below_the_horizon() {
+    let mut sky = Sky::default();
+    sky.clouds.coverage = 0.0;
+    let sunless = Sky { sun_size: 0.0, halo_strength: 0.0, ..sky };
+    let glow = |direction: &Vec3, cycle: &DayNightCycle| {
+        let (lit, unlit) = (sky.sample(direction, cycle, 0.0), sunless.sample(direction, cycle, 0.0));
+        (lit.r - unlit.r) + (lit.g - unlit.g) + (lit.b - unlit.b)
+    };
+
+    // Just after sunset, looking straight at the sun under the ground
+    let dusk = at(0.51);
+    assert!(glow(&-dusk.sun_direction(), &dusk) < 1e-6);
+
+    // With the sun low but up, the disc glows, yet the ground just beneath it does not
+    let evening = at(0.49);
+    let to_sun = -evening.sun_direction();
+    assert!(glow(&to_sun, &evening) > 1.0);
+    let below = Vec3::new(to_sun.x, -0.05, to_sun.z);
+    assert!(glow(&below, &evening) < 1e-6);
+}
@@ -0,0 +1,118 @@
+use nalgebra_glm::Vec3;
+use raytracer::bvh::Bvh;
+use raytracer::chunk::ChunkManager;
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::edit_history::{Edit, EditHistory};
+use raytracer::material::{Material, MaterialId, MaterialRegistry};
+use raytracer::object::Object;
+use raytracer::sampling::Sampler;
+use raytracer::terrain::TerrainSettings;
+use raytracer::world::World;
+
+fn materials() -> (MaterialRegistry, Vec<MaterialId>) {
+    let mut materials = MaterialRegistry::new();
+    let ids = (0..4)
+        .map(|i| materials.add(&format!("m{}", i), Material::builder(Color::new(60 * i, 0, 0)).build()))
+        .collect();
+    (materials, ids)
+}
+
+fn world(cubes: Vec<Cube>, terrain: MaterialId) -> World {
+    let objects = cubes.into_iter().map(Object::from).collect();
+    World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), terrain, 0))
+}
+
+// Every cube of the world as raw bits, so "equal" means byte for byte
+fn cube_bits(world: &World) -> Vec<([u32; 6], MaterialId)> {
+    world.scene.objects().iter()
+        .map(|object| match object {
+            Object::Cube(cube) => {
+                let [a, b, c] = [cube.min.x, cube.min.y, cube.min.z].map(f32::to_bits);
+                let [d, e, f] = [cube.max.x, cube.max.y, cube.max.z].map(f32::to_bits);
+                ([a, b, c, d, e, f], cube.material)
+            }
+            _ => panic!("the test scene only has cubes"),
+        })
+        .collect()
+}
+
+fn starting_cubes(materials: &[MaterialId]) -> Vec<Cube> {
+    (0..6)
+        .map(|i| {
+            let min = Vec3::new(i as f32 * 0.5, 0.0, 0.0);
+            Cube::new(min, min + Vec3::repeat(0.5), materials[i % materials.len()])
+        })
+        .collect()
+}
+
+// A random valid edit of the world as it is now
+fn random_edit(world: &World, materials: &[MaterialId], sampler: &mut Sampler) -> Edit {
+    let objects = world.scene.objects();
+    let pick = |sampler: &mut Sampler, n: usize| ((sampler.next_f32() * n as f32) as usize).min(n - 1);
+    let kind = if objects.is_empty() { 0 } else { pick(sampler, 3) };
+    match kind {
+        0 => {
+            let min = Vec3::new(sampler.next_f32() * 4.0, sampler.next_f32() * 4.0, sampler.next_f32() * 4.0);
+            let cube = Cube::new(min, min + Vec3::repeat(0.5), materials[pick(sampler, materials.len())]);
+            Edit::Place { index: pick(sampler, objects.len() + 1), cube }
+        }
+        1 => {
+            let index = pick(sampler, objects.len());
+            let Object::Cube(cube) = &objects[index] else { unreachable!() };
+            Edit::Remove { index, cube: cube.clone() }
+        }
+        _ => {
+            let index = pick(sampler, objects.len());
+            let Object::Cube(cube) = &objects[index] else { unreachable!() };
+            Edit::Repaint { index, from: cube.material, to: materials[pick(sampler, materials.len())] }
+        }
+    }
+}
+
+#[test]
+fn undoing_a_random_sequence_restores_the_original_cubes() {
+    let (_, ids) = materials();
+    for seed in 0..20 {
+        let mut world = world(starting_cubes(&ids), ids[0]);
+        let original = cube_bits(&world);
+        let mut history = EditHistory::new();
+        let mut sampler = Sampler::new(seed);
+
+        for _ in 0..100 {
+            let edit = random_edit(&world, &ids, &mut sampler);
+            history.apply(edit, &mut world);
+        }
+        let edited = cube_bits(&world);
+
+        while history.undo(&mut world) {}
+        assert_eq!(cube_bits(&world), original, "seed {}", seed);
+
+        // And redoing all of it lands on the edited scene again
+        while history.redo(&mut world) {}
+        assert_eq!(cube_bits(&world), edited, "seed {}", seed);
+    }
+}
+
+#[test]
+fn a_new_edit_drops_the_redo_steps_and_the_history_is_bounded() {
+    let (_, ids) = materials();
+    let mut world = world(Vec::new(), ids[0]);
+    let mut history = EditHistory::with_limit(3);
+    for i in 0..5 {
+        let cube = Cube::new(Vec3::new(i as f32, 0.0, 0.0), Vec3::new(i as f32 + 0.5, 0.5, 0.5), ids[0]);
+        history.apply(Edit::Place { index: i, cube }, &mut world);
+    }
+
+    // Only the last three placements can be undone
+    let mut undone = 0;
+    while history.undo(&mut world) {
+        undone += 1;
+    }
+    assert_eq!(undone, 3);
+    assert_eq!(world.scene.objects().len(), 2);
+
+    let cube = Cube::new(Vec3::new(9.0, 0.0, 0.0), Vec3::new(9.5, 0.5, 0.5), ids[1]);
+    history.apply(Edit::Place { index: 0, cube }, &mut world);
+    assert!(!history.redo(&mut world));
+}
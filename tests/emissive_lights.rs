@@ -0,0 +1,156 @@
+use image::{Rgba, RgbaImage};
+use nalgebra_glm::Vec3;
+use raytracer::bvh::Bvh;
+use raytracer::chunk::ChunkManager;
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::day_night::DayNightCycle;
+use raytracer::environment::Environment;
+use raytracer::light::{Light, LightKind};
+use raytracer::material::{Material, MaterialRegistry};
+use raytracer::object::Object;
+use raytracer::terrain::TerrainSettings;
+use raytracer::texture::{AnimatedTexture, Texture};
+use raytracer::world::World;
+use raytracer::Scene;
+use std::sync::Arc;
+
+// The portal of the demo scene: a purple frame of four thin cubes around a portal block
+// that touches it but has a material of its own
+fn portal_scene(merge: bool) -> Scene {
+    let mut materials = MaterialRegistry::new();
+    let ground = materials.add("ground", Material::builder(Color::new(128, 128, 128)).build());
+    let portal = materials.add("portal", Material::builder(Color::new(100, 0, 200)).build().with_emission(Color::new(70, 0, 180)).with_emission_strength(10.0));
+    let border = materials.add("border", Material::builder(Color::new(200, 0, 255)).build().with_emission(Color::new(180, 0, 255)).with_emission_strength(10.0));
+    let objects: Vec<Object> = vec![
+        Cube::new(Vec3::new(-0.5, 0.0, -2.5), Vec3::new(0.5, 2.0, -2.0), portal).into(),
+        Cube::new(Vec3::new(-0.7, -0.2, -2.6), Vec3::new(0.7, 0.0, -1.9), border).into(),
+        Cube::new(Vec3::new(-0.7, 2.0, -2.6), Vec3::new(0.7, 2.2, -1.9), border).into(),
+        Cube::new(Vec3::new(-0.7, 0.0, -2.6), Vec3::new(-0.5, 2.0, -1.9), border).into(),
+        Cube::new(Vec3::new(0.5, 0.0, -2.6), Vec3::new(0.7, 2.0, -1.9), border).into(),
+        // Same material, but off on its own
+        Cube::new(Vec3::new(3.0, 0.0, 0.0), Vec3::new(3.5, 0.5, 0.5), border).into(),
+    ];
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), ground, 0));
+    let lights = vec![
+        Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::new(255, 255, 255), 1.0),
+        Light::directional(Vec3::new(0.0, 1.0, 0.0), Color::new(170, 190, 255), 0.0),
+    ];
+    let mut scene = Scene::new(world, materials, lights, vec![Environment::SolidColor(Color::new(0, 0, 0))], DayNightCycle::new());
+    scene.merge_emissive_lights = merge;
+    scene.update_lights();
+    scene
+}
+
+fn total_intensity(lights: &[Light]) -> f32 {
+    lights.iter().map(|light| light.intensity).sum()
+}
+
+#[test]
+fn the_portal_frame_shares_a_single_light() {
+    let merged = portal_scene(true);
+    let emissive = &merged.lights[2..];
+    // Portal, frame and the loose cube
+    assert_eq!(emissive.len(), 3);
+    assert!(matches!(emissive[0].kind, LightKind::Area { .. }), "the portal block keeps its own area light");
+
+    let frame = &emissive[1];
+    assert_eq!(frame.kind, LightKind::Point);
+    assert!((frame.position - Vec3::new(0.0, 1.0, -2.25)).magnitude() < 1e-4, "{:?}", frame.position);
+    let diagonal = (Vec3::new(0.7, 2.2, -1.9) - Vec3::new(-0.7, -0.2, -2.6)).magnitude();
+    assert!((frame.radius - diagonal * 10.0).abs() < 1e-3);
+    assert_eq!(emissive[2].kind, LightKind::Area { min: Vec3::new(3.0, 0.0, 0.0), max: Vec3::new(3.5, 0.5, 0.5) });
+}
+
+#[test]
+fn merging_keeps_the_total_intensity() {
+    let (merged, separate) = (portal_scene(true), portal_scene(false));
+    assert_eq!(separate.lights.len(), 2 + 6);
+    let frame: f32 = separate.lights[3..7].iter().map(|light| light.intensity).sum();
+    assert!((merged.lights[3].intensity - frame).abs() < 1e-4);
+    assert!((total_intensity(&merged.lights) - total_intensity(&separate.lights)).abs() < 1e-4);
+}
+
+fn enabled(scene: &Scene) -> Vec<bool> {
+    scene.lights.iter().map(|light| light.enabled).collect()
+}
+
+#[test]
+fn lights_of_emissive_objects_can_be_switched_off_and_isolated() {
+    let mut scene = portal_scene(true);
+    scene.select_light(3); // The portal frame's light
+    assert_eq!(scene.selected_light, 3);
+    assert!(!scene.toggle_selected_light());
+    scene.update_lights();
+    assert_eq!(enabled(&scene), [true, true, true, false, true]);
+    // Regenerated every frame, the lights keep their order and so their switches
+    scene.update_lights();
+    assert_eq!(enabled(&scene), [true, true, true, false, true]);
+
+    scene.select_light(1);
+    scene.isolate_light = true;
+    scene.update_lights();
+    assert_eq!(enabled(&scene), [false, false, false, false, true]);
+
+    // Backward from the sun wraps around to the last light
+    scene.select_light(-5);
+    assert_eq!(scene.selected_light, 4);
+}
+
+#[test]
+fn resetting_the_switches_turns_every_light_back_on() {
+    let mut scene = portal_scene(true);
+    scene.select_light(2);
+    scene.toggle_selected_light();
+    scene.isolate_light = true;
+    scene.update_lights();
+    assert!(enabled(&scene).iter().all(|on| !on));
+
+    scene.reset_light_switches();
+    scene.update_lights();
+    assert_eq!(scene.selected_light, 0);
+    assert!(enabled(&scene).iter().all(|on| *on));
+}
+
+// Two frames of 2 x 2 texels, a bright orange one and a dark red one, half a second each
+fn two_frame_glow() -> AnimatedTexture {
+    let frame = |name: &str, color: [u8; 4]| {
+        let path = std::env::temp_dir().join(format!("raytracer_glow_{}_{}.png", std::process::id(), name));
+        RgbaImage::from_pixel(2, 2, Rgba(color)).save(&path).unwrap();
+        let texture = Texture::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        texture
+    };
+    AnimatedTexture::new(vec![frame("bright", [255, 160, 40, 255]), frame("dark", [120, 20, 0, 255])], 0.5)
+}
+
+#[test]
+fn a_texture_glow_lights_with_the_average_of_the_frame_on_screen() {
+    let lava = Arc::new(two_frame_glow());
+    let mut materials = MaterialRegistry::new();
+    let ground = materials.add("ground", Material::builder(Color::new(128, 128, 128)).build());
+    let glow = materials.add("lava", Material::builder(Color::new(255, 100, 0)).build()
+        .with_animation(lava.clone())
+        .with_texture_emission()
+        .with_emission_strength(4.0));
+    assert!(materials.get(glow).is_emissive(), "no flat emission is needed");
+    let objects: Vec<Object> = vec![Cube::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.1, 1.0), glow).into()];
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), ground, 0));
+    let lights = vec![
+        Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::new(255, 255, 255), 1.0),
+        Light::directional(Vec3::new(0.0, 1.0, 0.0), Color::new(170, 190, 255), 0.0),
+    ];
+    let mut scene = Scene::new(world, materials, lights, vec![Environment::SolidColor(Color::new(0, 0, 0))], DayNightCycle::new());
+
+    let mut colors = Vec::new();
+    for time in [0.0, 0.5, 1.0, 1.5] {
+        scene.time = time;
+        scene.update_lights();
+        let light = &scene.lights[2];
+        let expected = lava.frame(time).average().to_srgb();
+        assert_eq!(light.color, expected);
+        assert!((light.intensity - expected.intensity() * 4.0).abs() < 1e-5);
+        colors.push(light.color);
+    }
+    assert!(colors.windows(2).any(|pair| pair[0] != pair[1]), "the light should change with the frames");
+}
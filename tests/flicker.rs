@@ -0,0 +1,83 @@
+use nalgebra_glm::Vec3;
+use raytracer::bvh::Bvh;
+use raytracer::chunk::ChunkManager;
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::day_night::DayNightCycle;
+use raytracer::environment::Environment;
+use raytracer::light::{Flicker, Light, FLICKER_JITTER};
+use raytracer::material::{Material, MaterialRegistry};
+use raytracer::terrain::TerrainSettings;
+use raytracer::world::World;
+use raytracer::Scene;
+
+#[test]
+fn flicker_stays_within_its_amplitude_and_changes_smoothly() {
+    let flicker = Flicker::new(0.2, 4.0, 11);
+    let mut previous = flicker.intensity(0.0);
+    let (mut lowest, mut highest) = (f32::MAX, f32::MIN);
+    for frame in 1..600 {
+        let time = frame as f32 / 60.0;
+        let intensity = flicker.intensity(time);
+        assert!((0.8..=1.2).contains(&intensity), "{} at {}", intensity, time);
+        // At 60 fps no frame jumps by more than a small part of the range
+        assert!((intensity - previous).abs() < 0.08, "jump of {} at {}", intensity - previous, time);
+        let jitter = flicker.jitter(time);
+        assert!(jitter.iter().all(|offset| offset.abs() <= FLICKER_JITTER * 0.2 + 1e-6));
+        lowest = lowest.min(intensity);
+        highest = highest.max(intensity);
+        previous = intensity;
+    }
+    assert!(highest - lowest > 0.1, "it should actually flicker ({} to {})", lowest, highest);
+}
+
+#[test]
+fn the_same_seed_flickers_the_same_way() {
+    let (a, b, other) = (Flicker::new(0.3, 5.0, 1), Flicker::new(0.3, 5.0, 1), Flicker::new(0.3, 5.0, 2));
+    let times = (0..100).map(|i| i as f32 * 0.137);
+    assert!(times.clone().all(|t| a.intensity(t) == b.intensity(t) && a.jitter(t) == b.jitter(t)));
+    assert!(times.clone().any(|t| a.intensity(t) != other.intensity(t)));
+}
+
+fn scene(torch: Material) -> Scene {
+    let mut materials = MaterialRegistry::new();
+    let stone = materials.add("stone", Material::builder(Color::new(128, 128, 128)).build());
+    let torch = materials.add("torch", torch);
+    let objects = vec![Cube::new(Vec3::new(-0.5, 0.0, -0.5), Vec3::new(0.5, 1.0, 0.5), torch).into()];
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), stone, 0));
+    let lights = vec![
+        Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::new(255, 255, 255), 1.0),
+        Light::directional(Vec3::new(0.0, 1.0, 0.0), Color::new(170, 190, 255), 0.0),
+        Light::new(Vec3::new(3.0, 2.0, 0.0), Color::new(255, 200, 100), 1.5, 10.0),
+    ];
+    Scene::new(world, materials, lights, vec![Environment::SolidColor(Color::new(0, 0, 0))], DayNightCycle::new())
+}
+
+#[test]
+fn emissive_lights_flicker_when_their_material_asks_for_it() {
+    let torch = Material::builder(Color::new(255, 180, 80)).build().with_emission(Color::new(255, 160, 60));
+    let mut steady = scene(torch.clone());
+    let mut flickering = scene(torch.with_flicker(Flicker::new(0.25, 3.0, 5)));
+    assert_eq!(steady.lights.len(), 4);
+
+    let first = (steady.lights[3].intensity, steady.lights[3].position);
+    let mut seen = Vec::new();
+    for frame in 0..30 {
+        for scene in [&mut steady, &mut flickering] {
+            scene.time = frame as f32 * 0.1;
+            scene.update_lights();
+        }
+        // The hand-placed point light and the steady torch never drift from frame to frame
+        assert!(steady.lights[2] == flickering.lights[2]);
+        assert_eq!(flickering.lights[2].intensity, 1.5);
+        assert_eq!((steady.lights[3].intensity, steady.lights[3].position), first);
+        seen.push((flickering.lights[3].intensity, flickering.lights[3].position));
+    }
+    assert!(seen.iter().any(|(intensity, _)| *intensity != seen[0].0));
+    assert!(seen.iter().any(|(_, position)| *position != seen[0].1));
+
+    // Going back to an earlier time gives back exactly the same light
+    flickering.time = 0.5;
+    flickering.update_lights();
+    assert_eq!((flickering.lights[3].intensity, flickering.lights[3].position), seen[5]);
+}
@@ -0,0 +1,60 @@
+use image::{GrayImage, ImageBuffer, Luma};
+use raytracer::material::{Material, MaterialRegistry};
+use raytracer::color::Color;
+use raytracer::terrain::{self, HeightmapSettings};
+use std::path::PathBuf;
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("raytracer_heightmap_{}_{}", std::process::id(), name))
+}
+
+fn materials() -> MaterialRegistry {
+    let mut materials = MaterialRegistry::new();
+    materials.add("grass", Material::builder(Color::new(0, 255, 0)).build());
+    materials.add("stone", Material::builder(Color::new(128, 128, 128)).build());
+    materials
+}
+
+#[test]
+fn non_square_image_gives_one_column_per_pixel() {
+    // 3 x 1 image: black, white and mid gray
+    let path = temp_file("wide.png");
+    GrayImage::from_raw(3, 1, vec![0, 255, 128]).unwrap().save(&path).unwrap();
+    let materials = materials();
+    let (grass, stone) = (materials.id("grass").unwrap(), materials.id("stone").unwrap());
+    let settings = HeightmapSettings { max_height: 4, soil_depth: 1 };
+    let cubes = terrain::from_heightmap(&path, &settings, grass, stone).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    // Every block of the two columns borders the empty black pixel or the edge of the image
+    assert_eq!(cubes.len(), 4 + 2);
+    assert_eq!(cubes.iter().filter(|cube| cube.material == grass).count(), 2);
+    let top = cubes.iter().map(|cube| cube.max.y).fold(0.0, f32::max);
+    let lowest = cubes.iter().map(|cube| cube.min.y).fold(f32::MAX, f32::min);
+    assert_eq!(lowest, 0.0);
+    assert!(top > 0.0);
+}
+
+#[test]
+fn sixteen_bit_images_match_eight_bit_ones() {
+    let eight = temp_file("eight.png");
+    let sixteen = temp_file("sixteen.png");
+    let shades = [0u8, 64, 200, 255, 30, 90];
+    GrayImage::from_raw(2, 3, shades.to_vec()).unwrap().save(&eight).unwrap();
+    let wide: Vec<u16> = shades.iter().map(|&shade| shade as u16 * 257).collect();
+    ImageBuffer::<Luma<u16>, _>::from_raw(2, 3, wide).unwrap().save(&sixteen).unwrap();
+
+    let materials = materials();
+    let (grass, stone) = (materials.id("grass").unwrap(), materials.id("stone").unwrap());
+    let settings = HeightmapSettings::default();
+    let from_eight = terrain::from_heightmap(&eight, &settings, grass, stone).unwrap();
+    let from_sixteen = terrain::from_heightmap(&sixteen, &settings, grass, stone).unwrap();
+    std::fs::remove_file(&eight).ok();
+    std::fs::remove_file(&sixteen).ok();
+
+    assert!(!from_eight.is_empty());
+    assert_eq!(from_eight.len(), from_sixteen.len());
+    for (a, b) in from_eight.iter().zip(&from_sixteen) {
+        assert_eq!((a.min, a.max, a.material), (b.min, b.max, b.material));
+    }
+}
@@ -0,0 +1,127 @@
+use nalgebra_glm::Vec3;
+use raytracer::bvh::Bvh;
+use raytracer::camera::Camera;
+use raytracer::chunk::ChunkManager;
+use raytracer::color::Color;
+use raytracer::cube::Cube;
+use raytracer::day_night::DayNightCycle;
+use raytracer::environment::Environment;
+use raytracer::framebuffer::Framebuffer;
+use raytracer::light::Light;
+use raytracer::light_grid::{LightGrid, LIGHT_CELL_SIZE};
+use raytracer::material::{Material, MaterialRegistry};
+use raytracer::object::Object;
+use raytracer::plane::Plane;
+use raytracer::terrain::TerrainSettings;
+use raytracer::world::World;
+use raytracer::{RenderSettings, Renderer, Scene};
+use std::time::Instant;
+
+// Ten by ten glowstone blocks, far enough apart that each one lights only its neighbors, on a
+// floor, at midnight
+fn glowstone_field() -> Scene {
+    let mut materials = MaterialRegistry::new();
+    let ground = materials.add("ground", Material::builder(Color::new(128, 128, 128)).build());
+    let glowstone = materials.add("glowstone", Material::builder(Color::new(255, 255, 200)).build().with_emission(Color::new(255, 200, 100)).with_emission_strength(10.0));
+    let mut objects: Vec<Object> = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            let (x, z) = ((i as f32 - 4.5) * 5.0, (j as f32 - 4.5) * 5.0);
+            objects.push(Cube::new(Vec3::new(x - 0.15, 0.0, z - 0.15), Vec3::new(x + 0.15, 0.3, z + 0.15), glowstone).into());
+        }
+    }
+    objects.push(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground).into());
+
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), ground, 0));
+    let mut day_night = DayNightCycle::new();
+    day_night.time = 0.75;
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+    ];
+    Scene::new(world, materials, lights, vec![Environment::SolidColor(Color::new(0, 0, 0))], day_night)
+}
+
+fn render(scene: &Scene) -> Framebuffer {
+    render_at(scene, 80, 50)
+}
+
+fn render_at(scene: &Scene, width: usize, height: usize) -> Framebuffer {
+    let camera = Camera::new(Vec3::new(0.0, 18.0, 30.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(width, height);
+    Renderer::new(RenderSettings::default()).render(scene, &camera, &mut framebuffer);
+    framebuffer
+}
+
+#[test]
+fn culling_leaves_the_image_unchanged() {
+    let mut scene = glowstone_field();
+    assert_eq!(scene.lights.len(), 2 + 100, "the blocks are too far apart to share a light");
+    let culled = render(&scene);
+    scene.cull_lights = false;
+    let full = render(&scene);
+    assert!(culled.buffer == full.buffer, "culling changed the image");
+}
+
+// Timing, not a check: run with `cargo test --release --test light_grid -- --ignored --nocapture`
+// to see how much faster the field renders with culling than with every light checked
+#[test]
+#[ignore]
+fn culling_speeds_up_the_glowstone_field() {
+    const FRAMES: u32 = 5;
+    let mut scene = glowstone_field();
+    let mut time = |cull: bool| {
+        scene.cull_lights = cull;
+        render_at(&scene, 320, 200); // Warm-up
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            render_at(&scene, 320, 200);
+        }
+        start.elapsed() / FRAMES
+    };
+    let (full, culled) = (time(false), time(true));
+    println!(
+        "100 glowstones at 320x200: {:.1} ms per frame with every light, {:.1} ms culled ({:.1}x faster)",
+        full.as_secs_f64() * 1000.0, culled.as_secs_f64() * 1000.0, full.as_secs_f64() / culled.as_secs_f64(),
+    );
+    assert!(culled < full, "culling should not be slower");
+}
+
+#[test]
+fn a_point_only_sees_the_lights_that_reach_its_cell() {
+    let scene = glowstone_field();
+    let point = Vec3::new(-22.5, 0.0, -22.5); // Under the corner block
+    let listed: Vec<&Light> = scene.lights_at(&point).collect();
+    // The sun, the moon, the block above and its three neighbors within range
+    assert!(listed.len() < 10, "{} lights listed", listed.len());
+    for light in &scene.lights {
+        let (_, distance) = light.direction_from(&point);
+        if light.reaches(distance) {
+            assert!(listed.contains(&light), "a light that reaches the point is missing");
+        }
+    }
+}
+
+#[test]
+fn directional_and_huge_lights_are_listed_everywhere() {
+    let lights = vec![
+        Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::new(255, 255, 255), 1.0),
+        Light::new(Vec3::new(0.0, 0.0, 0.0), Color::new(255, 255, 255), 1.0, 1.0),
+        Light::new(Vec3::new(0.0, 0.0, 0.0), Color::new(255, 255, 255), 1.0, 1000.0),
+    ];
+    let grid = LightGrid::new(&lights);
+    assert_eq!(grid.light_count(), 3);
+    assert_eq!(grid.lights_at(&Vec3::new(0.5, 0.5, 0.5)), &[0, 1, 2]);
+    assert_eq!(grid.lights_at(&Vec3::new(LIGHT_CELL_SIZE * 5.0, 0.0, 0.0)), &[0, 2]);
+}
+
+#[test]
+fn lights_added_after_the_last_update_are_not_left_out() {
+    let mut scene = glowstone_field();
+    let point = Vec3::new(100.0, 0.0, 100.0);
+    let before = scene.lights_at(&point).count();
+    scene.lights.push(Light::new(point + Vec3::new(0.0, 1.0, 0.0), Color::new(255, 255, 255), 1.0, 2.0));
+    assert!(scene.lights_at(&point).count() > before);
+    scene.rebuild_light_grid();
+    assert_eq!(scene.lights_at(&point).count(), before + 1);
+}
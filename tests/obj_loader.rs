@@ -0,0 +1,43 @@
+use raytracer::obj_loader::{ObjError, ObjModel};
+use std::path::Path;
+
+fn parse(text: &str) -> Result<ObjModel, ObjError> {
+    ObjModel::parse(text.as_bytes(), Path::new("."))
+}
+
+#[test]
+fn a_missing_file_is_an_io_error() {
+    let error = ObjModel::load("assets/this_model_does_not_exist.obj").err().expect("the file does not exist");
+    assert!(matches!(error, ObjError::Io(_)), "{}", error);
+}
+
+#[test]
+fn a_bad_vertex_line_is_a_parse_error_on_that_line() {
+    let error = parse("# triangle\nv 0 0 0\nv 1 zero 0\nv 0 1 0\nf 1 2 3\n").err().unwrap();
+    assert!(matches!(error, ObjError::Parse { line: 3, .. }), "{}", error);
+
+    // Too few coordinates
+    let error = parse("v 0 0 0\nv 1 0\n").err().unwrap();
+    assert!(matches!(error, ObjError::Parse { line: 2, .. }), "{}", error);
+}
+
+#[test]
+fn a_bad_face_line_is_a_parse_error_on_that_line() {
+    let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 two 3\n").err().unwrap();
+    assert!(matches!(error, ObjError::Parse { line: 5, .. }), "{}", error);
+
+    // Only two corners
+    let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2\n").err().unwrap();
+    assert!(matches!(error, ObjError::Parse { line: 4, .. }), "{}", error);
+}
+
+#[test]
+fn a_face_past_the_vertex_list_is_out_of_range() {
+    let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").err().unwrap();
+    assert!(matches!(error, ObjError::IndexOutOfRange { line: 4, index: 4, count: 3 }), "{}", error);
+
+    // Negative indices count back from the last vertex, and can't go before the first
+    assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").is_ok());
+    let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1\n").err().unwrap();
+    assert!(matches!(error, ObjError::IndexOutOfRange { line: 4, index: -4, count: 3 }), "{}", error);
+}
@@ -0,0 +1,755 @@
+use nalgebra_glm::Vec3;
+use raytracer::camera::Camera;
+use raytracer::chunk::ChunkManager;
+use raytracer::color::{Color, ToneMapping};
+use raytracer::day_night::DayNightCycle;
+use raytracer::environment::Environment;
+use raytracer::framebuffer::Framebuffer;
+use raytracer::light::{Attenuation, Light};
+use raytracer::material::{Material, MaterialBuilder, MaterialRegistry, Sidedness, Waves};
+use raytracer::bvh::Bvh;
+use raytracer::cube::Cube;
+use raytracer::ray_intersect::{CubeFace, RayIntersect};
+use raytracer::object::Object;
+use raytracer::plane::Plane;
+use raytracer::scene_builder::SceneBuilder;
+use raytracer::terrain::TerrainSettings;
+use raytracer::world::{Pick, World};
+use raytracer::camera::cubemap_cameras;
+use raytracer::renderer::{equirect_direction, primary_direction, project, take_ray_counts, FrameTimes, FRAME_TIME_WINDOW};
+use std::time::Duration;
+use raytracer::texture::Skybox;
+use raytracer::medium::MediumStack;
+use raytracer::sampling::Sampler;
+use raytracer::scene::SUBMERSION_BAND;
+use raytracer::{cast_ray, Projection, RayContext, RayPath, RenderMode, RenderSettings, Renderer, Scene};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+const BACKGROUND: Color = Color::new(68, 142, 228);
+
+// A scene with `objects` in front of a plain background, lit by a noon sun. The terrain
+// manager never loads anything: chunks only arrive through `update`
+fn scene(objects: Vec<Object>, materials: MaterialRegistry) -> Scene {
+    let terrain = materials.id("red").expect("the test material is registered");
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), terrain, 0));
+    let mut day_night = DayNightCycle::new();
+    day_night.time = 0.25;
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+    ];
+    Scene::new(world, materials, lights, vec![Environment::SolidColor(BACKGROUND)], day_night)
+}
+
+fn materials() -> MaterialRegistry {
+    let mut materials = MaterialRegistry::new();
+    materials.add("red", Material::builder(Color::new(200, 30, 30)).build());
+    materials
+}
+
+// Clamp keeps colors below 1.0 as they are, so the background comes out unchanged
+fn render(scene: &Scene) -> Framebuffer {
+    let settings = RenderSettings { tone_mapping: ToneMapping::Clamp, fog: false, ..RenderSettings::default() };
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    Renderer::new(settings).render(scene, &camera, &mut framebuffer);
+    framebuffer
+}
+
+fn rgb(pixel: u32) -> (i32, i32, i32) {
+    (((pixel >> 16) & 0xFF) as i32, ((pixel >> 8) & 0xFF) as i32, (pixel & 0xFF) as i32)
+}
+
+// The round trip through linear light may be off by one
+fn assert_close(pixel: u32, color: Color) {
+    let (r, g, b) = rgb(pixel);
+    let (er, eg, eb) = rgb(color.to_hex());
+    assert!(
+        (r - er).abs() <= 1 && (g - eg).abs() <= 1 && (b - eb).abs() <= 1,
+        "pixel {:06x} should be close to {:06x}", pixel, color.to_hex(),
+    );
+}
+
+#[test]
+fn empty_scene_shows_the_background_everywhere() {
+    let framebuffer = render(&scene(Vec::new(), materials()));
+    for &pixel in &framebuffer.buffer {
+        assert_close(pixel, BACKGROUND);
+    }
+}
+
+#[test]
+fn cube_in_front_of_the_camera_covers_the_center() {
+    let materials = materials();
+    let red = materials.id("red").unwrap();
+    let mut builder = SceneBuilder::new();
+    builder.cube(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), red);
+    let framebuffer = render(&scene(builder.build(), materials));
+
+    // The middle of the image is the lit red cube
+    let (r, g, b) = rgb(framebuffer.buffer[HEIGHT / 2 * WIDTH + WIDTH / 2]);
+    assert!(r > 2 * g && r > 2 * b, "center pixel ({}, {}, {}) should be red", r, g, b);
+
+    // The corners miss it and keep the background
+    for &index in &[0, WIDTH - 1, (HEIGHT - 1) * WIDTH, HEIGHT * WIDTH - 1] {
+        assert_close(framebuffer.buffer[index], BACKGROUND);
+    }
+}
+
+#[test]
+fn picking_the_center_pixel_finds_the_cube_and_the_corner_finds_the_sky() {
+    let materials = materials();
+    let red = materials.id("red").unwrap();
+    let mut builder = SceneBuilder::new();
+    builder.cube(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), red);
+    let scene = scene(builder.build(), materials);
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let fov = RenderSettings::default().fov;
+    let (width, height) = (WIDTH as f32, HEIGHT as f32);
+
+    let center = primary_direction(&camera, fov, width, height, width / 2.0, height / 2.0);
+    assert!(matches!(scene.world.pick(&camera.eye, &center), Some(Pick::Object(0, _))));
+    let corner = primary_direction(&camera, fov, width, height, 0.0, 0.0);
+    assert!(scene.world.pick(&camera.eye, &corner).is_none());
+}
+
+#[test]
+fn projecting_a_point_on_a_primary_ray_gives_back_its_pixel() {
+    let camera = Camera::new(Vec3::new(2.0, 1.5, 4.0), Vec3::new(-1.0, 0.0, 0.5), Vec3::new(0.0, 1.0, 0.0));
+    let fov = RenderSettings::default().fov;
+    for &(x, y) in &[(0.0, 0.0), (10.0, 40.0), (63.0, 5.0), (32.0, 24.0)] {
+        let direction = primary_direction(&camera, fov, WIDTH as f32, HEIGHT as f32, x, y);
+        let (px, py) = project(&camera, fov, WIDTH as f32, HEIGHT as f32, &(camera.eye + direction * 7.0)).unwrap();
+        assert!((px - x).abs() < 1e-3 && (py - y).abs() < 1e-3, "({}, {}) came back as ({}, {})", x, y, px, py);
+    }
+}
+
+#[test]
+fn placed_blocks_sit_next_to_the_face_and_broken_ones_disappear() {
+    let materials = materials();
+    let red = materials.id("red").unwrap();
+    let block = Cube::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.5, 0.5, 0.5), red);
+    let above = block.adjacent(&CubeFace::Top, 0.5, red);
+    assert_eq!((above.min, above.max), (Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.5, 1.0, 0.5)));
+    let behind = block.adjacent(&CubeFace::Back, 0.5, red);
+    assert_eq!((behind.min, behind.max), (Vec3::new(0.0, 0.0, -0.5), Vec3::new(0.5, 0.5, 0.0)));
+
+    let mut scene = scene(vec![block.into()], materials);
+    let eye = Vec3::new(0.25, 0.25, 3.0);
+    let toward = Vec3::new(0.0, 0.0, -1.0);
+    assert!(matches!(scene.world.pick(&eye, &toward), Some(Pick::Object(0, _))));
+    scene.world.edit_objects(|objects| {
+        objects.remove(0);
+    });
+    assert!(scene.world.pick(&eye, &toward).is_none());
+}
+
+// The counters are shared by the whole process, but only this test turns them on
+#[test]
+fn counted_rays_have_one_primary_ray_per_pixel() {
+    let materials = materials();
+    let red = materials.id("red").unwrap();
+    let mut builder = SceneBuilder::new();
+    builder.cube(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), red);
+    let scene = scene(builder.build(), materials);
+
+    let settings = RenderSettings { count_rays: true, fog: false, ..RenderSettings::default() };
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    take_ray_counts();
+    Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+
+    let counts = take_ray_counts();
+    assert_eq!(counts.primary, (WIDTH * HEIGHT) as u64);
+    assert!(counts.shadow > 0, "the lit cube casts shadow rays toward the sun");
+    assert_eq!(take_ray_counts().total(), 0, "taking the counts resets them");
+}
+
+#[test]
+fn frame_times_average_only_the_latest_frames() {
+    let mut times = FrameTimes::new();
+    assert_eq!(times.average(), None);
+    assert_eq!(times.title(), "Raytracer");
+
+    times.push(Duration::from_millis(500));
+    for _ in 0..FRAME_TIME_WINDOW {
+        times.push(Duration::from_millis(80));
+    }
+    assert_eq!(times.average(), Some(Duration::from_millis(80)));
+    assert_eq!(times.title(), "Raytracer — 12.5 fps (80.0 ms)");
+}
+
+#[test]
+fn face_corners_lie_on_the_face_and_go_around_it() {
+    let cube = Cube::new(Vec3::new(0.0, 1.0, 2.0), Vec3::new(0.5, 1.5, 2.5), materials().id("red").unwrap());
+    for face in [CubeFace::Top, CubeFace::Bottom, CubeFace::Left, CubeFace::Right, CubeFace::Front, CubeFace::Back] {
+        let corners = cube.face_corners(&face);
+        let normal = face.normal();
+        let face_center = (cube.min + cube.max) * 0.5 + normal.component_mul(&(cube.max - cube.min)) * 0.5;
+        for i in 0..4 {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            assert!((a - face_center).dot(&normal).abs() < 1e-6, "{:?} corner {} is off the face", face, i);
+            // Neighbors differ along a single edge of the face
+            assert!(((b - a).norm() - 0.5).abs() < 1e-6, "{:?} corners {} and {} are not neighbors", face, i, (i + 1) % 4);
+        }
+    }
+}
+
+#[test]
+fn panorama_directions_are_unit_length_even_at_the_poles() {
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let (width, height) = (64.0, 32.0);
+    for y in [0.0, 0.5, 16.0, 31.5, 32.0] {
+        for x in [0.0, 16.0, 32.0, 63.0, 64.0] {
+            let direction = equirect_direction(&camera, width, height, x, y);
+            assert!(direction.iter().all(|c| c.is_finite()), "NaN at ({}, {})", x, y);
+            assert!((direction.norm() - 1.0).abs() < 1e-5);
+        }
+    }
+    assert!((equirect_direction(&camera, width, height, 32.0, 0.0) - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-5);
+    // The middle of the image looks where the camera does, but level with the horizon
+    let middle = equirect_direction(&camera, width, height, 32.0, 16.0);
+    assert!((middle - Vec3::new(0.0, 0.0, -1.0)).norm() < 1e-5);
+}
+
+#[test]
+fn panorama_of_an_empty_scene_is_all_background() {
+    let scene = scene(Vec::new(), materials());
+    let settings = RenderSettings { projection: Projection::Equirectangular, tone_mapping: ToneMapping::Clamp, fog: false, ..RenderSettings::default() };
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(WIDTH, WIDTH / 2);
+    Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+    for &pixel in &framebuffer.buffer {
+        assert_close(pixel, BACKGROUND);
+    }
+}
+
+// Blocks in three directions, each off to one corner of its face so a flipped or turned face
+// would show, rendered into cubemap faces, saved and loaded back as a skybox: looking toward
+// each block through the skybox finds it, and the opposite corner finds the background
+#[test]
+fn rendered_cubemap_faces_load_back_as_a_matching_skybox() {
+    let mut materials = materials();
+    let red = materials.id("red").unwrap();
+    let green = materials.add("green", Material::builder(Color::new(30, 200, 30)).build());
+    let white = materials.add("white", Material::builder(Color::new(240, 240, 240)).build());
+    let mut builder = SceneBuilder::new();
+    builder.cube(Vec3::new(2.0, 0.2, 0.2), Vec3::new(3.0, 1.0, 1.0), red)
+        .cube(Vec3::new(0.2, 0.2, -3.0), Vec3::new(1.0, 1.0, -2.0), green)
+        .cube(Vec3::new(0.2, -3.0, 0.2), Vec3::new(1.0, -2.0, 1.0), white);
+    let scene = scene(builder.build(), materials);
+
+    let dir = std::env::temp_dir().join(format!("raytracer_cubemap_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let settings = RenderSettings { fov: std::f32::consts::PI / 2.0, tone_mapping: ToneMapping::Clamp, fog: false, ..RenderSettings::default() };
+    for (name, camera) in cubemap_cameras(Vec3::zeros()) {
+        let mut framebuffer = Framebuffer::new(32, 32);
+        Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+        framebuffer.save(&dir.join(format!("{}.png", name))).unwrap();
+    }
+    let face = |name: &str| dir.join(format!("{}.png", name)).to_string_lossy().into_owned();
+    let skybox = Skybox::load(&face("px"), &face("nx"), &face("py"), &face("ny"), &face("pz"), &face("nz")).unwrap();
+
+    // The blocks' faces toward the origin are in shade, but still clearly of their color
+    let toward_red = skybox.sample(&Vec3::new(1.0, 0.3, 0.3));
+    assert!(toward_red.r > toward_red.g + 20 && toward_red.r > toward_red.b + 20, "{:?}", toward_red);
+    let toward_green = skybox.sample(&Vec3::new(0.3, 0.3, -1.0));
+    assert!(toward_green.g > toward_green.r + 20 && toward_green.g > toward_green.b + 20, "{:?}", toward_green);
+    // The block below is lit only by the sky, so it is anything but the background
+    assert_ne!(skybox.sample(&Vec3::new(0.3, -1.0, 0.3)).to_hex(), BACKGROUND.to_hex());
+
+    for empty in [Vec3::new(1.0, -0.3, -0.3), Vec3::new(-0.3, -0.3, -1.0), Vec3::new(-0.3, -1.0, -0.3)] {
+        assert_close(skybox.sample(&empty).to_hex(), BACKGROUND);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stereo_eyes_sit_either_side_of_the_camera_and_look_at_its_center() {
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let (left, right) = camera.stereo_eyes(0.2);
+    assert!(((left.eye + right.eye) * 0.5 - camera.eye).norm() < 1e-6);
+    assert!(((right.eye - left.eye) - camera.right() * 0.2).norm() < 1e-6);
+    assert_eq!(left.center, camera.center);
+    assert_eq!(right.center, camera.center);
+}
+
+// Red comes from the left eye and green and blue from the right, so with no separation the
+// anaglyph is the plain image
+#[test]
+fn anaglyph_with_the_eyes_together_matches_the_mono_image() {
+    let materials = materials();
+    let red = materials.id("red").unwrap();
+    let mut builder = SceneBuilder::new();
+    builder.cube(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), red);
+    let scene = scene(builder.build(), materials);
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+    let render_with = |stereo: bool, eye_separation: f32| {
+        let settings = RenderSettings { stereo, eye_separation, fog: false, ..RenderSettings::default() };
+        let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+        Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+        framebuffer.buffer
+    };
+    assert_eq!(render_with(true, 0.0), render_with(false, 0.0));
+
+    // Apart, the cube's edges land on different pixels for each eye
+    let apart = render_with(true, 0.5);
+    assert!(apart.iter().zip(render_with(false, 0.0)).any(|(a, b)| *a != b));
+}
+
+#[test]
+fn faces_turned_away_from_a_light_get_no_highlight_from_it() {
+    // The light sits off to the side and a bit behind the front face: its halfway vector
+    // still leans toward that face's normal, which used to leave a glint on the dark side
+    let render_with_specular = |specular: f32| {
+        let mut materials = materials();
+        let shiny = materials.add("shiny", Material::builder(Color::new(30, 30, 200)).specular(specular).shininess(5.0).build());
+        let mut scene = scene(vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), shiny).into()], materials);
+        scene.lights[0].intensity = 0.0;
+        scene.lights.push(Light::new(Vec3::new(3.0, 0.0, 0.4), Color::new(255, 255, 255), 3.0, 10.0).with_attenuation(Attenuation::None));
+        render(&scene)
+    };
+    let (shiny, matte) = (render_with_specular(1.0), render_with_specular(0.0));
+    let center = (HEIGHT / 2) * WIDTH + WIDTH / 2;
+    assert_eq!(shiny.buffer[center], matte.buffer[center]);
+}
+
+#[test]
+fn a_disabled_light_adds_nothing() {
+    let mut scene = scene(vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), materials().id("red").unwrap()).into()], materials());
+    let without = render(&scene);
+    let mut lamp = Light::new(Vec3::new(0.0, 2.0, 1.5), Color::new(255, 255, 255), 3.0, 10.0);
+    lamp.enabled = false;
+    scene.lights.push(lamp);
+    assert!(render(&scene).buffer == without.buffer);
+    scene.lights[2].enabled = true;
+    assert!(render(&scene).buffer != without.buffer, "the lamp should light the cube when on");
+}
+
+#[test]
+fn a_shadow_catcher_shows_the_background_with_the_shadows_on_it() {
+    let mut materials = materials();
+    let red = materials.id("red").unwrap();
+    let catcher = materials.add("catcher", Material::builder(Color::new(255, 255, 255)).build().as_shadow_catcher());
+    let scene = scene(vec![
+        Cube::new(Vec3::new(-0.3, 0.4, -0.3), Vec3::new(0.3, 1.0, 0.3), red).into(),
+        Plane::new(Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 1.0, 0.0), catcher).into(),
+    ], materials);
+    let framebuffer = render(&scene);
+    // The bottom row looks at the floor far from the cube's shadow: only the background
+    let corner = (HEIGHT - 1) * WIDTH;
+    assert_eq!(framebuffer.buffer[corner], BACKGROUND.to_hex());
+
+    // The noon sun comes from 45° above +z, so the cube's shadow falls behind it, and there
+    // the background is darkened
+    let settings = RenderSettings { tone_mapping: ToneMapping::Clamp, fog: false, ..RenderSettings::default() };
+    let context = RayContext { scene: &scene, settings: &settings, ambient_color: scene.ambient_color() };
+    let below = Vec3::new(0.0, -0.5, -1.2);
+    let origin = Vec3::new(0.0, 0.2, 2.0);
+    let direction = (below - origin).normalize();
+    let shadowed = cast_ray(&origin, &direction, &context, &mut Sampler::new(1), RayPath::primary(&MediumStack::new()));
+    let open = BACKGROUND.to_linear();
+    assert!(shadowed.r < open.r * 0.5 && shadowed.b < open.b * 0.5, "{:?}", (shadowed.r, shadowed.g, shadowed.b));
+}
+
+#[test]
+fn shadow_catchers_cast_no_shadows_and_are_not_seen_in_reflections() {
+    let with_roof = |roof: bool| {
+        let mut materials = materials();
+        let mirror = materials.add("mirror", Material::builder(Color::new(255, 255, 255)).diffuse(0.0).specular(0.0).reflectivity(1.0).build());
+        let catcher = materials.add("catcher", Material::builder(Color::new(255, 255, 255)).build().as_shadow_catcher());
+        let mut objects: Vec<Object> = vec![
+            Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), materials.id("red").unwrap()).into(),
+            // A mirror below the camera's view, facing up at the roof
+            Cube::new(Vec3::new(-5.0, -2.0, -5.0), Vec3::new(5.0, -1.9, 5.0), mirror).into(),
+        ];
+        if roof {
+            // Above the camera, between the cube and the sun
+            objects.push(Plane::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), catcher).into());
+        }
+        render(&scene(objects, materials))
+    };
+    assert!(with_roof(true).buffer == with_roof(false).buffer);
+}
+
+#[test]
+fn blocks_sharing_a_face_stay_evenly_lit_under_a_grazing_sun() {
+    // Two matte blocks side by side with the sun skimming their tops across the seam: any
+    // self-shadowing shows up as dark speckles on faces that should be one flat color
+    let mut materials = materials();
+    let matte = materials.add("matte", Material::builder(Color::new(180, 180, 180)).specular(0.0).build());
+    let mut builder = SceneBuilder::new();
+    builder.cube(Vec3::new(-0.5, 0.0, -0.25), Vec3::new(0.0, 0.5, 0.25), matte)
+        .cube(Vec3::new(0.0, 0.0, -0.25), Vec3::new(0.5, 0.5, 0.25), matte);
+    let mut scene = scene(builder.build(), materials);
+    scene.lights[0] = Light::directional(Vec3::new(-1.0, -0.1, -0.3), Color::new(255, 255, 255), 1.0);
+    let framebuffer = render(&scene);
+
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let fov = RenderSettings::default().fov;
+    let (mut top, mut front) = (Vec::new(), Vec::new());
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let direction = primary_direction(&camera, fov, WIDTH as f32, HEIGHT as f32, x as f32, y as f32);
+            let Some(Pick::Object(_, hit)) = scene.world.pick(&camera.eye, &direction) else { continue };
+            let pixel = rgb(framebuffer.buffer[y * WIDTH + x]);
+            if hit.normal.y > 0.5 { top.push(pixel) } else if hit.normal.z > 0.5 { front.push(pixel) }
+        }
+    }
+
+    for face in [&top, &front] {
+        assert!(face.len() > 20, "the face should cover some pixels, got {}", face.len());
+        for &(r, g, b) in face.iter() {
+            let (er, eg, eb) = face[0];
+            assert!((r - er).abs() <= 1 && (g - eg).abs() <= 1 && (b - eb).abs() <= 1, "{:?} differs from {:?}", (r, g, b), face[0]);
+        }
+    }
+}
+
+// A white wall across the view whose normal points away from the camera, so the camera sees
+// its back. The noon sun is on the camera's side
+fn back_of_a_wall(sidedness: Sidedness) -> Framebuffer {
+    let mut materials = materials();
+    let wall = materials.add("wall", Material::builder(Color::new(255, 255, 255)).build().with_sidedness(sidedness));
+    render(&scene(vec![Plane::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, -1.0), wall).into()], materials))
+}
+
+#[test]
+fn sidedness_decides_how_a_back_face_looks() {
+    let center = (HEIGHT / 2) * WIDTH + WIDTH / 2;
+    let brightness = |framebuffer: &Framebuffer| {
+        let (r, g, b) = rgb(framebuffer.buffer[center]);
+        r + g + b
+    };
+    let (modeled, double_sided, front_only) = (
+        back_of_a_wall(Sidedness::Geometry),
+        back_of_a_wall(Sidedness::DoubleSided),
+        back_of_a_wall(Sidedness::FrontOnly),
+    );
+    // Its own normal faces away from the sun: only the ambient light
+    assert!(brightness(&double_sided) > brightness(&modeled) + 100, "{:?} vs {:?}", rgb(double_sided.buffer[center]), rgb(modeled.buffer[center]));
+    assert_eq!(front_only.buffer[center], BACKGROUND.to_hex());
+}
+
+#[test]
+fn a_front_only_plane_casts_no_shadow_when_lit_from_behind() {
+    let with_roof = |normal: Option<Vec3>| {
+        let mut materials = materials();
+        let roof = materials.add("roof", Material::builder(Color::new(255, 255, 255)).build().with_sidedness(Sidedness::FrontOnly));
+        let mut objects: Vec<Object> = vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), materials.id("red").unwrap()).into()];
+        // Above the camera, between the cube and the sun
+        if let Some(normal) = normal {
+            objects.push(Plane::new(Vec3::new(0.0, 2.0, 0.0), normal, roof).into());
+        }
+        render(&scene(objects, materials))
+    };
+    // Only the cube is compared: the camera sees the underside of the roof up top
+    let center = (HEIGHT / 2) * WIDTH + WIDTH / 2;
+    let open = with_roof(None).buffer[center];
+    assert_eq!(with_roof(Some(Vec3::new(0.0, -1.0, 0.0))).buffer[center], open);
+    assert_ne!(with_roof(Some(Vec3::new(0.0, 1.0, 0.0))).buffer[center], open, "facing the sun, the roof shades the cube");
+}
+
+#[test]
+fn waves_lean_the_normal_only_when_they_have_height() {
+    let calm = Waves::new(0.0, 0.6, 0.25);
+    assert!((calm.normal(0.3, -1.2, 4.0) - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-6);
+    let waves = Waves::new(0.02, 0.6, 0.25);
+    let (before, after) = (waves.normal(0.3, -1.2, 0.0), waves.normal(0.3, -1.2, 0.5));
+    assert!((before.norm() - 1.0).abs() < 1e-5 && before.y > 0.9);
+    assert!((before - after).norm() > 1e-3, "the surface should move with time");
+}
+
+#[test]
+fn waves_move_the_top_of_a_block_and_leave_its_sides_still() {
+    let mut materials = materials();
+    let water = materials.add("water", Material::builder(Color::new(255, 255, 255)).build().with_waves(Waves::new(0.05, 0.5, 1.0)));
+    let mut scene = scene(vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), water).into()], materials);
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let fov = RenderSettings::default().fov;
+    let pixel = |point: Vec3| {
+        let (x, y) = project(&camera, fov, WIDTH as f32, HEIGHT as f32, &point).unwrap();
+        y as usize * WIDTH + x as usize
+    };
+    let (top, side) = (pixel(Vec3::new(0.1, 0.5, -0.1)), pixel(Vec3::new(0.1, -0.1, 0.5)));
+
+    let still = render(&scene);
+    scene.time = 0.3;
+    let later = render(&scene);
+    assert_ne!(still.buffer[top], later.buffer[top]);
+    assert_eq!(still.buffer[side], later.buffer[side]);
+}
+
+#[test]
+fn the_eye_has_to_pass_the_band_to_go_under_or_come_out() {
+    let mut materials = materials();
+    let water = materials.add("water", Material::water());
+    let mut scene = scene(vec![Cube::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), water).into()], materials);
+    let mut at_height = |y: f32| {
+        scene.update_submersion(&Vec3::new(0.5, y, 0.5));
+        scene.submerged
+    };
+    assert_eq!(at_height(1.0 - SUBMERSION_BAND / 2.0), None, "just below the surface, coming from above");
+    assert_eq!(at_height(0.9), Some(water));
+    assert_eq!(at_height(1.0 + SUBMERSION_BAND / 2.0), Some(water), "just above the surface, coming from below");
+    assert_eq!(at_height(1.5), None);
+    assert_eq!(at_height(1.0 - SUBMERSION_BAND / 2.0), None);
+}
+
+#[test]
+fn a_camera_under_water_sees_through_it() {
+    let underwater = |fluid: bool| {
+        let mut materials = materials();
+        let red = materials.id("red").unwrap();
+        let water = Material::builder(Color::new(255, 255, 255)).diffuse(0.0).specular(0.0).transparency(1.0).refractive_index(1.33).build()
+            .with_absorption(Color::new(60, 150, 210), 1.5);
+        let water = materials.add("water", if fluid { water.as_fluid() } else { water });
+        // The render camera, at (0, 1, 3), is in the water, and the red cube out of it
+        let mut scene = scene(vec![
+            Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), red).into(),
+            Cube::new(Vec3::new(-1.0, 0.0, 2.0), Vec3::new(1.0, 2.0, 4.0), water).into(),
+        ], materials);
+        scene.update_submersion(&Vec3::new(0.0, 1.0, 3.0));
+        assert_eq!(scene.submerged.is_some(), fluid);
+        let framebuffer = render(&scene);
+        rgb(framebuffer.buffer[(HEIGHT / 2) * WIDTH + WIDTH / 2])
+    };
+    let (dry, wet) = (underwater(false), underwater(true));
+    assert!(wet.0 < dry.0 && wet.2 > dry.2, "the fog and tint should turn the red cube blue: {:?} vs {:?}", wet, dry);
+}
+
+#[test]
+fn glass_under_water_shows_what_is_behind_it_without_black_pixels() {
+    // A glass block inside a water block: the rays that cross from water into glass and back
+    // have to keep going, not die at the interface between the two media
+    let mut materials = materials();
+    let water = materials.add("water", Material::builder(Color::new(255, 255, 255))
+        .diffuse(0.0)
+        .specular(0.3)
+        .transparency(0.95)
+        .refractive_index(1.33)
+        .build()
+        .with_absorption(Color::new(170, 220, 230), 0.5));
+    let glass = materials.add("glass", Material::builder(Color::new(255, 255, 255))
+        .diffuse(0.0)
+        .specular(0.5)
+        .reflectivity(0.05)
+        .transparency(0.9)
+        .refractive_index(1.5)
+        .build());
+    let submerged = Cube::new(Vec3::new(-0.3, -0.2, -0.2), Vec3::new(0.3, 0.2, 0.2), glass);
+    let scene = scene(vec![
+        Cube::new(Vec3::new(-0.75, -0.5, -0.5), Vec3::new(0.75, 0.5, 0.5), water).into(),
+        submerged.clone().into(),
+    ], materials);
+    let framebuffer = render(&scene);
+
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let fov = RenderSettings::default().fov;
+    let mut through_glass = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let direction = primary_direction(&camera, fov, WIDTH as f32, HEIGHT as f32, x as f32, y as f32);
+            if submerged.ray_intersect(&camera.eye, &direction).is_none() {
+                continue;
+            }
+            through_glass += 1;
+            let (r, g, b) = rgb(framebuffer.buffer[y * WIDTH + x]);
+            assert!(r.max(g).max(b) > 20, "pixel ({}, {}) through the glass is black: {:?}", x, y, (r, g, b));
+        }
+    }
+    assert!(through_glass > 20, "the glass should cover some pixels, got {}", through_glass);
+}
+
+#[test]
+fn facing_mirrors_end_in_the_environment_after_the_last_bounce() {
+    // The camera stands between two perfect mirrors, so every pixel bounces back and forth
+    // until max_depth. What is left then is the environment in the ray's direction (here a
+    // flat green, or black), never the plain sky blue that used to be hard-coded
+    let render_between_mirrors = |environment: Color, max_depth: u32| {
+        let mut materials = materials();
+        let mirror = materials.add("mirror", Material::builder(Color::new(255, 255, 255)).diffuse(0.0).specular(0.0).reflectivity(1.0).build());
+        let mut scene = scene(vec![
+            Cube::new(Vec3::new(-50.0, -50.0, -2.1), Vec3::new(50.0, 50.0, -2.0), mirror).into(),
+            Cube::new(Vec3::new(-50.0, -50.0, 3.5), Vec3::new(50.0, 50.0, 3.6), mirror).into(),
+        ], materials);
+        scene.environments = vec![Environment::SolidColor(environment)];
+        let settings = RenderSettings { tone_mapping: ToneMapping::Clamp, fog: false, max_depth, ..RenderSettings::default() };
+        let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+        Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+        framebuffer
+    };
+
+    let green = Color::new(40, 200, 60);
+    for max_depth in [0, 3, 8] {
+        for &pixel in &render_between_mirrors(green, max_depth).buffer {
+            assert_close(pixel, green);
+        }
+        for &pixel in &render_between_mirrors(Color::new(0, 0, 0), max_depth).buffer {
+            assert_eq!(pixel, 0);
+        }
+    }
+}
+
+// The red cube seen through a pane of glass between it and the camera, with many rays per
+// blurred refraction so the frosted pane comes out smooth
+fn through_a_pane(pane: MaterialBuilder) -> Framebuffer {
+    let mut materials = materials();
+    let red = materials.id("red").unwrap();
+    let pane = materials.add("pane", pane.build());
+    let scene = scene(vec![
+        Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), red).into(),
+        Cube::new(Vec3::new(-2.0, -1.0, 1.5), Vec3::new(2.0, 2.0, 1.6), pane).into(),
+    ], materials);
+    let settings = RenderSettings { tone_mapping: ToneMapping::Clamp, fog: false, glossy_samples: 16, ..RenderSettings::default() };
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+    framebuffer
+}
+
+#[test]
+fn a_frosted_pane_blurs_the_edges_behind_it_and_a_clear_one_does_not() {
+    let glass = || Material::builder(Color::new(255, 255, 255)).diffuse(0.0).specular(0.0).transparency(1.0).refractive_index(1.5);
+    let clear = through_a_pane(glass());
+    assert!(through_a_pane(glass().refraction_roughness(0.0)).buffer == clear.buffer);
+
+    // Sharpest step in red along the middle row, where the cube's sides cross it
+    let sharpest = |framebuffer: &Framebuffer| {
+        let row = &framebuffer.buffer[(HEIGHT / 2) * WIDTH..(HEIGHT / 2 + 1) * WIDTH];
+        row.windows(2).map(|pair| (rgb(pair[0]).0 - rgb(pair[1]).0).abs()).max().unwrap()
+    };
+    let frosted = through_a_pane(glass().refraction_roughness(0.3));
+    assert!(sharpest(&frosted) * 3 < sharpest(&clear) * 2, "{} vs {}", sharpest(&frosted), sharpest(&clear));
+}
+
+#[test]
+fn reflectivity_and_transparency_over_one_are_scaled_down_together() {
+    let mut overweight = Material::builder(Color::new(200, 30, 30)).build();
+    overweight.reflectivity = 0.8;
+    overweight.transparency = 0.8;
+    assert_eq!(overweight.surface_weights(), (0.5, 0.5));
+    let mut balanced = overweight.clone();
+    balanced.reflectivity = 0.5;
+    balanced.transparency = 0.5;
+
+    let shade = |material: Material| {
+        let mut materials = materials();
+        let id = materials.add("overweight", material);
+        let scene = scene(vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), id).into()], materials);
+        let settings = RenderSettings { fog: false, ..RenderSettings::default() };
+        let context = RayContext { scene: &scene, settings: &settings, ambient_color: scene.ambient_color() };
+        let origin = Vec3::new(0.2, 0.3, 3.0);
+        let direction = Vec3::new(0.0, -0.05, -1.0).normalize();
+        cast_ray(&origin, &direction, &context, &mut Sampler::new(1), RayPath::primary(&MediumStack::new()))
+    };
+    let color = shade(overweight);
+    for channel in [color.r, color.g, color.b] {
+        assert!(channel.is_finite() && channel >= 0.0, "{:?}", (color.r, color.g, color.b));
+    }
+    let expected = shade(balanced);
+    assert!((color.r - expected.r).abs() < 1e-5 && (color.g - expected.g).abs() < 1e-5 && (color.b - expected.b).abs() < 1e-5);
+}
+
+#[test]
+fn emission_adds_its_color_times_strength_and_sets_the_light() {
+    let shade = |strength: f32| {
+        let mut materials = materials();
+        let lamp = Material::builder(Color::new(40, 40, 40)).build().with_emission(Color::new(255, 128, 0)).with_emission_strength(strength);
+        let id = materials.add("lamp", lamp);
+        let scene = scene(vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), id).into()], materials);
+        let settings = RenderSettings { fog: false, ..RenderSettings::default() };
+        let context = RayContext { scene: &scene, settings: &settings, ambient_color: scene.ambient_color() };
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        let color = cast_ray(&Vec3::new(0.1, 0.2, 3.0), &direction, &context, &mut Sampler::new(1), RayPath::primary(&MediumStack::new()));
+        (color, scene.lights[2..].iter().map(|light| light.intensity).collect::<Vec<_>>())
+    };
+
+    // With no strength the block isn't emissive and gets no light of its own
+    let (dark, no_lights) = shade(0.0);
+    assert!(no_lights.is_empty());
+    let (lit, lights) = shade(3.0);
+    let emission = Color::new(255, 128, 0).to_linear() * 3.0;
+    assert!((lit.r - dark.r - emission.r).abs() < 1e-4);
+    assert!((lit.g - dark.g - emission.g).abs() < 1e-4);
+    assert!((lit.b - dark.b - emission.b).abs() < 1e-4);
+    assert_eq!(lights.len(), 1);
+    assert!((lights[0] - Color::new(255, 128, 0).intensity() * 3.0).abs() < 1e-5);
+}
+
+#[test]
+fn emission_is_added_in_full_on_reflective_surfaces() {
+    // A half mirror that glows shines as much as an opaque emitter: its emission isn't scaled
+    // down with the share of light it reflects (the ray reflects back to the plain background)
+    let shade = |strength: f32| {
+        let mut materials = materials();
+        let lamp = Material::builder(Color::new(40, 40, 40)).reflectivity(0.6).build()
+            .with_emission(Color::new(255, 128, 0)).with_emission_strength(strength);
+        let id = materials.add("lamp", lamp);
+        let scene = scene(vec![Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), id).into()], materials);
+        let settings = RenderSettings { fog: false, ..RenderSettings::default() };
+        let context = RayContext { scene: &scene, settings: &settings, ambient_color: scene.ambient_color() };
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        cast_ray(&Vec3::new(0.1, 0.2, 3.0), &direction, &context, &mut Sampler::new(1), RayPath::primary(&MediumStack::new()))
+    };
+    let (dark, lit) = (shade(0.0), shade(3.0));
+    let emission = Color::new(255, 128, 0).to_linear() * 3.0;
+    assert!((lit.r - dark.r - emission.r).abs() < 1e-4, "{} vs {}", lit.r - dark.r, emission.r);
+    assert!((lit.g - dark.g - emission.g).abs() < 1e-4);
+    assert!((lit.b - dark.b - emission.b).abs() < 1e-4);
+}
+
+// Brightness of the floor just in front of a glowstone, with the sun off and the glowstone's
+// light on or off. Averaged over a patch of pixels, so path tracing noise evens out
+fn floor_by_a_glowstone(settings: RenderSettings, lamp_on: bool) -> f32 {
+    let mut materials = materials();
+    let floor = materials.add("floor", Material::builder(Color::new(200, 200, 200)).specular(0.0).build());
+    let glowstone = materials.add("glowstone", Material::builder(Color::new(255, 220, 150)).build()
+        .with_emission(Color::new(255, 220, 150)).with_emission_strength(3.0));
+    let mut scene = scene(vec![
+        Cube::new(Vec3::new(-0.15, 0.35, -0.15), Vec3::new(0.15, 0.65, 0.15), glowstone).into(),
+        Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), floor).into(),
+    ], materials);
+    scene.lights[0].intensity = 0.0;
+    scene.lights[2].enabled = lamp_on;
+
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    let fov = settings.fov;
+    Renderer::new(settings).render(&scene, &camera, &mut framebuffer);
+
+    let (x, y) = project(&camera, fov, WIDTH as f32, HEIGHT as f32, &Vec3::new(0.0, 0.0, 0.4)).unwrap();
+    let (x, y) = (x as usize, y as usize);
+    let mut sum = 0;
+    for py in y - 2..=y + 2 {
+        for px in x - 2..=x + 2 {
+            let (r, g, b) = rgb(framebuffer.buffer[py * WIDTH + px]);
+            sum += r + g + b;
+        }
+    }
+    sum as f32 / 25.0
+}
+
+#[test]
+fn a_glowstone_lights_the_floor_as_a_point_light() {
+    // With area lights off the glowstone is a point light at its center, inside its own opaque
+    // cube: the shadow rays have to stop at the cube's faces instead of hitting them
+    let settings = RenderSettings { tone_mapping: ToneMapping::Clamp, fog: false, area_lights: false, ..RenderSettings::default() };
+    let (on, off) = (floor_by_a_glowstone(settings, true), floor_by_a_glowstone(settings, false));
+    assert!(on > off * 1.5, "the glowstone should light the floor: {} with it, {} without", on, off);
+}
+
+#[test]
+fn a_glowstone_lights_the_floor_when_path_tracing() {
+    // After a diffuse bounce the path counts lights only through the direct light, so the
+    // glowstone has to reach the floor there, sampled over its faces or as a point light
+    for area_lights in [true, false] {
+        let settings = RenderSettings { mode: RenderMode::PathTrace, tone_mapping: ToneMapping::Clamp, fog: false, area_lights, ..RenderSettings::default() };
+        let (on, off) = (floor_by_a_glowstone(settings, true), floor_by_a_glowstone(settings, false));
+        assert!(on > off * 1.5, "the glowstone should light the floor (area lights {}): {} with it, {} without", area_lights, on, off);
+    }
+}
@@ -0,0 +1,136 @@
+use nalgebra_glm::Vec3;
+use raytracer::color::Color;
+use raytracer::material::{Material, MaterialId, MaterialRegistry};
+use raytracer::object::Object;
+use raytracer::scene_builder::SceneBuilder;
+use raytracer::voxel_grid::BLOCK_SIZE;
+use std::collections::HashMap;
+
+struct Materials {
+    wood: MaterialId,
+    glass: MaterialId,
+    stone: MaterialId,
+    treewood: MaterialId,
+    leaves: MaterialId,
+    glowstone: MaterialId,
+    portal: MaterialId,
+    border: MaterialId,
+}
+
+fn materials() -> Materials {
+    let mut registry = MaterialRegistry::new();
+    let mut add = |name: &str| registry.add(name, Material::builder(Color::new(128, 128, 128)).build());
+    Materials {
+        wood: add("wood"),
+        glass: add("glass"),
+        stone: add("stone"),
+        treewood: add("treewood"),
+        leaves: add("leaves"),
+        glowstone: add("glowstone"),
+        portal: add("portal"),
+        border: add("border"),
+    }
+}
+
+// The house, tree and glowstone as they were written by hand before the builder, box by box
+fn hand_written_blocks(m: &Materials) -> Vec<(Vec3, Vec3, MaterialId)> {
+    let b = |min: [f32; 3], max: [f32; 3], material| (Vec3::from(min), Vec3::from(max), material);
+    vec![
+        // Walls, the window and the door gap
+        b([-1.5, 0.0, -1.5], [1.5, 2.0, -1.0], m.wood),
+        b([-1.5, 0.0, -1.5], [-1.0, 2.0, 1.5], m.wood),
+        b([1.0, 0.0, -1.5], [1.5, 0.5, 1.5], m.wood),
+        b([1.0, 0.0, -1.5], [1.5, 2.0, -0.5], m.wood),
+        b([1.0, 0.0, 0.5], [1.5, 2.0, 1.5], m.wood),
+        b([1.0, 1.5, -1.5], [1.5, 2.0, 1.5], m.wood),
+        b([1.0, 0.5, -0.5], [1.5, 1.5, 0.5], m.glass),
+        b([-1.5, 0.0, 1.0], [-0.5, 2.0, 1.5], m.wood),
+        b([0.5, 0.0, 1.0], [1.5, 2.0, 1.5], m.wood),
+        b([-0.5, 1.0, 1.0], [0.5, 2.0, 1.5], m.wood),
+        // Stepped roof
+        b([-2.0, 2.0, -2.0], [2.0, 2.5, 2.0], m.stone),
+        b([-1.5, 2.5, -1.5], [1.5, 3.0, 1.5], m.stone),
+        b([-1.0, 3.0, -1.0], [1.0, 3.5, 1.0], m.stone),
+        b([-0.5, 3.5, -0.5], [0.5, 4.0, 0.5], m.stone),
+        // Tree
+        b([-3.0, 0.0, 3.0], [-2.5, 0.5, 3.5], m.treewood),
+        b([-3.0, 0.5, 3.0], [-2.5, 1.0, 3.5], m.treewood),
+        b([-3.0, 1.0, 3.0], [-2.5, 1.5, 3.5], m.treewood),
+        b([-3.0, 1.5, 3.0], [-2.5, 2.0, 3.5], m.treewood),
+        b([-3.5, 2.0, 2.5], [-2.0, 2.5, 4.0], m.leaves),
+        b([-3.5, 2.5, 2.5], [-2.0, 3.0, 4.0], m.leaves),
+        b([-3.0, 3.0, 3.0], [-2.5, 3.5, 3.5], m.leaves),
+        // Glowstone
+        b([2.0, 0.0, -1.0], [2.5, 0.5, -0.5], m.glowstone),
+    ]
+}
+
+// The portal and its frame, which don't follow the grid
+fn hand_written_portal(m: &Materials) -> Vec<(Vec3, Vec3, MaterialId)> {
+    vec![
+        (Vec3::new(-0.5, 0.0, -2.5), Vec3::new(0.5, 2.0, -2.0), m.portal),
+        (Vec3::new(-0.7, -0.2, -2.6), Vec3::new(0.7, 0.0, -1.9), m.border),
+        (Vec3::new(-0.7, 2.0, -2.6), Vec3::new(0.7, 2.2, -1.9), m.border),
+        (Vec3::new(-0.7, 0.0, -2.6), Vec3::new(-0.5, 2.0, -1.9), m.border),
+        (Vec3::new(0.5, 0.0, -2.6), Vec3::new(0.7, 2.0, -1.9), m.border),
+    ]
+}
+
+// The grid cells a box covers, with its material; later boxes win where they overlap
+fn cells(boxes: &[(Vec3, Vec3, MaterialId)]) -> HashMap<[i32; 3], MaterialId> {
+    let mut cells = HashMap::new();
+    for (min, max, material) in boxes {
+        let (low, high) = (min.map(|c| (c / BLOCK_SIZE).round() as i32), max.map(|c| (c / BLOCK_SIZE).round() as i32));
+        for x in low.x..high.x {
+            for y in low.y..high.y {
+                for z in low.z..high.z {
+                    cells.insert([x, y, z], *material);
+                }
+            }
+        }
+    }
+    cells
+}
+
+fn cube_list(objects: &[Object]) -> Vec<(Vec3, Vec3, MaterialId)> {
+    objects.iter()
+        .map(|object| match object {
+            Object::Cube(cube) => (cube.min, cube.max, cube.material),
+            _ => panic!("the builder only makes cubes here"),
+        })
+        .collect()
+}
+
+#[test]
+fn the_builder_rebuilds_the_hand_written_scene() {
+    let m = materials();
+    let mut scene = SceneBuilder::new();
+    scene.wall((-3, -3), (2, -3), 0, 4, m.wood)
+        .wall((-3, -3), (-3, 2), 0, 4, m.wood)
+        .wall((2, -3), (2, 2), 0, 4, m.wood)
+        .wall((-3, 2), (2, 2), 0, 4, m.wood)
+        .clear([-1, 0, 2], [0, 1, 2])
+        .fill([2, 1, -1], [2, 2, 0], m.glass)
+        .floor(4, (-4, -4), (3, 3), m.stone)
+        .floor(5, (-3, -3), (2, 2), m.stone)
+        .floor(6, (-2, -2), (1, 1), m.stone)
+        .floor(7, (-1, -1), (0, 0), m.stone)
+        .fill([-6, 0, 6], [-6, 3, 6], m.treewood)
+        .fill([-7, 4, 5], [-5, 5, 7], m.leaves)
+        .place_block(-6, 6, 6, m.leaves)
+        .place_block(4, 0, -2, m.glowstone);
+    for (min, max, material) in hand_written_portal(&m) {
+        scene.cube(min, max, material);
+    }
+    let built = cube_list(&scene.build());
+
+    // Blocks first, one per cell, covering what the hand-written boxes covered
+    let portal = hand_written_portal(&m);
+    let (blocks, free) = built.split_at(built.len() - portal.len());
+    assert!(blocks.iter().all(|(min, max, _)| (max - min).iter().all(|side| (side - BLOCK_SIZE).abs() < 1e-6)));
+    assert_eq!(cells(blocks).len(), blocks.len(), "no two blocks share a cell");
+    assert_eq!(cells(blocks), cells(&hand_written_blocks(&m)));
+
+    // Free-form cubes come after, untouched and in order
+    assert_eq!(free, &portal[..]);
+}
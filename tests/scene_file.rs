@@ -0,0 +1,212 @@
+use nalgebra_glm::Vec3;
+use raytracer::bvh::Bvh;
+use raytracer::camera::Camera;
+use raytracer::chunk::ChunkManager;
+use raytracer::color::{Color, ToneMapping};
+use raytracer::cube::Cube;
+use raytracer::day_night::DayNightCycle;
+use raytracer::environment::Environment;
+use raytracer::framebuffer::Framebuffer;
+use raytracer::house::house;
+use raytracer::light::{Attenuation, Flicker, Light};
+use raytracer::material::{Material, MaterialRegistry, Sidedness};
+use raytracer::mesh::{Mesh, Triangle};
+use raytracer::obj_loader::ObjModel;
+use raytracer::object::Object;
+use raytracer::plane::Plane;
+use raytracer::scene_file;
+use raytracer::sphere::Sphere;
+use raytracer::terrain::TerrainSettings;
+use raytracer::texture::TextureCache;
+use raytracer::world::World;
+use raytracer::{RenderSettings, Renderer, Scene};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn scene(objects: Vec<Object>, materials: MaterialRegistry, lights: Vec<Light>) -> Scene {
+    let terrain = materials.iter().next().map(|(id, _)| id).unwrap();
+    let world = World::new(Bvh::new(objects), ChunkManager::new(TerrainSettings::default(), terrain, 0));
+    let mut day_night = DayNightCycle::new();
+    day_night.time = 0.25;
+    Scene::new(world, materials, lights, vec![Environment::SolidColor(Color::new(68, 142, 228))], day_night)
+}
+
+fn render(scene: &Scene, camera: &Camera) -> Vec<u32> {
+    let settings = RenderSettings { tone_mapping: ToneMapping::Clamp, ..RenderSettings::default() };
+    let mut framebuffer = Framebuffer::new(64, 48);
+    Renderer::new(settings).render(scene, camera, &mut framebuffer);
+    framebuffer.buffer
+}
+
+#[test]
+fn saved_scene_loads_back_to_the_same_render() {
+    let mut textures = TextureCache::new(Some(PathBuf::from("assets")));
+    let mut materials = MaterialRegistry::new();
+    let grass = materials.add("grass", Material::builder(Color::new(0, 255, 0)).build());
+    let mut textured = Material::builder(Color::new(200, 200, 200)).build();
+    textured.textures = vec![textures.get("bee_nest_top.png")];
+    textured.normal_map = Some(textures.get("stone_normal.png"));
+    let stone = materials.add("stone", textured);
+    let mut animated = Material::builder(Color::new(100, 0, 200)).build().with_emission(Color::new(80, 0, 160)).with_flicker(Flicker::new(0.2, 3.0, 9));
+    animated.animation = Some(Arc::new(textures.strip("end_portal_strip.png", 0.12)));
+    let portal = materials.add("portal", animated);
+    // Registered without a name, like the .mtl materials: saved under a generated one
+    let glass = materials.insert(Material::builder(Color::new(235, 245, 250)).build().with_sidedness(Sidedness::DoubleSided));
+
+    let mut triangle = Triangle::new(Vec3::new(-1.5, 0.0, -0.5), Vec3::new(-0.5, 0.0, -0.5), Vec3::new(-1.0, 1.0, -0.5));
+    triangle.normals = Some([Vec3::new(0.0, 0.0, 1.0); 3]);
+    let mesh = Mesh { triangles: vec![triangle], material: glass, materials: Vec::new(), min: Vec3::new(-1.5, 0.0, -0.5), max: Vec3::new(-0.5, 1.0, -0.5) };
+    let objects: Vec<Object> = vec![
+        Cube::new(Vec3::new(-0.25, 0.0, -0.25), Vec3::new(0.25, 0.5, 0.25), stone).into(),
+        Cube::new(Vec3::new(0.5, 0.0, -0.25), Vec3::new(1.0, 0.5, 0.25), portal).into(),
+        Sphere::new(Vec3::new(0.0, 0.9, 0.0), 0.3, glass).into(),
+        Plane::new(Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0), grass).with_tile_size(0.5).into(),
+        mesh.into(),
+    ];
+    let day_night = DayNightCycle::new();
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0).with_size(0.05),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+        Light::spot(Vec3::new(0.0, 1.4, 1.8), Vec3::new(0.0, -1.0, 0.35), 0.3, 0.6, Color::new(255, 200, 120), 2.0, 4.0)
+            .with_flicker(Some(Flicker::new(0.1, 2.0, 4)))
+            .with_attenuation(Attenuation::InverseSquare { constant: 1.0, linear: 0.0, quadratic: 1.5 }),
+    ];
+    let original = scene(objects, materials, lights);
+    let mut camera = Camera::new(Vec3::new(0.5, 1.2, 3.0), Vec3::new(0.0, 0.3, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    camera.focus_distance = 2.5;
+
+    let path = std::env::temp_dir().join(format!("raytracer_scene_{}.json", std::process::id()));
+    original.save(&path, &camera).unwrap();
+    let mut loaded_materials = MaterialRegistry::new();
+    let loaded = scene_file::load(&path, &mut loaded_materials, &mut textures);
+    std::fs::remove_file(&path).ok();
+    let loaded = loaded.unwrap();
+
+    assert_eq!(loaded.camera.eye, camera.eye);
+    assert_eq!(loaded.camera.focus_distance, camera.focus_distance);
+    assert_eq!(loaded.lights.len(), 3);
+    assert_eq!(loaded.lights[2].flicker, Some(Flicker::new(0.1, 2.0, 4)));
+    assert_eq!(loaded.lights[2].attenuation, Attenuation::InverseSquare { constant: 1.0, linear: 0.0, quadratic: 1.5 });
+    let glass = loaded_materials.id("material_3").expect("the unnamed material gets a generated name");
+    assert_eq!(loaded_materials.get(glass).sidedness, Sidedness::DoubleSided);
+    let reloaded = scene(loaded.objects, loaded_materials, loaded.lights);
+    assert!(render(&original, &camera) == render(&reloaded, &loaded.camera), "the reloaded scene renders differently");
+}
+
+#[test]
+fn the_house_scene_loads_back_to_the_same_render() {
+    let mut textures = TextureCache::new(Some(PathBuf::from("assets")));
+    let mut materials = MaterialRegistry::new();
+    // The objects main.rs opens with: the house, then the ground, the crystal sphere and the creeper
+    let house = house(&mut materials, &mut textures);
+    let mut objects = house.objects;
+    objects.push(Plane::new(Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0), house.grass).with_tile_size(0.5).into());
+    objects.push(Sphere::new(Vec3::new(2.6, 0.5, 1.6), 0.5, house.crystal).into());
+    let creeper = materials.add("creeper", Material::builder(Color::new(80, 180, 70)).specular(0.1).build());
+    let creeper_model = ObjModel::load("assets/creeper.obj").unwrap();
+    objects.push(Mesh::from_obj(&creeper_model, Vec3::new(2.8, 0.0, 3.0), 0.5, creeper, &mut materials).into());
+    let day_night = DayNightCycle::new();
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0).with_size(0.05),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+    ];
+    let original = scene(objects, materials, lights);
+    let camera = Camera::new(Vec3::new(1.0, 2.5, 6.5), Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+    let path = std::env::temp_dir().join(format!("raytracer_scene_house_{}.json", std::process::id()));
+    original.save(&path, &camera).unwrap();
+    let mut loaded_materials = MaterialRegistry::new();
+    let loaded = scene_file::load(&path, &mut loaded_materials, &mut textures);
+    std::fs::remove_file(&path).ok();
+    let loaded = loaded.unwrap();
+
+    assert_eq!(loaded.objects.len(), original.world.scene.objects().len());
+    let reloaded = scene(loaded.objects, loaded_materials, loaded.lights);
+    assert!(render(&original, &camera) == render(&reloaded, &loaded.camera), "the reloaded house renders differently");
+}
+
+#[test]
+fn a_material_the_builder_rejects_fails_to_load() {
+    let mut textures = TextureCache::new(None);
+    let mut materials = MaterialRegistry::new();
+    // Set past the builder: a reflection and a refraction that add up to more than the whole color
+    let mut stone = Material::builder(Color::new(128, 128, 128)).reflectivity(0.6).build();
+    stone.transparency = 0.7;
+    let stone = materials.add("stone", stone);
+    let day_night = DayNightCycle::new();
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+    ];
+    let original = scene(vec![Cube::new(Vec3::zeros(), Vec3::repeat(0.5), stone).into()], materials, lights);
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+
+    let path = std::env::temp_dir().join(format!("raytracer_scene_invalid_{}.json", std::process::id()));
+    original.save(&path, &camera).unwrap();
+    let loaded = scene_file::load(&path, &mut MaterialRegistry::new(), &mut textures);
+    std::fs::remove_file(&path).ok();
+    let error = loaded.err().expect("the material should be rejected");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("stone"), "the error should name the material: {}", error);
+}
+
+#[test]
+fn loading_keeps_the_ids_of_materials_already_registered() {
+    let mut textures = TextureCache::new(None);
+    let mut materials = MaterialRegistry::new();
+    materials.add("grass", Material::builder(Color::new(0, 255, 0)).build());
+    let stone = materials.add("stone", Material::builder(Color::new(128, 128, 128)).build());
+    let day_night = DayNightCycle::new();
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+    ];
+    let original = scene(vec![Cube::new(Vec3::zeros(), Vec3::repeat(0.5), stone).into()], materials, lights);
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+
+    let path = std::env::temp_dir().join(format!("raytracer_scene_ids_{}.json", std::process::id()));
+    original.save(&path, &camera).unwrap();
+    // A registry with the same names in another order, and one material of its own
+    let mut registry = MaterialRegistry::new();
+    let stone_here = registry.add("stone", Material::builder(Color::new(1, 2, 3)).build());
+    registry.add("lava", Material::builder(Color::new(255, 100, 0)).build());
+    let loaded = scene_file::load(&path, &mut registry, &mut textures);
+    std::fs::remove_file(&path).ok();
+    let loaded = loaded.unwrap();
+
+    match &loaded.objects[0] {
+        Object::Cube(cube) => assert_eq!(cube.material, stone_here),
+        _ => panic!("the cube should load as a cube"),
+    }
+    assert_eq!(registry.get(stone_here).color, Color::new(128, 128, 128));
+    assert!(registry.id("lava").is_some());
+}
+
+#[test]
+fn a_triangle_material_past_the_mesh_list_fails_to_load() {
+    let mut textures = TextureCache::new(None);
+    let mut materials = MaterialRegistry::new();
+    let grass = materials.add("grass", Material::builder(Color::new(0, 255, 0)).build());
+    let stone = materials.add("stone", Material::builder(Color::new(128, 128, 128)).build());
+    // The mesh lists one material of its own, and the second triangle points past it
+    let triangle = |material: usize| {
+        let mut triangle = Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        triangle.material = Some(material);
+        triangle
+    };
+    let mesh = Mesh { triangles: vec![triangle(0), triangle(1)], material: grass, materials: vec![stone], min: Vec3::zeros(), max: Vec3::new(1.0, 1.0, 0.0) };
+    let day_night = DayNightCycle::new();
+    let lights = vec![
+        Light::directional(day_night.sun_direction(), Color::new(255, 255, 255), 1.0),
+        Light::directional(day_night.moon_direction(), Color::new(170, 190, 255), 0.0),
+    ];
+    let original = scene(vec![mesh.into()], materials, lights);
+    let camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+
+    let path = std::env::temp_dir().join(format!("raytracer_scene_mesh_{}.json", std::process::id()));
+    original.save(&path, &camera).unwrap();
+    let loaded = scene_file::load(&path, &mut MaterialRegistry::new(), &mut textures);
+    std::fs::remove_file(&path).ok();
+    let error = loaded.err().expect("the out of range index should be rejected");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
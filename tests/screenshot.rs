@@ -0,0 +1,53 @@
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+use raytracer::framebuffer::Framebuffer;
+use raytracer::screenshot::{reserve_path, save_in_background, timestamp, Recorder};
+
+#[test]
+fn timestamps_are_utc_calendar_dates() {
+    assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01_00-00-00");
+    // 2024 is a leap year: the day after Feb 28th is the 29th
+    assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_709_164_800)), "2024-02-29_00-00-00");
+    assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_735_689_599)), "2024-12-31_23-59-59");
+}
+
+#[test]
+fn reserved_names_never_collide_and_saves_write_a_png() {
+    let dir = std::env::temp_dir().join(format!("raytracer_screenshots_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let first = reserve_path(&dir, "shot").unwrap();
+    let second = reserve_path(&dir, "shot").unwrap();
+    assert_eq!(first.file_name().unwrap(), "shot.png");
+    assert_eq!(second.file_name().unwrap(), "shot_2.png");
+
+    let mut framebuffer = Framebuffer::new(4, 3);
+    framebuffer.buffer[0] = 0x00FF8040;
+    save_in_background(framebuffer, first.clone()).join().unwrap();
+    let image = image::open(&first).unwrap().to_rgb8();
+    assert_eq!(image.dimensions(), (4, 3));
+    assert_eq!(image.get_pixel(0, 0).0, [0xFF, 0x80, 0x40]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn recorded_frames_are_numbered_without_gaps() {
+    let dir = std::env::temp_dir().join(format!("raytracer_recording_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut recorder = Recorder::start(&dir).unwrap();
+    let framebuffer = Framebuffer::new(8, 8);
+    let queued = (0..3).filter(|_| recorder.record(&framebuffer)).count() as u64;
+    let summary = recorder.stop();
+
+    assert_eq!(summary.written, queued);
+    assert_eq!(summary.written + summary.dropped, 3);
+    assert_eq!(summary.failed, 0);
+    for number in 1..=summary.written {
+        assert!(dir.join(format!("frame_{:05}.png", number)).is_file(), "frame {} is missing", number);
+    }
+    assert!(!dir.join(format!("frame_{:05}.png", summary.written + 1)).exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
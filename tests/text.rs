@@ -0,0 +1,54 @@
+use raytracer::color::Color;
+use raytracer::day_night::DayNightCycle;
+use raytracer::font::{glyph, GLYPH_SIZE};
+use raytracer::framebuffer::Framebuffer;
+
+const WHITE: Color = Color::new(255, 255, 255);
+
+fn lit_pixels(framebuffer: &Framebuffer) -> usize {
+    framebuffer.buffer.iter().filter(|&&pixel| pixel == WHITE.to_hex()).count()
+}
+
+fn glyph_pixels(c: char) -> usize {
+    glyph(c).iter().map(|row| row.count_ones() as usize).sum()
+}
+
+#[test]
+fn text_draws_exactly_the_set_pixels_of_its_glyphs() {
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_text(2, 3, "Hi\n4", WHITE);
+    assert_eq!(lit_pixels(&framebuffer), glyph_pixels('H') + glyph_pixels('i') + glyph_pixels('4'));
+
+    // The top row of 'H' starts at its left edge; '4' sits on the second line
+    assert_eq!(framebuffer.buffer[3 * 64 + 2], WHITE.to_hex());
+    let four_top = glyph('4')[0];
+    assert_eq!(framebuffer.buffer[(3 + GLYPH_SIZE) * 64 + 2 + four_top.trailing_zeros() as usize], WHITE.to_hex());
+}
+
+#[test]
+fn text_past_the_edges_is_clipped_instead_of_panicking() {
+    let mut framebuffer = Framebuffer::new(20, 10);
+    for (x, y) in [(-5, -5), (15, 6), (-100, 4), (1000, 1000), (isize::MIN / 2, 0)] {
+        framebuffer.draw_text(x, y, "clip me\nplease", WHITE);
+    }
+    // A single 'A' half off the left edge keeps only its right half
+    let mut framebuffer = Framebuffer::new(16, 16);
+    framebuffer.draw_text(-4, 0, "A", WHITE);
+    let right_half: usize = glyph('A').iter().map(|row| (row >> 4).count_ones() as usize).sum();
+    assert_eq!(lit_pixels(&framebuffer), right_half);
+}
+
+#[test]
+fn characters_outside_the_font_fall_back_to_a_question_mark() {
+    assert_eq!(glyph('€'), glyph('?'));
+    assert_ne!(glyph('á'), glyph('?'));
+}
+
+#[test]
+fn the_clock_puts_sunrise_at_six_and_noon_at_twelve() {
+    let mut cycle = DayNightCycle::new();
+    for (time, clock) in [(0.0, (6, 0)), (0.25, (12, 0)), (0.5, (18, 0)), (0.75, (0, 0)), (0.3125, (13, 30))] {
+        cycle.time = time;
+        assert_eq!(cycle.clock(), clock);
+    }
+}
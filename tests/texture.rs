@@ -0,0 +1,54 @@
+use raytracer::color::Color;
+use raytracer::texture::{Texture, TextureCache, WrapMode};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const MISSING: &str = "assets/this_texture_does_not_exist.png";
+
+#[test]
+fn missing_file_gives_the_placeholder_checker() {
+    let texture = Texture::load_or_placeholder(MISSING);
+    assert_eq!((texture.width, texture.height), (2, 2));
+    assert_eq!(texture.sample(0.25, 0.25), Color::new(255, 0, 255));
+    assert_eq!(texture.sample(0.75, 0.25), Color::new(0, 0, 0));
+    assert_eq!(texture.sample(0.75, 0.75), Color::new(255, 0, 255));
+}
+
+#[test]
+fn the_average_is_taken_in_linear_light() {
+    // Half magenta, half black: half of each channel's light, brighter than sRGB 128
+    let average = Texture::placeholder().average();
+    assert!((average.r - 0.5).abs() < 1e-4 && average.g.abs() < 1e-6 && (average.b - 0.5).abs() < 1e-4);
+    assert_eq!(average.to_srgb(), Color::new(188, 0, 188));
+}
+
+#[test]
+fn wrap_modes_map_coordinates_outside_the_image_back_into_it() {
+    let cases = [
+        (WrapMode::Repeat, [0.75, 0.0, 0.75]),
+        (WrapMode::Clamp, [0.0, 1.0, 1.0]),
+        (WrapMode::MirrorRepeat, [0.25, 1.0, 0.75]),
+    ];
+    for (mode, expected) in cases {
+        for (t, expected) in [-0.25, 1.0, 2.75].into_iter().zip(expected) {
+            assert!((mode.apply(t) - expected).abs() < 1e-6, "{:?} maps {} to {} instead of {}", mode, t, mode.apply(t), expected);
+        }
+    }
+}
+
+#[test]
+fn cache_shares_one_texture_per_path() {
+    let mut cache = TextureCache::new(Some(PathBuf::from("assets")));
+    let first = cache.get("this_texture_does_not_exist.png");
+    let second = cache.get("this_texture_does_not_exist.png");
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(first.width, 2);
+}
+
+#[test]
+#[cfg(feature = "embed-textures")]
+fn core_textures_come_embedded_without_an_asset_directory() {
+    let mut cache = TextureCache::new(None);
+    let grass = cache.get("UP_GRASSTEXTURE.jpg");
+    assert!(grass.width > 2, "the grass texture should not be the placeholder");
+}
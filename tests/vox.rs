@@ -0,0 +1,65 @@
+use raytracer::color::Color;
+use raytracer::material::MaterialRegistry;
+use raytracer::vox_loader::{VoxError, VoxModel};
+
+// A .vox file with one model of `size` holding `voxels` as (x, y, z, color index), and a
+// palette where index i is (i, 0, 0)
+fn vox_file(size: (u32, u32, u32), voxels: &[(u8, u8, u8, u8)]) -> Vec<u8> {
+    let chunk = |id: &[u8], content: Vec<u8>| {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as u32).to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(content);
+        bytes
+    };
+    let size_chunk = chunk(b"SIZE", [size.0, size.1, size.2].iter().flat_map(|n| n.to_le_bytes()).collect());
+    let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+    for &(x, y, z, i) in voxels {
+        xyzi.extend([x, y, z, i]);
+    }
+    let rgba = (1..=256u32).flat_map(|i| [i as u8, 0, 0, 255]).collect();
+    let children: Vec<u8> = [size_chunk, chunk(b"XYZI", xyzi), chunk(b"RGBA", rgba)].concat();
+
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend(150u32.to_le_bytes());
+    bytes.extend(b"MAIN");
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend((children.len() as u32).to_le_bytes());
+    bytes.extend(children);
+    bytes
+}
+
+#[test]
+fn reads_size_voxels_and_palette() {
+    let model = VoxModel::parse(&vox_file((2, 1, 1), &[(0, 0, 0, 3), (1, 0, 0, 3)])).unwrap();
+    assert_eq!(model.size, (2, 1, 1));
+    assert_eq!(model.voxels.len(), 2);
+    assert_eq!(model.palette[3], Color::new(3, 0, 0));
+}
+
+#[test]
+fn rejects_files_that_are_not_vox() {
+    assert!(matches!(VoxModel::parse(b"not a vox file"), Err(VoxError::Format(_))));
+    let mut truncated = vox_file((1, 1, 1), &[(0, 0, 0, 1)]);
+    truncated.truncate(truncated.len() - 10);
+    assert!(matches!(VoxModel::parse(&truncated), Err(VoxError::Format(_))));
+}
+
+#[test]
+fn merging_joins_runs_of_the_same_color() {
+    // Three voxels in a row along x, the last one a different color
+    let model = VoxModel::parse(&vox_file((3, 1, 1), &[(0, 0, 0, 5), (1, 0, 0, 5), (2, 0, 0, 7)])).unwrap();
+    let mut materials = MaterialRegistry::new();
+    let (separate, _) = model.to_cubes(1.0, &[], false, &mut materials);
+    assert_eq!(separate.len(), 3);
+
+    let (merged, offset) = model.to_cubes(1.0, &[7], true, &mut materials);
+    assert_eq!(merged.len(), 2);
+    assert_eq!((merged[0].min.x, merged[0].max.x), (0.0, 2.0));
+    assert_eq!((merged[0].min.y, merged[0].max.y), (0.0, 1.0));
+    assert_eq!(offset.x, -1.5);
+
+    // Only the index marked emissive glows
+    assert_eq!(materials.get(merged[0].material).emission, Color::new(0, 0, 0));
+    assert_eq!(materials.get(merged[1].material).emission, Color::new(7, 0, 0));
+}